@@ -0,0 +1,119 @@
+//! Peak heap usage and time of PaXoS encoding.
+//!
+//! Run with `cargo bench --bench paxos_memory_benchmark`.
+
+use preprocessing_mpsi_with_vole::solver::{PaxosSolver, Solver};
+use rand::Rng;
+use scuttlebutt::field::F128b;
+use scuttlebutt::{AesRng, Block};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// Allocator recording the current and the peak number of allocated bytes.
+struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl PeakAlloc {
+    const fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    fn add(&self, size: usize) {
+        let current = self.current.fetch_add(size, Ordering::SeqCst) + size;
+        self.peak.fetch_max(current, Ordering::SeqCst);
+    }
+
+    fn sub(&self, size: usize) {
+        self.current.fetch_sub(size, Ordering::SeqCst);
+    }
+
+    fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    fn reset_peak(&self) {
+        self.peak.store(self.current(), Ordering::SeqCst);
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            self.add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.sub(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            self.sub(layout.size());
+            self.add(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc::new();
+
+type EncodeFn = fn(
+    &mut AesRng,
+    &[(F128b, F128b)],
+    Block,
+    <PaxosSolver<F128b> as Solver<F128b>>::Params,
+) -> anyhow::Result<Vec<F128b>>;
+
+fn measure(name: &str, size: usize, encode: EncodeFn) {
+    let mut rng = AesRng::new();
+    let points = (0..size)
+        .map(|_| (rng.gen(), rng.gen()))
+        .collect::<Vec<(F128b, F128b)>>();
+    let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+    let params = PaxosSolver::<F128b>::calc_params(size);
+
+    let base = ALLOC.current();
+    ALLOC.reset_peak();
+
+    let start = Instant::now();
+    let p = encode(&mut rng, &points, aux, params).unwrap();
+    let elapsed = start.elapsed();
+
+    let peak = ALLOC.peak() - base;
+    let output = p.len() * std::mem::size_of::<F128b>();
+
+    println!(
+        "{:<12} n = 2^{:<2} time: {:>10.3} ms, peak heap: {:>8.1} MiB ({:>6.1} bytes/point, output {:.1} MiB)",
+        name,
+        size.trailing_zeros(),
+        elapsed.as_secs_f64() * 1000.0,
+        peak as f64 / (1 << 20) as f64,
+        peak as f64 / size as f64,
+        output as f64 / (1 << 20) as f64,
+    );
+}
+
+fn main() {
+    for e in [18, 20, 22] {
+        let size: usize = 1 << e;
+
+        measure("sequential", size, PaxosSolver::<F128b>::encode_sequential);
+        measure("parallel", size, PaxosSolver::<F128b>::encode_parallel);
+    }
+}
@@ -0,0 +1,136 @@
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use criterion::{criterion_group, criterion_main, Bencher};
+use preprocessing_mpsi_with_vole::hasher::Sha256Hasher;
+use preprocessing_mpsi_with_vole::solver::{BandSolver, PaxosSolver, TwoCoreSolver};
+use preprocessing_mpsi_with_vole::solver::{Solver, SolverOptions};
+use rand::Rng;
+use scuttlebutt::field::F128b;
+use scuttlebutt::AesRng;
+
+fn create_points(size: usize) -> Vec<(F128b, F128b)> {
+    let mut rng = AesRng::new();
+    (0..size).map(|_| (rng.gen(), rng.gen())).collect()
+}
+
+fn encode_fn<S: Solver<F128b>>() -> impl FnMut(&mut Bencher<'_>, &usize) {
+    move |b, &size| {
+        let points = create_points(size);
+        let mut rng = AesRng::new();
+        let aux = S::gen_aux(&mut rng).unwrap();
+        let params = S::calc_params(size);
+
+        b.iter(|| S::encode(&mut rng, &points, aux, params).unwrap());
+    }
+}
+
+fn decode_fn<S: Solver<F128b>>() -> impl FnMut(&mut Bencher<'_>, &usize) {
+    move |b, &size| {
+        let points = create_points(size);
+        let mut rng = AesRng::new();
+        let aux = S::gen_aux(&mut rng).unwrap();
+        let params = S::calc_params(size);
+        let p = S::encode(&mut rng, &points, aux, params).unwrap();
+
+        b.iter(|| {
+            points
+                .iter()
+                .map(|(x, _)| S::decode(&p, *x, aux, params).unwrap())
+                .collect::<Vec<_>>()
+        });
+    }
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let min_e = 10;
+    let max_e = 18;
+
+    let mut group = c.benchmark_group("solver_encode");
+    for e in (min_e..=max_e).step_by(2) {
+        let size: usize = 1 << e;
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Paxos", size),
+            &size,
+            encode_fn::<PaxosSolver<F128b>>(),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Paxos-Sha256", size),
+            &size,
+            encode_fn::<PaxosSolver<F128b, Sha256Hasher>>(),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("TwoCore", size),
+            &size,
+            encode_fn::<TwoCoreSolver<F128b>>(),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Band", size),
+            &size,
+            encode_fn::<BandSolver<F128b>>(),
+        );
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let min_e = 10;
+    let max_e = 18;
+
+    let mut group = c.benchmark_group("solver_decode");
+    for e in (min_e..=max_e).step_by(2) {
+        let size: usize = 1 << e;
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("Paxos", size),
+            &size,
+            decode_fn::<PaxosSolver<F128b>>(),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Paxos-Sha256", size),
+            &size,
+            decode_fn::<PaxosSolver<F128b, Sha256Hasher>>(),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("TwoCore", size),
+            &size,
+            decode_fn::<TwoCoreSolver<F128b>>(),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("Band", size),
+            &size,
+            decode_fn::<BandSolver<F128b>>(),
+        );
+    }
+    group.finish();
+}
+
+// cost of the statistical security parameter
+fn bench_lambda(c: &mut Criterion) {
+    let size: usize = 1 << 16;
+
+    let mut group = c.benchmark_group("solver_lambda");
+    group.throughput(Throughput::Elements(size as u64));
+    for lambda in [40, 60, 80] {
+        group.bench_with_input(BenchmarkId::new("Paxos", lambda), &lambda, |b, &lambda| {
+            let points = create_points(size);
+            let mut rng = AesRng::new();
+            let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+            let params =
+                PaxosSolver::<F128b>::calc_params_with(size, SolverOptions::with_lambda(lambda));
+
+            b.iter(|| PaxosSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    name = solver_benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_encode, bench_decode, bench_lambda
+);
+criterion_main!(solver_benches);
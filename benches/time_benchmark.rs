@@ -10,7 +10,10 @@ use scuttlebutt::field::F128b;
 use std::cell::RefCell;
 use std::rc::Rc;
 mod time_common;
-use time_common::{kmprt_tcp_fn, kmprt_unix_fn, preprocessed_tcp_fn, preprocessed_unix_fn};
+use time_common::{
+    execution_name, kmprt_tcp_fn, kmprt_unix_fn, preprocessed_tcp_fn, preprocessed_unix_fn,
+    EXECUTIONS,
+};
 
 fn bench_kmprt(c: &mut Criterion) {
     let nparties = 5;
@@ -21,17 +24,20 @@ fn bench_kmprt(c: &mut Criterion) {
     for e in min_e..=max_e {
         let size: usize = 1 << e;
         group.throughput(Throughput::Elements(size as u64));
-        group.bench_with_input(
-            BenchmarkId::new("UnixStream", size),
-            &size,
-            kmprt_unix_fn(nparties),
-        );
-        let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
-        group.bench_with_input(
-            BenchmarkId::new("TcpStream", size),
-            &size,
-            kmprt_tcp_fn(nparties, base_port_rc),
-        );
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            group.bench_with_input(
+                BenchmarkId::new(format!("UnixStream_{}", name), size),
+                &size,
+                kmprt_unix_fn(nparties, execution),
+            );
+            let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
+            group.bench_with_input(
+                BenchmarkId::new(format!("TcpStream_{}", name), size),
+                &size,
+                kmprt_tcp_fn(nparties, execution, base_port_rc),
+            );
+        }
     }
     group.finish();
 }
@@ -45,26 +51,31 @@ fn bench_preprocessed_svole_poly(c: &mut Criterion) {
     for e in min_e..=max_e {
         let size: usize = 1 << e;
         group.throughput(Throughput::Elements(size as u64));
-        group.bench_with_input(
-            BenchmarkId::new("UnixStream", size),
-            &size,
-            preprocessed_unix_fn::<F128b, VandelmondeSolver<F128b>, _, _>(
-                nparties,
-                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
-                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
-            ),
-        );
-        let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
-        group.bench_with_input(
-            BenchmarkId::new("TcpStream", size),
-            &size,
-            preprocessed_tcp_fn::<F128b, VandelmondeSolver<F128b>, _, _>(
-                nparties,
-                LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
-                LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
-                base_port_rc,
-            ),
-        );
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            group.bench_with_input(
+                BenchmarkId::new(format!("UnixStream_{}", name), size),
+                &size,
+                preprocessed_unix_fn::<F128b, VandelmondeSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    execution,
+                ),
+            );
+            let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
+            group.bench_with_input(
+                BenchmarkId::new(format!("TcpStream_{}", name), size),
+                &size,
+                preprocessed_tcp_fn::<F128b, VandelmondeSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                    execution,
+                    base_port_rc,
+                ),
+            );
+        }
     }
     group.finish();
 }
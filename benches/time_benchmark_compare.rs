@@ -12,7 +12,10 @@ use scuttlebutt::field::F128b;
 use std::cell::RefCell;
 use std::rc::Rc;
 mod time_common;
-use time_common::{kmprt_tcp_fn, kmprt_unix_fn, preprocessed_tcp_fn, preprocessed_unix_fn};
+use time_common::{
+    execution_name, kmprt_tcp_fn, kmprt_unix_fn, preprocessed_tcp_fn, preprocessed_unix_fn,
+    EXECUTIONS,
+};
 
 fn bench_unix(c: &mut Criterion) {
     let nparties = 5;
@@ -31,20 +34,24 @@ fn bench_unix(c: &mut Criterion) {
         };
 
         group.throughput(Throughput::Elements(size as u64));
-        group.bench_with_input(
-            BenchmarkId::new("KMPRT", size),
-            &size,
-            kmprt_unix_fn(nparties),
-        );
-        group.bench_with_input(
-            BenchmarkId::new("Preprocessing_Paxos", size),
-            &size,
-            preprocessed_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-            ),
-        );
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            group.bench_with_input(
+                BenchmarkId::new(format!("KMPRT_{}", name), size),
+                &size,
+                kmprt_unix_fn(nparties, execution),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_Paxos_{}", name), size),
+                &size,
+                preprocessed_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(setup_param, extend_param),
+                    LPNVoleReceiver::new(setup_param, extend_param),
+                    execution,
+                ),
+            );
+        }
         /*
         group.bench_with_input(
             BenchmarkId::new("Preprocessing_poly", size),
@@ -53,6 +60,7 @@ fn bench_unix(c: &mut Criterion) {
                 nparties,
                 LPNVoleSender::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
                 LPNVoleReceiver::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL),
+                Execution::Sequential,
             ),
         );
         */
@@ -77,23 +85,27 @@ fn bench_tcp(c: &mut Criterion) {
         };
 
         group.throughput(Throughput::Elements(size as u64));
-        let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
-        group.bench_with_input(
-            BenchmarkId::new("KMPRT", size),
-            &size,
-            kmprt_tcp_fn(nparties, base_port_rc),
-        );
-        let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(20000));
-        group.bench_with_input(
-            BenchmarkId::new("Preprocessing_Paxos", size),
-            &size,
-            preprocessed_tcp_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-                base_port_rc,
-            ),
-        );
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
+            group.bench_with_input(
+                BenchmarkId::new(format!("KMPRT_{}", name), size),
+                &size,
+                kmprt_tcp_fn(nparties, execution, base_port_rc),
+            );
+            let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(20000));
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_Paxos_{}", name), size),
+                &size,
+                preprocessed_tcp_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(setup_param, extend_param),
+                    LPNVoleReceiver::new(setup_param, extend_param),
+                    execution,
+                    base_port_rc,
+                ),
+            );
+        }
     }
     group.finish();
 }
@@ -3,7 +3,6 @@ use criterion::Criterion;
 use criterion::Throughput;
 use criterion::{criterion_group, criterion_main};
 use criterion::{AxisScale, PlotConfiguration};
-use preprocessing_mpsi_with_vole::set_utils::create_sets_random;
 use preprocessing_mpsi_with_vole::solver::Solver;
 use preprocessing_mpsi_with_vole::solver::{PaxosSolver, SolverParams};
 use preprocessing_mpsi_with_vole::vole::{
@@ -11,15 +10,13 @@ use preprocessing_mpsi_with_vole::vole::{
     LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM, LPN_SETUP_SMALL,
 };
 use scuttlebutt::field::F128b;
-use scuttlebutt::{AesRng, Block};
 use std::cell::RefCell;
 use std::rc::Rc;
-mod time_common_mt;
+mod time_common;
 use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
-use std::sync::Arc;
-use time_common_mt::{
-    kmprt_mt_tcp_fn, kmprt_mt_unix_fn, preprocessed_mt_tcp_fn, preprocessed_mt_unix_fn,
-    /* preprocessed_mt_with_offline_tcp_fn, */ preprocessed_mt_with_offline_unix_fn,
+use time_common::{
+    execution_name, kmprt_tcp_fn, kmprt_unix_fn, preprocessed_tcp_fn, preprocessed_unix_fn,
+    preprocessed_with_offline_unix_fn, EXECUTIONS,
 };
 
 fn bench_unix_mt_base(
@@ -48,49 +45,45 @@ fn bench_unix_mt_base(
             (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
         };
 
-        let mut rng = AesRng::new();
-        let (_common, sets): (Vec<Block>, _) =
-            create_sets_random(nparties, size, &mut rng).unwrap();
-        let sets_for_kmprt = sets.into_iter().map(Arc::new).collect::<Vec<_>>();
-        let (_common, sets): (Vec<F128b>, _) =
-            create_sets_random(nparties, size, &mut rng).unwrap();
-        let sets_for_prep = sets.into_iter().map(Arc::new).collect::<Vec<_>>();
-
         group.throughput(Throughput::Elements(size as u64));
-        group.bench_function(
-            BenchmarkId::new("KMPRT17_Multithread", size),
-            kmprt_mt_unix_fn(nparties, sets_for_kmprt.clone()),
-        );
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_Paxos_Multithread", size),
-            preprocessed_mt_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-            ),
-        );
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_offLPN_Paxos_Multithread", size),
-            preprocessed_mt_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-            ),
-        );
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_offOT_Paxos_Multithread", size),
-            preprocessed_mt_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                OtVoleSender::<F128b, 128, OtSender>::new(),
-                OtVoleReceiver::<F128b, 128, OtReceiver>::new(),
-            ),
-        );
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            group.bench_with_input(
+                BenchmarkId::new(format!("KMPRT17_{}", name), size),
+                &size,
+                kmprt_unix_fn(nparties, execution),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_Paxos_{}", name), size),
+                &size,
+                preprocessed_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(setup_param, extend_param),
+                    LPNVoleReceiver::new(setup_param, extend_param),
+                    execution,
+                ),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_offLPN_Paxos_{}", name), size),
+                &size,
+                preprocessed_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(setup_param, extend_param),
+                    LPNVoleReceiver::new(setup_param, extend_param),
+                    execution,
+                ),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_offOT_Paxos_{}", name), size),
+                &size,
+                preprocessed_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    OtVoleSender::<F128b, OtSender>::new(),
+                    OtVoleReceiver::<F128b, OtReceiver>::new(),
+                    execution,
+                ),
+            );
+        }
     }
     group.finish();
 }
@@ -142,32 +135,30 @@ fn bench_unix_mt_with_offline_base(
             (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
         };
 
-        let mut rng = AesRng::new();
-        let (_common, sets): (Vec<F128b>, _) =
-            create_sets_random(nparties, size, &mut rng).unwrap();
-        let sets_for_prep = sets.into_iter().map(Arc::new).collect::<Vec<_>>();
-
         group.throughput(Throughput::Elements(size as u64));
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_offLPN_Paxos_Multithread", size),
-            preprocessed_mt_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-            ),
-        );
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_offOT_Paxos_Multithread", size),
-            preprocessed_mt_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                OtVoleSender::<F128b, 128, OtSender>::new(),
-                OtVoleReceiver::<F128b, 128, OtReceiver>::new(),
-            ),
-        );
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_offLPN_Paxos_{}", name), size),
+                &size,
+                preprocessed_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(setup_param, extend_param),
+                    LPNVoleReceiver::new(setup_param, extend_param),
+                    execution,
+                ),
+            );
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_offOT_Paxos_{}", name), size),
+                &size,
+                preprocessed_with_offline_unix_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    OtVoleSender::<F128b, OtSender>::new(),
+                    OtVoleReceiver::<F128b, OtReceiver>::new(),
+                    execution,
+                ),
+            );
+        }
     }
     group.finish();
 }
@@ -219,56 +210,28 @@ fn bench_tcp_mt_base(
             (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
         };
 
-        let mut rng = AesRng::new();
-        let (_common, sets): (Vec<Block>, _) =
-            create_sets_random(nparties, size, &mut rng).unwrap();
-        let sets_for_kmprt = sets.into_iter().map(Arc::new).collect::<Vec<_>>();
-        let (_common, sets): (Vec<F128b>, _) =
-            create_sets_random(nparties, size, &mut rng).unwrap();
-        let sets_for_prep = sets.into_iter().map(Arc::new).collect::<Vec<_>>();
-
         group.throughput(Throughput::Elements(size as u64));
-        let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
-        group.bench_function(
-            BenchmarkId::new("KMPRT17_Multithread", size),
-            kmprt_mt_tcp_fn(nparties, sets_for_kmprt, base_port_rc),
-        );
-        let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(20000));
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_Paxos_Multithread", size),
-            preprocessed_mt_tcp_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-                base_port_rc.clone(),
-            ),
-        );
-        /*
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_offLPN_Paxos_Multithread", size),
-            preprocessed_mt_with_offline_tcp_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep.clone(),
-                size,
-                LPNVoleSender::new(setup_param, extend_param),
-                LPNVoleReceiver::new(setup_param, extend_param),
-                base_port_rc.clone(),
-            ),
-        );
-        group.bench_function(
-            BenchmarkId::new("Preprocessing_offOT_Paxos_Multithread", size),
-            preprocessed_mt_with_offline_tcp_fn::<F128b, PaxosSolver<F128b>, _, _>(
-                nparties,
-                sets_for_prep,
-                size,
-                OtVoleSender::<F128b, 128, OtSender>::new(),
-                OtVoleReceiver::<F128b, 128, OtReceiver>::new(),
-                base_port_rc,
-            ),
-        );
-        */
+        for execution in EXECUTIONS {
+            let name = execution_name(execution);
+            let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(10000));
+            group.bench_with_input(
+                BenchmarkId::new(format!("KMPRT17_{}", name), size),
+                &size,
+                kmprt_tcp_fn(nparties, execution, base_port_rc),
+            );
+            let base_port_rc: Rc<RefCell<usize>> = Rc::new(RefCell::new(20000));
+            group.bench_with_input(
+                BenchmarkId::new(format!("Preprocessing_Paxos_{}", name), size),
+                &size,
+                preprocessed_tcp_fn::<F128b, PaxosSolver<F128b>, _, _>(
+                    nparties,
+                    LPNVoleSender::new(setup_param, extend_param),
+                    LPNVoleReceiver::new(setup_param, extend_param),
+                    execution,
+                    base_port_rc,
+                ),
+            );
+        }
     }
     group.finish();
 }
@@ -1,35 +1,82 @@
 use criterion::Bencher;
-use popsicle::kmprt::{Receiver as KmprtReceiver, Sender as KmprtSender};
 use preprocessing_mpsi_with_vole::channel_utils::sync_channel::create_unix_channels;
 use preprocessing_mpsi_with_vole::channel_utils::tcp_channel::{
     create_tcp_channels_for_receiver, create_tcp_channels_for_sender,
 };
+use preprocessing_mpsi_with_vole::kmprt17::{Receiver as KmprtReceiver, Sender as KmprtSender};
+use preprocessing_mpsi_with_vole::parallel_utils::{Execution, Threads};
+use preprocessing_mpsi_with_vole::preprocessed::handshake::FieldId;
 use preprocessing_mpsi_with_vole::preprocessed::psi::{
-    Receiver as SepReceiver, Sender as SepSender,
+    PsiOptions, Receiver as SepReceiver, Sender as SepSender,
 };
 use preprocessing_mpsi_with_vole::set_utils::{create_sets_random, FromU128};
 use preprocessing_mpsi_with_vole::solver::Solver;
+use preprocessing_mpsi_with_vole::testing::SeedTree;
 use preprocessing_mpsi_with_vole::vole::{VoleShareForReceiver, VoleShareForSender};
 use rand::distributions::{Distribution, Standard};
-use scuttlebutt::{field::FiniteField as FF, AesRng, Block, SyncChannel};
+use scuttlebutt::{field::FiniteField as FF, Block, SyncChannel};
 use std::cell::RefCell;
 use std::io::{Read, Write};
 use std::rc::Rc;
+use std::sync::OnceLock;
 use std::time::{Duration, Instant};
 
+// Benchmarks are deterministic so that runs are comparable.
+#[allow(unused)]
+const BENCH_SEED: u128 = 0x5eed;
+
+#[allow(unused)]
+pub(crate) fn bench_seeds() -> SeedTree {
+    SeedTree::new(Block::from(BENCH_SEED))
+}
+
+// Every benchmark runs with each of them.
+#[allow(unused)]
+pub(crate) const EXECUTIONS: [Execution; 3] = [
+    Execution::Sequential,
+    Execution::ThreadPerPeer,
+    Execution::Pool(2),
+];
+
+// Name of `execution` in benchmark IDs.
+#[allow(unused)]
+pub(crate) fn execution_name(execution: Execution) -> String {
+    match execution {
+        Execution::Sequential => "Sequential".to_string(),
+        Execution::ThreadPerPeer => "ThreadPerPeer".to_string(),
+        Execution::Pool(threads) => format!("Pool{}", threads),
+    }
+}
+
+// Options of the preprocessed parties. All parties of all benchmarks share one pool of the available parallelism.
+#[allow(unused)]
+fn psi_options(execution: Execution) -> PsiOptions {
+    static THREADS: OnceLock<Threads> = OnceLock::new();
+    PsiOptions {
+        execution,
+        threads: THREADS.get_or_init(|| Threads::new(0).unwrap()).clone(),
+        ..Default::default()
+    }
+}
+
 #[allow(unused)]
 fn kmprt_routine<R, W>(
     mut sets: Vec<Vec<Block>>,
     mut receiver_channels: Vec<(usize, SyncChannel<R, W>)>,
     channels: Vec<Vec<(usize, SyncChannel<R, W>)>>,
+    execution: Execution,
 ) -> Duration
 where
     R: Read + Send + 'static,
     W: Write + Send + 'static,
 {
     let recv_set = sets.pop().unwrap();
-    let mut rngs = vec![AesRng::new(); channels.len()];
-    let mut rng = AesRng::new();
+    // party `i` pops the `i`-th RNG
+    let mut rngs = (1..=channels.len())
+        .rev()
+        .map(|pid| bench_seeds().party(pid).phase("online").rng())
+        .collect::<Vec<_>>();
+    let mut rng = bench_seeds().party(0).phase("online").rng();
 
     let mut handles = Vec::new();
     for (i, mut channels) in channels.into_iter().enumerate() {
@@ -37,13 +84,15 @@ where
         let set = sets.pop().unwrap();
         let mut rng = rngs.pop().unwrap();
         let handle = std::thread::spawn(move || {
-            let mut sender = KmprtSender::init(i, &mut channels, &mut rng).unwrap();
+            let mut sender =
+                KmprtSender::init_with_execution(i, &mut channels, &mut rng, execution).unwrap();
             sender.send(&set, &mut channels, &mut rng).unwrap();
         });
         handles.push(handle);
     }
 
-    let mut receiver = KmprtReceiver::init(&mut receiver_channels, &mut rng).unwrap();
+    let mut receiver =
+        KmprtReceiver::init_with_execution(&mut receiver_channels, &mut rng, execution).unwrap();
     let start = Instant::now();
     let _res = receiver
         .receive(&recv_set, &mut receiver_channels, &mut rng)
@@ -55,10 +104,13 @@ where
 }
 
 #[allow(unused)]
-pub(crate) fn kmprt_unix_fn(nparties: usize) -> impl FnMut(&mut Bencher<'_>, &usize) {
+pub(crate) fn kmprt_unix_fn(
+    nparties: usize,
+    execution: Execution,
+) -> impl FnMut(&mut Bencher<'_>, &usize) {
     move |b, &size| {
         b.iter_custom(|iter| {
-            let mut rng = AesRng::new();
+            let mut rng = bench_seeds().sets().rng();
             let (_common, sets): (Vec<Block>, _) =
                 create_sets_random(nparties, size, &mut rng).unwrap();
             let mut total_time = Duration::new(0, 0);
@@ -68,7 +120,7 @@ pub(crate) fn kmprt_unix_fn(nparties: usize) -> impl FnMut(&mut Bencher<'_>, &us
                 let sets = sets.clone();
                 // let common = common.clone();
 
-                total_time += kmprt_routine(sets, receiver_channels, channels);
+                total_time += kmprt_routine(sets, receiver_channels, channels, execution);
             }
 
             total_time
@@ -79,12 +131,13 @@ pub(crate) fn kmprt_unix_fn(nparties: usize) -> impl FnMut(&mut Bencher<'_>, &us
 #[allow(unused)]
 pub(crate) fn kmprt_tcp_fn(
     nparties: usize,
+    execution: Execution,
     base_port_rc: Rc<RefCell<usize>>,
 ) -> impl FnMut(&mut Bencher<'_>, &usize) {
     move |b, &size| {
         let bport_rc = Rc::clone(&base_port_rc);
         b.iter_custom(|iter| {
-            let mut rng = AesRng::new();
+            let mut rng = bench_seeds().sets().rng();
             let (_common, sets): (Vec<Block>, _) =
                 create_sets_random(nparties, size, &mut rng).unwrap();
             let mut total_time = Duration::new(0, 0);
@@ -113,7 +166,7 @@ pub(crate) fn kmprt_tcp_fn(
                 let sets = sets.clone();
                 // let common = common.clone();
 
-                total_time += kmprt_routine(sets, receiver_channels, channels);
+                total_time += kmprt_routine(sets, receiver_channels, channels, execution);
             }
 
             total_time
@@ -132,15 +185,19 @@ fn preprocessed_routine<R, W, F, S, VS, VR>(
 where
     R: Read + Send + 'static,
     W: Write + Send + 'static,
-    F: FF,
+    F: FF + FieldId,
     S: Solver<F> + Send + 'static,
     VS: VoleShareForSender<F> + Send + 'static,
     VR: VoleShareForReceiver<F> + Send + 'static,
     Standard: Distribution<F>,
 {
     let recv_set = sets.pop().unwrap();
-    let mut rngs = vec![AesRng::new(); channels.len()];
-    let mut rng = AesRng::new();
+    // party `i` pops the `i`-th RNG
+    let mut rngs = (1..=channels.len())
+        .rev()
+        .map(|pid| bench_seeds().party(pid).phase("online").rng())
+        .collect::<Vec<_>>();
+    let mut rng = bench_seeds().party(0).phase("online").rng();
 
     let mut handles = Vec::new();
     for (i, mut channels) in channels.into_iter().enumerate() {
@@ -171,9 +228,10 @@ fn create_parties<F, S, VS, VR>(
     set_size: usize,
     vole_share_for_s: VS,
     vole_share_for_r: VR,
+    execution: Execution,
 ) -> (SepReceiver<F, S, VS, VR>, Vec<SepSender<F, S, VS, VR>>)
 where
-    F: FF,
+    F: FF + FieldId,
     S: Solver<F> + Send + 'static,
     VS: VoleShareForSender<F> + Send + 'static,
     VR: VoleShareForReceiver<F> + Send + 'static,
@@ -188,16 +246,17 @@ where
         let vole_share_for_s = vole_share_for_s.clone();
         let vole_share_for_r = vole_share_for_r.clone();
         handles.push(std::thread::spawn(move || {
-            let mut rng = AesRng::new();
+            let mut rng = bench_seeds().party(pid).phase("offline").rng();
 
             // offline phase
-            let sender = SepSender::<F, S, _, _>::precomp(
+            let sender = SepSender::<F, S, _, _>::precomp_with_options(
                 pid,
                 &mut channels,
                 &mut rng,
                 vole_share_for_s,
                 vole_share_for_r,
                 set_size,
+                psi_options(execution),
             )
             .unwrap();
 
@@ -205,13 +264,14 @@ where
         }));
     }
 
-    let mut rng = AesRng::new();
-    let receiver = SepReceiver::<F, S, _, _>::precomp(
+    let mut rng = bench_seeds().party(0).phase("offline").rng();
+    let receiver = SepReceiver::<F, S, _, _>::precomp_with_options(
         &mut receiver_channels,
         &mut rng,
         vole_share_for_s,
         vole_share_for_r,
         set_size,
+        psi_options(execution),
     )
     .unwrap();
 
@@ -228,20 +288,26 @@ pub(crate) fn preprocessed_unix_fn<F, S, VS, VR>(
     nparties: usize,
     vole_share_for_s: VS,
     vole_share_for_r: VR,
+    execution: Execution,
 ) -> impl FnMut(&mut Bencher<'_>, &usize)
 where
-    F: FF + FromU128,
+    F: FF + FieldId + FromU128,
     S: Solver<F> + Send + 'static,
     VS: VoleShareForSender<F> + Send + 'static,
     VR: VoleShareForReceiver<F> + Send + 'static,
     Standard: Distribution<F>,
 {
     move |b, &size| {
-        let (receiver, senders) =
-            create_parties(nparties, size, vole_share_for_s, vole_share_for_r);
+        let (receiver, senders) = create_parties(
+            nparties,
+            size,
+            vole_share_for_s,
+            vole_share_for_r,
+            execution,
+        );
 
         b.iter_custom(move |iter| {
-            let mut rng = AesRng::new();
+            let mut rng = bench_seeds().sets().rng();
             let (_common, sets): (Vec<F>, _) =
                 create_sets_random(nparties, size, &mut rng).unwrap();
             let mut total_time = Duration::new(0, 0);
@@ -267,10 +333,11 @@ pub(crate) fn preprocessed_tcp_fn<F, S, VS, VR>(
     nparties: usize,
     vole_share_for_s: VS,
     vole_share_for_r: VR,
+    execution: Execution,
     base_port_rc: Rc<RefCell<usize>>,
 ) -> impl FnMut(&mut Bencher<'_>, &usize)
 where
-    F: FF + FromU128,
+    F: FF + FieldId + FromU128,
     S: Solver<F> + Send + 'static,
     VS: VoleShareForSender<F> + Send + 'static,
     VR: VoleShareForReceiver<F> + Send + 'static,
@@ -278,11 +345,16 @@ where
 {
     move |b, &size| {
         let bport_rc = Rc::clone(&base_port_rc);
-        let (receiver, senders) =
-            create_parties(nparties, size, vole_share_for_s, vole_share_for_r);
+        let (receiver, senders) = create_parties(
+            nparties,
+            size,
+            vole_share_for_s,
+            vole_share_for_r,
+            execution,
+        );
 
         b.iter_custom(move |iter| {
-            let mut rng = AesRng::new();
+            let mut rng = bench_seeds().sets().rng();
             let (_common, sets): (Vec<F>, _) =
                 create_sets_random(nparties, size, &mut rng).unwrap();
             let mut total_time = Duration::new(0, 0);
@@ -320,3 +392,116 @@ where
         });
     }
 }
+
+#[allow(unused)]
+fn preprocessed_with_offline_routine<R, W, F, S, VS, VR>(
+    mut sets: Vec<Vec<F>>,
+    mut receiver_channels: Vec<(usize, SyncChannel<R, W>)>,
+    channels: Vec<Vec<(usize, SyncChannel<R, W>)>>,
+    vole_share_for_s: VS,
+    vole_share_for_r: VR,
+    execution: Execution,
+) -> Duration
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+    F: FF + FieldId,
+    S: Solver<F> + Send + 'static,
+    VS: VoleShareForSender<F> + Send + 'static,
+    VR: VoleShareForReceiver<F> + Send + 'static,
+    Standard: Distribution<F>,
+{
+    let recv_set = sets.pop().unwrap();
+    // party `i` pops the `i`-th RNG
+    let mut rngs = (1..=channels.len())
+        .rev()
+        .map(|pid| bench_seeds().party(pid).phase("online").rng())
+        .collect::<Vec<_>>();
+    let mut rng = bench_seeds().party(0).phase("online").rng();
+
+    let mut handles = Vec::new();
+    for (i, mut channels) in channels.into_iter().enumerate() {
+        let pid = i + 1;
+        let set = sets.pop().unwrap();
+        let mut rng = rngs.pop().unwrap();
+
+        let vole_share_for_s = vole_share_for_s.clone();
+        let vole_share_for_r = vole_share_for_r.clone();
+        let handle = std::thread::spawn(move || {
+            // offline phase
+            let sender = SepSender::<F, S, _, _>::precomp_with_options(
+                pid,
+                &mut channels,
+                &mut rng,
+                vole_share_for_s,
+                vole_share_for_r,
+                set.len(),
+                psi_options(execution),
+            )
+            .unwrap();
+
+            // online phase
+            sender.send(&set, &mut channels, &mut rng).unwrap();
+        });
+        handles.push(handle);
+    }
+
+    let start = Instant::now();
+    let receiver = SepReceiver::<F, S, _, _>::precomp_with_options(
+        &mut receiver_channels,
+        &mut rng,
+        vole_share_for_s,
+        vole_share_for_r,
+        recv_set.len(),
+        psi_options(execution),
+    )
+    .unwrap();
+    let _res = receiver
+        .receive(&recv_set, &mut receiver_channels, &mut rng)
+        .unwrap();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+#[allow(unused)]
+pub(crate) fn preprocessed_with_offline_unix_fn<F, S, VS, VR>(
+    nparties: usize,
+    vole_share_for_s: VS,
+    vole_share_for_r: VR,
+    execution: Execution,
+) -> impl FnMut(&mut Bencher<'_>, &usize)
+where
+    F: FF + FieldId + FromU128,
+    S: Solver<F> + Send + 'static,
+    VS: VoleShareForSender<F> + Send + 'static,
+    VR: VoleShareForReceiver<F> + Send + 'static,
+    Standard: Distribution<F>,
+{
+    move |b, &size| {
+        b.iter_custom(|iter| {
+            let mut rng = bench_seeds().sets().rng();
+            let (_common, sets): (Vec<F>, _) =
+                create_sets_random(nparties, size, &mut rng).unwrap();
+            let mut total_time = Duration::new(0, 0);
+
+            for _ in 0..iter {
+                let (receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+
+                let sets = sets.clone();
+
+                total_time += preprocessed_with_offline_routine::<_, _, _, S, _, _>(
+                    sets,
+                    receiver_channels,
+                    channels,
+                    vole_share_for_s.clone(),
+                    vole_share_for_r.clone(),
+                    execution,
+                );
+            }
+
+            total_time
+        });
+    }
+}
@@ -0,0 +1,337 @@
+//! Channel wrapper which lets a failing party abort the protocol. See [AbortableChannel].
+//!
+//! [AbortableChannel] sends messages in frames.
+//!
+//! - DATA: `0u8 || len || payload`, the bytes written since the last frame. `len` is at most $`2^{16}`$,
+//!   so a long write is split into several frames and a peer never makes us allocate more than that.
+//! - ABORT: `1u8 || culprit || len || reason`, the ID of the party which caused the abort and why.
+//!
+//! When a party fails, it sends ABORT to every peer by [AbortHandle::abort].
+//! A peer reading from the channel then fails with [Error::Aborted](crate::error::Error::Aborted) naming the culprit
+//! instead of waiting for data which never comes. A party failing by an abort forwards it with the same culprit.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::abortable::AbortableChannel;
+//! use preprocessing_mpsi_with_vole::channel_utils::sync_channel::create_unix_channels;
+//! use preprocessing_mpsi_with_vole::error::Error;
+//! use scuttlebutt::AbstractChannel;
+//!
+//! let (mut receiver_channels, mut channels) = create_unix_channels(2).unwrap();
+//! let mut channel = AbortableChannel::new(receiver_channels.pop().unwrap().1);
+//! let peer = AbortableChannel::new(channels.pop().unwrap().pop().unwrap().1);
+//!
+//! peer.abort_handle().abort(1, "out of memory").unwrap();
+//!
+//! let err = Error::from(channel.read_usize().unwrap_err());
+//! assert!(matches!(err, Error::Aborted { culprit: 1, .. }));
+//! ```
+
+use scuttlebutt::AbstractChannel;
+use std::fmt::Display;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::sync::{Arc, Mutex};
+
+const DATA: u8 = 0;
+const ABORT: u8 = 1;
+
+// tag and length
+const HEADER_LEN: usize = 9;
+// maximum payload of a DATA frame. A frame is sent without flush when this many bytes are pending
+const MAX_PENDING: usize = 1 << 16;
+const MAX_REASON_LEN: usize = 1 << 12;
+
+/// ABORT frame received from a peer.
+///
+/// Reading from an [AbortableChannel] fails with [ErrorKind::ConnectionAborted] wrapping it,
+/// which is converted to [Error::Aborted](crate::error::Error::Aborted).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aborted {
+    /// ID of the party which caused the abort.
+    pub culprit: usize,
+    /// Why the party aborted.
+    pub reason: String,
+}
+
+impl Display for Aborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "aborted by party {}: {}", self.culprit, self.reason)
+    }
+}
+
+impl std::error::Error for Aborted {}
+
+#[derive(Default)]
+struct Received {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+/// Channel wrapper sending data in frames so that an ABORT frame can interrupt it.
+///
+/// Clones share the buffers in the same way as [scuttlebutt::SyncChannel].
+/// Both ends of a channel must be wrapped.
+pub struct AbortableChannel<C: AbstractChannel> {
+    inner: C,
+    // header space followed by bytes not sent yet
+    pending: Arc<Mutex<Vec<u8>>>,
+    received: Arc<Mutex<Received>>,
+}
+
+impl<C: AbstractChannel> AbortableChannel<C> {
+    /// Wrap `inner`.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            pending: Arc::new(Mutex::new(vec![0u8; HEADER_LEN])),
+            received: Arc::new(Mutex::new(Received::default())),
+        }
+    }
+
+    /// Handle to send ABORT to the peer even while the channel is in use by another thread.
+    pub fn abort_handle(&self) -> AbortHandle<C> {
+        AbortHandle {
+            inner: self.inner.clone(),
+        }
+    }
+
+    fn send_pending(&mut self) -> IoResult<()> {
+        let mut pending = self.pending.lock().unwrap();
+        send_data(&mut self.inner, &mut pending)
+    }
+}
+
+fn send_data<C: AbstractChannel>(inner: &mut C, pending: &mut Vec<u8>) -> IoResult<()> {
+    let len = pending.len() - HEADER_LEN;
+    if len == 0 {
+        return Ok(());
+    }
+
+    pending[0] = DATA;
+    pending[1..HEADER_LEN].copy_from_slice(&(len as u64).to_le_bytes());
+    // header and payload in one write so that an ABORT frame never splits a DATA frame
+    inner.write_bytes(pending)?;
+    pending.truncate(HEADER_LEN);
+
+    Ok(())
+}
+
+// Payload of the next non-empty DATA frame.
+fn receive_data<C: AbstractChannel>(inner: &mut C) -> IoResult<Vec<u8>> {
+    loop {
+        match inner.read_u8()? {
+            DATA => {
+                let len = inner.read_u64()? as usize;
+                if len == 0 {
+                    continue;
+                }
+                if len > MAX_PENDING {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("too long data frame: {} bytes", len),
+                    ));
+                }
+                let mut buf = vec![0u8; len];
+                inner.read_bytes(&mut buf)?;
+                return Ok(buf);
+            }
+            ABORT => {
+                let culprit = inner.read_u64()? as usize;
+                let len = inner.read_u64()? as usize;
+                if len > MAX_REASON_LEN {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("too long reason of abort: {} bytes", len),
+                    ));
+                }
+                let mut buf = vec![0u8; len];
+                inner.read_bytes(&mut buf)?;
+                let reason = String::from_utf8_lossy(&buf).into_owned();
+                return Err(IoError::new(
+                    ErrorKind::ConnectionAborted,
+                    Aborted { culprit, reason },
+                ));
+            }
+            tag => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown frame tag: {}", tag),
+                ))
+            }
+        }
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for AbortableChannel<C> {
+    fn write_bytes(&mut self, mut bytes: &[u8]) -> IoResult<()> {
+        let mut pending = self.pending.lock().unwrap();
+        while !bytes.is_empty() {
+            let n = (HEADER_LEN + MAX_PENDING - pending.len()).min(bytes.len());
+            pending.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if pending.len() == HEADER_LEN + MAX_PENDING {
+                send_data(&mut self.inner, &mut pending)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> IoResult<()> {
+        // the peer may be waiting for what we wrote before
+        self.flush()?;
+
+        let mut received = self.received.lock().unwrap();
+        let mut filled = 0;
+        while filled < bytes.len() {
+            if received.pos == received.buf.len() {
+                received.buf = receive_data(&mut self.inner)?;
+                received.pos = 0;
+            }
+
+            let n = (bytes.len() - filled).min(received.buf.len() - received.pos);
+            bytes[filled..filled + n]
+                .copy_from_slice(&received.buf[received.pos..received.pos + n]);
+            filled += n;
+            received.pos += n;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.send_pending()?;
+        self.inner.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            pending: Arc::clone(&self.pending),
+            received: Arc::clone(&self.received),
+        }
+    }
+}
+
+/// Handle to send ABORT through an [AbortableChannel]. See [AbortableChannel::abort_handle].
+pub struct AbortHandle<C: AbstractChannel> {
+    inner: C,
+}
+
+impl<C: AbstractChannel> AbortHandle<C> {
+    /// Tell the peer that the protocol is aborted because of party `culprit`.
+    ///
+    /// `reason` is truncated to 4096 bytes.
+    pub fn abort(mut self, culprit: usize, reason: &str) -> IoResult<()> {
+        let mut end = reason.len().min(MAX_REASON_LEN);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        let reason = &reason.as_bytes()[..end];
+
+        let mut frame = Vec::with_capacity(2 * HEADER_LEN + reason.len());
+        frame.push(ABORT);
+        frame.extend_from_slice(&(culprit as u64).to_le_bytes());
+        frame.extend_from_slice(&(reason.len() as u64).to_le_bytes());
+        frame.extend_from_slice(reason);
+
+        self.inner.write_bytes(&frame)?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+    use crate::channel_utils::{read_vec_f, write_vec_f};
+    use crate::error::Error;
+    use rand::Rng;
+    use scuttlebutt::{field::F128b, AesRng};
+
+    #[test]
+    fn test_data_and_abort() {
+        let mut rng = AesRng::new();
+        let v = (0..10000).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let w = v.clone();
+
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+        let mut channel = AbortableChannel::new(receiver_channels.pop().unwrap().1);
+        let mut peer = AbortableChannel::new(channels.pop().unwrap().pop().unwrap().1);
+
+        let handle = std::thread::spawn(move || {
+            peer.write_u8(10).unwrap();
+            peer.flush().unwrap();
+
+            // larger than MAX_PENDING
+            write_vec_f(&mut peer, &w).unwrap();
+
+            // not flushed explicitly
+            peer.write_usize(42).unwrap();
+            assert_eq!(peer.read_usize().unwrap(), 43);
+
+            peer.abort_handle()
+                .abort(2, "something went wrong")
+                .unwrap();
+        });
+
+        assert_eq!(channel.read_u8().unwrap(), 10);
+        assert_eq!(read_vec_f::<F128b, _>(&mut channel).unwrap(), v);
+        assert_eq!(channel.clone().read_usize().unwrap(), 42);
+        channel.write_usize(43).unwrap();
+        channel.flush().unwrap();
+
+        let err = Error::from(channel.read_u8().unwrap_err());
+        match err {
+            Error::Aborted { culprit, reason } => {
+                assert_eq!(culprit, 2);
+                assert_eq!(reason, "something went wrong");
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_frame_length() {
+        let mut rng = AesRng::new();
+        let v = (0..3 * MAX_PENDING + 5)
+            .map(|_| rng.gen())
+            .collect::<Vec<u8>>();
+        let w = v.clone();
+
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+        let mut raw = receiver_channels.pop().unwrap().1;
+        let mut peer = AbortableChannel::new(channels.pop().unwrap().pop().unwrap().1);
+
+        let handle = std::thread::spawn(move || {
+            // one write longer than a frame
+            peer.write_bytes(&w).unwrap();
+            peer.flush().unwrap();
+            peer
+        });
+
+        // every frame carries at most MAX_PENDING bytes
+        let mut received = Vec::new();
+        while received.len() < v.len() {
+            assert_eq!(raw.read_u8().unwrap(), DATA);
+            let len = raw.read_u64().unwrap() as usize;
+            assert!(0 < len && len <= MAX_PENDING);
+            let mut buf = vec![0u8; len];
+            raw.read_bytes(&mut buf).unwrap();
+            received.extend(buf);
+        }
+        assert_eq!(received, v);
+
+        let mut peer = handle.join().unwrap();
+
+        // a frame longer than MAX_PENDING is rejected before allocation
+        raw.write_u8(DATA).unwrap();
+        raw.write_u64(u64::MAX).unwrap();
+        raw.flush().unwrap();
+        let err = peer.read_u8().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -51,117 +51,235 @@
 //!
 //! For more information, the document of [scuttlebutt::AbstractChannel] will help you.
 
-use anyhow::{Context, Result};
+use crate::error::{Context, Error, Result};
 use scuttlebutt::field::FiniteField as FF;
 use scuttlebutt::AbstractChannel;
-use std::sync::{Arc, Mutex};
 use typenum::marker_traits::Unsigned;
 
+pub mod abortable;
+pub mod mux;
 pub mod sync_channel;
 pub mod sync_channel_by_cb;
 pub mod tcp_channel;
+pub mod transcript;
+
+/// Bytes encoded at once by [write_vec_f].
+const WRITE_CHUNK_BYTES: usize = 1 << 16;
 
 /// Write a vector of field elements to a channel.
+///
+/// Elements are encoded in chunks of 64 KiB, so that the whole byte vector is never materialized.
 pub fn write_vec_f<F, C>(channel: &mut C, v: &[F]) -> Result<usize>
 where
     F: FF,
     C: AbstractChannel,
 {
-    let bytes = v
-        .iter()
-        .flat_map(|x| x.to_bytes().to_vec())
-        .collect::<Vec<_>>();
-
-    let len = bytes.len();
+    let elem_len = F::ByteReprLen::to_usize();
+    let len = v.len() * elem_len;
 
-    channel
-        .write_usize(len)
-        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    channel.write_usize(len).located()?;
 
-    channel
-        .write_bytes(&bytes)
-        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    let mut buf = Vec::with_capacity(WRITE_CHUNK_BYTES.min(len));
+    for chunk in v.chunks((WRITE_CHUNK_BYTES / elem_len).max(1)) {
+        buf.clear();
+        buf.extend(chunk.iter().flat_map(|x| x.to_bytes()));
+        channel.write_bytes(&buf).located()?;
+    }
 
-    channel
-        .flush()
-        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    channel.flush().located()?;
 
     Ok(len)
 }
 
+/// Bytes decoded at once by [VecFReader].
+const READ_CHUNK_BYTES: usize = 1 << 16;
+
 /// Read a vector of field elements from a channel.
+///
+/// The length sent by the peer is not trusted: memory is allocated as elements arrive, in the same way as [VecFReader].
+/// Use [read_vec_f_exact] if the length is known.
 pub fn read_vec_f<F, C>(channel: &mut C) -> Result<Vec<F>>
 where
     F: FF,
     C: AbstractChannel,
 {
-    let bytes_len = channel
-        .read_usize()
-        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-    let mut res = vec![0u8; bytes_len];
+    VecFReader::new(channel).located()?.collect()
+}
 
-    channel.read_bytes(&mut res)?;
+/// Read a vector of exactly `len` field elements from a channel.
+///
+/// Fails with [Error::ProtocolViolation] before reading the elements if the peer sends another length.
+pub fn read_vec_f_exact<F, C>(channel: &mut C, len: usize) -> Result<Vec<F>>
+where
+    F: FF,
+    C: AbstractChannel,
+{
+    let reader = VecFReader::new(channel).located()?;
+    if reader.len() != len {
+        return Err(Error::protocol_violation(format!(
+            "expected {} field elements, but the peer sends {}",
+            len,
+            reader.len()
+        )));
+    }
 
-    let res = res
-        .chunks(F::ByteReprLen::to_usize())
-        .into_iter()
-        .map(|x| {
-            F::from_bytes(x.as_ref().into()).with_context(|| format!("@{}:{}", file!(), line!()))
-        })
-        .collect::<Result<Vec<_>>>()?;
+    let mut res = Vec::with_capacity(len);
+    for x in reader {
+        res.push(x?);
+    }
 
     Ok(res)
 }
 
-/// Wrap channels with Arc<Mutex<_>>.
-pub fn ch_arcnize<C>(channels: Vec<(usize, C)>) -> Vec<(usize, Arc<Mutex<C>>)>
+/// Streaming reader of a vector written by [write_vec_f].
+///
+/// It reads the length prefix on [new](VecFReader::new) and then yields the elements,
+/// reading and decoding them in chunks of 64 KiB so that the whole vector is never buffered.
+///
+/// ```
+/// use preprocessing_mpsi_with_vole::channel_utils::{write_vec_f, VecFReader};
+/// use preprocessing_mpsi_with_vole::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+/// use preprocessing_mpsi_with_vole::set_utils::FromU128;
+/// use scuttlebutt::field::F128b;
+///
+/// let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+/// let v: Vec<F128b> = (0_u128..10000).map(F128b::from_u128).collect();
+/// write_vec_f(&mut channels[0][0].1, &v).unwrap();
+///
+/// let reader = VecFReader::<F128b, _>::new(&mut receiver_channels[0].1).unwrap();
+/// assert_eq!(reader.len(), 10000);
+///
+/// for (x, y) in reader.zip(v) {
+///     assert_eq!(x.unwrap(), y);
+/// }
+/// ```
+pub struct VecFReader<'a, F, C> {
+    channel: &'a mut C,
+    len: usize,
+    // elements not read from the channel yet
+    unread: usize,
+    // decoded elements of the current chunk in reverse order
+    chunk: Vec<F>,
+    buf: Vec<u8>,
+}
+
+impl<'a, F, C> VecFReader<'a, F, C>
 where
+    F: FF,
     C: AbstractChannel,
 {
-    let channels = channels
-        .into_iter()
-        .map(|(i, c)| (i, Arc::new(Mutex::new(c))))
-        .collect::<Vec<_>>();
+    /// Read the length prefix.
+    ///
+    /// Fails with [Error::ProtocolViolation] if the length in bytes is not a multiple of the element size.
+    pub fn new(channel: &'a mut C) -> Result<Self> {
+        let bytes_len = channel.read_usize().located()?;
 
-    channels
+        let elem_len = F::ByteReprLen::to_usize();
+        if bytes_len % elem_len != 0 {
+            return Err(Error::protocol_violation(format!(
+                "length (={}) is not a multiple of the size of a field element (={})",
+                bytes_len, elem_len
+            )));
+        }
+        let len = bytes_len / elem_len;
+
+        Ok(Self {
+            channel,
+            len,
+            unread: len,
+            chunk: Vec::new(),
+            buf: Vec::new(),
+        })
+    }
+
+    /// Number of elements sent by the peer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the peer sends no element.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements not yielded yet.
+    pub fn remaining(&self) -> usize {
+        self.unread + self.chunk.len()
+    }
+
+    fn read_chunk(&mut self) -> Result<()> {
+        let elem_len = F::ByteReprLen::to_usize();
+        let n = self.unread.min((READ_CHUNK_BYTES / elem_len).max(1));
+
+        self.buf.resize(n * elem_len, 0);
+        self.channel.read_bytes(&mut self.buf).located()?;
+        self.unread -= n;
+
+        self.chunk = self
+            .buf
+            .chunks_exact(elem_len)
+            .rev()
+            .map(|x| {
+                F::from_bytes(x.into()).map_err(|e| {
+                    Error::protocol_violation(format!("malformed field element: {}", e))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(())
+    }
 }
 
-/// Wrap channels with Arc<Mutex<_>> for all parties.
-pub fn ch_arcnize_all<C>(
-    receiver_channels: Vec<(usize, C)>,
-    channels: Vec<Vec<(usize, C)>>,
-) -> (
-    Vec<(usize, Arc<Mutex<C>>)>,
-    Vec<Vec<(usize, Arc<Mutex<C>>)>>,
-)
+impl<'a, F, C> Iterator for VecFReader<'a, F, C>
 where
+    F: FF,
     C: AbstractChannel,
 {
-    let receiver_channels = receiver_channels
-        .into_iter()
-        .map(|(i, c)| (i, Arc::new(Mutex::new(c))))
-        .collect::<Vec<_>>();
-    let channels = channels
-        .into_iter()
-        .map(|cs| {
-            cs.into_iter()
-                .map(|(i, c)| (i, Arc::new(Mutex::new(c))))
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+    type Item = Result<F>;
+
+    /// The next element. After an error, the reader yields nothing.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.is_empty() && self.unread > 0 {
+            if let Err(e) = self.read_chunk() {
+                // the channel is out of sync, so never read it again
+                self.unread = 0;
+                self.chunk.clear();
+                return Some(Err(e));
+            }
+        }
+
+        self.chunk.pop().map(Ok)
+    }
 
-    (receiver_channels, channels)
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // the remaining elements may never arrive, so the lower bound must not be used for allocation
+        (0, Some(self.remaining()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use generic_array::GenericArray;
     use rand::Rng;
-    use scuttlebutt::{field::F128b, AesRng, Channel};
-    use std::io::{BufReader, BufWriter};
+    use scuttlebutt::field::{F128b, F61p};
+    use scuttlebutt::{AesRng, Channel};
+    use std::io::{BufReader, BufWriter, Cursor, Sink};
     use std::os::unix::net::UnixStream;
 
+    // channel reading `bytes`
+    fn reading(bytes: Vec<u8>) -> Channel<Cursor<Vec<u8>>, Sink> {
+        Channel::new(Cursor::new(bytes), std::io::sink())
+    }
+
+    fn encode<F: FF>(v: &[F]) -> Vec<u8> {
+        let mut bytes = (v.len() * F::ByteReprLen::to_usize())
+            .to_le_bytes()
+            .to_vec();
+        bytes.extend(v.iter().flat_map(|x| x.to_bytes().to_vec()));
+        bytes
+    }
+
     #[test]
     fn test_write_read_vec_f() {
         let mut rng = AesRng::new();
@@ -197,4 +315,106 @@ mod tests {
 
         assert_eq!(v, res);
     }
+
+    #[test]
+    fn test_read_vec_f_exact() {
+        let mut rng = AesRng::new();
+        let v = (0..10000).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+
+        assert_eq!(
+            read_vec_f_exact::<F128b, _>(&mut reading(encode(&v)), v.len()).unwrap(),
+            v
+        );
+        assert_eq!(read_vec_f::<F128b, _>(&mut reading(encode(&v))).unwrap(), v);
+
+        let mut reader = VecFReader::<F128b, _>::new(&mut reading(encode(&v))).unwrap();
+        // over chunk boundaries
+        for (i, x) in v.iter().enumerate().take(5000) {
+            assert_eq!(reader.remaining(), v.len() - i);
+            assert_eq!(reader.next().unwrap().unwrap(), *x);
+        }
+        assert_eq!(reader.count(), v.len() - 5000);
+
+        // unexpected length
+        let err = read_vec_f_exact::<F128b, _>(&mut reading(encode(&v)), v.len() - 1).unwrap_err();
+        assert!(matches!(err.root(), Error::ProtocolViolation { .. }));
+
+        // a length which is not a multiple of the element size
+        let mut bytes = encode(&v);
+        bytes[..8].copy_from_slice(&(v.len() * 16 - 1).to_le_bytes());
+        let err = read_vec_f::<F128b, _>(&mut reading(bytes)).unwrap_err();
+        assert!(matches!(err.root(), Error::ProtocolViolation { .. }));
+
+        // a huge length is not allocated at once
+        let mut bytes = encode(&v);
+        bytes[..8].copy_from_slice(&(usize::MAX - 15).to_le_bytes());
+        let err = read_vec_f::<F128b, _>(&mut reading(bytes)).unwrap_err();
+        assert!(matches!(err.root(), Error::Channel(_)));
+    }
+
+    // decode `bytes` in the simplest way
+    fn decode_naive<F: FF>(bytes: &[u8]) -> Option<Vec<F>> {
+        let len = usize::from_le_bytes(bytes.get(..8)?.try_into().unwrap());
+        let elem_len = F::ByteReprLen::to_usize();
+        if len % elem_len != 0 || len > bytes.len() - 8 {
+            return None;
+        }
+        bytes[8..8 + len]
+            .chunks(elem_len)
+            .map(|x| F::from_bytes(GenericArray::from_slice(x)).ok())
+            .collect()
+    }
+
+    fn fuzz_read_vec_f<F: FF>(rng: &mut AesRng) {
+        let elem_len = F::ByteReprLen::to_usize();
+
+        for _ in 0..200 {
+            let body_len = rng.gen_range(0..3 * READ_CHUNK_BYTES);
+            let mut bytes = vec![0u8; 8 + body_len];
+            rng.fill(&mut bytes[..]);
+            let len = match rng.gen_range(0..4) {
+                // consistent
+                0 => body_len / elem_len * elem_len,
+                // longer than the body
+                1 => (body_len / elem_len + rng.gen_range(1..10)) * elem_len,
+                // small
+                2 => rng.gen_range(0..body_len + 1),
+                // anything
+                _ => rng.gen(),
+            };
+            bytes[..8].copy_from_slice(&len.to_le_bytes());
+            // truncated prefix
+            if rng.gen_range(0..100) == 0 {
+                bytes.truncate(rng.gen_range(0..8));
+            }
+
+            let expected = decode_naive::<F>(&bytes);
+
+            match read_vec_f::<F, _>(&mut reading(bytes.clone())) {
+                Ok(v) => assert_eq!(Some(v), expected),
+                Err(_) => assert!(expected.is_none()),
+            }
+
+            let n = if rng.gen() {
+                len / elem_len
+            } else {
+                rng.gen_range(0..body_len / elem_len + 1)
+            };
+            match read_vec_f_exact::<F, _>(&mut reading(bytes), n) {
+                Ok(v) => {
+                    assert_eq!(v.len(), n);
+                    assert_eq!(Some(v), expected);
+                }
+                Err(_) => assert!(!matches!(expected, Some(v) if v.len() == n)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_read_vec_f() {
+        let mut rng = AesRng::new();
+        fuzz_read_vec_f::<F128b>(&mut rng);
+        // not all byte strings are field elements
+        fuzz_read_vec_f::<F61p>(&mut rng);
+    }
 }
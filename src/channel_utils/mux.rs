@@ -0,0 +1,410 @@
+//! Multiplexing one channel into independent sub-channels. See [mux].
+//!
+//! Sub-channels send messages in frames `sub u32 || len u32 || payload` through the shared channel,
+//! so that threads can run independent protocols with the same peer at the same time,
+//! e.g. one per bin in [partitioned](crate::preprocessed::psi::partitioned) PSI.
+//!
+//! Whichever sub-channel needs data reads the next frame from the shared channel and hands it to the sub-channel it is for.
+//! A failure of the shared channel, including an [abort](crate::channel_utils::abortable), fails every sub-channel.
+//!
+//! # Memory
+//!
+//! A frame carries at most $`2^{16}`$ bytes, and a longer frame from the peer is rejected before allocation.
+//! Frames for a sub-channel which is not reading are queued, so a sub-channel may hold
+//! everything the peer sent on it and it has not read yet.
+//! The queue of each sub-channel is capped by [DEFAULT_MAX_QUEUED] bytes (see [mux_with_limit]),
+//! and exceeding it fails every sub-channel with [ErrorKind::InvalidData].
+//! An honest peer stays far below the cap as long as each sub-channel runs a protocol whose messages fit in it,
+//! e.g. a bin of [partitioned](crate::preprocessed::psi::partitioned) PSI.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::mux::mux;
+//! use preprocessing_mpsi_with_vole::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+//! use scuttlebutt::AbstractChannel;
+//!
+//! let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+//! let subs = mux(receiver_channels.pop().unwrap().1, 2);
+//! let peer_subs = mux(channels.pop().unwrap().pop().unwrap().1, 2);
+//!
+//! let handles = subs
+//!     .into_iter()
+//!     .zip(peer_subs)
+//!     .enumerate()
+//!     .map(|(i, (mut sub, mut peer_sub))| {
+//!         std::thread::spawn(move || {
+//!             peer_sub.write_usize(i).unwrap();
+//!             peer_sub.flush().unwrap();
+//!             assert_eq!(sub.read_usize().unwrap(), i);
+//!         })
+//!     })
+//!     .collect::<Vec<_>>();
+//!
+//! for handle in handles {
+//!     handle.join().unwrap();
+//! }
+//! ```
+
+use crate::channel_utils::abortable::Aborted;
+use scuttlebutt::AbstractChannel;
+use std::collections::VecDeque;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::sync::{Arc, Condvar, Mutex};
+
+// sub-channel and length
+const HEADER_LEN: usize = 8;
+// maximum payload of a frame. A frame is sent without flush when this many bytes are pending
+const MAX_PENDING: usize = 1 << 16;
+
+/// Default cap of bytes queued for one sub-channel. See [mux_with_limit].
+pub const DEFAULT_MAX_QUEUED: usize = 1 << 30;
+
+// Failure of the shared channel kept to fail the other sub-channels in the same way.
+struct Failure {
+    kind: ErrorKind,
+    aborted: Option<Aborted>,
+    message: String,
+}
+
+impl Failure {
+    fn new(err: &IoError) -> Self {
+        Self {
+            kind: err.kind(),
+            aborted: err
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<Aborted>())
+                .cloned(),
+            message: err.to_string(),
+        }
+    }
+
+    fn to_error(&self) -> IoError {
+        match &self.aborted {
+            Some(aborted) => IoError::new(self.kind, aborted.clone()),
+            None => IoError::new(self.kind, self.message.clone()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Frames {
+    // payloads received for each sub-channel
+    queues: Vec<VecDeque<Vec<u8>>>,
+    // total length of the payloads in each queue
+    queued: Vec<usize>,
+    // whether a sub-channel is reading the shared channel
+    reading: bool,
+    failure: Option<Failure>,
+}
+
+struct Shared<C> {
+    writer: Mutex<C>,
+    reader: Mutex<C>,
+    frames: Mutex<Frames>,
+    arrived: Condvar,
+    max_queued: usize,
+}
+
+/// Split `channel` into `n` sub-channels.
+///
+/// The peer must split its end into the same number of sub-channels,
+/// and nothing else may use the channel until all sub-channels are dropped.
+pub fn mux<C: AbstractChannel>(channel: C, n: usize) -> Vec<MuxChannel<C>> {
+    mux_with_limit(channel, n, DEFAULT_MAX_QUEUED)
+}
+
+/// Same as [mux] except that at most `max_queued` bytes not read yet are kept for each sub-channel.
+pub fn mux_with_limit<C: AbstractChannel>(
+    channel: C,
+    n: usize,
+    max_queued: usize,
+) -> Vec<MuxChannel<C>> {
+    let shared = Arc::new(Shared {
+        writer: Mutex::new(channel.clone()),
+        reader: Mutex::new(channel),
+        frames: Mutex::new(Frames {
+            queues: (0..n).map(|_| VecDeque::new()).collect(),
+            queued: vec![0; n],
+            ..Default::default()
+        }),
+        arrived: Condvar::new(),
+        max_queued,
+    });
+
+    (0..n)
+        .map(|id| MuxChannel {
+            id,
+            shared: Arc::clone(&shared),
+            pending: vec![0u8; HEADER_LEN],
+            received: Vec::new(),
+            pos: 0,
+        })
+        .collect()
+}
+
+/// Sub-channel created by [mux].
+///
+/// Unlike other channels, a clone does not share buffers with the original, so only one of them may be used.
+pub struct MuxChannel<C: AbstractChannel> {
+    id: usize,
+    shared: Arc<Shared<C>>,
+    // header space followed by bytes not sent yet
+    pending: Vec<u8>,
+    received: Vec<u8>,
+    pos: usize,
+}
+
+impl<C: AbstractChannel> MuxChannel<C> {
+    fn send_pending(&mut self) -> IoResult<()> {
+        let len = self.pending.len() - HEADER_LEN;
+        if len == 0 {
+            return Ok(());
+        }
+
+        self.pending[..4].copy_from_slice(&(self.id as u32).to_le_bytes());
+        self.pending[4..HEADER_LEN].copy_from_slice(&(len as u32).to_le_bytes());
+        // header and payload in one write so that frames of other sub-channels never split it
+        self.shared
+            .writer
+            .lock()
+            .unwrap()
+            .write_bytes(&self.pending)?;
+        self.pending.truncate(HEADER_LEN);
+
+        Ok(())
+    }
+
+    // Payload of the next frame for this sub-channel.
+    fn receive_frame(&mut self) -> IoResult<Vec<u8>> {
+        let shared = &*self.shared;
+        let mut frames = shared.frames.lock().unwrap();
+        loop {
+            if let Some(payload) = frames.queues[self.id].pop_front() {
+                frames.queued[self.id] -= payload.len();
+                return Ok(payload);
+            }
+            if let Some(failure) = &frames.failure {
+                return Err(failure.to_error());
+            }
+            if frames.reading {
+                frames = shared.arrived.wait(frames).unwrap();
+                continue;
+            }
+
+            // read a frame for whichever sub-channel without blocking the others
+            frames.reading = true;
+            drop(frames);
+            let res = read_frame(&mut *shared.reader.lock().unwrap());
+            frames = shared.frames.lock().unwrap();
+            frames.reading = false;
+            shared.arrived.notify_all();
+
+            match res {
+                Ok((id, payload)) if id < frames.queues.len() => {
+                    if frames.queued[id] + payload.len() > shared.max_queued {
+                        let err = IoError::new(
+                            ErrorKind::InvalidData,
+                            format!(
+                                "more than {} bytes are queued for sub-channel {}",
+                                shared.max_queued, id
+                            ),
+                        );
+                        frames.failure = Some(Failure::new(&err));
+                        return Err(err);
+                    }
+                    frames.queued[id] += payload.len();
+                    frames.queues[id].push_back(payload);
+                }
+                Ok((id, _)) => {
+                    let err = IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("frame for unknown sub-channel {}", id),
+                    );
+                    frames.failure = Some(Failure::new(&err));
+                    return Err(err);
+                }
+                Err(err) => {
+                    frames.failure = Some(Failure::new(&err));
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+fn read_frame<C: AbstractChannel>(channel: &mut C) -> IoResult<(usize, Vec<u8>)> {
+    let mut header = [0u8; HEADER_LEN];
+    channel.read_bytes(&mut header)?;
+    let id = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+    if len > MAX_PENDING {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            format!("too long frame: {} bytes", len),
+        ));
+    }
+
+    let mut payload = vec![0u8; len];
+    channel.read_bytes(&mut payload)?;
+
+    Ok((id, payload))
+}
+
+impl<C: AbstractChannel> AbstractChannel for MuxChannel<C> {
+    fn write_bytes(&mut self, mut bytes: &[u8]) -> IoResult<()> {
+        while !bytes.is_empty() {
+            let n = (HEADER_LEN + MAX_PENDING - self.pending.len()).min(bytes.len());
+            self.pending.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.pending.len() == HEADER_LEN + MAX_PENDING {
+                self.send_pending()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> IoResult<()> {
+        // the peer may be waiting for what we wrote before
+        self.flush()?;
+
+        let mut filled = 0;
+        while filled < bytes.len() {
+            if self.pos == self.received.len() {
+                self.received = self.receive_frame()?;
+                self.pos = 0;
+            }
+
+            let n = (bytes.len() - filled).min(self.received.len() - self.pos);
+            bytes[filled..filled + n].copy_from_slice(&self.received[self.pos..self.pos + n]);
+            filled += n;
+            self.pos += n;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.send_pending()?;
+        self.shared.writer.lock().unwrap().flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            shared: Arc::clone(&self.shared),
+            pending: vec![0u8; HEADER_LEN],
+            received: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::abortable::AbortableChannel;
+    use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+    use crate::channel_utils::{read_vec_f, write_vec_f};
+    use crate::error::Error;
+    use rand::Rng;
+    use scuttlebutt::{field::F128b, AesRng};
+
+    #[test]
+    fn test_mux() {
+        let n = 8;
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+        let channel = AbortableChannel::new(receiver_channels.pop().unwrap().1);
+        let peer = AbortableChannel::new(channels.pop().unwrap().pop().unwrap().1);
+        let abort = peer.abort_handle();
+
+        // sub-channels exchange messages of various sizes in both directions at the same time
+        let run = |subs: Vec<MuxChannel<_>>, first: bool| {
+            subs.into_iter()
+                .enumerate()
+                .map(|(i, mut sub)| {
+                    std::thread::spawn(move || {
+                        let mut rng = AesRng::new();
+                        for round in 0..10 {
+                            let len = rng.gen_range(0..(i + 1) * 1000);
+                            let v = (0..len).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+                            if first {
+                                write_vec_f(&mut sub, &v).unwrap();
+                                sub.write_usize(round).unwrap();
+                                assert_eq!(sub.read_usize().unwrap(), round + i);
+                            } else {
+                                let w = read_vec_f::<F128b, _>(&mut sub).unwrap();
+                                assert_eq!(sub.read_usize().unwrap(), round);
+                                sub.write_usize(round + i).unwrap();
+                                sub.flush().unwrap();
+                                assert!(w.len() < (i + 1) * 1000);
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let handles = run(mux(channel.clone(), n), true)
+            .into_iter()
+            .chain(run(mux(peer, n), false))
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // an abort fails every sub-channel
+        abort.abort(1, "bye").unwrap();
+        let handles = mux(channel, n)
+            .into_iter()
+            .map(|mut sub| std::thread::spawn(move || sub.read_u8().unwrap_err()))
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let err = Error::from(handle.join().unwrap());
+            assert!(matches!(err, Error::Aborted { culprit: 1, .. }));
+        }
+    }
+
+    #[test]
+    fn test_frame_bounds() {
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+        let mut raw = receiver_channels.pop().unwrap().1;
+        let channel = channels.pop().unwrap().pop().unwrap().1;
+
+        // frames of a long write carry at most MAX_PENDING bytes
+        let mut sub = mux(channel.clone(), 1).pop().unwrap();
+        sub.write_bytes(&[1u8; 10]).unwrap();
+        sub.write_bytes(&vec![2u8; 2 * MAX_PENDING]).unwrap();
+        sub.flush().unwrap();
+        let mut total = 0;
+        while total < 2 * MAX_PENDING + 10 {
+            let (id, payload) = read_frame(&mut raw).unwrap();
+            assert_eq!(id, 0);
+            assert!(payload.len() <= MAX_PENDING);
+            total += payload.len();
+        }
+
+        // a frame longer than MAX_PENDING is rejected
+        let mut sub = mux(channel.clone(), 1).pop().unwrap();
+        raw.write_u32(0).unwrap();
+        raw.write_u32(u32::MAX).unwrap();
+        raw.flush().unwrap();
+        assert_eq!(sub.read_u8().unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // frames for a sub-channel not reading are capped
+        let mut subs = mux_with_limit(channel, 2, 100);
+        for _ in 0..2 {
+            raw.write_u32(1).unwrap();
+            raw.write_u32(60).unwrap();
+            raw.write_bytes(&[0u8; 60]).unwrap();
+        }
+        raw.flush().unwrap();
+        let err = subs[0].read_u8().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        // the frame queued before the failure is still delivered
+        let mut buf = [0u8; 61];
+        let err = subs[1].read_bytes(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -1,12 +1,13 @@
 //! Module about unix domain socket channel. See [UnixStream].
 //! This module provides a function to create a set of unix domain socket channels for receiver and senders.
 
-use anyhow::{Context, Result};
+use crate::error::{Context, Result};
 use itertools::Itertools;
 use scuttlebutt::SyncChannel;
 use std::{
     io::{BufReader, BufWriter},
     os::unix::net::UnixStream,
+    time::Duration,
 };
 
 type Channel = (
@@ -18,6 +19,16 @@ type Channel = (
 ///
 /// Return a tuple of two vectors of channels. The first vector contains the receiver channels, and the second vector contains the sender channels.
 pub fn create_unix_channels(nparties: usize) -> Result<(Vec<Channel>, Vec<Vec<Channel>>)> {
+    create_unix_channels_with_timeout(nparties, None)
+}
+
+/// Create a set of unix domain socket channels with read and write deadlines of `timeout` per operation.
+///
+/// See [create_unix_channels] and [UnixStream::set_read_timeout].
+pub fn create_unix_channels_with_timeout(
+    nparties: usize,
+    timeout: Option<Duration>,
+) -> Result<(Vec<Channel>, Vec<Vec<Channel>>)> {
     let mut channels = (0..nparties)
         .map(|_| (0..nparties).map(|_| None).collect_vec())
         .collect_vec();
@@ -25,14 +36,13 @@ pub fn create_unix_channels(nparties: usize) -> Result<(Vec<Channel>, Vec<Vec<Ch
     for i in 0..nparties {
         for j in 0..nparties {
             if i != j {
-                let (s, r) =
-                    UnixStream::pair().with_context(|| format!("@{}:{}", file!(), line!()))?;
-                let rs = s
-                    .try_clone()
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                let rr = r
-                    .try_clone()
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
+                let (s, r) = UnixStream::pair().located()?;
+                for stream in [&s, &r] {
+                    stream.set_read_timeout(timeout).located()?;
+                    stream.set_write_timeout(timeout).located()?;
+                }
+                let rs = s.try_clone().located()?;
+                let rr = r.try_clone().located()?;
                 let left = SyncChannel::new(BufReader::new(rs), BufWriter::new(s));
                 let right = SyncChannel::new(BufReader::new(rr), BufWriter::new(r));
                 channels[i][j] = Some((j, left));
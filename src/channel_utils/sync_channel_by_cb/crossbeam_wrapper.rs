@@ -1,12 +1,21 @@
 //! Crossbeam channel wrapper to use it in [scuttlebutt::SyncChannel].
 
-use crossbeam::channel::{unbounded, Receiver, RecvError, SendError, Sender};
+use crossbeam::channel::{unbounded, Receiver, RecvError, RecvTimeoutError, SendError, Sender};
 use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::time::Duration;
 
 /// Wrapper for [Sender] to implement [Write] trait.
 pub struct CrossbeamSender(Sender<Vec<u8>>);
 /// Wrapper for [Receiver] to implement [Read] trait.
-pub struct CrossbeamReceiver(Receiver<Vec<u8>>);
+///
+/// A message may be read in pieces of any size; the rest is kept for the next `read`.
+/// If a timeout is set, `read` fails with [ErrorKind::TimedOut] when nothing arrives in time.
+pub struct CrossbeamReceiver {
+    receiver: Receiver<Vec<u8>>,
+    timeout: Option<Duration>,
+    buf: Vec<u8>,
+    pos: usize,
+}
 
 impl Write for CrossbeamSender {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
@@ -25,18 +34,39 @@ impl Write for CrossbeamSender {
 
 impl Read for CrossbeamReceiver {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        match self.0.recv() {
-            Ok(v) => buf.copy_from_slice(&v),
-            Err(RecvError) => return Err(Error::new(ErrorKind::BrokenPipe, RecvError)),
+        if self.pos == self.buf.len() {
+            self.buf = match self.timeout {
+                Some(timeout) => self.receiver.recv_timeout(timeout).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => Error::new(ErrorKind::TimedOut, e),
+                    RecvTimeoutError::Disconnected => Error::new(ErrorKind::BrokenPipe, e),
+                })?,
+                None => self
+                    .receiver
+                    .recv()
+                    .map_err(|RecvError| Error::new(ErrorKind::BrokenPipe, RecvError))?,
+            };
+            self.pos = 0;
         }
 
-        Ok(buf.len())
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
     }
 }
 
-pub(crate) fn cbch_pair() -> (CrossbeamSender, CrossbeamReceiver) {
+pub(crate) fn cbch_pair(timeout: Option<Duration>) -> (CrossbeamSender, CrossbeamReceiver) {
     let (s, r) = unbounded();
-    (CrossbeamSender(s), CrossbeamReceiver(r))
+    (
+        CrossbeamSender(s),
+        CrossbeamReceiver {
+            receiver: r,
+            timeout,
+            buf: Vec::new(),
+            pos: 0,
+        },
+    )
 }
 
 #[cfg(test)]
@@ -46,7 +76,7 @@ mod tests {
 
     #[test]
     fn test() {
-        let (mut s1, mut r1) = cbch_pair();
+        let (mut s1, mut r1) = cbch_pair(None);
 
         let handle = std::thread::spawn(move || {
             let mut v = vec![0u8; 3];
@@ -63,7 +93,7 @@ mod tests {
     /*
     #[test]
     fn test_empty_res() {
-        let (mut s1, mut r1) = cbch_pair();
+        let (mut s1, mut r1) = cbch_pair(None);
 
         let handle = std::thread::spawn(move || {
             let mut v = vec![0u8; 16];
@@ -88,7 +118,7 @@ mod tests {
 
     #[test]
     fn test_broken_pipe() {
-        let (mut s1, mut r1) = cbch_pair();
+        let (mut s1, mut r1) = cbch_pair(None);
 
         let handle = std::thread::spawn(move || {
             let mut v = vec![0u8; 3];
@@ -107,10 +137,40 @@ mod tests {
         handle.join().unwrap();
     }
 
+    #[test]
+    fn test_split_read() {
+        let (mut s1, mut r1) = cbch_pair(None);
+
+        s1.write_all(&[1, 2, 3, 4, 5]).unwrap();
+        s1.write_all(&[6]).unwrap();
+
+        let mut v = vec![0u8; 2];
+        r1.read_exact(&mut v).unwrap();
+        assert_eq!(v, [1, 2]);
+
+        let mut v = vec![0u8; 4];
+        r1.read_exact(&mut v).unwrap();
+        assert_eq!(v, [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (mut s1, mut r1) = cbch_pair(Some(Duration::from_millis(10)));
+
+        let mut v = vec![0u8; 3];
+        let e = r1.read_exact(&mut v).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+        // the channel is still usable after a timeout
+        s1.write_all(&[1, 2, 3]).unwrap();
+        r1.read_exact(&mut v).unwrap();
+        assert_eq!(v, [1, 2, 3]);
+    }
+
     #[test]
     fn test_channel() {
-        let (s1, r1) = cbch_pair();
-        let (s2, r2) = cbch_pair();
+        let (s1, r1) = cbch_pair(None);
+        let (s2, r2) = cbch_pair(None);
         let mut ch1 = SyncChannel::new(r1, s2);
         let mut ch2 = SyncChannel::new(r2, s1);
 
@@ -3,6 +3,7 @@
 
 use itertools::Itertools;
 use scuttlebutt::SyncChannel;
+use std::time::Duration;
 pub mod crossbeam_wrapper;
 use crossbeam_wrapper::cbch_pair;
 pub use crossbeam_wrapper::{CrossbeamReceiver, CrossbeamSender};
@@ -13,6 +14,16 @@ type Channel = (usize, SyncChannel<CrossbeamReceiver, CrossbeamSender>);
 ///
 /// Return a tuple of two vectors of channels. The first vector contains the receiver channels, and the second vector contains the sender channels.
 pub fn create_crossbeam_channels(nparties: usize) -> (Vec<Channel>, Vec<Vec<Channel>>) {
+    create_crossbeam_channels_with_timeout(nparties, None)
+}
+
+/// Create a set of crossbeam channels whose reads fail after `timeout` without messages.
+///
+/// See [create_crossbeam_channels].
+pub fn create_crossbeam_channels_with_timeout(
+    nparties: usize,
+    timeout: Option<Duration>,
+) -> (Vec<Channel>, Vec<Vec<Channel>>) {
     let mut channels = (0..nparties)
         .map(|_| (0..nparties).map(|_| None).collect_vec())
         .collect_vec();
@@ -20,8 +31,8 @@ pub fn create_crossbeam_channels(nparties: usize) -> (Vec<Channel>, Vec<Vec<Chan
     for i in 0..nparties {
         for j in 0..nparties {
             if i != j {
-                let (sr, rl) = cbch_pair();
-                let (sl, rr) = cbch_pair();
+                let (sr, rl) = cbch_pair(timeout);
+                let (sl, rr) = cbch_pair(timeout);
                 let left = SyncChannel::new(rl, sl);
                 let right = SyncChannel::new(rr, sr);
                 channels[i][j] = Some((j, left));
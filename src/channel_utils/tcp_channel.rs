@@ -1,7 +1,7 @@
 //! Module about tcp channel. See [TcpStream].
 //! This module provides a function to create a set of tcp stream channels for receiver and senders.
 
-use anyhow::{bail, Context, Result};
+use crate::error::{Context, Error, Result};
 use scuttlebutt::SyncChannel;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::net::{SocketAddr, TcpListener, TcpStream};
@@ -19,10 +19,10 @@ fn create_tcp_channel_for_party(
     nparties: usize,
     base_port: usize,
     me: usize,
+    timeout: Option<Duration>,
 ) -> Result<Vec<Channel>> {
     let addr = SocketAddr::from(([127, 0, 0, 1], (base_port + me) as _));
-    let listener = TcpListener::bind(addr)
-        .with_context(|| format!("me={} addr={} @{}:{}", me, addr, file!(), line!()))?;
+    let listener = TcpListener::bind(addr).with_context(|| format!("me={} addr={}", me, addr))?;
 
     sleep(Duration::from_millis(100 * me as u64));
 
@@ -31,15 +31,11 @@ fn create_tcp_channel_for_party(
             let port = base_port + i;
             let addr = SocketAddr::from(([127, 0, 0, 1], port as _));
             let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)
-                .with_context(|| format!("me={} addr={} @{}:{}", me, addr, file!(), line!()))?;
+                .with_context(|| format!("me={} addr={}", me, addr))?;
             let m = me.to_be_bytes();
-            stream
-                .write(&m)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            stream.write(&m).located()?;
             let mut buf = [0u8; 8];
-            stream
-                .read(&mut buf)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            stream.read(&mut buf).located()?;
             let m = usize::from_be_bytes(buf);
             Ok((m, stream))
         })
@@ -49,15 +45,13 @@ fn create_tcp_channel_for_party(
         .incoming()
         .take(nparties - 1 - me)
         .map(|s| {
-            let mut s = s.with_context(|| format!("@{}:{}", file!(), line!()))?;
+            let mut s = s.located()?;
 
             let mut buf = [0u8; 8];
-            s.read(&mut buf)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            s.read(&mut buf).located()?;
             let m = usize::from_be_bytes(buf);
             let mm = me.to_be_bytes();
-            s.write(&mm)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            s.write(&mm).located()?;
             Ok((m, s))
         })
         .collect::<Result<Vec<_>>>()?;
@@ -69,9 +63,9 @@ fn create_tcp_channel_for_party(
     let res = streams
         .into_iter()
         .map(|(m, s)| {
-            let ss = s
-                .try_clone()
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            s.set_read_timeout(timeout).located()?;
+            s.set_write_timeout(timeout).located()?;
+            let ss = s.try_clone().located()?;
             Ok((m, SyncChannel::new(BufReader::new(ss), BufWriter::new(s))))
         })
         .collect::<Result<Vec<Channel>>>()?;
@@ -86,17 +80,17 @@ pub fn create_tcp_channels_for_sender(
     me: usize,
 ) -> Result<Vec<Channel>> {
     if me == 0 {
-        bail!("me must be > 0 (now me = {})", 0);
+        return Err(Error::Config(format!("me must be > 0 (now me = {})", 0)));
     }
 
-    let res = create_tcp_channel_for_party(nparties, port, me)?;
+    let res = create_tcp_channel_for_party(nparties, port, me, None)?;
 
     Ok(res)
 }
 
 /// Return a vector of channels for receiver channel.
 pub fn create_tcp_channels_for_receiver(nparties: usize, port: usize) -> Result<Vec<Channel>> {
-    let res = create_tcp_channel_for_party(nparties, port, 0)?;
+    let res = create_tcp_channel_for_party(nparties, port, 0, None)?;
 
     Ok(res)
 }
@@ -107,12 +101,25 @@ pub fn create_tcp_channels_for_receiver(nparties: usize, port: usize) -> Result<
 pub fn create_tcp_channels(
     nparties: usize,
     port: usize,
+) -> Result<(Vec<Channel>, Vec<Vec<Channel>>)> {
+    create_tcp_channels_with_timeout(nparties, port, None)
+}
+
+/// Create a set of tcp stream socket channels with read and write deadlines of `timeout` per operation.
+///
+/// See [create_tcp_channels] and [TcpStream::set_read_timeout].
+pub fn create_tcp_channels_with_timeout(
+    nparties: usize,
+    port: usize,
+    timeout: Option<Duration>,
 ) -> Result<(Vec<Channel>, Vec<Vec<Channel>>)> {
     let receiver_handle =
-        std::thread::spawn(move || create_tcp_channels_for_receiver(nparties, port));
+        std::thread::spawn(move || create_tcp_channel_for_party(nparties, port, 0, timeout));
 
     let handles = (1..nparties)
-        .map(|me| std::thread::spawn(move || create_tcp_channel_for_party(nparties, port, me)))
+        .map(|me| {
+            std::thread::spawn(move || create_tcp_channel_for_party(nparties, port, me, timeout))
+        })
         .collect::<Vec<_>>();
 
     let receiver_channels = receiver_handle.join().unwrap()?;
@@ -157,6 +164,16 @@ mod tests {
         handle.join().unwrap();
     }
 
+    #[test]
+    fn test_timeout() {
+        let (mut receiver_channels, _channels) =
+            create_tcp_channels_with_timeout(2, 10100, Some(Duration::from_millis(100))).unwrap();
+
+        // the peer never writes
+        let err = Error::from(receiver_channels[0].1.read_usize().unwrap_err());
+        assert!(matches!(err, Error::Timeout(_)));
+    }
+
     fn test_nparty(nparties: usize, base_port: usize) {
         let handles = (1..nparties)
             .map(|me| {
@@ -0,0 +1,492 @@
+//! Recording and replay of protocol transcripts for debugging.
+//!
+//! [RecordingChannel] records every `write_bytes` and `read_bytes` of a party with its direction, phase and peer
+//! to a transcript file through a [Recorder] shared by all channels of the party.
+//! [ReplayChannel] lets the same party run again without any peer:
+//! reads are served from the transcript and writes are compared with it.
+//! The first differing byte is reported as [Divergence].
+//!
+//! Replay is deterministic only if the party uses the same inputs and the same seeded RNG,
+//! so the seed is stored in the [TranscriptHeader].
+//!
+//! # Format
+//!
+//! - header: `"PMPSITR1" || party (u64) || seed (u128)`
+//! - records: `direction (u8) || peer (u64) || phase length (u16) || phase || length (u64) || bytes`
+//!
+//! All integers are little-endian.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+//! use preprocessing_mpsi_with_vole::channel_utils::transcript::{
+//!     read_transcript, Recorder, RecordingChannel, ReplayChannel, TranscriptHeader,
+//! };
+//! use scuttlebutt::{AbstractChannel, Block};
+//!
+//! let path = std::env::temp_dir().join(format!("doc_transcript_{}", std::process::id()));
+//! let header = TranscriptHeader { party: 0, seed: Block::from(42u128) };
+//!
+//! let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+//! let recorder = Recorder::create(&path, header).unwrap();
+//! let mut channel = RecordingChannel::new(receiver_channels.pop().unwrap().1, 1, recorder.clone());
+//! let mut peer = channels.pop().unwrap().pop().unwrap().1;
+//!
+//! recorder.set_phase("offline");
+//! peer.write_u64(7).unwrap();
+//! assert_eq!(channel.read_u64().unwrap(), 7);
+//! channel.write_u64(8).unwrap();
+//! drop((channel, recorder));
+//!
+//! let (header, records) = read_transcript(&path).unwrap();
+//! assert_eq!(header.party, 0);
+//! assert_eq!(records.len(), 2);
+//!
+//! let mut replay = ReplayChannel::new(1, &records);
+//! assert_eq!(replay.read_u64().unwrap(), 7);
+//! assert!(replay.write_u64(9).is_err());
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::error::{Context, Error, Result};
+use scuttlebutt::{AbstractChannel, Block};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const MAGIC: &[u8; 8] = b"PMPSITR1";
+
+/// Direction of a record seen from the recording party.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Written to the peer.
+    Write,
+    /// Read from the peer.
+    Read,
+}
+
+/// Header of a transcript file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TranscriptHeader {
+    /// ID of the recording party.
+    pub party: usize,
+    /// Seed from which the party derived its RNG.
+    pub seed: Block,
+}
+
+/// One `write_bytes` or `read_bytes` call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Direction of the call.
+    pub direction: Direction,
+    /// ID of the peer.
+    pub peer: usize,
+    /// Phase set by [Recorder::set_phase] at the call.
+    pub phase: String,
+    /// Bytes written or read.
+    pub bytes: Vec<u8>,
+}
+
+/// Transcript file of one party shared by its [RecordingChannel]s.
+///
+/// The file is flushed when the last reference is dropped or by [Recorder::flush].
+pub struct Recorder {
+    out: Mutex<BufWriter<File>>,
+    phase: Mutex<String>,
+}
+
+impl Recorder {
+    /// Create the transcript file at `path` and write `header`.
+    pub fn create<P: AsRef<Path>>(path: P, header: TranscriptHeader) -> Result<Arc<Self>> {
+        let file = File::create(path.as_ref())
+            .with_context(|| format!("path={}", path.as_ref().display()))?;
+        let mut out = BufWriter::new(file);
+
+        out.write_all(MAGIC)
+            .and_then(|_| out.write_all(&(header.party as u64).to_le_bytes()))
+            .and_then(|_| out.write_all(&u128::from(header.seed).to_le_bytes()))
+            .located()?;
+
+        Ok(Arc::new(Self {
+            out: Mutex::new(out),
+            phase: Mutex::new(String::new()),
+        }))
+    }
+
+    /// Label the following records with `phase`, e.g. "offline" or "online".
+    pub fn set_phase(&self, phase: &str) {
+        *self.phase.lock().unwrap() = phase.to_string();
+    }
+
+    /// Write the buffered records to the file.
+    pub fn flush(&self) -> Result<()> {
+        self.out.lock().unwrap().flush().located()
+    }
+
+    fn record(&self, direction: Direction, peer: usize, bytes: &[u8]) -> std::io::Result<()> {
+        let phase = self.phase.lock().unwrap().clone();
+        let mut out = self.out.lock().unwrap();
+
+        out.write_all(&[direction as u8])?;
+        out.write_all(&(peer as u64).to_le_bytes())?;
+        out.write_all(&(phase.len() as u16).to_le_bytes())?;
+        out.write_all(phase.as_bytes())?;
+        out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        out.write_all(bytes)
+    }
+}
+
+/// Read a transcript file written by a [Recorder].
+pub fn read_transcript<P: AsRef<Path>>(path: P) -> Result<(TranscriptHeader, Vec<Record>)> {
+    let file =
+        File::open(path.as_ref()).with_context(|| format!("path={}", path.as_ref().display()))?;
+    let mut input = BufReader::new(file);
+
+    fn read_array<const N: usize>(input: &mut impl Read) -> std::io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        input.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    let magic = read_array::<8>(&mut input).located()?;
+    if &magic != MAGIC {
+        return Err(Error::Config(format!(
+            "not a transcript file: {}",
+            path.as_ref().display()
+        )));
+    }
+
+    let party = u64::from_le_bytes(read_array(&mut input)?) as usize;
+    let seed = Block::from(u128::from_le_bytes(read_array(&mut input)?));
+
+    let mut records = Vec::new();
+    loop {
+        let direction = match read_array::<1>(&mut input) {
+            Ok([0]) => Direction::Write,
+            Ok([1]) => Direction::Read,
+            Ok([d]) => {
+                return Err(Error::Config(format!(
+                    "unknown direction {} in record {}",
+                    d,
+                    records.len()
+                )))
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).located(),
+        };
+
+        let peer = u64::from_le_bytes(read_array(&mut input)?) as usize;
+        let mut phase = vec![0u8; u16::from_le_bytes(read_array(&mut input)?) as usize];
+        input.read_exact(&mut phase)?;
+        let mut bytes = vec![0u8; u64::from_le_bytes(read_array(&mut input)?) as usize];
+        input
+            .read_exact(&mut bytes)
+            .with_context(|| format!("record {}", records.len()))?;
+
+        records.push(Record {
+            direction,
+            peer,
+            phase: String::from_utf8_lossy(&phase).into_owned(),
+            bytes,
+        });
+    }
+
+    Ok((TranscriptHeader { party, seed }, records))
+}
+
+/// Channel wrapper recording every call to a [Recorder].
+pub struct RecordingChannel<C: AbstractChannel> {
+    inner: C,
+    peer: usize,
+    recorder: Arc<Recorder>,
+}
+
+impl<C: AbstractChannel> RecordingChannel<C> {
+    /// Wrap `inner`, the channel to `peer`.
+    pub fn new(inner: C, peer: usize, recorder: Arc<Recorder>) -> Self {
+        Self {
+            inner,
+            peer,
+            recorder,
+        }
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for RecordingChannel<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.recorder.record(Direction::Write, self.peer, bytes)?;
+        self.inner.write_bytes(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.inner.read_bytes(bytes)?;
+        self.recorder.record(Direction::Read, self.peer, bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            peer: self.peer,
+            recorder: Arc::clone(&self.recorder),
+        }
+    }
+}
+
+/// First difference between a replayed party and its transcript.
+///
+/// Writing to a [ReplayChannel] fails with [ErrorKind::InvalidData] wrapping it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// ID of the peer.
+    pub peer: usize,
+    /// Phase of the recorded write.
+    pub phase: String,
+    /// Index of the recorded write among the writes to the peer.
+    pub write_index: usize,
+    /// Offset of the differing byte in the recorded write.
+    pub offset: usize,
+    /// Recorded byte, or `None` if the transcript has no more writes.
+    pub expected: Option<u8>,
+    /// Written byte.
+    pub actual: u8,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.expected {
+            Some(expected) => write!(
+                f,
+                "diverged from the transcript at byte {} of write {} to party {} in phase \"{}\": expected {:#04x}, got {:#04x}",
+                self.offset, self.write_index, self.peer, self.phase, expected, self.actual
+            ),
+            None => write!(
+                f,
+                "wrote more than the transcript to party {} after write {}",
+                self.peer, self.write_index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+struct ReplayState {
+    writes: Vec<Record>,
+    write_index: usize,
+    write_offset: usize,
+    reads: Vec<u8>,
+    read_offset: usize,
+}
+
+/// Channel to `peer` replaying a transcript instead of communicating.
+///
+/// Clones share the position in the transcript.
+pub struct ReplayChannel {
+    peer: usize,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayChannel {
+    /// Replay the records of `peer` in `records`.
+    pub fn new(peer: usize, records: &[Record]) -> Self {
+        let records = records.iter().filter(|r| r.peer == peer);
+        let writes = records
+            .clone()
+            .filter(|r| r.direction == Direction::Write && !r.bytes.is_empty())
+            .cloned()
+            .collect();
+        let reads = records
+            .filter(|r| r.direction == Direction::Read)
+            .flat_map(|r| r.bytes.iter().copied())
+            .collect();
+
+        Self {
+            peer,
+            state: Arc::new(Mutex::new(ReplayState {
+                writes,
+                write_index: 0,
+                write_offset: 0,
+                reads,
+                read_offset: 0,
+            })),
+        }
+    }
+
+    /// Number of recorded bytes which have not been written or read yet.
+    ///
+    /// Both are 0 if the party did everything in the transcript.
+    pub fn remaining(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        let written = state.writes[..state.write_index.min(state.writes.len())]
+            .iter()
+            .map(|r| r.bytes.len())
+            .sum::<usize>()
+            + state.write_offset;
+        let total = state.writes.iter().map(|r| r.bytes.len()).sum::<usize>();
+
+        (total - written, state.reads.len() - state.read_offset)
+    }
+}
+
+impl AbstractChannel for ReplayChannel {
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        for &actual in bytes.iter() {
+            let ReplayState {
+                writes,
+                write_index,
+                write_offset,
+                ..
+            } = &mut *state;
+
+            let Some(record) = writes.get(*write_index) else {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    Divergence {
+                        peer: self.peer,
+                        phase: String::new(),
+                        write_index: *write_index,
+                        offset: 0,
+                        expected: None,
+                        actual,
+                    },
+                ));
+            };
+
+            let expected = record.bytes[*write_offset];
+            if expected != actual {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    Divergence {
+                        peer: self.peer,
+                        phase: record.phase.clone(),
+                        write_index: *write_index,
+                        offset: *write_offset,
+                        expected: Some(expected),
+                        actual,
+                    },
+                ));
+            }
+
+            *write_offset += 1;
+            if *write_offset == record.bytes.len() {
+                *write_index += 1;
+                *write_offset = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let start = state.read_offset;
+        if state.reads.len() - start < bytes.len() {
+            return Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "read more than the transcript from party {}: {} bytes at offset {}",
+                    self.peer,
+                    bytes.len(),
+                    start
+                ),
+            ));
+        }
+
+        bytes.copy_from_slice(&state.reads[start..start + bytes.len()]);
+        state.read_offset += bytes.len();
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            peer: self.peer,
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+    use rand::Rng;
+    use scuttlebutt::AesRng;
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut rng = AesRng::new();
+        let path = std::env::temp_dir().join(format!("test_transcript_{}", rng.gen::<u64>()));
+        let header = TranscriptHeader {
+            party: 1,
+            seed: rng.gen(),
+        };
+
+        let data = (0..100).map(|_| rng.gen()).collect::<Vec<u8>>();
+
+        // party 1 talks with party 0 and 2
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(3);
+        let recorder = Recorder::create(&path, header).unwrap();
+        let mut to_0 = RecordingChannel::new(channels[0].remove(0).1, 0, recorder.clone());
+        let mut to_2 = RecordingChannel::new(channels[0].remove(0).1, 2, recorder.clone());
+
+        recorder.set_phase("offline");
+        receiver_channels[0].1.write_bytes(&data).unwrap();
+        let mut buf = vec![0u8; 100];
+        to_0.read_bytes(&mut buf[..30]).unwrap();
+        to_0.read_bytes(&mut buf[30..]).unwrap();
+        assert_eq!(buf, data);
+
+        recorder.set_phase("online");
+        to_2.write_bytes(&data[..50]).unwrap();
+        to_2.write_bytes(&data[50..]).unwrap();
+        to_0.write_u64(42).unwrap();
+        drop((to_0, to_2, recorder));
+
+        let (read_header, records) = read_transcript(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_header, header);
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].direction, Direction::Read);
+        assert_eq!(records[0].phase, "offline");
+        assert_eq!(records[2].peer, 2);
+        assert_eq!(records[2].phase, "online");
+
+        // the same behavior in different pieces
+        let mut replay_0 = ReplayChannel::new(0, &records);
+        let mut replay_2 = ReplayChannel::new(2, &records);
+        replay_0.read_bytes(&mut buf).unwrap();
+        assert_eq!(buf, data);
+        replay_2.write_bytes(&data).unwrap();
+        assert_eq!(replay_0.remaining(), (8, 0));
+        replay_0.write_u64(42).unwrap();
+        assert_eq!(replay_0.remaining(), (0, 0));
+        assert!(replay_0.read_u8().is_err());
+
+        // the first differing byte is reported
+        let mut replay_2 = ReplayChannel::new(2, &records);
+        let mut changed = data.clone();
+        changed[60] ^= 1;
+        let err = replay_2.write_bytes(&changed).unwrap_err();
+        let divergence = err.get_ref().unwrap().downcast_ref::<Divergence>().unwrap();
+        assert_eq!(divergence.write_index, 1);
+        assert_eq!(divergence.offset, 10);
+        assert_eq!(divergence.phase, "online");
+        assert_eq!(divergence.expected, Some(data[60]));
+    }
+}
@@ -3,22 +3,25 @@
 //! Here, you can know the options for the protocol through enum types and structs.
 //! See other modules for the actual implementation of the protocol or details of what options mean.
 
-use crate::channel_utils::sync_channel::create_unix_channels;
-use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+use crate::channel_utils::sync_channel::create_unix_channels_with_timeout;
+use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels_with_timeout;
 use crate::channel_utils::sync_channel_by_cb::{CrossbeamReceiver, CrossbeamSender};
-use crate::channel_utils::tcp_channel::create_tcp_channels;
-use crate::solver::{Solver, SolverParams};
+use crate::channel_utils::tcp_channel::create_tcp_channels_with_timeout;
+use crate::channel_utils::transcript::{RecordingChannel, ReplayChannel};
+use crate::error::Result;
+use crate::solver::{RetryPolicy, Solver, SolverOptions, SolverParams, DEFAULT_LAMBDA};
 use crate::vole::{
     LPNVoleReceiver, LPNVoleSender, OtVoleReceiver, OtVoleSender, VoleShareForReceiver,
-    VoleShareForSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM, LPN_SETUP_SMALL,
+    VoleShareForSender,
 };
-use anyhow::Result;
 use clap::{Parser, ValueEnum};
 use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
 use scuttlebutt::field::F128b;
 use scuttlebutt::{AbstractChannel, SyncChannel};
 use std::fmt::Display;
 use std::net::TcpStream;
+use std::path::PathBuf;
+use std::time::Duration;
 use std::{
     io::{BufReader, BufWriter},
     os::unix::net::UnixStream,
@@ -51,6 +54,10 @@ pub enum SolverType {
     Vandelmonde,
     /// Use PaXoS (Probe-and-XOR of Strings) to encode algorithm. See [PaxosSolver](crate::solver::PaxosSolver).
     Paxos,
+    /// Use 3-hash 2-core peeling OKVS to encode algorithm. See [TwoCoreSolver](crate::solver::TwoCoreSolver).
+    TwoCore,
+    /// Use random band matrix OKVS to encode algorithm. See [BandSolver](crate::solver::BandSolver).
+    Band,
 }
 
 impl Display for SolverType {
@@ -58,6 +65,8 @@ impl Display for SolverType {
         match self {
             SolverType::Vandelmonde => write!(f, "vandelmonde"),
             SolverType::Paxos => write!(f, "paxos"),
+            SolverType::TwoCore => write!(f, "two-core"),
+            SolverType::Band => write!(f, "band"),
         }
     }
 }
@@ -83,26 +92,6 @@ impl Display for ChannelType {
     }
 }
 
-/// Multi-thread optimization.
-/// Off doesn’t mean single-threaded version. The difference between the optimized version and the not one is that in where parties exchange messages.
-/// More details: [psi::Receiver](crate::preprocessed::psi::Receiver) and [psi::Sender](crate::preprocessed::psi::Sender). `*_mt` functions are used in the optimized version.
-#[derive(Clone, Copy, ValueEnum, Debug)]
-pub enum MultiThreadOptimization {
-    /// On means that the optimized version is used.
-    On,
-    /// Off means that the not optimized version is used.
-    Off,
-}
-
-impl Display for MultiThreadOptimization {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            MultiThreadOptimization::On => write!(f, "on"),
-            MultiThreadOptimization::Off => write!(f, "off"),
-        }
-    }
-}
-
 /// Arguments for Preprocessing MPSI protocol.
 /// This struct implements [clap::Parser] to make that this binary has CommandLine Arguments.
 #[derive(Parser, Debug)]
@@ -138,11 +127,72 @@ pub struct PrePSIArgs {
     #[arg(short = 'p', long = "port", default_value_t = 10000)]
     pub port: usize,
 
-    /// Multi-thread optimization.
+    /// Number of threads for per-element work such as hashing and decoding. 0 means the available parallelism.
+    ///
+    /// All parties in this process share one [pool](crate::parallel_utils::Threads) of this many threads.
+    /// With 1, each party communicates with its peers in turn in one thread.
+    /// Otherwise, it also has a thread per peer ([Execution::ThreadPerPeer](crate::parallel_utils::Execution::ThreadPerPeer)), which hands its per-element work to the pool.
+    #[arg(short = 't', long = "threads", default_value_t = 0)]
+    pub threads: usize,
+
+    /// Statistical security parameter of the solver.
+    ///
+    /// Encoding fails with probability about $`2^{-\lambda}`$. See [SolverOptions].
+    #[arg(long = "lambda", default_value_t = DEFAULT_LAMBDA)]
+    pub lambda: usize,
+
+    /// Expansion factor of the solver, i.e. the code length divided by the set size.
+    ///
+    /// If not specified, the default of each solver is used. See [SolverOptions].
+    #[arg(long = "expansion")]
+    pub expansion: Option<f64>,
+
+    /// Number of extra dense columns (stash) of the solver.
+    ///
+    /// It lets encoding absorb more constraints at the cost of a longer code vector. See [SolverOptions].
+    #[arg(long = "stash", default_value_t = 0)]
+    pub stash_size: usize,
+
+    /// Maximum number of encoding attempts with fresh solver keys. See [RetryPolicy].
+    #[arg(long = "max-attempts", default_value_t = RetryPolicy::default().max_attempts)]
+    pub max_attempts: usize,
+
+    /// Deadline in seconds of each read and write on the channels. 0 (the default) means no deadline.
+    ///
+    /// A party waiting longer than this for a peer fails and aborts the protocol.
+    /// A peer may be silent for long while it computes locally, e.g. encoding a large set,
+    /// so the deadline must be longer than the local computation of every party.
+    #[arg(long = "timeout", default_value_t = 0)]
+    pub timeout: u64,
+
+    /// Seed from which the sets and the RNGs of all parties are derived.
     ///
-    /// Off doesn't mean single-threaded and at least as many threads are created as parties.
-    #[arg(short = 't', long = "threads", default_value_t = MultiThreadOptimization::On)]
-    pub multi_thread: MultiThreadOptimization,
+    /// The same seed reproduces the same run. For testing only: the seed reveals every secret.
+    /// Needs a build with the `testing` feature (see `testing::SeedTree`).
+    #[arg(long = "seed")]
+    pub seed: Option<u128>,
+
+    /// Record the transcript of each party to `<DIR>/party_<ID>.transcript`.
+    ///
+    /// Parties use RNGs derived from `--seed`, which is required and stored in the transcripts,
+    /// so the transcripts reveal every secret. See [transcript](crate::channel_utils::transcript).
+    #[arg(
+        long = "record",
+        value_name = "DIR",
+        conflicts_with = "replay",
+        requires = "seed"
+    )]
+    pub record: Option<PathBuf>,
+
+    /// Re-run the party `--party` alone against its transcript in `<DIR>` recorded by `--record`.
+    ///
+    /// The other arguments must be the same as the recorded run. The first byte where the party diverges is reported.
+    #[arg(long = "replay", value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+
+    /// ID of the party to replay. 0 is the receiver.
+    #[arg(long = "party", default_value_t = 0, requires = "replay")]
+    pub party: usize,
 
     /// Verbose mode.
     ///
@@ -191,6 +241,10 @@ pub enum ChannelUnion {
     Tcp(SyncChannel<BufReader<TcpStream>, BufWriter<TcpStream>>),
     /// Native channel of Rust. See [CrossbeamReceiver] and [CrossbeamSender].
     CrossBeam(SyncChannel<CrossbeamReceiver, CrossbeamSender>),
+    /// Another channel whose transcript is recorded. See [RecordingChannel].
+    Recording(Box<RecordingChannel<ChannelUnion>>),
+    /// Replay of a transcript. See [ReplayChannel].
+    Replay(ReplayChannel),
 }
 
 use ChannelUnion::*;
@@ -202,6 +256,8 @@ impl AbstractChannel for ChannelUnion {
             Unix(c) => c.write_bytes(bytes),
             Tcp(c) => c.write_bytes(bytes),
             CrossBeam(c) => c.write_bytes(bytes),
+            Recording(c) => c.write_bytes(bytes),
+            Replay(c) => c.write_bytes(bytes),
         }
     }
 
@@ -211,6 +267,8 @@ impl AbstractChannel for ChannelUnion {
             Unix(c) => c.read_bytes(bytes),
             Tcp(c) => c.read_bytes(bytes),
             CrossBeam(c) => c.read_bytes(bytes),
+            Recording(c) => c.read_bytes(bytes),
+            Replay(c) => c.read_bytes(bytes),
         }
     }
 
@@ -220,6 +278,8 @@ impl AbstractChannel for ChannelUnion {
             Unix(c) => c.flush(),
             Tcp(c) => c.flush(),
             CrossBeam(c) => c.flush(),
+            Recording(c) => c.flush(),
+            Replay(c) => c.flush(),
         }
     }
 
@@ -229,6 +289,8 @@ impl AbstractChannel for ChannelUnion {
             Unix(c) => Unix(c.clone()),
             Tcp(c) => Tcp(c.clone()),
             CrossBeam(c) => CrossBeam(c.clone()),
+            Recording(c) => Recording(Box::new(AbstractChannel::clone(c.as_ref()))),
+            Replay(c) => Replay(c.clone()),
         }
     }
 }
@@ -252,17 +314,26 @@ macro_rules! make_union_channel {
 type Ucu = (usize, ChannelUnion);
 
 /// Create channels for the protocol. Runtime utility.
+///
+/// Each read and write on the channels fails after `timeout` if it is not `None`.
 pub fn create_channels(
     type_: ChannelType,
     nparties: usize,
     port: usize,
+    timeout: Option<Duration>,
 ) -> Result<(Vec<Ucu>, Vec<Vec<Ucu>>)> {
     match type_ {
-        ChannelType::Unix => make_union_channel!(create_unix_channels(nparties)?, Unix),
-        ChannelType::Tcp => make_union_channel!(create_tcp_channels(nparties, port)?, Tcp),
-        ChannelType::CrossBeam => {
-            make_union_channel!(create_crossbeam_channels(nparties), CrossBeam)
+        ChannelType::Unix => {
+            make_union_channel!(create_unix_channels_with_timeout(nparties, timeout)?, Unix)
         }
+        ChannelType::Tcp => make_union_channel!(
+            create_tcp_channels_with_timeout(nparties, port, timeout)?,
+            Tcp
+        ),
+        ChannelType::CrossBeam => make_union_channel!(
+            create_crossbeam_channels_with_timeout(nparties, timeout),
+            CrossBeam
+        ),
     }
 }
 
@@ -270,7 +341,7 @@ pub fn create_channels(
 #[derive(Clone, Copy)]
 pub enum VoleShareForReceiverUnion {
     /// Use Oblivious Transfer. See [OtVoleReceiver].
-    Ot(OtVoleReceiver<F128b, 128, OtReceiver>),
+    Ot(OtVoleReceiver<F128b, OtReceiver>),
     /// Use Learning Parity with Noise assumption. See [LPNVoleReceiver].
     Lpn(LPNVoleReceiver<F128b>),
 }
@@ -279,12 +350,19 @@ pub enum VoleShareForReceiverUnion {
 #[derive(Clone, Copy)]
 pub enum VoleShareForSenderUnion {
     /// Use Oblivious Transfer. See [OtVoleSender].
-    Ot(OtVoleSender<F128b, 128, OtSender>),
+    Ot(OtVoleSender<F128b, OtSender>),
     /// Use Learning Parity with Noise assumption. See [LPNVoleSender].
     Lpn(LPNVoleSender<F128b>),
 }
 
 impl VoleShareForReceiver<F128b> for VoleShareForReceiverUnion {
+    fn id(&self) -> String {
+        match self {
+            VoleShareForReceiverUnion::Ot(v) => v.id(),
+            VoleShareForReceiverUnion::Lpn(v) => v.id(),
+        }
+    }
+
     fn receive<C: AbstractChannel, RNG: rand::CryptoRng + rand::Rng>(
         &mut self,
         channel: &mut C,
@@ -299,6 +377,13 @@ impl VoleShareForReceiver<F128b> for VoleShareForReceiverUnion {
 }
 
 impl VoleShareForSender<F128b> for VoleShareForSenderUnion {
+    fn id(&self) -> String {
+        match self {
+            VoleShareForSenderUnion::Ot(v) => v.id(),
+            VoleShareForSenderUnion::Lpn(v) => v.id(),
+        }
+    }
+
     fn receive<C: AbstractChannel, RNG: rand::CryptoRng + rand::Rng>(
         &mut self,
         channel: &mut C,
@@ -314,18 +399,12 @@ impl VoleShareForSender<F128b> for VoleShareForSenderUnion {
 
 fn create_lpn_vole_sr<S: Solver<F128b>>(
     set_size: usize,
+    options: SolverOptions,
 ) -> (LPNVoleSender<F128b>, LPNVoleReceiver<F128b>) {
-    let m_size = S::calc_params(set_size).code_length();
-    let (setup_param, extend_param) = if m_size < (1 << 17) {
-        println!("Small parameters are used.");
-        (LPN_SETUP_SMALL, LPN_EXTEND_SMALL)
-    } else {
-        println!("Medium parameters are used.");
-        (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
-    };
+    let m_size = S::calc_params_with(set_size, options).code_length();
     (
-        LPNVoleSender::new(setup_param, extend_param),
-        LPNVoleReceiver::new(setup_param, extend_param),
+        LPNVoleSender::for_size(m_size),
+        LPNVoleReceiver::for_size(m_size),
     )
 }
 
@@ -333,6 +412,7 @@ fn create_lpn_vole_sr<S: Solver<F128b>>(
 pub fn create_vole_sr<S: Solver<F128b>>(
     vole_type: VoleType,
     set_size: usize,
+    options: SolverOptions,
 ) -> (VoleShareForSenderUnion, VoleShareForReceiverUnion) {
     match vole_type {
         VoleType::Ot => (
@@ -340,7 +420,7 @@ pub fn create_vole_sr<S: Solver<F128b>>(
             VoleShareForReceiverUnion::Ot(OtVoleReceiver::new()),
         ),
         VoleType::Lpn => {
-            let (s, r) = create_lpn_vole_sr::<S>(set_size);
+            let (s, r) = create_lpn_vole_sr::<S>(set_size, options);
             (
                 VoleShareForSenderUnion::Lpn(s),
                 VoleShareForReceiverUnion::Lpn(r),
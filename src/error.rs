@@ -0,0 +1,239 @@
+//! Errors of this library.
+//!
+//! Public APIs of [preprocessed](crate::preprocessed), [vole](crate::vole), [solver](crate::solver) and [channel_utils](crate::channel_utils)
+//! return [Error], so that callers can tell a peer disconnect from a solver failure, a VOLE length mismatch or a bad argument.
+//!
+//! Errors are decorated by [Context] in the same way as [anyhow::Context].
+//! [Context::located] records the location where the error passed by `#[track_caller]`,
+//! so the location is not written into the messages of the variants.
+//! [Error::root] strips the decorations and returns the class of the failure.
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::error::{Context, Error, Result};
+//! use std::io::{Error as IoError, ErrorKind};
+//!
+//! fn read() -> Result<()> {
+//!     Err(IoError::from(ErrorKind::BrokenPipe)).located()
+//! }
+//!
+//! let err = read().unwrap_err();
+//! assert!(matches!(err.root(), Error::Channel(_)));
+//! assert!(err.to_string().starts_with("at "));
+//! ```
+
+use crate::channel_utils::abortable::Aborted;
+use crate::solver::SolverError;
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::panic::Location;
+use thiserror::Error;
+
+/// [Result](std::result::Result) with [Error] as the default error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error of this library.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Communication with a peer failed, e.g. the peer disconnected.
+    #[error("channel error: {0}")]
+    Channel(#[source] std::io::Error),
+    /// Communication with a peer did not complete in time.
+    #[error("timed out: {0}")]
+    Timeout(#[source] std::io::Error),
+    /// A party aborted the protocol. See [abortable](crate::channel_utils::abortable).
+    #[error("aborted by party {culprit}: {reason}")]
+    Aborted {
+        /// ID of the party which caused the abort.
+        culprit: usize,
+        /// Why the party aborted.
+        reason: String,
+    },
+    /// VOLE correlations could not be shared, or are exhausted or of an unexpected length.
+    #[error("VOLE error: {0}")]
+    Vole(String),
+    /// A solver failed to encode or decode.
+    #[error("solver error: {0}")]
+    Solver(#[from] SolverError),
+    /// A peer sent a malformed or inconsistent message.
+    #[error("protocol violation{}: {reason}", .peer.map(|p| format!(" by party {}", p)).unwrap_or_default())]
+    ProtocolViolation {
+        /// ID of the peer if known.
+        peer: Option<usize>,
+        /// What was wrong.
+        reason: String,
+    },
+    /// A peer runs the protocol with different parameters. See [handshake](crate::preprocessed::handshake).
+    #[error("party {peer} is incompatible: {what} is {theirs}, but ours is {ours}")]
+    Incompatible {
+        /// ID of the peer.
+        peer: usize,
+        /// Which parameter differs.
+        what: &'static str,
+        /// Our value of the parameter.
+        ours: String,
+        /// The peer's value of the parameter.
+        theirs: String,
+    },
+    /// An argument or a configuration is invalid.
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    /// A local computation failed, e.g. a hash value is not a canonical field element.
+    #[error("internal error: {0}")]
+    Internal(String),
+    /// An error decorated by [Context].
+    #[error("{context}")]
+    Context {
+        /// Decoration, e.g. the location recorded by [located](Context::located).
+        context: String,
+        /// The decorated error.
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// A [ProtocolViolation](Error::ProtocolViolation) by an unknown peer.
+    pub fn protocol_violation(reason: impl Into<String>) -> Self {
+        Error::ProtocolViolation {
+            peer: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// The error without decorations by [Context].
+    pub fn root(&self) -> &Error {
+        let mut err = self;
+        while let Error::Context { source, .. } = err {
+            err = source;
+        }
+        err
+    }
+
+    /// Attribute a [ProtocolViolation](Error::ProtocolViolation) by an unknown peer to `peer`.
+    ///
+    /// Other errors are returned as they are.
+    pub fn with_peer(self, peer: usize) -> Self {
+        match self {
+            Error::ProtocolViolation { peer: None, reason } => Error::ProtocolViolation {
+                peer: Some(peer),
+                reason,
+            },
+            Error::Context { context, source } => Error::Context {
+                context,
+                source: Box::new(source.with_peer(peer)),
+            },
+            err => err,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        if let Some(Aborted { culprit, reason }) =
+            err.get_ref().and_then(|e| e.downcast_ref::<Aborted>())
+        {
+            return Error::Aborted {
+                culprit: *culprit,
+                reason: reason.clone(),
+            };
+        }
+
+        match err.kind() {
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Error::Timeout(err),
+            _ => Error::Channel(err),
+        }
+    }
+}
+
+impl From<ocelot::Error> for Error {
+    fn from(err: ocelot::Error) -> Self {
+        match err {
+            ocelot::Error::IoError(err) => err.into(),
+            err => Error::Vole(err.to_string()),
+        }
+    }
+}
+
+/// Decorate errors in the same way as [anyhow::Context] keeping their class.
+pub trait Context<T> {
+    /// Wrap the error with `context`.
+    fn context<C: Display>(self, context: C) -> Result<T>;
+
+    /// Wrap the error with the context evaluated lazily by `f`.
+    fn with_context<C: Display, Fun: FnOnce() -> C>(self, f: Fun) -> Result<T>;
+
+    /// Wrap the error with the location of the caller.
+    fn located(self) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|err| Error::Context {
+            context: context.to_string(),
+            source: Box::new(err.into()),
+        })
+    }
+
+    fn with_context<C: Display, Fun: FnOnce() -> C>(self, f: Fun) -> Result<T> {
+        self.map_err(|err| Error::Context {
+            context: f().to_string(),
+            source: Box::new(err.into()),
+        })
+    }
+
+    #[track_caller]
+    fn located(self) -> Result<T> {
+        let location = Location::caller();
+        self.map_err(|err| Error::Context {
+            context: format!("at {}", location),
+            source: Box::new(err.into()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(err: Error) -> Result<()> {
+        Err(err).located()
+    }
+
+    #[test]
+    fn test_located() {
+        let err = fail(Error::Internal("x".into())).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("at {}:", file!())));
+        assert!(matches!(err.root(), Error::Internal(_)));
+    }
+
+    #[test]
+    fn test_root_and_peer() {
+        let err = fail(std::io::Error::from(ErrorKind::UnexpectedEof).into())
+            .context("outer")
+            .unwrap_err();
+        assert!(matches!(err.root(), Error::Channel(_)));
+        assert_eq!(err.to_string(), "outer");
+
+        let err = fail(std::io::Error::from(ErrorKind::TimedOut).into()).unwrap_err();
+        assert!(matches!(err.root(), Error::Timeout(_)));
+
+        let err = fail(SolverError::Unsolvable.into()).unwrap_err();
+        assert!(matches!(err.root(), Error::Solver(SolverError::Unsolvable)));
+
+        let err = fail(Error::protocol_violation("bad"))
+            .unwrap_err()
+            .with_peer(3);
+        assert!(matches!(
+            err.root(),
+            Error::ProtocolViolation { peer: Some(3), .. }
+        ));
+        assert_eq!(err.root().to_string(), "protocol violation by party 3: bad");
+
+        // already attributed
+        let err = err.with_peer(4);
+        assert!(matches!(
+            err.root(),
+            Error::ProtocolViolation { peer: Some(3), .. }
+        ));
+    }
+}
@@ -1,82 +1,141 @@
-use anyhow::{Context, Result};
+//! Random oracles $`H: \mathbb{F} \times \mathbb{F} \to \mathbb{F}`$ and $`H^{\mathbb{F}}: \mathbb{F} \to \mathbb{F}`$ used by the OPRF.
+//!
+//! They are generic over [KeyedHasher] keyed by a public constant, so the hash is selected by a type parameter
+//! as [solvers](crate::solver) do. The OPRF uses [Sha256Hasher](crate::hasher::Sha256Hasher) by default.
+
+use crate::error::{Context, Error, Result};
+use crate::hasher::KeyedHasher;
+use crate::parallel_utils::par_map;
 use scuttlebutt::field::FiniteField as FF;
-use sha2::{Digest, Sha256};
-use typenum::marker_traits::Unsigned;
+use scuttlebutt::Block;
+
+// Public key of the hash used as random oracle.
+const FIXED_KEY: u128 = 0x243f6a8885a308d313198a2e03707344;
+
+/// Hasher of the random oracle. Prepare it once and pass it to [hash] and [hash_f].
+pub fn oracle<H: KeyedHasher>() -> H {
+    H::new(Block::from(FIXED_KEY))
+}
 
 /// Hash Function s.t.
 /// H: F x F -> F
 #[inline]
-pub fn hash<F: FF>(x: F, y: F) -> Result<F> {
-    let mut hasher = Sha256::new();
-    hasher.update(x.to_bytes());
-    hasher.update(y.to_bytes());
-    let res = hasher.finalize();
-    let slc = res.as_slice();
-    let len = F::ByteReprLen::to_usize();
-    let byt = (&slc[..len]).as_ref().into();
-    Ok(F::from_bytes(byt).with_context(|| format!("@{}:{}", file!(), line!()))?)
+pub fn hash<F: FF, H: KeyedHasher>(hasher: &H, x: F, y: F) -> Result<F> {
+    hasher
+        .hash_to_field(&[x.to_bytes().as_slice(), y.to_bytes().as_slice()])
+        .located()
 }
 
 /// Hash Function s.t.
 /// H^F: F -> F
 #[inline]
-pub fn hash_f<F: FF>(x: F) -> Result<F> {
-    let mut hasher = Sha256::new();
-    hasher.update(x.to_bytes());
-    let res = hasher.finalize();
-    let slc = res.as_slice();
-    let len = F::ByteReprLen::to_usize();
-    let byt = (&slc[..len]).as_ref().into();
-    Ok(F::from_bytes(byt).with_context(|| format!("@{}:{}", file!(), line!()))?)
+pub fn hash_f<F: FF, H: KeyedHasher>(hasher: &H, x: F) -> Result<F> {
+    hasher.hash_to_field(&[x.to_bytes().as_slice()]).located()
+}
+
+/// Batched [hash_f]. Large batches are computed in parallel.
+pub fn hash_f_batch<F: FF, H: KeyedHasher>(xs: &[F]) -> Result<Vec<F>> {
+    let hasher = oracle::<H>();
+    par_map(xs, |x| hash_f(&hasher, *x))
+}
+
+/// Batched [hash] s.t. returns $`H(x_i, y_i)`$ for each $`i`$. Large batches are computed in parallel.
+pub fn hash_batch<F: FF, H: KeyedHasher>(xs: &[F], ys: &[F]) -> Result<Vec<F>> {
+    if xs.len() != ys.len() {
+        return Err(Error::Config(format!(
+            "xs.len() (={}) != ys.len() (={})",
+            xs.len(),
+            ys.len()
+        )));
+    }
+
+    let hasher = oracle::<H>();
+    let pairs = xs
+        .iter()
+        .copied()
+        .zip(ys.iter().copied())
+        .collect::<Vec<_>>();
+    par_map(&pairs, |(x, y)| hash(&hasher, *x, *y))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hasher::{AesHasher, Sha256Hasher};
     use rand::Rng;
     use scuttlebutt::field::F128b;
     use scuttlebutt::serialization::CanonicalSerialize;
     use scuttlebutt::AesRng;
 
-    #[test]
-    fn test_hash() {
+    fn test_hash_base<H: KeyedHasher>() {
         let mut rng = AesRng::new();
         let x: F128b = rng.gen();
         let y: F128b = rng.gen();
 
-        let h = hash(x, y).unwrap();
-
-        let mut hasher = Sha256::new();
-        hasher.update(x.to_bytes());
-        hasher.update(y.to_bytes());
-        let res = hasher.finalize();
-        let slc = res.as_slice();
-        let len = <F128b as CanonicalSerialize>::ByteReprLen::to_usize();
-        let byt = (&slc[..len]).as_ref().into();
-        let h2 = F128b::from_bytes(byt).unwrap();
+        let hasher = oracle::<H>();
+        let h = hash(&hasher, x, y).unwrap();
 
-        dbg!(x, y, h);
+        let mut out = [0u8; 16];
+        H::new(Block::from(FIXED_KEY)).hash_into(
+            &[x.to_bytes().as_slice(), y.to_bytes().as_slice()],
+            &mut out,
+        );
+        let h2 = F128b::from_bytes(out.as_slice().into()).unwrap();
 
         assert_eq!(h, h2);
+        assert_ne!(h, hash(&hasher, y, x).unwrap());
     }
 
-    #[test]
-    fn test_hash_f() {
+    fn test_hash_f_base<H: KeyedHasher>() {
         let mut rng = AesRng::new();
         let x: F128b = rng.gen();
 
-        let h = hash_f(x).unwrap();
+        let hasher = oracle::<H>();
+        let h = hash_f(&hasher, x).unwrap();
 
-        let mut hasher = Sha256::new();
-        hasher.update(x.to_bytes());
-        let res = hasher.finalize();
-        let slc = res.as_slice();
-        let len = <F128b as CanonicalSerialize>::ByteReprLen::to_usize();
-        let byt = (&slc[..len]).as_ref().into();
-        let h2 = F128b::from_bytes(byt).unwrap();
-
-        dbg!(x, h);
+        let mut out = [0u8; 16];
+        H::new(Block::from(FIXED_KEY)).hash_into(&[x.to_bytes().as_slice()], &mut out);
+        let h2 = F128b::from_bytes(out.as_slice().into()).unwrap();
 
         assert_eq!(h, h2);
+        assert_ne!(h, hash_f(&hasher, x + F128b::one()).unwrap());
+    }
+
+    fn test_hash_batch_base<H: KeyedHasher>() {
+        let mut rng = AesRng::new();
+        let xs = (0..5000).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        let ys = (0..5000).map(|_| rng.gen()).collect::<Vec<F128b>>();
+
+        let hasher = oracle::<H>();
+
+        let hs = hash_f_batch::<_, H>(&xs).unwrap();
+        for (x, h) in xs.iter().zip(hs) {
+            assert_eq!(hash_f(&hasher, *x).unwrap(), h);
+        }
+
+        let hs = hash_batch::<_, H>(&xs, &ys).unwrap();
+        for ((x, y), h) in xs.iter().zip(ys.iter()).zip(hs) {
+            assert_eq!(hash(&hasher, *x, *y).unwrap(), h);
+        }
+
+        assert!(hash_batch::<_, H>(&xs, &ys[1..]).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        test_hash_base::<Sha256Hasher>();
+        test_hash_base::<AesHasher>();
+    }
+
+    #[test]
+    fn test_hash_f() {
+        test_hash_f_base::<Sha256Hasher>();
+        test_hash_f_base::<AesHasher>();
+    }
+
+    #[test]
+    fn test_hash_batch() {
+        test_hash_batch_base::<Sha256Hasher>();
+        test_hash_batch_base::<AesHasher>();
     }
 }
@@ -0,0 +1,248 @@
+//! Keyed hash functions used by [solvers](crate::solver) and the OPRF.
+//!
+//! A [KeyedHasher] maps byte strings to as many pseudorandom bytes as requested.
+//! Solvers call it once per point and cut the output into cuckoo indices and the bits of $`r(x)`$
+//! by [KeyedHasher::indices_and_bits], so the hash can be selected per solver by its type parameter.
+//!
+//! - [AesHasher]: fixed-key AES in Matyas–Meyer–Oseas mode. It is the default of solvers and much faster than SHA-256.
+//! - [Sha256Hasher]: SHA-256 with the key as prefix. It is the default random oracle of the [OPRF](crate::preprocessed::oprf) output.
+//!
+//! [AesHasher] is based on the correlation robustness of fixed-key AES (see [Efficient and Secure Multiparty Computation from Fixed-Key Block Ciphers](https://eprint.iacr.org/2019/074)).
+//! Since the key is public, it is not collision resistant for inputs longer than one block against a party choosing inputs adversarially.
+//! Use [Sha256Hasher] if that matters.
+
+use crate::error::{Error, Result};
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{Aes128, Block};
+use sha2::{Digest, Sha256};
+use typenum::marker_traits::Unsigned;
+
+/// Hash function keyed by a [Block].
+pub trait KeyedHasher: 'static + Clone + Send + Sync {
+    /// Stable name of the hash function sent in the [handshake](crate::preprocessed::handshake) as part of [Solver::id](crate::solver::Solver::id).
+    const ID: &'static str;
+
+    /// Prepare the hash function for `key`.
+    fn new(key: Block) -> Self;
+
+    /// Hash the concatenation of `input` and fill `out` with the result.
+    fn hash_into(&self, input: &[&[u8]], out: &mut [u8]);
+
+    /// Hash `x` once into `N` indices in $`[0, max)`$ and `m` bits.
+    #[inline]
+    fn indices_and_bits<F: FF, const N: usize>(
+        &self,
+        x: F,
+        max: usize,
+        m: usize,
+    ) -> ([usize; N], Vec<bool>) {
+        let len = 8 * N + m.div_ceil(8);
+        let mut buf = [0u8; 64];
+        let mut heap;
+        let out = if len <= buf.len() {
+            &mut buf[..len]
+        } else {
+            heap = vec![0u8; len];
+            &mut heap[..]
+        };
+
+        self.hash_into(&[x.to_bytes().as_slice()], out);
+
+        let mut indices = [0; N];
+        for (i, chunk) in indices.iter_mut().zip(out.chunks(8)) {
+            *i = (u64::from_le_bytes(chunk.try_into().unwrap()) % max as u64) as usize;
+        }
+
+        let bits = out[8 * N..]
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |i| byte & (1 << i) != 0))
+            .take(m)
+            .collect();
+
+        (indices, bits)
+    }
+
+    /// Hash the concatenation of `input` to a field element.
+    #[inline]
+    fn hash_to_field<F: FF>(&self, input: &[&[u8]]) -> Result<F> {
+        let len = F::ByteReprLen::to_usize();
+        let mut buf = vec![0u8; len];
+        self.hash_into(input, &mut buf);
+        let byt = buf.as_slice().into();
+        F::from_bytes(byt)
+            .map_err(|e| Error::Internal(format!("hash value is not a field element: {}", e)))
+    }
+}
+
+/// Fixed-key AES hash.
+///
+/// The input is absorbed block by block as $`h_0 = |x|, h_{i+1} = \pi(h_i \oplus x_i) \oplus x_i`$
+/// and the output is $`\pi(h \oplus j) \oplus h \oplus j`$ for the $`j`$-th block, where $`\pi`$ is AES with the key.
+#[derive(Clone)]
+pub struct AesHasher {
+    aes: Aes128,
+}
+
+impl AesHasher {
+    #[inline]
+    fn mmo(&self, b: Block) -> Block {
+        self.aes.encrypt(b) ^ b
+    }
+}
+
+impl KeyedHasher for AesHasher {
+    const ID: &'static str = "aes";
+
+    fn new(key: Block) -> Self {
+        Self {
+            aes: Aes128::new(key),
+        }
+    }
+
+    #[inline]
+    fn hash_into(&self, input: &[&[u8]], out: &mut [u8]) {
+        let total = input.iter().map(|part| part.len()).sum::<usize>();
+        let mut h = Block::from(total as u128);
+
+        let mut buf = [0u8; 16];
+        let mut filled = 0;
+        for &byte in input.iter().flat_map(|part| part.iter()) {
+            buf[filled] = byte;
+            filled += 1;
+            if filled == 16 {
+                let m = Block::from(u128::from_le_bytes(buf));
+                h = self.aes.encrypt(h ^ m) ^ m;
+                filled = 0;
+            }
+        }
+        if filled > 0 {
+            buf[filled..].fill(0);
+            let m = Block::from(u128::from_le_bytes(buf));
+            h = self.aes.encrypt(h ^ m) ^ m;
+        }
+
+        for (j, chunk) in out.chunks_mut(16).enumerate() {
+            let v = u128::from(self.mmo(h ^ Block::from(j as u128)));
+            chunk.copy_from_slice(&v.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// SHA-256 hash with the key as prefix.
+///
+/// The output is $`\mathrm{SHA256}(k \| x \| j)`$ for the $`j`$-th 32 bytes.
+#[derive(Clone)]
+pub struct Sha256Hasher {
+    hasher: Sha256,
+}
+
+impl KeyedHasher for Sha256Hasher {
+    const ID: &'static str = "sha256";
+
+    fn new(key: Block) -> Self {
+        Self {
+            hasher: Sha256::new_with_prefix(u128::from(key).to_le_bytes()),
+        }
+    }
+
+    #[inline]
+    fn hash_into(&self, input: &[&[u8]], out: &mut [u8]) {
+        let mut hasher = self.hasher.clone();
+        for part in input.iter() {
+            hasher.update(part);
+        }
+
+        for (j, chunk) in out.chunks_mut(32).enumerate() {
+            let res = hasher
+                .clone()
+                .chain_update((j as u32).to_le_bytes())
+                .finalize();
+            chunk.copy_from_slice(&res[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use scuttlebutt::field::{F128b, F61p};
+    use scuttlebutt::serialization::CanonicalSerialize;
+    use scuttlebutt::AesRng;
+
+    fn test_hasher_base<H: KeyedHasher>() {
+        let mut rng = AesRng::new();
+        let hasher = H::new(rng.gen());
+
+        // deterministic, and the output does not depend on how the input is split
+        let x = (0..40).map(|_| rng.gen()).collect::<Vec<u8>>();
+        let mut out1 = [0u8; 80];
+        let mut out2 = [0u8; 80];
+        hasher.hash_into(&[&x[..]], &mut out1);
+        hasher.hash_into(&[&x[..7], &x[7..33], &x[33..]], &mut out2);
+        assert_eq!(out1, out2);
+
+        // a prefix of a longer output is a shorter output
+        let mut out3 = [0u8; 20];
+        hasher.hash_into(&[&x[..]], &mut out3);
+        assert_eq!(out1[..20], out3);
+
+        // different keys, inputs and lengths give different outputs
+        let other = H::new(rng.gen());
+        other.hash_into(&[&x[..]], &mut out2);
+        assert_ne!(out1, out2);
+        hasher.hash_into(&[&x[..39]], &mut out2);
+        assert_ne!(out1, out2);
+        hasher.hash_into(&[&x[..39], &[0u8][..]], &mut out2);
+        assert_eq!(out1 == out2, x[39] == 0);
+
+        for _ in 0..1000 {
+            let x: F128b = rng.gen();
+            let max = rng.gen_range(1..1 << 20);
+            let m = rng.gen_range(1..300);
+
+            let (indices, bits) = hasher.indices_and_bits::<_, 3>(x, max, m);
+            assert_eq!(
+                (indices, bits.clone()),
+                hasher.indices_and_bits::<_, 3>(x, max, m)
+            );
+            assert!(indices.iter().all(|&i| i < max));
+            assert_eq!(bits.len(), m);
+        }
+
+        let x: F61p = rng.gen();
+        assert_eq!(
+            hasher
+                .hash_to_field::<F128b>(&[x.to_bytes().as_slice()])
+                .unwrap(),
+            hasher
+                .hash_to_field::<F128b>(&[x.to_bytes().as_slice()])
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aes_hasher() {
+        test_hasher_base::<AesHasher>();
+    }
+
+    #[test]
+    fn test_sha256_hasher() {
+        test_hasher_base::<Sha256Hasher>();
+
+        // the first block is SHA256(k || x || 0)
+        let mut rng = AesRng::new();
+        let key: Block = rng.gen();
+        let x = (0..40).map(|_| rng.gen()).collect::<Vec<u8>>();
+
+        let mut out = [0u8; 32];
+        Sha256Hasher::new(key).hash_into(&[&x[..]], &mut out);
+
+        let mut hasher = Sha256::new();
+        hasher.update(u128::from(key).to_le_bytes());
+        hasher.update(&x);
+        hasher.update(0u32.to_le_bytes());
+
+        assert_eq!(out.as_slice(), hasher.finalize().as_slice());
+    }
+}
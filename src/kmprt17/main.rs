@@ -44,7 +44,7 @@ fn protocol(
 
     // create channels
     let (mut receiver_channels, channels) =
-        create_channels(channel_type, num_parties, port).unwrap();
+        create_channels(channel_type, num_parties, port, None).unwrap();
 
     for (i, mut channels) in channels.into_iter().enumerate() {
         // create and fork senders
@@ -1,4 +1,565 @@
-//! Module that re-exports [popsicle::kmprt] module and provides its multithread optimized implementaion.
+//! KMPRT protocol, the baseline of the preprocessed protocol. Based on [popsicle::kmprt].
+//!
+//! [Sender] and [Receiver] replace the re-exports of [popsicle::kmprt::Sender] and [popsicle::kmprt::Receiver].
+//! With [Execution::Sequential], they compute the same outputs as popsicle's implementation, which the tests keep as the reference.
+//!
+//! The sub-protocols with the other parties are run according to an [Execution] given to
+//! [Sender::init_with_execution] or [Receiver::init_with_execution]. Every [Execution] sends the same messages.
 
-pub mod mt;
-pub use popsicle::kmprt::{Receiver, Sender};
+use crate::error::{Context, Error, Result};
+use crate::parallel_utils::{par_zip_mut, Execution};
+use itertools::Itertools;
+use ocelot::oprf::{KmprtReceiver, KmprtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Block512};
+use std::sync::Mutex;
+
+/// usize is used as a party ID. Receiver's ID is always 0.
+pub type PartyId = usize;
+
+struct Party {
+    id: PartyId,
+    execution: Execution,
+    opprf_senders: Vec<(usize, KmprtSender)>,
+    opprf_receivers: Vec<(usize, KmprtReceiver)>,
+}
+
+/// A kind of party in the protocol. They play sender and receiver in Conditional Zero Sharing, and play sender in Conditional Reconstruction.
+pub struct Sender(Party);
+
+/// A kind of party in the protocol. They play sender and receiver in Conditional Zero Sharing, and play receiver in Conditional Reconstruction.
+pub struct Receiver(Party);
+
+impl Sender {
+    /// Initialize a sender party. It can be runned in the offline phase. (but this operation will be done immediately.)
+    pub fn init<C, RNG>(me: PartyId, channels: &mut [(PartyId, C)], rng: &mut RNG) -> Result<Self>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        Self::init_with_execution(me, channels, rng, Execution::default())
+    }
+
+    /// [init](Sender::init) running the sub-protocols with the peers according to `execution`, also in [send](Sender::send).
+    ///
+    /// `channels` are sorted by party ID.
+    pub fn init_with_execution<C, RNG>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        execution: Execution,
+    ) -> Result<Self>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        Party::init(me, channels, rng, execution).map(Self)
+    }
+
+    /// Send protocol which consists of conditional secret sharing and conditional reconstruction sending.
+    /// It runned in the online phase.
+    pub fn send<C, RNG>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<()>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        assert!(self.0.id != 0);
+
+        // conditional zero sharing
+        let s_hat_sum = self.0.conditional_secret_sharing(inputs, channels, rng)?;
+
+        // conditional reconstruction
+        let points = inputs
+            .iter()
+            .cloned()
+            .zip(s_hat_sum.into_iter())
+            .collect_vec();
+
+        // channels and OPPRF instances are sorted by party ID, so the first ones are for the receiver
+        self.0.opprf_senders[0]
+            .1
+            .send(&mut channels[0].1, &points, inputs.len(), rng)
+            .located()?;
+
+        Ok(())
+    }
+}
+
+impl Receiver {
+    /// Initialize a receiver party. It can be runned in the offline phase. (but this operation will be done immediately.)
+    pub fn init<C, RNG>(channels: &mut [(PartyId, C)], rng: &mut RNG) -> Result<Self>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        Self::init_with_execution(channels, rng, Execution::default())
+    }
+
+    /// [init](Receiver::init) running the sub-protocols with the peers according to `execution`, also in [receive](Receiver::receive).
+    ///
+    /// `channels` are sorted by party ID.
+    pub fn init_with_execution<C, RNG>(
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        execution: Execution,
+    ) -> Result<Self>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        Party::init(0, channels, rng, execution).map(Self)
+    }
+
+    /// Receive protocol which consists of conditional secret sharing and conditional reconstruction receiving.
+    /// It runned in the online phase.
+    pub fn receive<C, RNG>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        // conditional zero sharing
+        let s_hat_sum = self
+            .0
+            .conditional_secret_sharing(inputs, channels, rng)
+            .located()?;
+
+        // conditional reconstruction
+        let items = self
+            .0
+            .opprf_receivers
+            .iter_mut()
+            .map(|receiver| (receiver, AesRng::from_seed(rng.gen())))
+            .collect_vec();
+        let s_hat_sum = Mutex::new(s_hat_sum);
+        self.0.execution.for_each_peer(
+            channels,
+            items,
+            |_, (them, channel), ((ri, receiver), mut rng)| {
+                assert_eq!(them, ri);
+
+                let shares = receiver.receive(channel, inputs, &mut rng).located()?;
+                par_zip_mut(&mut s_hat_sum.lock().unwrap(), &shares, |s, &y| *s ^= y);
+                Ok::<_, Error>(())
+            },
+        )?;
+
+        let intersection = inputs
+            .iter()
+            .zip(s_hat_sum.into_inner().unwrap())
+            .filter_map(|(x, s)| {
+                if s == Block512::default() {
+                    Some(*x)
+                } else {
+                    None
+                }
+            })
+            .collect_vec();
+
+        Ok(intersection)
+    }
+}
+
+impl Party {
+    fn init<C, RNG>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        execution: Execution,
+    ) -> Result<Self>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        channels.sort_by_key(|(them, _)| *them);
+
+        let rngs = channels
+            .iter()
+            .map(|_| AesRng::from_seed(rng.gen()))
+            .collect_vec();
+
+        let (opprf_senders, opprf_receivers) = execution
+            .for_each_peer(channels, rngs, |_, (them, channel), mut rng| {
+                let them = *them;
+
+                // the party with the lowest PID gets to initialize their OPPRF sender first
+                if me < them {
+                    let sndr = KmprtSender::init(channel, &mut rng).located()?;
+                    let rcvr = KmprtReceiver::init(channel, &mut rng).located()?;
+                    Ok::<_, Error>(((them, sndr), (them, rcvr)))
+                } else {
+                    let rcvr = KmprtReceiver::init(channel, &mut rng).located()?;
+                    let sndr = KmprtSender::init(channel, &mut rng).located()?;
+                    Ok(((them, sndr), (them, rcvr)))
+                }
+            })?
+            .into_iter()
+            .unzip();
+
+        Ok(Self {
+            id: me,
+            execution,
+            opprf_senders,
+            opprf_receivers,
+        })
+    }
+
+    /// `channels` are sorted by party ID.
+    fn conditional_secret_sharing<C, RNG>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block512>>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        channels.sort_by_key(|(them, _)| *them);
+
+        let nparties = channels.len() + 1;
+        let ninputs = inputs.len();
+
+        let mut s_hat_sum = vec![Block512::default(); ninputs];
+
+        let s = (0..ninputs)
+            .map(|i| {
+                let shares = secret_sharing_of_zero(nparties, rng);
+                s_hat_sum[i] = shares[self.id];
+                shares
+            })
+            .collect_vec();
+
+        let id = self.id;
+        let items = self
+            .opprf_senders
+            .iter_mut()
+            .zip(self.opprf_receivers.iter_mut())
+            .map(|(sender, receiver)| (sender, receiver, AesRng::from_seed(rng.gen())))
+            .collect_vec();
+        let s_hat_sum = Mutex::new(s_hat_sum);
+
+        self.execution.for_each_peer(
+            channels,
+            items,
+            |_, (other_id, channel), ((si, sender), (ri, receiver), mut rng)| {
+                let other_id = *other_id;
+                assert_eq!(other_id, *si);
+                assert_eq!(other_id, *ri);
+
+                let points = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &x)| (x, s[k][other_id]))
+                    .collect_vec();
+
+                let s_hats = if id < other_id {
+                    sender
+                        .send(channel, &points, inputs.len(), &mut rng)
+                        .located()?;
+                    receiver.receive(channel, inputs, &mut rng).located()?
+                } else {
+                    let s_hats = receiver.receive(channel, inputs, &mut rng).located()?;
+                    sender
+                        .send(channel, &points, inputs.len(), &mut rng)
+                        .located()?;
+                    s_hats
+                };
+
+                par_zip_mut(&mut s_hat_sum.lock().unwrap(), &s_hats, |s, &s_hat| {
+                    *s ^= s_hat
+                });
+                Ok::<_, Error>(())
+            },
+        )?;
+
+        Ok(s_hat_sum.into_inner().unwrap())
+    }
+}
+
+fn secret_sharing_of_zero<R: Rng>(nparties: usize, rng: &mut R) -> Vec<Block512> {
+    let mut sum = Block512::default();
+    let mut shares = (0..nparties - 1)
+        .map(|_| {
+            let b = rng.gen();
+            sum ^= b;
+            b
+        })
+        .collect_vec();
+    shares.push(sum);
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel::create_unix_channels;
+    use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+    use crate::channel_utils::tcp_channel::{
+        create_tcp_channels_for_receiver, create_tcp_channels_for_sender,
+    };
+    use crate::set_utils::create_sets_without_check;
+    use std::collections::HashSet;
+
+    const EXECUTIONS: [Execution; 3] = [
+        Execution::Sequential,
+        Execution::ThreadPerPeer,
+        Execution::Pool(2),
+    ];
+
+    type Channels<C> = (Vec<(PartyId, C)>, Vec<Vec<(PartyId, C)>>);
+
+    /// Larger sets are tested with one mix of the strategies only, because every run takes long.
+    const ALL_EXECUTIONS_MAX_SET_SIZE: usize = 1 << 10;
+
+    /// Run the protocol over `channels` where party `i` uses `execution_of(i)`, and return the output of the receiver.
+    ///
+    /// Party `i` has `sets[i]`.
+    fn run_protocol<C: AbstractChannel + Send + 'static>(
+        sets: &[Vec<Block>],
+        (mut receiver_channels, channels): Channels<C>,
+        execution_of: impl Fn(PartyId) -> Execution,
+    ) -> HashSet<Block> {
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                // create and fork senders
+                let pid = i + 1;
+                let set = sets[pid].clone();
+                let execution = execution_of(pid);
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+
+                    // offline phase
+                    let mut sender =
+                        Sender::init_with_execution(pid, &mut channels, &mut rng, execution)
+                            .unwrap();
+
+                    println!("sender {} initialized.", pid);
+
+                    // online phase
+                    sender.send(&set, &mut channels, &mut rng).unwrap();
+
+                    println!("sender {} finished.", pid);
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // create and run receiver
+        let mut rng = AesRng::new();
+
+        // offline phase
+        let mut receiver =
+            Receiver::init_with_execution(&mut receiver_channels, &mut rng, execution_of(0))
+                .unwrap();
+
+        println!("receiver initialized.");
+
+        // online phase
+        let res = receiver
+            .receive(&sets[0], &mut receiver_channels, &mut rng)
+            .unwrap();
+
+        println!("receiver finished with {:?}.", execution_of(0));
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        HashSet::from_iter(res)
+    }
+
+    /// Run the protocol over channels created by `create_channels(round)`.
+    ///
+    /// Party `i` uses `EXECUTIONS[(i + round) % 3]` in the `round`-th run, so that every party runs every strategy.
+    /// Sets larger than [ALL_EXECUTIONS_MAX_SET_SIZE] are run only in the 0th round.
+    fn test_protocol_base<C: AbstractChannel + Send + 'static>(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        create_channels: impl Fn(usize) -> Channels<C>,
+    ) {
+        let mut rng = AesRng::new();
+
+        let (intersection, sets): (Vec<Block>, Vec<Vec<Block>>) =
+            create_sets_without_check(nparties, set_size, common_size, &mut rng).unwrap();
+        let intersection: HashSet<Block> = HashSet::from_iter(intersection);
+
+        println!("intersection prepared.");
+
+        let rounds = if set_size <= ALL_EXECUTIONS_MAX_SET_SIZE {
+            EXECUTIONS.len()
+        } else {
+            1
+        };
+        for round in 0..rounds {
+            let res = run_protocol(&sets, create_channels(round), |pid| {
+                EXECUTIONS[(pid + round) % EXECUTIONS.len()]
+            });
+
+            assert_eq!(res, intersection);
+        }
+    }
+
+    #[test]
+    fn test_same_outputs_as_popsicle() {
+        let nparties = 4;
+        let set_size = 1 << 8;
+        let common_size = 1 << 4;
+        let mut rng = AesRng::new();
+
+        let (_, sets): (Vec<Block>, Vec<Vec<Block>>) =
+            create_sets_without_check(nparties, set_size, common_size, &mut rng).unwrap();
+
+        let res = run_protocol(&sets, create_unix_channels(nparties).unwrap(), |_| {
+            Execution::Sequential
+        });
+
+        // popsicle's implementation over the same sets
+        let (mut receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let pid = i + 1;
+                let set = sets[pid].clone();
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let mut sender =
+                        popsicle::kmprt::Sender::init(pid, &mut channels, &mut rng).unwrap();
+                    sender.send(&set, &mut channels, &mut rng).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut receiver =
+            popsicle::kmprt::Receiver::init(&mut receiver_channels, &mut rng).unwrap();
+        let expected = receiver
+            .receive(&sets[0], &mut receiver_channels, &mut rng)
+            .unwrap();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(res, HashSet::from_iter(expected));
+    }
+
+    fn test_protocol_base_unix(nparties: usize, set_size: usize, common_size: usize) {
+        test_protocol_base(nparties, set_size, common_size, |_| {
+            create_unix_channels(nparties).unwrap()
+        });
+    }
+
+    #[test]
+    fn test_protocol_unix_small() {
+        let nparties = 3;
+        let set_size = 10;
+        let common_size = 5;
+        test_protocol_base_unix(nparties, set_size, common_size);
+    }
+
+    #[test]
+    fn test_protocol_unix_middle() {
+        let nparties = 5;
+        let set_size = 1 << 10;
+        let common_size = 1 << 5;
+        test_protocol_base_unix(nparties, set_size, common_size);
+    }
+
+    #[test]
+    fn test_protocol_unix_large() {
+        let nparties = 5;
+        let set_size = 1 << 20;
+        let common_size = 1 << 5;
+        test_protocol_base_unix(nparties, set_size, common_size);
+    }
+
+    // Each test owns the ports from `base_port` up to `base_port + 100`, apart from the other tests of the crate.
+    fn test_protocol_base_tcp(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        base_port: usize,
+    ) {
+        test_protocol_base(nparties, set_size, common_size, |round| {
+            // ports of the previous runs may still be in TIME_WAIT
+            let base_port = base_port + 10 * round;
+            let handles = (1..nparties)
+                .map(|me| {
+                    std::thread::spawn(move || {
+                        create_tcp_channels_for_sender(nparties, base_port, me)
+                    })
+                })
+                .collect::<Vec<_>>();
+            let receiver_channels = create_tcp_channels_for_receiver(nparties, base_port).unwrap();
+            let channels = handles
+                .into_iter()
+                .map(|h| h.join().unwrap().unwrap())
+                .collect::<Vec<_>>();
+            (receiver_channels, channels)
+        });
+    }
+
+    #[test]
+    fn test_protocol_tcp_small() {
+        let nparties = 3;
+        let set_size = 10;
+        let common_size = 5;
+        test_protocol_base_tcp(nparties, set_size, common_size, 31000);
+    }
+
+    #[test]
+    fn test_protocol_tcp_middle() {
+        let nparties = 5;
+        let set_size = 1 << 10;
+        let common_size = 1 << 5;
+        test_protocol_base_tcp(nparties, set_size, common_size, 31100);
+    }
+
+    #[test]
+    fn test_protocol_tcp_large() {
+        let nparties = 5;
+        let set_size = 1 << 20;
+        let common_size = 1 << 5;
+        test_protocol_base_tcp(nparties, set_size, common_size, 31200);
+    }
+
+    fn test_protocol_base_crossbeam(nparties: usize, set_size: usize, common_size: usize) {
+        test_protocol_base(nparties, set_size, common_size, |_| {
+            create_crossbeam_channels(nparties)
+        });
+    }
+
+    #[test]
+    fn test_protocol_crossbeam_small() {
+        let nparties = 3;
+        let set_size = 10;
+        let common_size = 5;
+        test_protocol_base_crossbeam(nparties, set_size, common_size);
+    }
+
+    #[test]
+    fn test_protocol_crossbeam_middle() {
+        let nparties = 5;
+        let set_size = 1 << 10;
+        let common_size = 1 << 5;
+        test_protocol_base_crossbeam(nparties, set_size, common_size);
+    }
+
+    #[test]
+    fn test_protocol_crossbeam_large() {
+        let nparties = 5;
+        let set_size = 1 << 20;
+        let common_size = 1 << 5;
+        test_protocol_base_crossbeam(nparties, set_size, common_size);
+    }
+}
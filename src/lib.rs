@@ -7,10 +7,15 @@
 
 pub mod channel_utils;
 pub mod cli_utils;
+pub mod error;
 mod hash_utils;
+pub mod hasher;
 pub mod kmprt17;
+pub mod parallel_utils;
 pub mod preprocessed;
 pub mod rs21;
 pub mod set_utils;
 pub mod solver;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod vole;
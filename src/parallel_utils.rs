@@ -0,0 +1,355 @@
+//! Parallelism inside a party. See [Threads] and [Execution].
+//!
+//! Per-element work such as hashing, decoding and summing shares is split into chunks run on [Threads],
+//! a pool shared by all sub-protocols of a party, so that the per-element work of several peers does not oversubscribe the CPU.
+//! The pool is given by [PsiOptions::threads](crate::preprocessed::psi::PsiOptions::threads) or [Threads::install].
+//! Without one, per-element work runs on the calling thread.
+//!
+//! Per-peer work of the protocols (one sub-protocol for each pair of parties) is run according to an [Execution].
+//! It mostly waits for messages, so it runs on threads of its own which hand per-element work to the pool of the caller.
+
+use crate::error::{Error, Result};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Below this length, [par_map] runs sequentially because splitting the work costs more than it saves.
+pub(crate) const PARALLEL_THRESHOLD: usize = 1 << 12;
+
+thread_local! {
+    // pool installed on this thread by Threads::install
+    static CURRENT: RefCell<Option<Arc<ThreadPool>>> = const { RefCell::new(None) };
+}
+
+/// Pool of threads for per-element work. Clones share the same threads.
+///
+/// The default has no threads of its own and uses the pool of the caller, so installing it changes nothing.
+/// The number of threads does not change results, only how fast they are computed.
+///
+/// ```
+/// use preprocessing_mpsi_with_vole::parallel_utils::Threads;
+///
+/// let threads = Threads::new(2).unwrap();
+/// assert_eq!(Threads::current().num_threads(), 1);
+/// threads.install(|| assert_eq!(Threads::current().num_threads(), 2));
+/// ```
+#[derive(Clone, Default)]
+pub struct Threads(Option<Arc<ThreadPool>>);
+
+impl Threads {
+    /// Pool of `threads` threads. 0 means the available parallelism, and 1 runs per-element work on the calling thread.
+    pub fn new(threads: usize) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("pmpsi-worker-{}", i))
+            .build()
+            .map_err(|e| Error::Internal(format!("failed to start threads: {}", e)))?;
+
+        Ok(Self(Some(Arc::new(pool))))
+    }
+
+    /// Pool installed on the calling thread, or the default if none.
+    pub fn current() -> Self {
+        Self(CURRENT.with(|current| current.borrow().clone()))
+    }
+
+    /// Number of threads per-element work is split into.
+    pub fn num_threads(&self) -> usize {
+        self.0.as_ref().map_or(1, |pool| pool.current_num_threads())
+    }
+
+    /// Run `f` on the calling thread with this pool for per-element work, including that of the threads started by [Execution] in `f`.
+    pub fn install<T>(&self, f: impl FnOnce() -> T) -> T {
+        let _guard = self.enter();
+        f()
+    }
+
+    /// Install this pool on the calling thread until the guard is dropped.
+    pub(crate) fn enter(&self) -> ThreadsGuard {
+        ThreadsGuard {
+            previous: self
+                .0
+                .clone()
+                .map(|pool| CURRENT.with(|current| current.replace(Some(pool)))),
+        }
+    }
+
+    // The pool if per-element work of `len` elements is worth splitting.
+    fn pool_for(len: usize) -> Option<Arc<ThreadPool>> {
+        Self::current()
+            .0
+            .filter(|pool| len >= PARALLEL_THRESHOLD && pool.current_num_threads() > 1)
+    }
+}
+
+/// Restores the pool installed before [Threads::enter] when dropped.
+pub(crate) struct ThreadsGuard {
+    // None if the pool was inherited and nothing was installed
+    previous: Option<Option<Arc<ThreadPool>>>,
+}
+
+impl Drop for ThreadsGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+}
+
+impl fmt::Debug for Threads {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(pool) => write!(f, "Threads({})", pool.current_num_threads()),
+            None => write!(f, "Threads(inherited)"),
+        }
+    }
+}
+
+/// Same threads.
+impl PartialEq for Threads {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Apply `f` to each element of `xs` keeping the order. Large inputs are split on the [current](Threads::current) pool.
+pub(crate) fn par_map<T, U, Fun>(xs: &[T], f: Fun) -> Result<Vec<U>>
+where
+    T: Sync,
+    U: Send,
+    Fun: Fn(&T) -> Result<U> + Sync + Send,
+{
+    match Threads::pool_for(xs.len()) {
+        Some(pool) => pool.install(|| xs.par_iter().map(f).collect()),
+        None => xs.iter().map(f).collect(),
+    }
+}
+
+/// Apply `f` to each element of `xs` and the corresponding one of `ys` in place, splitting large inputs like [par_map].
+pub(crate) fn par_zip_mut<T, U, Fun>(xs: &mut [T], ys: &[U], f: Fun)
+where
+    T: Send,
+    U: Sync,
+    Fun: Fn(&mut T, &U) + Sync + Send,
+{
+    assert_eq!(xs.len(), ys.len());
+
+    match Threads::pool_for(xs.len()) {
+        Some(pool) => pool.install(|| {
+            xs.par_iter_mut()
+                .zip(ys.par_iter())
+                .for_each(|(x, y)| f(x, y))
+        }),
+        None => xs.iter_mut().zip(ys).for_each(|(x, y)| f(x, y)),
+    }
+}
+
+/// How a party runs its sub-protocols with the other parties.
+///
+/// Every strategy gives the same outputs and the same messages on each channel; only the interleaving differs.
+/// Peers are always started in increasing order of their IDs, so the parties never wait for each other in a cycle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Execution {
+    /// One peer after another on the calling thread.
+    #[default]
+    Sequential,
+    /// One thread for each peer.
+    ThreadPerPeer,
+    /// A fixed number of threads taking the peers in order. `Pool(1)` is the same as [Execution::Sequential].
+    Pool(usize),
+}
+
+impl Execution {
+    fn threads(self, npeers: usize) -> usize {
+        match self {
+            Execution::Sequential => 1,
+            Execution::ThreadPerPeer => npeers,
+            Execution::Pool(threads) => threads.clamp(1, npeers.max(1)),
+        }
+    }
+
+    /// Run `f(i, channel, item)` for the `i`-th channel and the `i`-th item, and return the outputs in the same order.
+    ///
+    /// Threads started for the peers use the [current](Threads::current) pool of the caller for per-element work.
+    /// On an error, the peers not started yet are skipped and the first error in the channel order is returned.
+    pub(crate) fn for_each_peer<C, I, T, E, Fun>(
+        self,
+        channels: &mut [(usize, C)],
+        items: Vec<I>,
+        f: Fun,
+    ) -> std::result::Result<Vec<T>, E>
+    where
+        C: Send,
+        I: Send,
+        T: Send,
+        E: Send,
+        Fun: Fn(usize, &mut (usize, C), I) -> std::result::Result<T, E> + Sync,
+    {
+        assert_eq!(channels.len(), items.len());
+
+        let threads = self.threads(channels.len());
+        if threads <= 1 {
+            return channels
+                .iter_mut()
+                .zip(items)
+                .enumerate()
+                .map(|(i, (channel, item))| f(i, channel, item))
+                .collect();
+        }
+
+        let queue = Mutex::new(channels.iter_mut().zip(items).enumerate());
+        let failed = AtomicBool::new(false);
+        let pool = Threads::current();
+        let (queue, failed, f, pool) = (&queue, &failed, &f, &pool);
+
+        let mut outputs = thread::scope(|s| {
+            let handles = (0..threads)
+                .map(|_| {
+                    s.spawn(move || {
+                        let _pool = pool.enter();
+                        let mut outputs = Vec::new();
+                        while !failed.load(Ordering::Relaxed) {
+                            let next = queue.lock().unwrap().next();
+                            let Some((i, (channel, item))) = next else {
+                                break;
+                            };
+                            let output = f(i, channel, item);
+                            if output.is_err() {
+                                failed.store(true, Ordering::Relaxed);
+                            }
+                            outputs.push((i, output));
+                        }
+                        outputs
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            // a panic in a worker is propagated as it is
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<Vec<_>>()
+        });
+
+        outputs.sort_by_key(|(i, _)| *i);
+        outputs.into_iter().map(|(_, output)| output).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_map() {
+        for threads in [Threads::default(), Threads::new(3).unwrap()] {
+            threads.install(|| {
+                for n in [0, 1, 100, PARALLEL_THRESHOLD, PARALLEL_THRESHOLD * 3 + 7] {
+                    let xs = (0..n).collect::<Vec<usize>>();
+                    let ys = par_map(&xs, |x| Ok(x * 2)).unwrap();
+
+                    assert_eq!(ys, xs.iter().map(|x| x * 2).collect::<Vec<_>>());
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_threads() {
+        assert_eq!(Threads::current(), Threads::default());
+        assert_eq!(Threads::current().num_threads(), 1);
+        assert!(Threads::new(0).unwrap().num_threads() >= 1);
+
+        let threads = Threads::new(3).unwrap();
+        threads.install(|| {
+            assert_eq!(Threads::current(), threads);
+            assert_eq!(Threads::current().num_threads(), 3);
+
+            // the default keeps the pool of the caller
+            Threads::default().install(|| assert_eq!(Threads::current(), threads));
+            let other = Threads::new(1).unwrap();
+            other.install(|| assert_eq!(Threads::current(), other));
+            assert_eq!(Threads::current(), threads);
+
+            // threads of the peers share the pool of the caller
+            let mut channels = (1..=4).map(|id| (id, ())).collect::<Vec<_>>();
+            let pools = Execution::ThreadPerPeer
+                .for_each_peer(&mut channels, vec![(); 4], |_, _, _| {
+                    Ok::<_, Error>(Threads::current())
+                })
+                .unwrap();
+            assert!(pools.iter().all(|pool| *pool == threads));
+        });
+
+        assert_eq!(Threads::current(), Threads::default());
+    }
+
+    #[test]
+    fn test_par_zip_mut() {
+        for threads in [Threads::default(), Threads::new(3).unwrap()] {
+            threads.install(|| {
+                for n in [0, 1, 100, PARALLEL_THRESHOLD, PARALLEL_THRESHOLD * 3 + 7] {
+                    let mut xs = (0..n).collect::<Vec<usize>>();
+                    let ys = (0..n).map(|y| y * 3).collect::<Vec<usize>>();
+                    par_zip_mut(&mut xs, &ys, |x, y| *x += y);
+
+                    assert_eq!(xs, (0..n).map(|x| x * 4).collect::<Vec<_>>());
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_for_each_peer() {
+        for execution in [
+            Execution::Sequential,
+            Execution::ThreadPerPeer,
+            Execution::Pool(2),
+        ] {
+            let mut channels = (1..=5).map(|id| (id, 0usize)).collect::<Vec<_>>();
+            let items = (0..5).map(|i| i * 10).collect::<Vec<usize>>();
+            let outputs = execution
+                .for_each_peer(&mut channels, items, |i, (id, c), item| {
+                    *c = *id;
+                    Ok::<_, Error>(i + item)
+                })
+                .unwrap();
+
+            assert_eq!(outputs, vec![0, 11, 22, 33, 44]);
+            assert!(channels.iter().all(|(id, c)| id == c));
+
+            let mut channels = (1..=5).map(|id| (id, ())).collect::<Vec<_>>();
+            let res = execution.for_each_peer(&mut channels, vec![(); 5], |i, _, _| {
+                if i == 2 {
+                    return Err(Error::Config("error".to_string()));
+                }
+                Ok(i)
+            });
+
+            assert!(res.is_err());
+        }
+    }
+
+    #[test]
+    fn test_par_map_error() {
+        let xs = (0..PARALLEL_THRESHOLD * 2).collect::<Vec<usize>>();
+        let res = Threads::new(3).unwrap().install(|| {
+            par_map(&xs, |&x| {
+                if x == PARALLEL_THRESHOLD + 1 {
+                    return Err(Error::Config("error".to_string()));
+                }
+                Ok(x)
+            })
+        });
+
+        assert!(res.is_err());
+    }
+}
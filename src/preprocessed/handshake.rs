@@ -0,0 +1,526 @@
+//! Handshake run at the start of the offline phase. See [Hello].
+//!
+//! Parties must agree on the field, the solver and its parameters, the VOLE type, the set size, the number of bins and the number of parties.
+//! Otherwise they desync somewhere in the middle of the protocol with an opaque error, or worse, compute a wrong intersection.
+//! So each party sends its [Hello] to every peer first, and fails with [Error::Incompatible] naming what differs.
+//!
+//! A hello is sent as
+//!
+//! `MAGIC || version u32 || party u64 || nparties u64 || field || solver || code_length u64 || vole || lambda u64 || expansion || stash_size u64 || set_size u64 || nbins u64`
+//!
+//! where a string is `len u16 || utf-8 bytes` and `expansion` is `0u8` for `None` or `1u8 || f64 bits u64`.
+//! The version comes right after the magic, so that a peer of another version is told apart before parsing the rest.
+//!
+//! The handshake goes through the same channels as the protocol, so it is recorded in [transcripts](crate::channel_utils::transcript) as well.
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::channel_utils::sync_channel::create_unix_channels;
+//! use preprocessing_mpsi_with_vole::error::Error;
+//! use preprocessing_mpsi_with_vole::preprocessed::handshake::{handshake, Hello};
+//! use preprocessing_mpsi_with_vole::solver::{PaxosSolver, SolverOptions};
+//! use preprocessing_mpsi_with_vole::vole::{LPNVoleReceiver, LPNVoleSender};
+//! use scuttlebutt::field::F128b;
+//!
+//! type S = PaxosSolver<F128b>;
+//! let vs = LPNVoleSender::<F128b>::for_size(1 << 10);
+//! let vr = LPNVoleReceiver::<F128b>::for_size(1 << 10);
+//!
+//! let (mut receiver_channels, mut channels) = create_unix_channels(2).unwrap();
+//!
+//! let handle = std::thread::spawn(move || {
+//!     let hello = Hello::new::<F128b, S, _, _>(1, 2, 100, SolverOptions::default(), &vs, &vr);
+//!     handshake(&hello, &mut channels[0])
+//! });
+//!
+//! let hello = Hello::new::<F128b, S, _, _>(0, 2, 200, SolverOptions::default(), &vs, &vr);
+//! let err = handshake(&hello, &mut receiver_channels).unwrap_err();
+//! assert!(matches!(err.root(), Error::Incompatible { peer: 1, what: "set size", .. }));
+//!
+//! assert!(handle.join().unwrap().is_err());
+//! ```
+
+use crate::error::{Context, Error};
+use crate::preprocessed::psi::PartyId;
+use crate::solver::{Solver, SolverOptions, SolverParams};
+use crate::vole::{VoleShareForReceiver, VoleShareForSender};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::{F128b, F128p, F61p, F64b, FiniteField as FF};
+use std::fmt::Debug;
+
+/// Version of the wire protocol. Bump it whenever messages of the protocol change.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+const MAGIC: &[u8; 8] = b"PMPSIHLO";
+
+/// Field whose elements the parties exchange, with a stable name sent in the [Hello].
+pub trait FieldId: FF {
+    /// Stable name of the field.
+    const ID: &'static str;
+}
+
+impl FieldId for F128b {
+    const ID: &'static str = "f128b";
+}
+
+impl FieldId for F64b {
+    const ID: &'static str = "f64b";
+}
+
+impl FieldId for F128p {
+    const ID: &'static str = "f128p";
+}
+
+impl FieldId for F61p {
+    const ID: &'static str = "f61p";
+}
+
+/// What a party tells its peers about itself before the protocol.
+///
+/// Types are identified by the stable names [FieldId::ID], [Solver::id], [VoleShareForSender::id] and [VoleShareForReceiver::id],
+/// which do not depend on the compiler or on where the types are defined.
+/// The OT extensions under the VOLEs are not included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hello {
+    /// [PROTOCOL_VERSION] of the party.
+    pub version: u32,
+    /// ID of the party.
+    pub party: PartyId,
+    /// Number of parties including the receiver.
+    pub nparties: usize,
+    /// Field of the set elements.
+    pub field: String,
+    /// Solver of the OPPRFs.
+    pub solver: String,
+    /// Code length of the solver for `set_size`.
+    pub code_length: usize,
+    /// VOLE sender and receiver types.
+    pub vole: String,
+    /// Statistical security parameter $`\lambda`$ of the solver.
+    pub lambda: usize,
+    /// [SolverOptions::expansion].
+    pub expansion: Option<f64>,
+    /// [SolverOptions::stash_size].
+    pub stash_size: usize,
+    /// Upper bound of the set size, which decides the VOLE lengths.
+    ///
+    /// In [partitioned](crate::preprocessed::psi::partitioned) mode, this is the capacity of each bin.
+    pub set_size: usize,
+    /// Number of bins in [partitioned](crate::preprocessed::psi::partitioned) mode, or 1 otherwise.
+    pub nbins: usize,
+}
+
+impl Hello {
+    /// Hello of party `me` of `nparties` parties running the protocol with these types, these VOLEs, `set_size` and `options`.
+    ///
+    /// `options.retry` is not included since only the encoding party uses it.
+    pub fn new<F, S, VS, VR>(
+        me: PartyId,
+        nparties: usize,
+        set_size: usize,
+        options: SolverOptions,
+        vole_share_for_s: &VS,
+        vole_share_for_r: &VR,
+    ) -> Self
+    where
+        F: FieldId,
+        S: Solver<F>,
+        VS: VoleShareForSender<F>,
+        VR: VoleShareForReceiver<F>,
+    {
+        Self {
+            version: PROTOCOL_VERSION,
+            party: me,
+            nparties,
+            field: F::ID.to_owned(),
+            solver: S::id(),
+            code_length: S::calc_params_with(set_size, options).code_length(),
+            vole: format!("{} / {}", vole_share_for_s.id(), vole_share_for_r.id()),
+            lambda: options.lambda,
+            expansion: options.expansion,
+            stash_size: options.stash_size,
+            set_size,
+            nbins: 1,
+        }
+    }
+
+    /// Send the hello to a peer. The channel is not flushed.
+    pub fn send<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        channel.write_bytes(MAGIC).located()?;
+        channel.write_u32(self.version).located()?;
+        channel.write_usize(self.party).located()?;
+        channel.write_usize(self.nparties).located()?;
+        write_str(channel, &self.field).located()?;
+        write_str(channel, &self.solver).located()?;
+        channel.write_usize(self.code_length).located()?;
+        write_str(channel, &self.vole).located()?;
+        channel.write_usize(self.lambda).located()?;
+        match self.expansion {
+            None => channel.write_u8(0),
+            Some(e) => channel
+                .write_u8(1)
+                .and_then(|()| channel.write_u64(e.to_bits())),
+        }
+        .located()?;
+        channel.write_usize(self.stash_size).located()?;
+        channel.write_usize(self.set_size).located()?;
+        channel.write_usize(self.nbins).located()?;
+
+        Ok(())
+    }
+
+    /// Receive a hello from party `them`.
+    ///
+    /// Fails with [Error::ProtocolViolation] if the peer does not send a hello,
+    /// and with [Error::Incompatible] if the peer's [version](Hello::version) differs from [PROTOCOL_VERSION],
+    /// in which case the rest of the hello is not read since its layout may differ.
+    pub fn receive<C: AbstractChannel>(channel: &mut C, them: PartyId) -> Result<Self, Error> {
+        let mut magic = [0u8; MAGIC.len()];
+        channel.read_bytes(&mut magic).located()?;
+        if &magic != MAGIC {
+            return Err(Error::protocol_violation(format!(
+                "expected a handshake, but received {:02x?}.",
+                magic
+            )));
+        }
+
+        let version = channel.read_u32().located()?;
+        if version != PROTOCOL_VERSION {
+            return Err(Error::Incompatible {
+                peer: them,
+                what: "protocol version",
+                ours: PROTOCOL_VERSION.to_string(),
+                theirs: version.to_string(),
+            });
+        }
+
+        let party = channel.read_usize().located()?;
+        let nparties = channel.read_usize().located()?;
+        let field = read_str(channel).located()?;
+        let solver = read_str(channel).located()?;
+        let code_length = channel.read_usize().located()?;
+        let vole = read_str(channel).located()?;
+        let lambda = channel.read_usize().located()?;
+        let expansion = match channel.read_u8().located()? {
+            0 => None,
+            1 => Some(f64::from_bits(channel.read_u64().located()?)),
+            tag => {
+                return Err(Error::protocol_violation(format!(
+                    "invalid tag of expansion: {}.",
+                    tag
+                )))
+            }
+        };
+        let stash_size = channel.read_usize().located()?;
+        let set_size = channel.read_usize().located()?;
+        let nbins = channel.read_usize().located()?;
+
+        Ok(Self {
+            version,
+            party,
+            nparties,
+            field,
+            solver,
+            code_length,
+            vole,
+            lambda,
+            expansion,
+            stash_size,
+            set_size,
+            nbins,
+        })
+    }
+
+    /// Send the hello to party `them` and check the one received from them.
+    ///
+    /// Only for a channel which has its own thread; use [handshake] to handshake with peers in one thread.
+    pub fn exchange<C: AbstractChannel>(
+        &self,
+        them: PartyId,
+        channel: &mut C,
+    ) -> Result<(), Error> {
+        (|| -> Result<_, Error> {
+            self.send(channel).located()?;
+            channel.flush().located()?;
+            let theirs = Hello::receive(channel, them).located()?;
+            self.check(them, &theirs).located()
+        })()
+        .map_err(|e| e.with_peer(them))
+    }
+
+    /// Check that `theirs` received from party `them` is compatible with this hello.
+    ///
+    /// Fails with [Error::ProtocolViolation] if the peer claims to be another party,
+    /// and with [Error::Incompatible] naming the first difference otherwise.
+    pub fn check(&self, them: PartyId, theirs: &Hello) -> Result<(), Error> {
+        if theirs.party != them {
+            return Err(Error::ProtocolViolation {
+                peer: Some(them),
+                reason: format!("claims to be party {} in the handshake.", theirs.party),
+            });
+        }
+
+        ensure_eq(them, "protocol version", &self.version, &theirs.version)?;
+        ensure_eq(them, "number of parties", &self.nparties, &theirs.nparties)?;
+        ensure_eq(them, "field", &self.field, &theirs.field)?;
+        ensure_eq(them, "solver", &self.solver, &theirs.solver)?;
+        ensure_eq(them, "VOLE", &self.vole, &theirs.vole)?;
+        ensure_eq(them, "lambda", &self.lambda, &theirs.lambda)?;
+        ensure_eq(them, "expansion", &self.expansion, &theirs.expansion)?;
+        ensure_eq(them, "stash size", &self.stash_size, &theirs.stash_size)?;
+        ensure_eq(them, "set size", &self.set_size, &theirs.set_size)?;
+        ensure_eq(them, "number of bins", &self.nbins, &theirs.nbins)?;
+        // implied by the above as long as both run the same solver implementation
+        ensure_eq(them, "code length", &self.code_length, &theirs.code_length)?;
+
+        Ok(())
+    }
+}
+
+/// Exchange hellos with every peer and check theirs by [Hello::check].
+///
+/// Hellos are sent to all peers before receiving any, so that parties never wait for each other.
+pub fn handshake<C: AbstractChannel>(
+    hello: &Hello,
+    channels: &mut [(PartyId, C)],
+) -> Result<(), Error> {
+    for (them, channel) in channels.iter_mut() {
+        hello
+            .send(channel)
+            .and_then(|()| channel.flush().map_err(Error::from))
+            .located()
+            .map_err(|e| e.with_peer(*them))?;
+    }
+
+    for (them, channel) in channels.iter_mut() {
+        let theirs = Hello::receive(channel, *them)
+            .located()
+            .map_err(|e| e.with_peer(*them))?;
+        hello.check(*them, &theirs).located()?;
+    }
+
+    Ok(())
+}
+
+fn ensure_eq<T: PartialEq + Debug>(
+    them: PartyId,
+    what: &'static str,
+    ours: &T,
+    theirs: &T,
+) -> Result<(), Error> {
+    if ours == theirs {
+        Ok(())
+    } else {
+        Err(Error::Incompatible {
+            peer: them,
+            what,
+            ours: format!("{:?}", ours),
+            theirs: format!("{:?}", theirs),
+        })
+    }
+}
+
+// Strings are short IDs, so u16 is enough for their lengths.
+fn write_str<C: AbstractChannel>(channel: &mut C, s: &str) -> Result<(), Error> {
+    let len = u16::try_from(s.len()).map_err(|_| {
+        Error::Internal(format!(
+            "too long string in a handshake: {} bytes.",
+            s.len()
+        ))
+    })?;
+    channel.write_u16(len)?;
+    channel.write_bytes(s.as_bytes())?;
+
+    Ok(())
+}
+
+fn read_str<C: AbstractChannel>(channel: &mut C) -> Result<String, Error> {
+    let len = channel.read_u16()? as usize;
+    let mut buf = vec![0u8; len];
+    channel.read_bytes(&mut buf)?;
+
+    String::from_utf8(buf)
+        .map_err(|e| Error::protocol_violation(format!("invalid string in a handshake: {}.", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+    use crate::cli_utils::{VoleShareForReceiverUnion, VoleShareForSenderUnion};
+    use crate::solver::{PaxosSolver, VandelmondeSolver};
+    use crate::vole::{
+        CheckedVoleReceiver, CheckedVoleSender, LPNVoleReceiver, LPNVoleSender, OtVoleReceiver,
+        OtVoleSender,
+    };
+    use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
+    use scuttlebutt::field::{F128b, F64b};
+
+    type Vs = LPNVoleSender<F128b>;
+    type Vr = LPNVoleReceiver<F128b>;
+
+    fn hello(me: PartyId) -> Hello {
+        hello_with(me, SolverOptions::default())
+    }
+
+    fn hello_with(me: PartyId, options: SolverOptions) -> Hello {
+        let (vs, vr) = (Vs::for_size(1 << 10), Vr::for_size(1 << 10));
+        Hello::new::<F128b, PaxosSolver<F128b>, _, _>(me, 2, 100, options, &vs, &vr)
+    }
+
+    // result of party 0 exchanging `ours` with party 1 sending `theirs`
+    fn exchange(ours: Hello, theirs: Hello) -> Result<(), Error> {
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+        let (_, mut peer) = channels.pop().unwrap().pop().unwrap();
+        theirs.send(&mut peer).unwrap();
+        peer.flush().unwrap();
+
+        let result = ours.exchange(1, &mut receiver_channels[0].1);
+        assert_eq!(Hello::receive(&mut peer, 0).unwrap(), ours);
+
+        result
+    }
+
+    fn incompatibility(ours: Hello, theirs: Hello) -> &'static str {
+        match exchange(ours, theirs).unwrap_err().root() {
+            Error::Incompatible { peer: 1, what, .. } => what,
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_handshake() {
+        exchange(hello(0), hello(1)).unwrap();
+
+        // stable IDs rather than type names
+        let ours = hello(0);
+        assert_eq!(ours.field, "f128b");
+        assert_eq!(ours.solver, "paxos(aes)");
+        assert_eq!(ours.vole, "lpn / lpn");
+
+        let options = SolverOptions {
+            expansion: Some(2.0),
+            ..Default::default()
+        };
+        exchange(hello_with(0, options), hello_with(1, options)).unwrap();
+
+        // each parameter
+        let theirs = Hello {
+            version: PROTOCOL_VERSION + 1,
+            ..hello(1)
+        };
+        assert_eq!(incompatibility(hello(0), theirs), "protocol version");
+        let theirs = Hello {
+            nparties: 3,
+            ..hello(1)
+        };
+        assert_eq!(incompatibility(hello(0), theirs), "number of parties");
+        let theirs = Hello::new::<F64b, PaxosSolver<F64b>, _, _>(
+            1,
+            2,
+            100,
+            SolverOptions::default(),
+            &LPNVoleSender::for_size(1 << 10),
+            &LPNVoleReceiver::for_size(1 << 10),
+        );
+        assert_eq!(incompatibility(hello(0), theirs), "field");
+        let theirs = Hello::new::<F128b, VandelmondeSolver<F128b>, _, _>(
+            1,
+            2,
+            100,
+            SolverOptions::default(),
+            &Vs::for_size(1 << 10),
+            &Vr::for_size(1 << 10),
+        );
+        assert_eq!(incompatibility(hello(0), theirs), "solver");
+        let theirs = Hello::new::<F128b, PaxosSolver<F128b>, _, _>(
+            1,
+            2,
+            100,
+            SolverOptions::default(),
+            &OtVoleSender::<F128b, OtSender>::new(),
+            &OtVoleReceiver::<F128b, OtReceiver>::new(),
+        );
+        assert_eq!(incompatibility(hello(0), theirs), "VOLE");
+        let theirs = hello_with(1, SolverOptions::with_lambda(80));
+        assert_eq!(incompatibility(hello(0), theirs), "lambda");
+        let theirs = hello_with(1, options);
+        assert_eq!(incompatibility(hello(0), theirs), "expansion");
+        let theirs = Hello {
+            stash_size: 1,
+            ..hello(1)
+        };
+        assert_eq!(incompatibility(hello(0), theirs), "stash size");
+        let theirs = Hello {
+            set_size: 101,
+            ..hello(1)
+        };
+        assert_eq!(incompatibility(hello(0), theirs), "set size");
+        let theirs = Hello {
+            nbins: 2,
+            ..hello(1)
+        };
+        assert_eq!(incompatibility(hello(0), theirs), "number of bins");
+        let theirs = Hello {
+            code_length: 0,
+            ..hello(1)
+        };
+        assert_eq!(incompatibility(hello(0), theirs), "code length");
+
+        // a peer claiming to be another party
+        let err = exchange(hello(0), hello(2)).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            Error::ProtocolViolation { peer: Some(1), .. }
+        ));
+
+        // a peer not sending a hello
+        let (mut receiver_channels, mut channels) = create_crossbeam_channels(2);
+        let (_, mut peer) = channels.pop().unwrap().pop().unwrap();
+        peer.write_bytes(&[0u8; 64]).unwrap();
+        peer.flush().unwrap();
+        let err = hello(0)
+            .exchange(1, &mut receiver_channels[0].1)
+            .unwrap_err();
+        assert!(matches!(
+            err.root(),
+            Error::ProtocolViolation { peer: Some(1), .. }
+        ));
+    }
+
+    #[test]
+    fn test_union_vole_ids() {
+        // VOLEs chosen at runtime are told apart by the variant they wrap
+        let ot = (
+            VoleShareForSenderUnion::Ot(OtVoleSender::new()),
+            VoleShareForReceiverUnion::Ot(OtVoleReceiver::new()),
+        );
+        let lpn = (
+            VoleShareForSenderUnion::Lpn(Vs::for_size(1 << 10)),
+            VoleShareForReceiverUnion::Lpn(Vr::for_size(1 << 10)),
+        );
+        assert_eq!((ot.0.id(), ot.1.id()), ("ot".to_owned(), "ot".to_owned()));
+        assert_eq!(lpn.0.id(), "lpn");
+        assert_eq!(lpn.1.id(), "lpn");
+
+        let hello_of = |me, (vs, vr): &(VoleShareForSenderUnion, VoleShareForReceiverUnion)| {
+            Hello::new::<F128b, PaxosSolver<F128b>, _, _>(
+                me,
+                2,
+                100,
+                SolverOptions::default(),
+                vs,
+                vr,
+            )
+        };
+        exchange(hello_of(0, &lpn), hello_of(1, &lpn)).unwrap();
+        assert_eq!(incompatibility(hello_of(0, &ot), hello_of(1, &lpn)), "VOLE");
+
+        // wrapped VOLEs are named after the VOLE they wrap
+        let checked = (
+            CheckedVoleSender::<F128b, _>::new(OtVoleSender::<F128b, OtSender>::new()),
+            CheckedVoleReceiver::<F128b, _>::new(OtVoleReceiver::<F128b, OtReceiver>::new()),
+        );
+        assert_eq!(checked.0.id(), "checked(ot)");
+        assert_eq!(checked.1.id(), "checked(ot)");
+    }
+}
@@ -1,5 +1,6 @@
 //! The main module of the Preprocessing Multi-party PSI project
 
+pub mod handshake;
 pub mod opprf;
 pub mod oprf;
 /// Preprocessed MPSI module using OPPRF and OPRF.
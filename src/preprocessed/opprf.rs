@@ -17,7 +17,7 @@
 //!     LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL,
 //! };
 //! use scuttlebutt::{field::F128b, AesRng};
-//! use anyhow::Result;
+//! use preprocessing_mpsi_with_vole::error::Result;
 //! # use scuttlebutt::Channel;
 //! # use std::io::{BufReader, BufWriter};
 //! # use std::os::unix::net::UnixStream;
@@ -104,13 +104,15 @@
 //! # }
 //! ```
 
-use crate::channel_utils::{read_vec_f, write_vec_f};
+use crate::channel_utils::{read_vec_f_exact, write_vec_f};
+use crate::error::{Context, Error};
 use crate::preprocessed::oprf::{
-    SepOprfReceiver, SepOprfReceiverWithVole, SepOprfSender, SepOprfSenderWithVole,
+    SepOprfOutputs, SepOprfReceiver, SepOprfReceiverWithVole, SepOprfSender, SepOprfSenderWithVole,
+};
+use crate::solver::{encode_with_retry, RetryPolicy, Solver, SolverOptions, SolverParams};
+use crate::vole::{
+    VoleReceiverSession, VoleSenderSession, VoleShareForReceiver, VoleShareForSender,
 };
-use crate::solver::Solver;
-use crate::vole::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{anyhow, Context, Error};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField as FF;
 use scuttlebutt::AbstractChannel;
@@ -204,10 +206,38 @@ where
     V: VoleShareForSender<F>,
 {
     params: S::Params,
+    retry: RetryPolicy,
     oprf_sender: SepOprfSenderWithVole<F, S, V>,
     // fk: Option<Box<dyn Fn(&Self, F) -> Result<F, Error> + Send>>,
 }
 
+impl<F, S, V> SepOpprfSenderWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+{
+    /// Precomputation for the sender drawing VOLE correlations from a [session](crate::vole::session).
+    /// No communication is needed.
+    ///
+    /// Solver parameters are decided by `query_num` and `options`, which must be the same as the receiver's.
+    pub fn precomp_with_session(
+        session: &mut VoleSenderSession<F>,
+        query_num: usize,
+        options: SolverOptions,
+    ) -> Result<Self, Error> {
+        let params = S::calc_params_with(query_num, options);
+        let oprf_sender =
+            SepOprfSenderWithVole::precomp_with_session(session, query_num, options).located()?;
+        Ok(Self {
+            params,
+            retry: options.retry,
+            oprf_sender,
+            // fk: None,
+        })
+    }
+}
+
 impl<F, S, V> ObliviousProgrammablePrf for SepOpprfSenderWithVole<F, S, V>
 where
     F: FF,
@@ -235,10 +265,11 @@ where
         system: Self::PrecompSystem,
     ) -> Result<Self, Error> {
         let params = S::calc_params(query_num);
-        let oprf_sender = SepOprfSenderWithVole::precomp(channel, rng, query_num, system)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let oprf_sender =
+            SepOprfSenderWithVole::precomp(channel, rng, query_num, system).located()?;
         Ok(Self {
             params,
+            retry: RetryPolicy::default(),
             oprf_sender,
             // fk: None,
         })
@@ -249,47 +280,35 @@ where
         self,
         channel: &mut C,
         points: &[(Self::Input, Self::Output)],
-        query_num: usize,
+        _query_num: usize,
         rng: &mut RNG,
     ) -> Result<Box<dyn Fn(F) -> Result<F, Error> + Send>, Error>
     where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
-        let fk = self
-            .oprf_sender
-            .send(channel, query_num, rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let key = self.oprf_sender.send_key(channel, rng).located()?;
 
+        let xs = points.iter().map(|&(x, _)| x).collect::<Vec<_>>();
+        let fkxs = key.eval_batch(&xs).located()?;
         let points = points
             .iter()
-            .map(|&(x, z)| {
-                let y = z - (fk(x).with_context(|| format!("@{}:{}", file!(), line!()))?);
-                Ok((x, y))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
-
-        let mut aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let mut p = Err(anyhow!("dummy!"));
-        for _ in 0..2 {
-            p = S::encode(rng, &points, aux, self.params)
-                .with_context(|| format!("@{}:{}", file!(), line!()));
-            if p.is_ok() {
-                break;
-            }
-            aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        }
-        let p = p?;
+            .zip(fkxs)
+            .map(|(&(x, z), fkx)| (x, z - fkx))
+            .collect::<Vec<_>>();
+
+        let (p, aux) =
+            encode_with_retry::<F, S, _>(rng, &points, self.params, self.retry).located()?;
 
-        S::aux_send(channel, rng, aux).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        S::aux_send(channel, rng, aux).located()?;
 
-        write_vec_f(channel, &p).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        write_vec_f(channel, &p).located()?;
 
+        let fk = key.into_fn();
         let params = self.params.clone();
         let fk = move |x: F| -> Result<F, Error> {
-            let d = S::decode(&p, x, aux, params)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
-            let res = d + fk(x).with_context(|| format!("@{}:{}", file!(), line!()))?;
+            let d = S::decode(&p, x, aux, params).located()?;
+            let res = d + fk(x).located()?;
             Ok(res)
         };
 
@@ -299,7 +318,7 @@ where
     /*
     fn compute(&self, input: Self::Input) -> Result<Self::Output, Error> {
         let fk = self.fk.as_ref().unwrap();
-        let res = fk(self, input).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let res = fk(self, input).located()?;
         Ok(res)
     }
     */
@@ -318,6 +337,31 @@ where
     oprf_receiver: SepOprfReceiverWithVole<F, S, V>,
 }
 
+impl<F, S, V> SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    /// Precomputation for the receiver drawing VOLE correlations from a [session](crate::vole::session).
+    /// No communication is needed.
+    ///
+    /// Solver parameters are decided by `query_num` and `options`, which must be the same as the sender's.
+    pub fn precomp_with_session(
+        session: &mut VoleReceiverSession<F>,
+        query_num: usize,
+        options: SolverOptions,
+    ) -> Result<Self, Error> {
+        let params = S::calc_params_with(query_num, options);
+        let oprf_receiver =
+            SepOprfReceiverWithVole::precomp_with_session(session, query_num, options).located()?;
+        Ok(Self {
+            params,
+            oprf_receiver,
+        })
+    }
+}
+
 impl<F, S, V> ObliviousProgrammablePrf for SepOpprfReceiverWithVole<F, S, V>
 where
     F: FF,
@@ -345,8 +389,8 @@ where
         system: Self::PrecompSystem,
     ) -> Result<Self, Error> {
         let params = S::calc_params(query_num);
-        let oprf_receiver = SepOprfReceiverWithVole::precomp(channel, rng, query_num, system)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let oprf_receiver =
+            SepOprfReceiverWithVole::precomp(channel, rng, query_num, system).located()?;
         Ok(Self {
             params,
             oprf_receiver,
@@ -360,6 +404,32 @@ where
         queries: &[Self::Input],
         rng: &mut RNG,
     ) -> Result<Vec<(Self::Input, Self::Output)>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let outputs = self.receive_outputs(channel, queries, rng).located()?;
+        let ys = outputs.eval_batch(queries).located()?;
+        let points = queries.iter().copied().zip(ys).collect::<Vec<_>>();
+
+        Ok(points)
+    }
+}
+
+impl<F, S, V> SepOpprfReceiverWithVole<F, S, V>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+{
+    /// Same as [receive](SepOpprfReceiver::receive) except that the outputs are evaluated later by [SepOpprfOutputs],
+    /// so that they need not be held for all queries at once.
+    pub fn receive_outputs<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<SepOpprfOutputs<F, S>, Error>
     where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
@@ -369,26 +439,49 @@ where
             oprf_receiver,
         } = self;
 
-        let oprf_res = oprf_receiver
-            .receive(channel, queries, rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let oprf_outputs = oprf_receiver
+            .receive_outputs(channel, queries, rng)
+            .located()?;
 
-        let aux =
-            S::aux_receive(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let aux = S::aux_receive(channel, rng).located()?;
 
-        let p = read_vec_f(channel).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let p = read_vec_f_exact(channel, params.code_length()).located()?;
 
-        let points = oprf_res
-            .iter()
-            .map(|&(x, fkx)| {
-                let y = S::decode(&p, x, aux, params)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?
-                    + fkx;
-                Ok((x, y))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(SepOpprfOutputs {
+            oprf_outputs,
+            p,
+            aux,
+            params,
+        })
+    }
+}
 
-        Ok(points)
+/// OPPRF outputs held by the receiver after [receive_outputs](SepOpprfReceiverWithVole::receive_outputs).
+///
+/// The output for a query $`x`$ is $`\mathrm{Decode}(P, x) + F_k(x)`$, which is the programmed value if $`x`$ is one of the sender's points.
+/// Values for the other inputs are meaningless.
+pub struct SepOpprfOutputs<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    oprf_outputs: SepOprfOutputs<F, S>,
+    p: Vec<F>,
+    aux: S::AuxInfo,
+    params: S::Params,
+}
+
+impl<F, S> SepOpprfOutputs<F, S>
+where
+    F: FF,
+    S: Solver<F>,
+{
+    /// Outputs for `queries` using [decode_batch](Solver::decode_batch).
+    pub fn eval_batch(&self, queries: &[F]) -> Result<Vec<F>, Error> {
+        let fkxs = self.oprf_outputs.eval_batch(queries).located()?;
+        let ds = S::decode_batch(&self.p, queries, self.aux, self.params).located()?;
+
+        Ok(ds.into_iter().zip(fkxs).map(|(d, fkx)| d + fkx).collect())
     }
 }
 
@@ -404,6 +497,7 @@ where
     fn clone(&self) -> Self {
         Self {
             params: self.params,
+            retry: self.retry,
             oprf_sender: self.oprf_sender.clone(),
             // fk: None,
         }
@@ -17,7 +17,7 @@
 //!     LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_SMALL, LPN_SETUP_SMALL,
 //! };
 //! use scuttlebutt::{field::F128b, AesRng};
-//! use anyhow::Result;
+//! use preprocessing_mpsi_with_vole::error::Result;
 //! # use scuttlebutt::Channel;
 //! # use std::io::{BufReader, BufWriter};
 //! # use std::os::unix::net::UnixStream;
@@ -98,11 +98,14 @@
 //! # }
 //! ```
 
-use crate::channel_utils::{read_vec_f, write_vec_f};
-use crate::hash_utils::{hash, hash_f};
-use crate::solver::{Solver, SolverParams};
-use crate::vole::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{anyhow, bail, Context, Error};
+use crate::channel_utils::{read_vec_f_exact, write_vec_f};
+use crate::error::{Context, Error};
+use crate::hash_utils::{hash, hash_batch, hash_f, hash_f_batch, oracle};
+use crate::hasher::{KeyedHasher, Sha256Hasher};
+use crate::solver::{encode_with_retry, RetryPolicy, Solver, SolverOptions, SolverParams};
+use crate::vole::{
+    VoleReceiverSession, VoleSenderSession, VoleShareForReceiver, VoleShareForSender,
+};
 use ocelot::oprf::ObliviousPrf;
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField as FF;
@@ -171,36 +174,172 @@ where
 
 /// Actual implementation of Separated OPRF sender using VOLE.
 ///
+/// `H` is the random oracle of the OPRF output (see [SepOprfKey]). It must be the same as the receiver's.
+///
 /// Please look the parent document ( [crate::preprocessed::oprf] ) for usage example.
-pub struct SepOprfSenderWithVole<F, S, V>
+pub struct SepOprfSenderWithVole<F, S, V, H = Sha256Hasher>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForSender<F>,
+    H: KeyedHasher,
 {
     params: S::Params,
     delta: F,
     vec_b: Vec<F>,
     // fk: Option<Box<dyn Fn(F) -> Result<F, Error> + Send>>,
-    _p: PhantomData<(F, S, V)>,
+    _p: PhantomData<(F, S, V, H)>,
 }
 
-impl<F, S, V> ObliviousPrf for SepOprfSenderWithVole<F, S, V>
+impl<F, S, V, H> SepOprfSenderWithVole<F, S, V, H>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForSender<F>,
+    H: KeyedHasher,
+{
+    /// Precomputation for the sender drawing VOLE correlations from a [session](crate::vole::session).
+    /// No communication is needed.
+    ///
+    /// Solver parameters are decided by `query_num` and `options`, which must be the same as the receiver's.
+    pub fn precomp_with_session(
+        session: &mut VoleSenderSession<F>,
+        query_num: usize,
+        options: SolverOptions,
+    ) -> Result<Self, Error> {
+        let params = S::calc_params_with(query_num, options);
+        let m = params.code_length();
+
+        let vec_b = session.draw(m).located()?;
+
+        Ok(Self {
+            params,
+            delta: session.delta(),
+            vec_b,
+            // fk: None,
+            _p: PhantomData,
+        })
+    }
+
+    /// Send protocol returning the OPRF key. Same as [send](SepOprfSender::send) except the return type.
+    pub fn send_key<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<SepOprfKey<F, S, H>, Error> {
+        let aux = S::aux_receive(channel, rng).located()?;
+
+        let a_dash: Vec<F> = read_vec_f_exact(channel, self.params.code_length()).located()?;
+
+        let delta = self.delta;
+
+        let k = a_dash
+            .into_iter()
+            .zip(self.vec_b.iter())
+            .map(|(ad, &b)| delta * ad + b)
+            .collect::<Vec<_>>();
+
+        Ok(SepOprfKey {
+            k,
+            delta,
+            aux,
+            params: self.params,
+            hasher: oracle(),
+            _p: PhantomData,
+        })
+    }
+}
+
+fn eval_key<F: FF, S: Solver<F>, H: KeyedHasher>(
+    k: &[F],
+    delta: F,
+    aux: S::AuxInfo,
+    params: S::Params,
+    hasher: &H,
+    x: F,
+) -> Result<F, Error> {
+    let d = S::decode(k, x, aux, params).located()?;
+    let f_dash = d - (delta * hash_f(hasher, x).located()?);
+    let res = hash(hasher, f_dash, x).located()?;
+    Ok(res)
+}
+
+/// OPRF key held by the sender after [send_key](SepOprfSenderWithVole::send_key).
+///
+/// $`F_k(x) = H(\mathrm{Decode}(K, x) - \Delta \cdot H^{\mathbb{F}}(x), x)`$
+/// where $`H`$ and $`H^{\mathbb{F}}`$ are computed by `H` keyed by a public constant.
+pub struct SepOprfKey<F, S, H = Sha256Hasher>
+where
+    F: FF,
+    S: Solver<F>,
+    H: KeyedHasher,
+{
+    k: Vec<F>,
+    delta: F,
+    aux: S::AuxInfo,
+    params: S::Params,
+    hasher: H,
+    _p: PhantomData<fn() -> S>,
+}
+
+impl<F, S, H> SepOprfKey<F, S, H>
+where
+    F: FF,
+    S: Solver<F>,
+    H: KeyedHasher,
+{
+    /// Evaluate $`F_k(x)`$.
+    pub fn eval(&self, x: F) -> Result<F, Error> {
+        eval_key::<F, S, H>(&self.k, self.delta, self.aux, self.params, &self.hasher, x)
+    }
+
+    // The closure captures fields only, so that `S` is not required to be `'static`.
+    pub(crate) fn into_fn(self) -> Box<dyn Fn(F) -> Result<F, Error> + Send> {
+        let Self {
+            k,
+            delta,
+            aux,
+            params,
+            hasher,
+            ..
+        } = self;
+
+        Box::new(move |x| eval_key::<F, S, H>(&k, delta, aux, params, &hasher, x))
+    }
+
+    /// Evaluate $`F_k(x)`$ for all of `xs` using [decode_batch](Solver::decode_batch) and batched hashes.
+    pub fn eval_batch(&self, xs: &[F]) -> Result<Vec<F>, Error> {
+        let ds = S::decode_batch(&self.k, xs, self.aux, self.params).located()?;
+        let hs = hash_f_batch::<F, H>(xs).located()?;
+
+        let f_dashes = ds
+            .into_iter()
+            .zip(hs)
+            .map(|(d, h)| d - self.delta * h)
+            .collect::<Vec<_>>();
+
+        hash_batch::<F, H>(&f_dashes, xs).located()
+    }
+}
+
+impl<F, S, V, H> ObliviousPrf for SepOprfSenderWithVole<F, S, V, H>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForSender<F>,
+    H: KeyedHasher,
 {
     type Seed = ();
     type Input = F;
     type Output = F;
 }
 
-impl<F, S, V> SepOprfSender for SepOprfSenderWithVole<F, S, V>
+impl<F, S, V, H> SepOprfSender for SepOprfSenderWithVole<F, S, V, H>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForSender<F>,
+    H: KeyedHasher,
 {
     type PrecompSystem = V;
 
@@ -211,118 +350,106 @@ where
         query_num: usize,
         mut vole_share_for_s: V,
     ) -> Result<Self, Error> {
-        let params = S::calc_params(query_num);
-        let m = params.code_length();
+        let m = S::calc_params(query_num).code_length();
 
-        let (delta, vec_b) = vole_share_for_s
-            .receive(channel, rng, m)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        if vec_b.len() != m {
-            bail!(
-                "vec_b.len() (={}) != m (={}) @ {}:{}",
-                vec_b.len(),
-                m,
-                file!(),
-                line!()
-            );
-        }
+        let mut session = vole_share_for_s.open_session(channel, rng, m).located()?;
 
-        Ok(Self {
-            params,
-            delta,
-            vec_b,
-            // fk: None,
-            _p: PhantomData,
-        })
+        Self::precomp_with_session(&mut session, query_num, SolverOptions::default())
     }
 
     /// Actual implementation of send protocol. It called in online phase and solver decoding is run.
+    ///
+    /// Use [send_key](SepOprfSenderWithVole::send_key) to evaluate many inputs at once.
     fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
         self,
         channel: &mut C,
         _query_num: usize,
         rng: &mut RNG,
     ) -> Result<Box<dyn Fn(F) -> Result<F, Error> + Send>, Error> {
-        let aux =
-            S::aux_receive(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        let a_dash: Vec<F> = read_vec_f(channel)?;
-
-        let m = self.params.code_length();
-        if a_dash.len() != m {
-            bail!(
-                "a_dash.len() (={}) != (={}) m @ {}:{}",
-                a_dash.len(),
-                m,
-                file!(),
-                line!()
-            );
-        }
+        let key = self.send_key(channel, rng).located()?;
 
-        let delta = self.delta;
-
-        let k = a_dash
-            .into_iter()
-            .zip(self.vec_b.iter())
-            .map(|(ad, &b)| delta * ad + b)
-            .collect::<Vec<_>>();
-
-        let params = self.params.clone();
-        let fk = move |x| -> Result<F, Error> {
-            let d = S::decode(&k, x, aux, params)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
-            let f_dash =
-                d - (delta * hash_f(x).with_context(|| format!("@{}:{}", file!(), line!()))?);
-            let res = hash(f_dash, x).with_context(|| format!("@{}:{}", file!(), line!()))?;
-            Ok(res)
-        };
-
-        Ok(Box::new(fk))
+        Ok(key.into_fn())
     }
 
     /*
     fn compute(&self, input: Self::Input) -> Result<Self::Output, Error> {
         let Some(fk) = &self.fk else {
-            bail!("k has not been set yet. @ {}:{}", file!(), line!());
+            bail!("k has not been set yet.");
         };
 
-        Ok(fk(input).with_context(|| format!("@{}:{}", file!(), line!()))?)
+        Ok(fk(input).located()?)
     }
     */
 }
 
 /// Actual implementation of Separated OPRF receiver using VOLE.
 ///
+/// `H` is the random oracle of the OPRF output (see [SepOprfOutputs]). It must be the same as the sender's.
+///
 /// Please look the parent document ( [crate::preprocessed::oprf] ) for usage example.
-pub struct SepOprfReceiverWithVole<F, S, V>
+pub struct SepOprfReceiverWithVole<F, S, V, H = Sha256Hasher>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForReceiver<F>,
+    H: KeyedHasher,
 {
     params: S::Params,
+    retry: RetryPolicy,
     vec_a: Vec<F>,
     vec_c: Vec<F>,
-    _p: PhantomData<(F, S, V)>,
+    _p: PhantomData<(F, S, V, H)>,
+}
+
+impl<F, S, V, H> SepOprfReceiverWithVole<F, S, V, H>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+    H: KeyedHasher,
+{
+    /// Precomputation for the receiver drawing VOLE correlations from a [session](crate::vole::session).
+    /// No communication is needed.
+    ///
+    /// Solver parameters are decided by `query_num` and `options`, which must be the same as the sender's.
+    pub fn precomp_with_session(
+        session: &mut VoleReceiverSession<F>,
+        query_num: usize,
+        options: SolverOptions,
+    ) -> Result<Self, Error> {
+        let params = S::calc_params_with(query_num, options);
+        let m = params.code_length();
+
+        let (vec_a, vec_c) = session.draw(m).located()?;
+
+        Ok(Self {
+            params,
+            retry: options.retry,
+            vec_a,
+            vec_c,
+            _p: PhantomData,
+        })
+    }
 }
 
-impl<F, S, V> ObliviousPrf for SepOprfReceiverWithVole<F, S, V>
+impl<F, S, V, H> ObliviousPrf for SepOprfReceiverWithVole<F, S, V, H>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForReceiver<F>,
+    H: KeyedHasher,
 {
     type Seed = ();
     type Input = F;
     type Output = F;
 }
 
-impl<F, S, V> SepOprfReceiver for SepOprfReceiverWithVole<F, S, V>
+impl<F, S, V, H> SepOprfReceiver for SepOprfReceiverWithVole<F, S, V, H>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForReceiver<F>,
+    H: KeyedHasher,
 {
     type PrecompSystem = V;
 
@@ -333,30 +460,11 @@ where
         query_num: usize,
         mut vole_share_for_r: V,
     ) -> Result<Self, Error> {
-        let params = S::calc_params(query_num);
-        let m = params.code_length();
+        let m = S::calc_params(query_num).code_length();
 
-        let (vec_a, vec_c) = vole_share_for_r
-            .receive(channel, rng, m)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        if vec_a.len() != m || vec_c.len() != m {
-            bail!(
-                "vec_a.len() (={}) != m (={}) or vec_c.len() (={}) != m @ {}:{}",
-                vec_a.len(),
-                m,
-                vec_c.len(),
-                file!(),
-                line!()
-            );
-        }
+        let mut session = vole_share_for_r.open_session(channel, rng, m).located()?;
 
-        Ok(Self {
-            params,
-            vec_a,
-            vec_c,
-            _p: PhantomData,
-        })
+        Self::precomp_with_session(&mut session, query_num, SolverOptions::default())
     }
 
     /// Actual implementation of receive protocol. It called in online phase and solver encoding (e.g. cukoo graph creating by PaXoS solver) is run.
@@ -370,69 +478,105 @@ where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
-        let points = queries
-            .iter()
-            .map(|input| {
-                let x = input.clone();
-                let y = hash_f(x).with_context(|| format!("@{}:{}", file!(), line!()))?;
-                Ok((x, y))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
-
-        let mut aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let mut p = Err(anyhow!("dummy!"));
-        for _ in 0..2 {
-            p = S::encode(rng, &points, aux, self.params)
-                .with_context(|| format!("@{}:{}", file!(), line!()));
-            if p.is_ok() {
-                break;
-            }
-            aux = S::gen_aux(rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        }
-        let p = p?;
+        let outputs = self.receive_outputs(channel, queries, rng).located()?;
+        let ys = outputs.eval_batch(queries).located()?;
+        let res = queries.iter().copied().zip(ys).collect::<Vec<_>>();
 
-        S::aux_send(channel, rng, aux).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        Ok(res)
+    }
+}
+
+impl<F, S, V, H> SepOprfReceiverWithVole<F, S, V, H>
+where
+    F: FF,
+    S: Solver<F>,
+    V: VoleShareForReceiver<F>,
+    H: KeyedHasher,
+{
+    /// Same as [receive](SepOprfReceiver::receive) except that the outputs are evaluated later by [SepOprfOutputs],
+    /// so that they need not be held for all queries at once.
+    pub fn receive_outputs<C, RNG>(
+        self,
+        channel: &mut C,
+        queries: &[F],
+        rng: &mut RNG,
+    ) -> Result<SepOprfOutputs<F, S, H>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let ys = hash_f_batch::<F, H>(queries).located()?;
+        let points = queries.iter().copied().zip(ys).collect::<Vec<_>>();
+
+        let (p, aux) =
+            encode_with_retry::<F, S, _>(rng, &points, self.params, self.retry).located()?;
+        drop(points);
+
+        S::aux_send(channel, rng, aux).located()?;
 
         if p.len() != self.vec_a.len() {
-            bail!(
-                "p.len() (={}) != vec_a.len() (={}) @ {}:{}",
+            return Err(Error::Vole(format!(
+                "p.len() (={}) != vec_a.len() (={})",
                 p.len(),
-                self.vec_a.len(),
-                file!(),
-                line!()
-            );
+                self.vec_a.len()
+            )));
         }
 
         let p_plus_a = p
-            .iter()
-            .zip(self.vec_a.iter())
-            .map(|(&p, &a)| p + a)
+            .into_iter()
+            .zip(self.vec_a)
+            .map(|(p, a)| p + a)
             .collect::<Vec<_>>();
 
-        write_vec_f(channel, &p_plus_a).with_context(|| format!("@{}:{}", file!(), line!()))?;
+        write_vec_f(channel, &p_plus_a).located()?;
 
-        let res = queries
-            .into_iter()
-            .map(|&x| {
-                let d = S::decode(&self.vec_c, x, aux, self.params)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                let y = hash(d, x).with_context(|| format!("@{}:{}", file!(), line!()))?;
-                Ok((x, y))
-            })
-            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(SepOprfOutputs {
+            vec_c: self.vec_c,
+            aux,
+            params: self.params,
+            _p: PhantomData,
+        })
+    }
+}
 
-        Ok(res)
+/// OPRF outputs held by the receiver after [receive_outputs](SepOprfReceiverWithVole::receive_outputs).
+///
+/// $`F_k(x) = H(\mathrm{Decode}(C, x), x)`$ for a query $`x`$. Values for the other inputs are meaningless.
+pub struct SepOprfOutputs<F, S, H = Sha256Hasher>
+where
+    F: FF,
+    S: Solver<F>,
+    H: KeyedHasher,
+{
+    vec_c: Vec<F>,
+    aux: S::AuxInfo,
+    params: S::Params,
+    _p: PhantomData<fn() -> (S, H)>,
+}
+
+impl<F, S, H> SepOprfOutputs<F, S, H>
+where
+    F: FF,
+    S: Solver<F>,
+    H: KeyedHasher,
+{
+    /// Outputs for `queries` using [decode_batch](Solver::decode_batch) and batched hashes.
+    pub fn eval_batch(&self, queries: &[F]) -> Result<Vec<F>, Error> {
+        let ds = S::decode_batch(&self.vec_c, queries, self.aux, self.params).located()?;
+
+        hash_batch::<F, H>(&ds, queries).located()
     }
 }
 
 /// You are allowed to clone them **FOR BENCHMARKING PURPOSES ONLY**.
 ///
 /// **DO NOT USE THEM IN PRODUCTION** because of the security reasons.
-impl<F, S, V> Clone for SepOprfSenderWithVole<F, S, V>
+impl<F, S, V, H> Clone for SepOprfSenderWithVole<F, S, V, H>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForSender<F>,
+    H: KeyedHasher,
 {
     fn clone(&self) -> Self {
         Self {
@@ -448,15 +592,17 @@ where
 /// You are allowed to clone them **FOR BENCHMARKING PURPOSES ONLY**.
 ///
 /// **DO NOT USE THEM IN PRODUCTION** because of the security reasons.
-impl<F, S, V> Clone for SepOprfReceiverWithVole<F, S, V>
+impl<F, S, V, H> Clone for SepOprfReceiverWithVole<F, S, V, H>
 where
     F: FF,
     S: Solver<F>,
     V: VoleShareForReceiver<F>,
+    H: KeyedHasher,
 {
     fn clone(&self) -> Self {
         Self {
             params: self.params,
+            retry: self.retry,
             vec_a: self.vec_a.clone(),
             vec_c: self.vec_c.clone(),
             _p: PhantomData,
@@ -467,6 +613,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::channel_utils::read_vec_f;
     use crate::solver::{PaxosSolver, VandelmondeSolver};
     use crate::vole::{
         LPNVoleReceiver, LPNVoleSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM,
@@ -659,6 +806,62 @@ mod tests {
         test_2party_psi_base::<PaxosSolver<F128b>>(1 << 17, 1 << 16, false);
     }
 
+    #[test]
+    fn test_oprf_key_eval_batch() {
+        let set_size = 1 << 13;
+        let (set, _, _) = create_sets::<F128b>(set_size, 0);
+        let m_size = PaxosSolver::<F128b>::calc_params(set_size).code_length();
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_set = set.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let oprf_sender = SepOprfSenderWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+                &mut channel,
+                &mut rng,
+                set_size,
+                LPNVoleSender::for_size(m_size),
+            )
+            .unwrap();
+
+            let key = oprf_sender.send_key(&mut channel, &mut rng).unwrap();
+
+            let batch = key.eval_batch(&sender_set).unwrap();
+            let single = sender_set
+                .iter()
+                .map(|&x| key.eval(x).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(batch, single);
+
+            batch
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let oprf_receiver = SepOprfReceiverWithVole::<F128b, PaxosSolver<F128b>, _>::precomp(
+            &mut channel,
+            &mut rng,
+            set_size,
+            LPNVoleReceiver::for_size(m_size),
+        )
+        .unwrap();
+
+        let received = oprf_receiver.receive(&mut channel, &set, &mut rng).unwrap();
+
+        let sender_res = handle.join().unwrap();
+        let receiver_res = received.into_iter().map(|(_, y)| y).collect::<Vec<_>>();
+
+        assert_eq!(sender_res, receiver_res);
+    }
+
     // If you want to finish below calculation within the expected time (60s), you should consider a more intelligent two-party PSI. there is no problem with PaXoS
     // The filter_map is taking a crazy amount of time.
     /*
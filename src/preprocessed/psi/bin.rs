@@ -1,17 +1,94 @@
-use crate::channel_utils::ch_arcnize;
+use crate::channel_utils::abortable::{AbortHandle, AbortableChannel};
+#[cfg(any(test, feature = "testing"))]
+use crate::channel_utils::transcript::{read_transcript, ReplayChannel, TranscriptHeader};
+use crate::channel_utils::transcript::{Recorder, RecordingChannel};
 use crate::cli_utils::{
-    self as cli, create_vole_sr, ChannelUnion, MultiThreadOptimization, PrePSIArgs, SolverType,
-    VoleShareForReceiverUnion, VoleShareForSenderUnion,
+    self as cli, create_vole_sr, ChannelUnion, PrePSIArgs, SolverType, VoleShareForReceiverUnion,
+    VoleShareForSenderUnion,
 };
-use crate::preprocessed::psi::{Receiver, Sender};
+use crate::error::{Context, Error, Result};
+use crate::parallel_utils::{Execution, Threads};
+use crate::preprocessed::psi::{PsiOptions, Receiver, Sender};
 use crate::set_utils::create_sets_without_check;
-use crate::solver::{PaxosSolver, VandelmondeSolver};
-use anyhow::{Context, Result};
+use crate::solver::{
+    BandSolver, PaxosSolver, RetryPolicy, SolverOptions, TwoCoreSolver, VandelmondeSolver,
+};
+#[cfg(any(test, feature = "testing"))]
+use crate::testing::SeedTree;
 use scuttlebutt::field::F128b;
 use scuttlebutt::AesRng;
+#[cfg(any(test, feature = "testing"))]
+use scuttlebutt::{AbstractChannel, Block};
+use std::any::Any;
 use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+#[cfg(any(test, feature = "testing"))]
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+type Channel = AbortableChannel<ChannelUnion>;
+
+fn abort_handles(channels: &[(usize, Channel)]) -> Vec<(usize, AbortHandle<ChannelUnion>)> {
+    channels
+        .iter()
+        .map(|(i, c)| (*i, c.abort_handle()))
+        .collect()
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
+// The error and all its sources joined by ": ".
+fn error_message(err: &Error) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+// Run `f` as party `me`. If it fails or panics, tell every peer to abort
+// so that they exit with an error naming the culprit instead of waiting for this party.
+fn run_or_abort<T>(
+    me: usize,
+    aborts: Vec<(usize, AbortHandle<ChannelUnion>)>,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let res = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        Err(Error::Internal(format!(
+            "party {} panicked: {}",
+            me,
+            panic_message(panic.as_ref())
+        )))
+    });
+
+    if let Err(err) = &res {
+        // an abort by a peer is forwarded as it is
+        let (culprit, reason) = match err.root() {
+            Error::Aborted { culprit, reason } => (*culprit, reason.clone()),
+            _ => (me, error_message(err)),
+        };
+
+        for (_, handle) in aborts {
+            // the peer may have already exited
+            let _ = handle.abort(culprit, &reason);
+        }
+    }
+
+    res
+}
 
 fn intersection_prepare(
     rng: &mut AesRng,
@@ -28,18 +105,335 @@ fn intersection_prepare(
     Ok((intersection, sets))
 }
 
-fn protocol_base(
-    intersection: Vec<F128b>,
-    mut sets: Vec<Vec<F128b>>,
-    receiver_channels: Vec<(usize, ChannelUnion)>,
-    channels: Vec<Vec<(usize, ChannelUnion)>>,
-    multi_thread: MultiThreadOptimization,
+// Settings shared by all parties.
+#[derive(Clone)]
+struct ProtocolConfig {
+    execution: Execution,
+    threads: Threads,
     solver_type: SolverType,
     vole_share_for_s: VoleShareForSenderUnion,
     vole_share_for_r: VoleShareForReceiverUnion,
+    options: SolverOptions,
+}
+
+// RNGs of a party for each phase and the recorder of its transcript if recording.
+struct PartyState {
+    offline_rng: AesRng,
+    online_rng: AesRng,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl PartyState {
+    // RNGs seeded by the OS.
+    fn new() -> Self {
+        Self {
+            offline_rng: AesRng::new(),
+            online_rng: AesRng::new(),
+            recorder: None,
+        }
+    }
+
+    // RNGs derived from `seeds`.
+    #[cfg(any(test, feature = "testing"))]
+    fn seeded(seeds: SeedTree, recorder: Option<Arc<Recorder>>) -> Self {
+        Self {
+            offline_rng: seeds.phase("offline").rng(),
+            online_rng: seeds.phase("online").rng(),
+            recorder,
+        }
+    }
+
+    fn set_phase(&self, phase: &str) {
+        if let Some(recorder) = &self.recorder {
+            recorder.set_phase(phase);
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+fn transcript_path(dir: &Path, pid: usize) -> PathBuf {
+    dir.join(format!("party_{}.transcript", pid))
+}
+
+// The RNG of the sets and the state of each party, derived from `seed` if given and recording to `record` if given.
+#[cfg(any(test, feature = "testing"))]
+fn prepare_parties(
+    num_parties: usize,
+    seed: Option<u128>,
+    record: Option<&Path>,
+) -> Result<(AesRng, Vec<PartyState>)> {
+    // the transcripts store the seed to be replayable, so recording is only allowed with a seed chosen on purpose
+    let seeds = match (seed, record) {
+        (Some(seed), _) => SeedTree::new(Block::from(seed)),
+        (None, Some(_)) => {
+            return Err(Error::Config(
+                "--record needs --seed: the transcripts store the seed, which reveals the secrets of all parties."
+                    .to_string(),
+            ))
+        }
+        (None, None) => {
+            let parties = (0..num_parties).map(|_| PartyState::new()).collect();
+            return Ok((AesRng::new(), parties));
+        }
+    };
+    println!("seed: {}", u128::from(seeds.seed()));
+
+    let recorders = match record {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}.", dir.display()))?;
+            let recorders = (0..num_parties)
+                .map(|pid| {
+                    let header = TranscriptHeader {
+                        party: pid,
+                        seed: seeds.seed(),
+                    };
+                    Recorder::create(transcript_path(dir, pid), header).map(Some)
+                })
+                .collect::<Result<Vec<_>, Error>>()
+                .with_context(|| "Failed to create transcripts.")?;
+
+            println!("recording transcripts to {}.", dir.display());
+
+            recorders
+        }
+        None => vec![None; num_parties],
+    };
+
+    let parties = recorders
+        .into_iter()
+        .enumerate()
+        .map(|(pid, recorder)| PartyState::seeded(seeds.party(pid), recorder))
+        .collect();
+
+    Ok((seeds.sets().rng(), parties))
+}
+
+// Seeded and recorded runs reveal every secret, so they are not in builds without the `testing` feature.
+#[cfg(not(any(test, feature = "testing")))]
+fn prepare_parties(
+    num_parties: usize,
+    seed: Option<u128>,
+    record: Option<&Path>,
+) -> Result<(AesRng, Vec<PartyState>)> {
+    if seed.is_some() || record.is_some() {
+        return Err(Error::Config(
+            "--seed and --record need a build with the `testing` feature.".to_string(),
+        ));
+    }
+
+    let parties = (0..num_parties).map(|_| PartyState::new()).collect();
+    Ok((AesRng::new(), parties))
+}
+
+// Wrap the channels of a party so that it can abort the others, recording below the framing.
+fn wrap_channels(
+    channels: Vec<(usize, ChannelUnion)>,
+    recorder: &Option<Arc<Recorder>>,
+) -> Vec<(usize, Channel)> {
+    channels
+        .into_iter()
+        .map(|(i, c)| {
+            let c = match recorder {
+                Some(recorder) => ChannelUnion::Recording(Box::new(RecordingChannel::new(
+                    c,
+                    i,
+                    Arc::clone(recorder),
+                ))),
+                None => c,
+            };
+            (i, AbortableChannel::new(c))
+        })
+        .collect()
+}
+
+fn run_sender(
+    pid: usize,
+    channels: Vec<(usize, Channel)>,
+    set: Vec<F128b>,
+    party: PartyState,
+    config: ProtocolConfig,
+) -> Result<()> {
+    let aborts = abort_handles(&channels);
+    let ProtocolConfig {
+        execution,
+        threads,
+        solver_type,
+        vole_share_for_s,
+        vole_share_for_r,
+        options,
+    } = config;
+
+    run_or_abort(pid, aborts, move || {
+        let mut party = party;
+
+        macro_rules! sender_protocol {
+            ( $s:path ) => {{
+                let mut chns = channels;
+
+                // offline phase
+                // Sender::<F128b, S, _, _>::precomp_with_options(
+                party.set_phase("offline");
+                let sender = $s(
+                    pid,
+                    &mut chns,
+                    &mut party.offline_rng,
+                    vole_share_for_s,
+                    vole_share_for_r,
+                    set.len(),
+                    PsiOptions {
+                        solver: options,
+                        execution,
+                        threads: threads.clone(),
+                    },
+                )
+                .with_context(|| format!("Failed to create sender {}.", pid))?;
+
+                println!("sender {} prepared.", pid);
+
+                // online phase
+                party.set_phase("online");
+                sender
+                    .send(&set, &mut chns, &mut party.online_rng)
+                    .with_context(|| format!("Failed to run sender {}.", pid))?;
+
+                println!("sender {} finished.", pid);
+            }};
+        }
+
+        match solver_type {
+            SolverType::Vandelmonde => {
+                sender_protocol!(
+                    Sender::<F128b, VandelmondeSolver<F128b>, _, _>::precomp_with_options
+                )
+            }
+            SolverType::Paxos => {
+                sender_protocol!(Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp_with_options)
+            }
+            SolverType::TwoCore => {
+                sender_protocol!(Sender::<F128b, TwoCoreSolver<F128b>, _, _>::precomp_with_options)
+            }
+            SolverType::Band => {
+                sender_protocol!(Sender::<F128b, BandSolver<F128b>, _, _>::precomp_with_options)
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn run_receiver(
+    channels: Vec<(usize, Channel)>,
+    set: Vec<F128b>,
+    party: PartyState,
+    config: ProtocolConfig,
+) -> Result<Vec<F128b>> {
+    let aborts = abort_handles(&channels);
+    let ProtocolConfig {
+        execution,
+        threads,
+        solver_type,
+        vole_share_for_s,
+        vole_share_for_r,
+        options,
+    } = config;
+
+    run_or_abort(0, aborts, move || {
+        let mut party = party;
+
+        macro_rules! receiver_protocol {
+            ( $r:path ) => {{
+                println!("offline phase started.");
+                let start = Instant::now();
+
+                let mut chns = channels;
+
+                // create and run receiver
+                // offline phase
+                // let receiver = Receiver::<F128b, S, _, _>::precomp_with_options(
+                party.set_phase("offline");
+                let receiver = $r(
+                    &mut chns,
+                    &mut party.offline_rng,
+                    vole_share_for_s,
+                    vole_share_for_r,
+                    set.len(),
+                    PsiOptions {
+                        solver: options,
+                        execution,
+                        threads: threads.clone(),
+                    },
+                )
+                .with_context(|| "Failed to create receiver.")?;
+
+                println!("receiver prepared. offline time: {:?}", start.elapsed());
+                println!("online phase started.");
+
+                let start = Instant::now();
+
+                // online phase
+                party.set_phase("online");
+                let res = receiver
+                    .receive(&set, &mut chns, &mut party.online_rng)
+                    .with_context(|| "Failed to run receiver.")?;
+
+                println!("receiver finished. online time: {:?}", start.elapsed());
+
+                res
+            }};
+        }
+
+        let res = match solver_type {
+            SolverType::Vandelmonde => {
+                receiver_protocol!(
+                    Receiver::<F128b, VandelmondeSolver<F128b>, _, _>::precomp_with_options
+                )
+            }
+            SolverType::Paxos => {
+                receiver_protocol!(
+                    Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp_with_options
+                )
+            }
+            SolverType::TwoCore => {
+                receiver_protocol!(
+                    Receiver::<F128b, TwoCoreSolver<F128b>, _, _>::precomp_with_options
+                )
+            }
+            SolverType::Band => {
+                receiver_protocol!(Receiver::<F128b, BandSolver<F128b>, _, _>::precomp_with_options)
+            }
+        };
+
+        Ok(res)
+    })
+}
+
+fn check_intersection(res: Vec<F128b>, intersection: Vec<F128b>, verbose: bool) {
+    let res: HashSet<F128b> = HashSet::from_iter(res);
+    let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+
+    if verbose {
+        println!("intersection: {:?}", intersection);
+        println!("res: {:?}", res);
+    }
+
+    assert_eq!(res, intersection);
+}
+
+fn protocol_base(
+    intersection: Vec<F128b>,
+    sets: Vec<Vec<F128b>>,
+    receiver_channels: Vec<(usize, Channel)>,
+    channels: Vec<Vec<(usize, Channel)>>,
+    parties: Vec<PartyState>,
+    config: ProtocolConfig,
     verbose: bool,
 ) -> Result<()> {
-    let r_set = sets.pop().unwrap();
+    // party i has sets[i]; the receiver is party 0
+    let mut sets = sets.into_iter();
+    let mut parties = parties.into_iter();
+    let r_set = sets.next().unwrap();
+    let r_party = parties.next().unwrap();
 
     if verbose {
         println!("receiver's set: {:?}", r_set);
@@ -47,174 +441,113 @@ fn protocol_base(
 
     let handles = channels
         .into_iter()
+        .zip(sets.zip(parties))
         .enumerate()
-        .map(move |(i, channels)| {
+        .map(|(i, (channels, (set, party)))| {
             // create and fork senders
             let pid = i + 1;
-            let set = sets.pop().unwrap();
-            let vole_share_for_s = vole_share_for_s.clone();
-            let vole_share_for_r = vole_share_for_r.clone();
 
             if verbose {
                 println!("sender {}'s set: {:?}", pid, set);
             }
 
-            std::thread::spawn(move || -> Result<()> {
-                let mut rng = AesRng::new();
-
-                macro_rules! sender_protocol {
-                    ( $chns:expr, $set:expr, $s:path, $send:ident ) => {{
-                        let mut chns = $chns;
-
-                        // offline phase
-                        // Sender::<F128b, S, _, _>::precomp(
-                        let sender = $s(
-                            pid,
-                            &mut chns,
-                            &mut rng,
-                            vole_share_for_s,
-                            vole_share_for_r,
-                            set.len(),
-                        )
-                        .with_context(|| format!("Failed to create sender {}.", pid))?;
-
-                        println!("sender {} prepared.", pid);
-
-                        // online phase
-                        sender
-                            .$send($set, &mut chns, &mut rng)
-                            .with_context(|| format!("Failed to run sender {}.", pid))?;
-
-                        println!("sender {} finished.", pid);
-                    }};
-                }
-
-                match (solver_type, multi_thread) {
-                    (SolverType::Vandelmonde, MultiThreadOptimization::Off) => {
-                        sender_protocol!(
-                            channels,
-                            &set,
-                            Sender::<F128b, VandelmondeSolver<F128b>, _, _>::precomp,
-                            send
-                        )
-                    }
-                    (SolverType::Paxos, MultiThreadOptimization::Off) => {
-                        sender_protocol!(
-                            channels,
-                            &set,
-                            Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp,
-                            send
-                        )
-                    }
-                    (SolverType::Vandelmonde, MultiThreadOptimization::On) => {
-                        sender_protocol!(
-                            ch_arcnize(channels),
-                            Arc::new(set),
-                            Sender::<F128b, VandelmondeSolver<F128b>, _, _>::precomp_mt,
-                            send_mt
-                        )
-                    }
-                    (SolverType::Paxos, MultiThreadOptimization::On) => {
-                        sender_protocol!(
-                            ch_arcnize(channels),
-                            Arc::new(set),
-                            Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp_mt,
-                            send_mt
-                        )
-                    }
-                }
-
-                Ok(())
-            })
+            let config = config.clone();
+            std::thread::spawn(move || run_sender(pid, channels, set, party, config))
         })
         .collect::<Vec<_>>();
 
-    let mut rng = AesRng::new();
-
-    macro_rules! receiver_protocol {
-        ( $chns:expr, $set:expr, $r:path, $receive:ident ) => {{
-            println!("offline phase started.");
-            let start = Instant::now();
+    let res = run_receiver(receiver_channels, r_set, r_party, config);
 
-            let mut chns = $chns;
-
-            // create and run receiver
-            // offline phase
-            // let receiver = Receiver::<F128b, S, _, _>::precomp(
-            let receiver = $r(
-                &mut chns,
-                &mut rng,
-                vole_share_for_s,
-                vole_share_for_r,
-                r_set.len(),
-            )
-            .with_context(|| "Failed to create receiver.")?;
+    // senders exit soon after an abort, so their errors are collected as well
+    let sender_results = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("Failed to join a thread."))
+        .collect::<Vec<_>>();
 
-            println!("receiver prepared. offline time: {:?}", start.elapsed());
-            println!("online phase started.");
+    let res = res?;
+    for sender_result in sender_results {
+        sender_result?;
+    }
 
-            let start = Instant::now();
+    check_intersection(res, intersection, verbose);
 
-            // online phase
-            let res = receiver
-                .$receive($set, &mut chns, &mut rng)
-                .with_context(|| "Failed to run receiver.")?;
+    Ok(())
+}
 
-            println!("receiver finished. online time: {:?}", start.elapsed());
+// Re-run party `pid` alone against its transcript in `dir`.
+#[cfg(any(test, feature = "testing"))]
+fn replay_party(
+    dir: &Path,
+    pid: usize,
+    (num_parties, set_size, common_size): (usize, usize, usize),
+    config: ProtocolConfig,
+    verbose: bool,
+) -> Result<()> {
+    if pid >= num_parties {
+        return Err(Error::Config(format!(
+            "party must be less than the number of parties {} (now party = {}).",
+            num_parties, pid
+        )));
+    }
 
-            res
-        }};
+    let path = transcript_path(dir, pid);
+    let (header, records) =
+        read_transcript(&path).with_context(|| format!("Failed to read {}.", path.display()))?;
+    if header.party != pid {
+        return Err(Error::Config(format!(
+            "{} is a transcript of party {}.",
+            path.display(),
+            header.party
+        )));
+    }
+    if let Some(r) = records.iter().find(|r| r.peer >= num_parties) {
+        return Err(Error::Config(format!(
+            "the transcript has party {} although the number of parties is {}.",
+            r.peer, num_parties
+        )));
     }
 
-    let res = match (solver_type, multi_thread) {
-        (SolverType::Vandelmonde, MultiThreadOptimization::Off) => {
-            receiver_protocol!(
-                receiver_channels,
-                &r_set,
-                Receiver::<F128b, VandelmondeSolver<F128b>, _, _>::precomp,
-                receive
-            )
-        }
-        (SolverType::Paxos, MultiThreadOptimization::Off) => {
-            receiver_protocol!(
-                receiver_channels,
-                &r_set,
-                Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp,
-                receive
-            )
-        }
-        (SolverType::Vandelmonde, MultiThreadOptimization::On) => {
-            receiver_protocol!(
-                ch_arcnize(receiver_channels),
-                Arc::new(r_set),
-                Receiver::<F128b, VandelmondeSolver<F128b>, _, _>::precomp_mt,
-                receive_mt
-            )
-        }
-        (SolverType::Paxos, MultiThreadOptimization::On) => {
-            receiver_protocol!(
-                ch_arcnize(receiver_channels),
-                Arc::new(r_set),
-                Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp_mt,
-                receive_mt
-            )
-        }
-    };
+    // the same sets and RNGs as the recorded run
+    let seeds = SeedTree::new(header.seed);
+    let (intersection, mut sets) =
+        intersection_prepare(&mut seeds.sets().rng(), num_parties, set_size, common_size)
+            .with_context(|| "Failed to prepare intersection.")?;
+    let party = PartyState::seeded(seeds.party(pid), None);
+    let set = sets.swap_remove(pid);
+
+    let replays = (0..num_parties)
+        .filter(|&i| i != pid)
+        .map(|i| (i, ReplayChannel::new(i, &records)))
+        .collect::<Vec<_>>();
+    let channels = replays
+        .iter()
+        .map(|(i, r)| {
+            let r = AbstractChannel::clone(r);
+            (*i, AbortableChannel::new(ChannelUnion::Replay(r)))
+        })
+        .collect::<Vec<_>>();
 
-    let res: HashSet<F128b> = HashSet::from_iter(res);
-    let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+    println!("replaying party {} from {}.", pid, path.display());
 
-    if verbose {
-        println!("intersection: {:?}", intersection);
-        println!("res: {:?}", res);
+    if pid == 0 {
+        let res = run_receiver(channels, set, party, config)?;
+        check_intersection(res, intersection, verbose);
+    } else {
+        run_sender(pid, channels, set, party, config)?;
     }
 
-    assert_eq!(res, intersection);
-
-    for handle in handles {
-        handle.join().expect("Failed to join a thread.")?;
+    for (i, replay) in replays.iter() {
+        let (written, read) = replay.remaining();
+        if written > 0 || read > 0 {
+            return Err(Error::protocol_violation(format!(
+                "party {} finished before the transcript: {} bytes to party {} not written and {} bytes from it not read.",
+                pid, written, i, read
+            )));
+        }
     }
 
+    println!("party {} matched the transcript.", pid);
+
     Ok(())
 }
 
@@ -228,11 +561,84 @@ pub fn run(
         solver_type,
         channel_type,
         port,
-        multi_thread,
+        threads,
+        lambda,
+        expansion,
+        stash_size,
+        max_attempts,
+        timeout,
+        seed,
+        record,
+        replay,
+        party,
         verbose,
     }: PrePSIArgs,
 ) -> Result<()> {
-    let mut rng = AesRng::new();
+    if lambda == 0 {
+        return Err(Error::Config("lambda must be positive.".to_string()));
+    }
+    if expansion.is_some_and(|e| e.is_nan() || e <= 1.0) {
+        return Err(Error::Config(
+            "expansion must be greater than 1.".to_string(),
+        ));
+    }
+    if max_attempts == 0 {
+        return Err(Error::Config("max attempts must be positive.".to_string()));
+    }
+    let options = SolverOptions {
+        lambda,
+        expansion,
+        stash_size,
+        retry: RetryPolicy { max_attempts },
+    };
+
+    // create vole share
+    let (vole_share_for_s, vole_share_for_r) = match solver_type {
+        SolverType::Vandelmonde => {
+            create_vole_sr::<VandelmondeSolver<F128b>>(vole_type, set_size, options)
+        }
+        SolverType::Paxos => create_vole_sr::<PaxosSolver<F128b>>(vole_type, set_size, options),
+        SolverType::TwoCore => create_vole_sr::<TwoCoreSolver<F128b>>(vole_type, set_size, options),
+        SolverType::Band => create_vole_sr::<BandSolver<F128b>>(vole_type, set_size, options),
+    };
+
+    println!("vole share prepared.");
+
+    // one pool for the per-element work of all parties in this process
+    let pool = Threads::new(threads).with_context(|| "Failed to create a thread pool.")?;
+
+    let config = ProtocolConfig {
+        execution: if threads == 1 {
+            Execution::Sequential
+        } else {
+            Execution::ThreadPerPeer
+        },
+        threads: pool,
+        solver_type,
+        vole_share_for_s,
+        vole_share_for_r,
+        options,
+    };
+
+    if let Some(dir) = replay {
+        #[cfg(any(test, feature = "testing"))]
+        return replay_party(
+            &dir,
+            party,
+            (num_parties, set_size, common_size),
+            config,
+            verbose,
+        );
+        #[cfg(not(any(test, feature = "testing")))]
+        return Err(Error::Config(format!(
+            "replaying party {} from {} needs a build with the `testing` feature.",
+            party,
+            dir.display()
+        )));
+    }
+
+    // all RNGs are derived from the seed if given, so that the run can be reproduced or replayed
+    let (mut rng, parties) = prepare_parties(num_parties, seed, record.as_deref())?;
 
     // create sets
     let (intersection, sets) = intersection_prepare(&mut rng, num_parties, set_size, common_size)
@@ -241,30 +647,95 @@ pub fn run(
     println!("sets prepared.");
 
     // create channels
-    let (receiver_channels, channels) = cli::create_channels(channel_type, num_parties, port)
-        .with_context(|| "Failed to create channels.")?;
+    let timeout = (timeout > 0).then(|| Duration::from_secs(timeout));
+    let (receiver_channels, channels) =
+        cli::create_channels(channel_type, num_parties, port, timeout)
+            .with_context(|| "Failed to create channels.")?;
+
+    // both ends are wrapped so that a failing party can abort the others
+    let receiver_channels = wrap_channels(receiver_channels, &parties[0].recorder);
+    let channels = channels
+        .into_iter()
+        .zip(parties[1..].iter())
+        .map(|(cs, party)| wrap_channels(cs, &party.recorder))
+        .collect::<Vec<_>>();
 
     println!("channels prepared.");
 
-    // create vole share
-    let (vole_share_for_s, vole_share_for_r) = match solver_type {
-        SolverType::Vandelmonde => create_vole_sr::<VandelmondeSolver<F128b>>(vole_type, set_size),
-        SolverType::Paxos => create_vole_sr::<PaxosSolver<F128b>>(vole_type, set_size),
-    };
-
-    println!("vole share prepared.");
-
     protocol_base(
         intersection,
         sets,
         receiver_channels,
         channels,
-        multi_thread,
-        solver_type,
-        vole_share_for_s,
-        vole_share_for_r,
+        parties,
+        config,
         verbose,
     )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::transcript::Direction;
+    use clap::Parser;
+    use std::collections::HashMap;
+
+    fn args(extra: &[&str]) -> PrePSIArgs {
+        let base = [
+            "prep_psi",
+            "-N",
+            "3",
+            "-n",
+            "20",
+            "-m",
+            "5",
+            "--threads",
+            "1",
+        ];
+        PrePSIArgs::parse_from(base.iter().chain(extra).copied())
+    }
+
+    // bytes written to and read from each peer by party `pid`
+    fn traffic(dir: &Path, pid: usize) -> HashMap<(usize, bool), Vec<u8>> {
+        let (_, records) = read_transcript(transcript_path(dir, pid)).unwrap();
+
+        let mut traffic = HashMap::new();
+        for record in records {
+            traffic
+                .entry((record.peer, record.direction == Direction::Write))
+                .or_insert_with(Vec::new)
+                .extend(record.bytes);
+        }
+        traffic
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        for dir in dirs.iter() {
+            run(args(&[
+                "--seed",
+                "42",
+                "--record",
+                dir.path().to_str().unwrap(),
+            ]))
+            .unwrap();
+        }
+
+        let dir = dirs[0].path().to_str().unwrap();
+        for pid in 0..3 {
+            // the same seed gives the same messages
+            assert_eq!(traffic(dirs[0].path(), pid), traffic(dirs[1].path(), pid));
+
+            // every party reproduces its transcript alone to the end
+            run(args(&["--replay", dir, "--party", &pid.to_string()])).unwrap();
+        }
+
+        // a party with another set diverges from its transcript
+        let mut other = args(&["--replay", dir, "--party", "1"]);
+        other.set_size = 21;
+        assert!(run(other).is_err());
+    }
+}
@@ -1,24 +1,80 @@
 //! based on: <https://github.com/GaloisInc/swanky/blob/master/popsicle/src/psi/kmprt.rs>
 
+use crate::error::{Context, Error};
+use crate::parallel_utils::{par_zip_mut, Execution, Threads};
+use crate::preprocessed::handshake::{handshake, FieldId, Hello};
 use crate::preprocessed::opprf::{
     SepOpprfReceiver, SepOpprfReceiverWithVole, SepOpprfSender, SepOpprfSenderWithVole,
 };
-use crate::solver::Solver;
-use crate::vole::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{bail, Context, Error};
+use crate::solver::{Solver, SolverOptions};
+use crate::vole::{
+    VoleReceiverSession, VoleSenderSession, VoleShareForReceiver, VoleShareForSender,
+};
 use rand::distributions::{Distribution, Standard};
-use rand::{CryptoRng, Rng};
+use rand::{CryptoRng, Rng, SeedableRng};
 use scuttlebutt::channel::AbstractChannel;
 use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::AesRng;
 use std::clone::Clone;
+use std::sync::Mutex;
 
 mod bin;
-mod multithread_ver;
+pub mod partitioned;
+pub mod stream;
 pub use bin::run;
+pub use partitioned::{Bins, PartitionedReceiver, PartitionedSender};
+pub use stream::{SetFile, SetSource};
 
 /// usize is used as a party ID. Receiver's ID is always 0.
 pub type PartyId = usize;
 
+/// VOLE sessions with a peer: one where I have $`\Delta`$ and one where the peer has it.
+type PeerSessions<F> = (PartyId, VoleSenderSession<F>, VoleReceiverSession<F>);
+
+/// Options of [Sender] and [Receiver].
+///
+/// All parties must use the same `solver` options, while each party may choose its own `execution` and `threads`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PsiOptions {
+    /// Options deciding solver parameters.
+    pub solver: SolverOptions,
+    /// How the sub-protocols with the peers are run, in both the offline and the online phase.
+    pub execution: Execution,
+    /// Pool for the per-element work of all sub-protocols, in both the offline and the online phase.
+    ///
+    /// Parties in one process may share a pool. The default uses the pool of the caller. See [Threads].
+    pub threads: Threads,
+}
+
+impl From<SolverOptions> for PsiOptions {
+    fn from(solver: SolverOptions) -> Self {
+        Self {
+            solver,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Execution> for PsiOptions {
+    fn from(execution: Execution) -> Self {
+        Self {
+            execution,
+            ..Default::default()
+        }
+    }
+}
+
+/// Capacities of VOLE sessions between the party of `hello` and `them` as `(sender session, receiver session)`.
+///
+/// Each direction serves one OPPRF instance per bin for conditional zero sharing,
+/// and the direction towards the receiver (party 0) serves one more per bin for conditional reconstruction.
+fn session_capacities(hello: &Hello, them: PartyId) -> (usize, usize) {
+    let m = hello.nbins * hello.code_length;
+    let s_capacity = if them == 0 { 2 * m } else { m };
+    let r_capacity = if hello.party == 0 { 2 * m } else { m };
+    (s_capacity, r_capacity)
+}
+
 struct Party<F, S, VS, VR>
 where
     F: FF,
@@ -28,19 +84,16 @@ where
     Standard: Distribution<F>,
 {
     id: PartyId,
+    execution: Execution,
+    threads: Threads,
     opprf_senders: Vec<(usize, SepOpprfSenderWithVole<F, S, VS>)>,
     opprf_receivers: Vec<(usize, SepOpprfReceiverWithVole<F, S, VR>)>,
 }
 
 /// A kind of party in the protocol. They play sender and receiver in Conditional Zero Sharing, and play sender in Conditional Reconstruction.
 ///
-/// `*_mt` means multi-threads optimization.
-///
-/// Not optimized version doesn't mean single-threaded version. The difference between the optimized version and the not one is that in where parties exchange messages.
-///
-/// In the optimized version, each party has a separate thread to communicate with each of the other parties.
-///
-/// On the other hand, in the not optimized version, each party communicates in the same thread with all the other parties.
+/// The sub-protocols with the other parties are run according to [PsiOptions::execution].
+/// Every [Execution] sends the same messages, so parties choosing different ones run together.
 pub struct Sender<F, S, VS, VR>
 where
     F: FF,
@@ -56,10 +109,10 @@ where
 
 impl<F, S, VS, VR> Sender<F, S, VS, VR>
 where
-    F: FF,
-    S: Solver<F>,
-    VS: VoleShareForSender<F>,
-    VR: VoleShareForReceiver<F>,
+    F: FF + FieldId,
+    S: Solver<F> + Send,
+    VS: VoleShareForSender<F> + Send,
+    VR: VoleShareForReceiver<F> + Send,
     Standard: Distribution<F>,
 {
     /// Get the party ID. Receiver is always 0.
@@ -68,33 +121,81 @@ where
     }
 
     /// Precomputation for the sender. It runned in the offline phase.
-    pub fn precomp<C: AbstractChannel, RNG: Rng + CryptoRng>(
+    pub fn precomp<C: AbstractChannel + Send, RNG: Rng + CryptoRng>(
         me: PartyId,
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
         set_size: usize,
+    ) -> Result<Self, Error> {
+        Self::precomp_with_options(
+            me,
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+            PsiOptions::default(),
+        )
+    }
+
+    /// [precomp](Sender::precomp) with solver parameters and execution strategy given by `options`.
+    ///
+    /// All parties must use the same solver options. `channels` are sorted by party ID.
+    pub fn precomp_with_options<C: AbstractChannel + Send, RNG: Rng + CryptoRng>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        set_size: usize,
+        options: PsiOptions,
     ) -> Result<Self, Error> {
         if me == 0 {
-            bail!("sender index must not be 0. @{}:{}", file!(), line!());
+            return Err(Error::Config("sender index must not be 0.".to_string()));
         }
+        let _threads = options.threads.enter();
 
-        let id = me;
-
-        let party_for_zs = Party::precomp(
+        let hello = Hello::new::<F, S, VS, VR>(
             me,
+            channels.len() + 1,
+            set_size,
+            options.solver,
+            &vole_share_for_s,
+            &vole_share_for_r,
+        );
+        let mut sessions = Party::<F, S, VS, VR>::open_sessions(
             channels,
             rng,
             vole_share_for_s,
             vole_share_for_r,
-            set_size,
+            &hello,
+            options.execution,
         )
-        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        .located()?;
+
+        Self::precomp_with_sessions(me, &mut sessions, set_size, &options)
+    }
 
+    /// Create the OPPRF instances drawing VOLE correlations from `sessions` opened with every peer.
+    fn precomp_with_sessions(
+        me: PartyId,
+        sessions: &mut [PeerSessions<F>],
+        set_size: usize,
+        options: &PsiOptions,
+    ) -> Result<Self, Error> {
+        let id = me;
+
+        let party_for_zs =
+            Party::precomp_with_sessions(me, sessions, set_size, options).located()?;
+
+        let Some((_, s_session, _)) = sessions.iter_mut().find(|(them, _, _)| *them == 0) else {
+            return Err(Error::Config("no channel to the receiver.".to_string()));
+        };
         let opprf_sender_for_rc =
-            SepOpprfSenderWithVole::precomp(&mut channels[0].1, rng, set_size, vole_share_for_s)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            SepOpprfSenderWithVole::precomp_with_session(s_session, set_size, options.solver)
+                .located()?;
 
         Ok(Self {
             id,
@@ -105,7 +206,7 @@ where
 
     /// Send protocol which consists of conditional secret sharing and conditional reconstruction sending.
     /// It runned in the online phase.
-    pub fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    pub fn send<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         self,
         inputs: &[F],
         channels: &mut [(PartyId, C)],
@@ -118,6 +219,7 @@ where
             party_for_zs,
             opprf_sender_for_rc,
         } = self;
+        let _threads = party_for_zs.threads.enter();
 
         // conditional zero sharing
         let s_hat_sum = party_for_zs.conditional_secret_sharing(inputs, channels, rng)?;
@@ -128,10 +230,11 @@ where
             .cloned()
             .zip(s_hat_sum.into_iter())
             .collect::<Vec<_>>();
+        // channels are sorted by party ID in conditional zero sharing
         let channel = &mut channels[0].1;
         let _fk = opprf_sender_for_rc
             .send(channel, &points, inputs.len(), rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            .located()?;
 
         Ok(())
     }
@@ -139,13 +242,7 @@ where
 
 /// A kind of party in the protocol. They play sender and receiver in Conditional Zero Sharing, and play receiver in Conditional Reconstruction.
 ///
-/// `*_mt` means multi-threads optimization.
-///
-/// Not optimized version doesn't mean single-threaded version. The difference between the optimized version and the not one is that in where parties exchange messages.
-///
-/// In the optimized version, each party has a separate thread to communicate with each of the other parties.
-///
-/// On the other hand, in the not optimized version, each party communicates in the same thread with all the other parties.
+/// The sub-protocols with the other parties are run according to [PsiOptions::execution] as [Sender].
 pub struct Receiver<F, S, VS, VR>
 where
     F: FF,
@@ -160,10 +257,10 @@ where
 
 impl<F, S, VS, VR> Receiver<F, S, VS, VR>
 where
-    F: FF,
-    S: Solver<F>,
-    VS: VoleShareForSender<F>,
-    VR: VoleShareForReceiver<F>,
+    F: FF + FieldId,
+    S: Solver<F> + Send,
+    VS: VoleShareForSender<F> + Send,
+    VR: VoleShareForReceiver<F> + Send,
     Standard: Distribution<F>,
 {
     /// Get the party ID. Receiver is always 0.
@@ -172,29 +269,75 @@ where
     }
 
     /// Precomputation for the receiver. It runned in the offline phase.
-    pub fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    pub fn precomp<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
         set_size: usize,
     ) -> Result<Self, Error> {
-        let party_for_zs = Party::precomp(
-            0,
+        Self::precomp_with_options(
             channels,
             rng,
             vole_share_for_s,
             vole_share_for_r,
             set_size,
+            PsiOptions::default(),
         )
-        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+    }
+
+    /// [precomp](Receiver::precomp) with solver parameters and execution strategy given by `options`.
+    ///
+    /// All parties must use the same solver options. `channels` are sorted by party ID.
+    pub fn precomp_with_options<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        set_size: usize,
+        options: PsiOptions,
+    ) -> Result<Self, Error> {
+        let _threads = options.threads.enter();
+
+        let hello = Hello::new::<F, S, VS, VR>(
+            0,
+            channels.len() + 1,
+            set_size,
+            options.solver,
+            &vole_share_for_s,
+            &vole_share_for_r,
+        );
+        let mut sessions = Party::<F, S, VS, VR>::open_sessions(
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            &hello,
+            options.execution,
+        )
+        .located()?;
+
+        Self::precomp_with_sessions(&mut sessions, set_size, &options)
+    }
+
+    /// Create the OPPRF instances drawing VOLE correlations from `sessions` opened with every peer.
+    fn precomp_with_sessions(
+        sessions: &mut [PeerSessions<F>],
+        set_size: usize,
+        options: &PsiOptions,
+    ) -> Result<Self, Error> {
+        let party_for_zs =
+            Party::precomp_with_sessions(0, sessions, set_size, options).located()?;
 
-        let opprf_receivers_for_rc = channels
+        let opprf_receivers_for_rc = sessions
             .iter_mut()
-            .map(|(them, channel)| {
-                let rcvr =
-                    SepOpprfReceiverWithVole::precomp(channel, rng, set_size, vole_share_for_r)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            .map(|(them, _, r_session)| {
+                let rcvr = SepOpprfReceiverWithVole::precomp_with_session(
+                    r_session,
+                    set_size,
+                    options.solver,
+                )
+                .located()?;
                 Ok((*them, rcvr))
             })
             .collect::<Result<Vec<_>, Error>>()?;
@@ -207,7 +350,7 @@ where
 
     /// Receive protocol which consists of conditional secret sharing and conditional reconstruction receiving.
     /// It runned in the online phase.
-    pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    pub fn receive<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         self,
         inputs: &[F],
         channels: &mut [(PartyId, C)],
@@ -217,27 +360,38 @@ where
             party_for_zs,
             opprf_receivers_for_rc,
         } = self;
+        let execution = party_for_zs.execution;
+        let _threads = party_for_zs.threads.enter();
 
         // conditional zero sharing
-        let mut s_hat_sum = party_for_zs.conditional_secret_sharing(inputs, channels, rng)?;
+        let s_hat_sum = party_for_zs.conditional_secret_sharing(inputs, channels, rng)?;
 
         // conditional reconstruction
-        for ((them, channel), (ri, receiver)) in
-            channels.iter_mut().zip(opprf_receivers_for_rc.into_iter())
-        {
-            assert!(ri == *them);
-
-            let shares = receiver
-                .receive(channel, inputs, rng)
-                .with_context(|| format!("@{}:{}", file!(), line!()))?;
-            for (i, (_, y)) in shares.into_iter().enumerate() {
-                s_hat_sum[i] += y;
-            }
-        }
+        let items = opprf_receivers_for_rc
+            .into_iter()
+            .map(|receiver| (receiver, AesRng::from_seed(rng.gen())))
+            .collect::<Vec<_>>();
+        let s_hat_sum = Mutex::new(s_hat_sum);
+        execution.for_each_peer(
+            channels,
+            items,
+            |_, (them, channel), ((ri, receiver), mut rng)| {
+                assert!(ri == *them);
+
+                let shares = receiver
+                    .receive(channel, inputs, &mut rng)
+                    .located()
+                    .map_err(|e| e.with_peer(ri))?;
+                par_zip_mut(&mut s_hat_sum.lock().unwrap(), &shares, |s, &(_, y)| {
+                    *s += y
+                });
+                Ok::<_, Error>(())
+            },
+        )?;
 
         let intersection = inputs
             .iter()
-            .zip(s_hat_sum.into_iter())
+            .zip(s_hat_sum.into_inner().unwrap())
             .filter_map(|(&x, s)| if s.is_zero() { Some(x) } else { None })
             .collect::<Vec<_>>();
 
@@ -248,60 +402,113 @@ where
 impl<F, S, VS, VR> Party<F, S, VS, VR>
 where
     F: FF,
-    S: Solver<F>,
-    VS: VoleShareForSender<F>,
-    VR: VoleShareForReceiver<F>,
+    S: Solver<F> + Send,
+    VS: VoleShareForSender<F> + Send,
+    VR: VoleShareForReceiver<F> + Send,
     Standard: Distribution<F>,
 {
-    pub fn precomp<C: AbstractChannel, RNG: CryptoRng + Rng>(
-        me: PartyId,
+    /// Check that every peer runs the protocol described by `hello` by [handshake]
+    /// and open VOLE sessions of both directions with every peer, large enough for all bins of `hello`.
+    ///
+    /// `channels` are sorted by party ID, and so are the sessions.
+    fn open_sessions<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
+        hello: &Hello,
+        execution: Execution,
+    ) -> Result<Vec<PeerSessions<F>>, Error> {
+        channels.sort_by_key(|(them, _)| *them);
+
+        handshake(hello, channels).located()?;
+
+        let me = hello.party;
+        let items = channels
+            .iter()
+            .map(|_| {
+                (
+                    vole_share_for_s.clone(),
+                    vole_share_for_r.clone(),
+                    AesRng::from_seed(rng.gen()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        execution.for_each_peer(
+            channels,
+            items,
+            |_, (them, channel), (mut vole_share_for_s, mut vole_share_for_r, mut rng)| {
+                let them = *them;
+                let (s_capacity, r_capacity) = session_capacities(hello, them);
+
+                // the party with the lowest PID gets to initialize their VOLE sender session first
+                (|| -> Result<_, Error> {
+                    if me < them {
+                        let s_session = vole_share_for_s
+                            .open_session(channel, &mut rng, s_capacity)
+                            .located()?;
+                        let r_session = vole_share_for_r
+                            .open_session(channel, &mut rng, r_capacity)
+                            .located()?;
+                        Ok((them, s_session, r_session))
+                    } else {
+                        let r_session = vole_share_for_r
+                            .open_session(channel, &mut rng, r_capacity)
+                            .located()?;
+                        let s_session = vole_share_for_s
+                            .open_session(channel, &mut rng, s_capacity)
+                            .located()?;
+                        Ok((them, s_session, r_session))
+                    }
+                })()
+                .map_err(|e| e.with_peer(them))
+            },
+        )
+    }
+
+    /// Create OPPRF instances for conditional zero sharing drawing VOLE correlations from `sessions`.
+    ///
+    /// Sessions still have correlations for conditional reconstruction afterwards.
+    fn precomp_with_sessions(
+        me: PartyId,
+        sessions: &mut [PeerSessions<F>],
         set_size: usize,
+        options: &PsiOptions,
     ) -> Result<Self, Error> {
-        let mut opprf_senders = Vec::with_capacity(channels.len());
-        let mut opprf_receivers = Vec::with_capacity(channels.len());
-
-        for (them, channel) in channels.iter_mut() {
-            // the party with the lowest PID gets to initialize their OPPRF sender first
-            if me < *them {
-                let sndr =
-                    SepOpprfSenderWithVole::precomp(channel, rng, set_size, vole_share_for_s)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_senders.push((*them, sndr));
-
-                let rcvr =
-                    SepOpprfReceiverWithVole::precomp(channel, rng, set_size, vole_share_for_r)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_receivers.push((*them, rcvr));
-            } else {
-                let rcvr =
-                    SepOpprfReceiverWithVole::precomp(channel, rng, set_size, vole_share_for_r)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_receivers.push((*them, rcvr));
-
-                let sndr =
-                    SepOpprfSenderWithVole::precomp(channel, rng, set_size, vole_share_for_s)
-                        .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                opprf_senders.push((*them, sndr));
-            }
+        let mut opprf_senders = Vec::with_capacity(sessions.len());
+        let mut opprf_receivers = Vec::with_capacity(sessions.len());
+
+        for (them, s_session, r_session) in sessions.iter_mut() {
+            let sndr =
+                SepOpprfSenderWithVole::precomp_with_session(s_session, set_size, options.solver)
+                    .located()?;
+            opprf_senders.push((*them, sndr));
+
+            let rcvr =
+                SepOpprfReceiverWithVole::precomp_with_session(r_session, set_size, options.solver)
+                    .located()?;
+            opprf_receivers.push((*them, rcvr));
         }
 
         Ok(Self {
             id: me,
+            execution: options.execution,
+            threads: options.threads.clone(),
             opprf_senders,
             opprf_receivers,
         })
     }
 
-    fn conditional_secret_sharing<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    /// `channels` are sorted by party ID.
+    fn conditional_secret_sharing<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
         self,
         inputs: &[F],
         channels: &mut [(PartyId, C)],
         rng: &mut RNG,
     ) -> Result<Vec<F>, Error> {
+        channels.sort_by_key(|(them, _)| *them);
+
         let nparties = channels.len() + 1;
         let ninputs = inputs.len();
 
@@ -318,49 +525,58 @@ where
             .collect::<Vec<Vec<F>>>();
 
         let Self {
-            id: _,
+            id,
+            execution,
+            threads: _,
             opprf_senders,
             opprf_receivers,
         } = self;
 
-        for (((other_id, channel), (si, sender)), (ri, receiver)) in channels
-            .iter_mut()
-            .zip(opprf_senders.into_iter())
-            .zip(opprf_receivers.into_iter())
-        {
-            let other_id = *other_id;
-            assert!(other_id == si);
-            assert!(other_id == ri);
-
-            let points = inputs
-                .iter()
-                .enumerate()
-                .map(|(k, &x)| (x, s[k][other_id]))
-                .collect::<Vec<_>>();
-
-            let s_hats;
-            if self.id < other_id {
-                let _fk = sender
-                    .send(channel, &points, inputs.len(), rng)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                s_hats = receiver
-                    .receive(channel, inputs, rng)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-            } else {
-                s_hats = receiver
-                    .receive(channel, inputs, rng)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                let _fk = sender
-                    .send(channel, &points, inputs.len(), rng)
-                    .with_context(|| format!("@{}:{}", file!(), line!()))?;
-            };
-
-            for (k, &(_, s_hats)) in s_hats.iter().enumerate() {
-                s_hat_sum[k] += s_hats;
-            }
-        }
+        let items = opprf_senders
+            .into_iter()
+            .zip(opprf_receivers)
+            .map(|(sender, receiver)| (sender, receiver, AesRng::from_seed(rng.gen())))
+            .collect::<Vec<_>>();
+        let s_hat_sum = Mutex::new(s_hat_sum);
 
-        Ok(s_hat_sum)
+        execution.for_each_peer(
+            channels,
+            items,
+            |_, (other_id, channel), ((si, sender), (ri, receiver), mut rng)| {
+                let other_id = *other_id;
+                assert!(other_id == si);
+                assert!(other_id == ri);
+
+                let points = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &x)| (x, s[k][other_id]))
+                    .collect::<Vec<_>>();
+
+                let s_hats = (|| -> Result<Vec<(F, F)>, Error> {
+                    if id < other_id {
+                        let _fk = sender
+                            .send(channel, &points, inputs.len(), &mut rng)
+                            .located()?;
+                        receiver.receive(channel, inputs, &mut rng).located()
+                    } else {
+                        let s_hats = receiver.receive(channel, inputs, &mut rng).located()?;
+                        let _fk = sender
+                            .send(channel, &points, inputs.len(), &mut rng)
+                            .located()?;
+                        Ok(s_hats)
+                    }
+                })()
+                .map_err(|e| e.with_peer(other_id))?;
+
+                par_zip_mut(&mut s_hat_sum.lock().unwrap(), &s_hats, |s, &(_, s_hat)| {
+                    *s += s_hat
+                });
+                Ok::<_, Error>(())
+            },
+        )?;
+
+        Ok(s_hat_sum.into_inner().unwrap())
     }
 }
 
@@ -395,6 +611,8 @@ where
     fn clone(&self) -> Self {
         Self {
             id: self.id,
+            execution: self.execution,
+            threads: self.threads.clone(),
             opprf_senders: self.opprf_senders.clone(),
             opprf_receivers: self.opprf_receivers.clone(),
         }
@@ -446,18 +664,28 @@ where
 mod tests {
     use super::*;
     use crate::channel_utils::sync_channel::create_unix_channels;
+    use crate::channel_utils::sync_channel_by_cb::create_crossbeam_channels;
+    use crate::channel_utils::tcp_channel::{
+        create_tcp_channels_for_receiver, create_tcp_channels_for_sender,
+    };
     use crate::set_utils::create_sets_without_check;
-    use crate::solver::{PaxosSolver, Solver, SolverParams, VandelmondeSolver};
+    use crate::solver::{
+        BandSolver, PaxosSolver, Solver, SolverParams, TwoCoreSolver, VandelmondeSolver,
+    };
+    use crate::testing::SeedTree;
     use crate::vole::{
         LPNVoleReceiver, LPNVoleSender, OtVoleReceiver, OtVoleSender, VoleShareForReceiver,
-        VoleShareForSender, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_MEDIUM, LPN_SETUP_SMALL,
+        VoleShareForSender,
     };
     use num_traits::Zero;
     use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
     use rand::Rng;
     use scuttlebutt::field::F128b;
-    use scuttlebutt::AesRng;
+    use scuttlebutt::{AesRng, SyncChannel};
     use std::collections::HashSet;
+    use std::io::{BufReader, BufWriter};
+    use std::net::TcpStream;
+    use std::time::Instant;
 
     #[test]
     fn test_secret_sharing_of_zero() {
@@ -477,96 +705,206 @@ mod tests {
         set_size: usize,
     ) -> (LPNVoleSender<F128b>, LPNVoleReceiver<F128b>) {
         let m_size = S::calc_params(set_size).code_length();
-        let (setup_param, extend_param) = if m_size < (1 << 17) {
-            println!("Small parameters are used.");
-            (LPN_SETUP_SMALL, LPN_EXTEND_SMALL)
-        } else {
-            println!("Medium parameters are used.");
-            (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
-        };
         (
-            LPNVoleSender::new(setup_param, extend_param),
-            LPNVoleReceiver::new(setup_param, extend_param),
+            LPNVoleSender::for_size(m_size),
+            LPNVoleReceiver::for_size(m_size),
         )
     }
 
-    fn test_protocol_base<S, VS, VR>(
+    const EXECUTIONS: [Execution; 3] = [
+        Execution::Sequential,
+        Execution::ThreadPerPeer,
+        Execution::Pool(2),
+    ];
+
+    type Channels<C> = (Vec<(PartyId, C)>, Vec<Vec<(PartyId, C)>>);
+    type TcpChannel = SyncChannel<BufReader<TcpStream>, BufWriter<TcpStream>>;
+
+    fn create_tcp_channels(nparties: usize, base_port: usize) -> Channels<TcpChannel> {
+        let handles = (1..nparties)
+            .map(|me| {
+                std::thread::spawn(move || create_tcp_channels_for_sender(nparties, base_port, me))
+            })
+            .collect::<Vec<_>>();
+        let receiver_channels = create_tcp_channels_for_receiver(nparties, base_port).unwrap();
+        let channels = handles
+            .into_iter()
+            .map(|h| h.join().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        (receiver_channels, channels)
+    }
+
+    /// Larger sets are tested with one mix of the strategies only, because every run takes long.
+    const ALL_EXECUTIONS_MAX_SET_SIZE: usize = 1 << 10;
+
+    /// Run the protocol once for each [Execution] over channels created by `create_channels(round)`.
+    ///
+    /// Party `i` uses `EXECUTIONS[(i + round) % 3]` in the `round`-th run,
+    /// so that every party runs every strategy and parties with different strategies run together.
+    /// Sets larger than [ALL_EXECUTIONS_MAX_SET_SIZE] are run only in the 0th round.
+    fn test_protocol_with_channels<S, VS, VR, C>(
         nparties: usize,
         set_size: usize,
         common_size: usize,
         vole_share_for_s: VS,
         vole_share_for_r: VR,
+        create_channels: impl Fn(usize) -> Channels<C>,
     ) where
-        S: Solver<F128b>,
+        S: Solver<F128b> + Send + 'static,
         VS: VoleShareForSender<F128b> + 'static + Send,
         VR: VoleShareForReceiver<F128b> + 'static + Send,
+        C: AbstractChannel + Send + 'static,
     {
-        let mut rng = AesRng::new();
+        // set PMPSI_TEST_SEED to the printed seed to reproduce a failure
+        let seeds = SeedTree::from_env_or_random("PMPSI_TEST_SEED");
 
-        let (intersection, mut sets): (Vec<F128b>, Vec<Vec<F128b>>) =
-            create_sets_without_check(nparties, set_size, common_size, &mut rng).unwrap();
+        let (intersection, sets): (Vec<F128b>, Vec<Vec<F128b>>) =
+            create_sets_without_check(nparties, set_size, common_size, &mut seeds.sets().rng())
+                .unwrap();
+        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
 
         println!("intersection prepared.");
 
-        // create channels
-        let (mut receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+        // all parties share one pool as the parties of the CLI do
+        let threads = Threads::new(2).unwrap();
 
-        for (i, mut channels) in channels.into_iter().enumerate() {
-            // create and fork senders
-            let pid = i + 1;
+        let rounds = if set_size <= ALL_EXECUTIONS_MAX_SET_SIZE {
+            EXECUTIONS.len()
+        } else {
+            1
+        };
+        for round in 0..rounds {
+            let execution_of = |pid: PartyId| EXECUTIONS[(pid + round) % EXECUTIONS.len()];
+            let mut sets = sets.clone();
+            let mut rng = seeds.party(0).rng();
+
+            // create channels
+            let (mut receiver_channels, channels) = create_channels(round);
+
+            for (i, mut channels) in channels.into_iter().enumerate() {
+                // create and fork senders
+                let pid = i + 1;
+                let set = sets.pop().unwrap();
+                let vole_share_for_s = vole_share_for_s.clone();
+                let vole_share_for_r = vole_share_for_r.clone();
+                let options = PsiOptions {
+                    execution: execution_of(pid),
+                    threads: threads.clone(),
+                    ..Default::default()
+                };
+                std::thread::spawn(move || {
+                    let mut rng = seeds.party(pid).rng();
+
+                    // offline phase
+                    let sender = Sender::<F128b, S, _, _>::precomp_with_options(
+                        pid,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                        options,
+                    )
+                    .unwrap();
+
+                    println!("sender {} prepared.", pid);
+
+                    // online phase
+                    sender.send(&set, &mut channels, &mut rng).unwrap();
+
+                    println!("sender {} finished.", pid);
+                });
+            }
+
+            // create and run receiver
+            // offline phase
+            let receiver = Receiver::<F128b, S, _, _>::precomp_with_options(
+                &mut receiver_channels,
+                &mut rng,
+                vole_share_for_s.clone(),
+                vole_share_for_r.clone(),
+                set_size,
+                PsiOptions {
+                    execution: execution_of(0),
+                    threads: threads.clone(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            println!("receiver prepared. online phase started.");
+
+            // online phase
             let set = sets.pop().unwrap();
-            let vole_share_for_s = vole_share_for_s.clone();
-            let vole_share_for_r = vole_share_for_r.clone();
-            std::thread::spawn(move || {
-                let mut rng = AesRng::new();
-
-                // offline phase
-                let sender = Sender::<F128b, S, _, _>::precomp(
-                    pid,
-                    &mut channels,
-                    &mut rng,
-                    vole_share_for_s,
-                    vole_share_for_r,
-                    set_size,
-                )
+
+            let start = Instant::now();
+            let res = receiver
+                .receive(&set, &mut receiver_channels, &mut rng)
                 .unwrap();
 
-                println!("sender {} prepared.", pid);
+            println!(
+                "receiver finished with {:?}. online time: {:?}",
+                execution_of(0),
+                start.elapsed()
+            );
 
-                // online phase
-                sender.send(&set, &mut channels, &mut rng).unwrap();
+            let res: HashSet<F128b> = HashSet::from_iter(res);
 
-                println!("sender {} finished.", pid);
-            });
+            assert_eq!(res, intersection);
         }
+    }
 
-        // create and run receiver
-        // offline phase
-        // let vole_share_for_s = vole_share_for_s.clone();
-        // let vole_share_for_r = vole_share_for_r.clone();
-        let receiver = Receiver::<F128b, S, _, _>::precomp(
-            &mut receiver_channels,
-            &mut rng,
+    fn test_protocol_base<S, VS, VR>(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+    ) where
+        S: Solver<F128b> + Send + 'static,
+        VS: VoleShareForSender<F128b> + 'static + Send,
+        VR: VoleShareForReceiver<F128b> + 'static + Send,
+    {
+        test_protocol_with_channels::<S, _, _, _>(
+            nparties,
+            set_size,
+            common_size,
             vole_share_for_s,
             vole_share_for_r,
-            set_size,
-        )
-        .unwrap();
-
-        println!("receiver prepared.");
-
-        // online phase
-        let set = sets.pop().unwrap();
-        let res = receiver
-            .receive(&set, &mut receiver_channels, &mut rng)
-            .unwrap();
-
-        println!("receiver finished.");
+            |_| create_unix_channels(nparties).unwrap(),
+        );
+    }
 
-        let res: HashSet<F128b> = HashSet::from_iter(res);
-        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+    // Each test owns the ports from `base_port` up to `base_port + 100`, apart from the other tests of the crate.
+    fn test_protocol_paxos_tcp_base(
+        nparties: usize,
+        set_size: usize,
+        common_size: usize,
+        base_port: usize,
+    ) {
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+        test_protocol_with_channels::<PaxosSolver<F128b>, _, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+            |round| create_tcp_channels(nparties, base_port + 10 * round),
+        );
+    }
 
-        assert_eq!(res, intersection);
+    fn test_protocol_paxos_crossbeam_base(nparties: usize, set_size: usize, common_size: usize) {
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+        test_protocol_with_channels::<PaxosSolver<F128b>, _, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+            |_| create_crossbeam_channels(nparties),
+        );
     }
 
     #[test]
@@ -601,6 +939,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_protocol_two_core_middle() {
+        let nparties = 5;
+        let set_size = 1 << 10;
+        let common_size = 1 << 5;
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<TwoCoreSolver<F128b>>(set_size);
+        test_protocol_base::<TwoCoreSolver<F128b>, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+        );
+    }
+
+    #[test]
+    fn test_protocol_band_middle() {
+        let nparties = 5;
+        let set_size = 1 << 10;
+        let common_size = 1 << 5;
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<BandSolver<F128b>>(set_size);
+        test_protocol_base::<BandSolver<F128b>, _, _>(
+            nparties,
+            set_size,
+            common_size,
+            vole_share_for_s,
+            vole_share_for_r,
+        );
+    }
+
     #[test]
     fn test_protocol_paxos_middle() {
         let nparties = 5;
@@ -633,13 +1003,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_protocol_paxos_tcp_small() {
+        test_protocol_paxos_tcp_base(3, 10, 5, 30000);
+    }
+
+    #[test]
+    fn test_protocol_paxos_tcp_middle() {
+        test_protocol_paxos_tcp_base(5, 1 << 10, 1 << 5, 30100);
+    }
+
+    #[test]
+    fn test_protocol_paxos_tcp_large() {
+        test_protocol_paxos_tcp_base(5, 1 << 20, 1 << 5, 30200);
+    }
+
+    #[test]
+    fn test_protocol_paxos_crossbeam_small() {
+        test_protocol_paxos_crossbeam_base(3, 10, 5);
+    }
+
+    #[test]
+    fn test_protocol_paxos_crossbeam_middle() {
+        test_protocol_paxos_crossbeam_base(5, 1 << 10, 1 << 5);
+    }
+
+    #[test]
+    fn test_protocol_paxos_crossbeam_large() {
+        test_protocol_paxos_crossbeam_base(5, 1 << 20, 1 << 5);
+    }
+
     #[test]
     fn test_protocol_paxos_small_with_ot() {
         let nparties = 3;
         let set_size = 10;
         let common_size = 5;
-        let vole_share_for_s = OtVoleSender::<F128b, 128, OtSender>::new();
-        let vole_share_for_r = OtVoleReceiver::<F128b, 128, OtReceiver>::new();
+        let vole_share_for_s = OtVoleSender::<F128b, OtSender>::new();
+        let vole_share_for_r = OtVoleReceiver::<F128b, OtReceiver>::new();
         test_protocol_base::<PaxosSolver<F128b>, _, _>(
             nparties,
             set_size,
@@ -654,8 +1054,8 @@ mod tests {
         let nparties = 5;
         let set_size = 1 << 10;
         let common_size = 1 << 5;
-        let vole_share_for_s = OtVoleSender::<F128b, 128, OtSender>::new();
-        let vole_share_for_r = OtVoleReceiver::<F128b, 128, OtReceiver>::new();
+        let vole_share_for_s = OtVoleSender::<F128b, OtSender>::new();
+        let vole_share_for_r = OtVoleReceiver::<F128b, OtReceiver>::new();
         test_protocol_base::<PaxosSolver<F128b>, _, _>(
             nparties,
             set_size,
@@ -670,8 +1070,8 @@ mod tests {
         let nparties = 5;
         let set_size = 1 << 20;
         let common_size = 1 << 5;
-        let vole_share_for_s = OtVoleSender::<F128b, 128, OtSender>::new();
-        let vole_share_for_r = OtVoleReceiver::<F128b, 128, OtReceiver>::new();
+        let vole_share_for_s = OtVoleSender::<F128b, OtSender>::new();
+        let vole_share_for_r = OtVoleReceiver::<F128b, OtReceiver>::new();
         test_protocol_base::<PaxosSolver<F128b>, _, _>(
             nparties,
             set_size,
@@ -680,4 +1080,65 @@ mod tests {
             vole_share_for_r,
         );
     }
+
+    #[test]
+    fn test_precomp_with_mismatched_set_size() {
+        let set_size = 10;
+        let (vole_share_for_s, vole_share_for_r) =
+            create_lpn_vole_sr::<PaxosSolver<F128b>>(set_size);
+        let seeds = SeedTree::random();
+
+        let (mut receiver_channels, channels) = create_unix_channels(3).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let pid = i + 1;
+                // party 2 has a larger set than the others
+                let set_size = if pid == 2 { 2 * set_size } else { set_size };
+                std::thread::spawn(move || {
+                    Sender::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+                        pid,
+                        &mut channels,
+                        &mut seeds.party(pid).rng(),
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                    )
+                    .map(|_| ())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let err = Receiver::<F128b, PaxosSolver<F128b>, _, _>::precomp(
+            &mut receiver_channels,
+            &mut seeds.party(0).rng(),
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .err()
+        .unwrap();
+        match err.root() {
+            Error::Incompatible {
+                peer,
+                what,
+                ours,
+                theirs,
+            } => {
+                assert_eq!(*peer, 2);
+                assert_eq!(*what, "set size");
+                assert_eq!(ours, "10");
+                assert_eq!(theirs, "20");
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+
+        // every party fails in the handshake without waiting for VOLE
+        for handle in handles {
+            let err = handle.join().unwrap().unwrap_err();
+            assert!(matches!(err.root(), Error::Incompatible { .. }));
+        }
+    }
 }
@@ -0,0 +1,577 @@
+//! Partitioned PSI for large sets. See [PartitionedSender] and [PartitionedReceiver].
+//!
+//! Every party hashes its set into $`B`$ bins with a key chosen by the receiver,
+//! pads each bin with random dummies up to a common [capacity](Bins::capacity)
+//! and runs the protocol of [Sender] and [Receiver] independently per bin.
+//! An element falls into the same bin at every party, so the intersection is the union of the intersections of the bins.
+//!
+//! Solvers only see one bin at a time, and bins run in parallel, each over its own [sub-channel](crate::channel_utils::mux) of every peer channel.
+//! Bins are run by as many threads as the pool [installed](crate::parallel_utils::Threads::install) by the caller has.
+//! VOLE sessions are opened once for all bins in the offline phase.
+//!
+//! Dummies are random field elements, so the field must be large enough that they do not collide.
+//!
+//! Parties take bins in increasing order, so the smallest unfinished bin always makes progress
+//! even if parties have different numbers of threads.
+//! When a bin fails, bins of peers waiting for it are stuck until the channel fails,
+//! so channels should have timeouts or be [abortable](crate::channel_utils::abortable).
+
+use super::{Party, PartyId, Receiver, Sender};
+use crate::channel_utils::mux::{mux, MuxChannel};
+use crate::error::{Context, Error};
+use crate::hasher::{AesHasher, KeyedHasher};
+use crate::parallel_utils::{Execution, Threads};
+use crate::preprocessed::handshake::{FieldId, Hello};
+use crate::solver::{Solver, SolverOptions};
+use crate::vole::{VoleShareForReceiver, VoleShareForSender};
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{AesRng, Block};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Partitioning of sets into bins. All parties must use the same one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bins {
+    /// Number of bins $`B`$.
+    pub nbins: usize,
+    /// Maximum number of elements in a bin, which is the set size of the protocol run per bin.
+    pub capacity: usize,
+}
+
+impl Bins {
+    /// `nbins` bins for sets of up to `set_size` elements, which overflow with probability at most $`2^{-\lambda}`$.
+    pub fn new(set_size: usize, nbins: usize, lambda: usize) -> Self {
+        Self {
+            nbins,
+            capacity: bin_capacity(set_size, nbins, lambda),
+        }
+    }
+}
+
+/// Smallest capacity $`c`$ such that hashing `set_size` elements into `nbins` bins overflows one with probability at most $`2^{-\lambda}`$.
+///
+/// The load of a bin exceeds $`c`$ with probability at most $`e^{-\mu} (e \mu / (c + 1))^{c + 1}`$ where $`\mu = n / B`$ by the Chernoff bound,
+/// and the union bound over the bins adds $`\log_2 B`$.
+pub fn bin_capacity(set_size: usize, nbins: usize, lambda: usize) -> usize {
+    if nbins <= 1 || set_size == 0 {
+        return set_size;
+    }
+
+    let mu = set_size as f64 / nbins as f64;
+    let log2_nbins = (nbins as f64).log2();
+
+    (mu.floor() as usize..set_size)
+        .find(|&c| {
+            let t = (c + 1) as f64;
+            let log2_p = (t * (std::f64::consts::E * mu / t).ln() - mu) / std::f64::consts::LN_2;
+            t > mu && log2_nbins + log2_p <= -(lambda as f64)
+        })
+        .unwrap_or(set_size)
+}
+
+/// Hash `inputs` into bins by `key` and pad each bin with random dummies.
+fn partition<F: FF, RNG: CryptoRng + Rng>(
+    inputs: &[F],
+    key: Block,
+    bins: Bins,
+    rng: &mut RNG,
+) -> Result<Vec<Vec<F>>, Error>
+where
+    Standard: Distribution<F>,
+{
+    let hasher = AesHasher::new(key);
+    let mut parts = (0..bins.nbins)
+        .map(|_| Vec::with_capacity(bins.capacity))
+        .collect::<Vec<_>>();
+
+    for &x in inputs.iter() {
+        let ([b], _) = hasher.indices_and_bits::<F, 1>(x, bins.nbins, 0);
+        if parts[b].len() == bins.capacity {
+            return Err(Error::Config(format!(
+                "more than {} elements fall into bin {}; the set is larger than the set size of the bins.",
+                bins.capacity,
+                b
+            )));
+        }
+        parts[b].push(x);
+    }
+
+    for part in parts.iter_mut() {
+        part.resize_with(bins.capacity, || rng.gen());
+    }
+
+    Ok(parts)
+}
+
+/// Run `f` for each bin over sub-channels of `channels` on worker threads, and return the results in the order of the bins.
+///
+/// There are as many workers as the threads of the [current](Threads::current) pool, which the workers use for per-element work.
+///
+/// `items[b]` is what `f` needs for bin `b`. Workers stop taking bins after a failure.
+fn run_bins<C, I, T, Fun>(channels: &[(PartyId, C)], items: Vec<I>, f: Fun) -> Result<Vec<T>, Error>
+where
+    C: AbstractChannel + Send,
+    I: Send,
+    T: Send,
+    Fun: Fn(I, &mut [(PartyId, MuxChannel<C>)]) -> Result<T, Error> + Sync,
+{
+    let nbins = items.len();
+    let mut subs = channels
+        .iter()
+        .map(|(them, channel)| {
+            let them = *them;
+            mux(channel.clone(), nbins)
+                .into_iter()
+                .map(move |sub| (them, sub))
+        })
+        .collect::<Vec<_>>();
+    // bin_channels[b]: sub-channels of bin b with every peer
+    let bin_channels = (0..nbins)
+        .map(|_| {
+            subs.iter_mut()
+                .map(|s| s.next().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // bins are taken in increasing order
+    let queue = Mutex::new(items.into_iter().zip(bin_channels).enumerate());
+    let failed = AtomicBool::new(false);
+    let pool = Threads::current();
+    let (f, queue, failed, pool) = (&f, &queue, &failed, &pool);
+
+    let results = thread::scope(|s| {
+        let handles = (0..pool.num_threads().min(nbins))
+            .map(|_| {
+                s.spawn(move || {
+                    let _pool = pool.enter();
+                    let mut results = Vec::new();
+                    while !failed.load(Ordering::Relaxed) {
+                        let Some((b, (item, mut chs))) = queue.lock().unwrap().next() else {
+                            break;
+                        };
+
+                        let res = f(item, &mut chs).and_then(|t| {
+                            for (them, ch) in chs.iter_mut() {
+                                ch.flush().located().map_err(|e| e.with_peer(*them))?;
+                            }
+                            Ok(t)
+                        });
+                        match res {
+                            Ok(t) => results.push((b, t)),
+                            Err(e) => {
+                                failed.store(true, Ordering::Relaxed);
+                                return Err(e);
+                            }
+                        }
+                    }
+                    Ok(results)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // a panic in a worker is propagated as it is
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    let mut results = results.into_iter().flatten().collect::<Vec<_>>();
+    results.sort_by_key(|(b, _)| *b);
+
+    Ok(results.into_iter().map(|(_, t)| t).collect())
+}
+
+/// Sender of partitioned PSI. It runs a [Sender] per bin.
+pub struct PartitionedSender<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    id: PartyId,
+    key: Block,
+    bins: Bins,
+    senders: Vec<Sender<F, S, VS, VR>>,
+}
+
+impl<F, S, VS, VR> PartitionedSender<F, S, VS, VR>
+where
+    F: FF + FieldId,
+    S: Solver<F> + Send + 'static,
+    VS: VoleShareForSender<F> + Send + 'static,
+    VR: VoleShareForReceiver<F> + Send + 'static,
+    Standard: Distribution<F>,
+{
+    /// Get the party ID. Receiver is always 0.
+    pub fn get_id(&self) -> PartyId {
+        self.id
+    }
+
+    /// Precomputation for the sender. It runned in the offline phase.
+    pub fn precomp<C: AbstractChannel + Send, RNG: Rng + CryptoRng>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        bins: Bins,
+    ) -> Result<Self, Error> {
+        Self::precomp_with_options(
+            me,
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            bins,
+            SolverOptions::default(),
+        )
+    }
+
+    /// [precomp](PartitionedSender::precomp) with solver parameters derived from `options`.
+    ///
+    /// All parties must use the same `bins` and `options`.
+    pub fn precomp_with_options<C: AbstractChannel + Send, RNG: Rng + CryptoRng>(
+        me: PartyId,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        bins: Bins,
+        options: SolverOptions,
+    ) -> Result<Self, Error> {
+        if me == 0 {
+            return Err(Error::Config("sender index must not be 0.".to_string()));
+        }
+        if bins.nbins == 0 {
+            return Err(Error::Config("number of bins must not be 0.".to_string()));
+        }
+
+        let hello = Hello {
+            nbins: bins.nbins,
+            ..Hello::new::<F, S, VS, VR>(
+                me,
+                channels.len() + 1,
+                bins.capacity,
+                options,
+                &vole_share_for_s,
+                &vole_share_for_r,
+            )
+        };
+        let mut sessions = Party::<F, S, VS, VR>::open_sessions(
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            &hello,
+            Execution::Sequential,
+        )
+        .located()?;
+
+        let Some((_, channel)) = channels.iter_mut().find(|(them, _)| *them == 0) else {
+            return Err(Error::Config("no channel to the receiver.".to_string()));
+        };
+        let key = channel.read_block().located().map_err(|e| e.with_peer(0))?;
+
+        let senders = (0..bins.nbins)
+            .map(|_| {
+                Sender::precomp_with_sessions(me, &mut sessions, bins.capacity, &options.into())
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .located()?;
+
+        Ok(Self {
+            id: me,
+            key,
+            bins,
+            senders,
+        })
+    }
+
+    /// Send protocol of every bin. It runned in the online phase.
+    pub fn send<C, RNG>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        let Self {
+            id: _,
+            key,
+            bins,
+            senders,
+        } = self;
+
+        let parts = partition(inputs, key, bins, rng)?;
+        let items = senders
+            .into_iter()
+            .zip(parts)
+            .map(|(sender, part)| (sender, part, AesRng::from_seed(rng.gen())))
+            .collect::<Vec<_>>();
+
+        run_bins(channels, items, |(sender, part, mut rng), chs| {
+            sender.send(&part, chs, &mut rng).located()
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Receiver of partitioned PSI. It runs a [Receiver] per bin.
+pub struct PartitionedReceiver<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    key: Block,
+    bins: Bins,
+    receivers: Vec<Receiver<F, S, VS, VR>>,
+}
+
+impl<F, S, VS, VR> PartitionedReceiver<F, S, VS, VR>
+where
+    F: FF + FieldId,
+    S: Solver<F> + Send + 'static,
+    VS: VoleShareForSender<F> + Send + 'static,
+    VR: VoleShareForReceiver<F> + Send + 'static,
+    Standard: Distribution<F>,
+{
+    /// Get the party ID. Receiver is always 0.
+    pub fn get_id(&self) -> PartyId {
+        0
+    }
+
+    /// Precomputation for the receiver. It runned in the offline phase.
+    pub fn precomp<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        bins: Bins,
+    ) -> Result<Self, Error> {
+        Self::precomp_with_options(
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            bins,
+            SolverOptions::default(),
+        )
+    }
+
+    /// [precomp](PartitionedReceiver::precomp) with solver parameters derived from `options`.
+    ///
+    /// All parties must use the same `bins` and `options`.
+    pub fn precomp_with_options<C: AbstractChannel + Send, RNG: CryptoRng + Rng>(
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        vole_share_for_s: VS,
+        vole_share_for_r: VR,
+        bins: Bins,
+        options: SolverOptions,
+    ) -> Result<Self, Error> {
+        if bins.nbins == 0 {
+            return Err(Error::Config("number of bins must not be 0.".to_string()));
+        }
+
+        let hello = Hello {
+            nbins: bins.nbins,
+            ..Hello::new::<F, S, VS, VR>(
+                0,
+                channels.len() + 1,
+                bins.capacity,
+                options,
+                &vole_share_for_s,
+                &vole_share_for_r,
+            )
+        };
+        let mut sessions = Party::<F, S, VS, VR>::open_sessions(
+            channels,
+            rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            &hello,
+            Execution::Sequential,
+        )
+        .located()?;
+
+        // the key of the bins is chosen after the handshake so that every peer is known to be compatible
+        let key = rng.gen::<Block>();
+        for (them, channel) in channels.iter_mut() {
+            channel
+                .write_block(&key)
+                .and_then(|()| channel.flush())
+                .located()
+                .map_err(|e| e.with_peer(*them))?;
+        }
+
+        let receivers = (0..bins.nbins)
+            .map(|_| Receiver::precomp_with_sessions(&mut sessions, bins.capacity, &options.into()))
+            .collect::<Result<Vec<_>, Error>>()
+            .located()?;
+
+        Ok(Self {
+            key,
+            bins,
+            receivers,
+        })
+    }
+
+    /// Receive protocol of every bin. It runned in the online phase.
+    ///
+    /// The intersection is returned bin by bin.
+    pub fn receive<C, RNG>(
+        self,
+        inputs: &[F],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<F>, Error>
+    where
+        C: AbstractChannel + Send,
+        RNG: CryptoRng + Rng,
+    {
+        let Self {
+            key,
+            bins,
+            receivers,
+        } = self;
+
+        let parts = partition(inputs, key, bins, rng)?;
+        let items = receivers
+            .into_iter()
+            .zip(parts)
+            .map(|(receiver, part)| (receiver, part, AesRng::from_seed(rng.gen())))
+            .collect::<Vec<_>>();
+
+        let intersections = run_bins(channels, items, |(receiver, part, mut rng), chs| {
+            receiver.receive(&part, chs, &mut rng).located()
+        })?;
+
+        Ok(intersections.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel::create_unix_channels;
+    use crate::set_utils::create_sets_without_check;
+    use crate::solver::{PaxosSolver, SolverParams};
+    use crate::testing::SeedTree;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender};
+    use scuttlebutt::field::F128b;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_bin_capacity() {
+        assert_eq!(bin_capacity(1000, 1, 40), 1000);
+        assert_eq!(bin_capacity(0, 8, 40), 0);
+
+        for (n, nbins) in [(1 << 10, 8), (1 << 16, 64), (1 << 20, 1 << 10), (10, 100)] {
+            let c = bin_capacity(n, nbins, 40);
+            assert!(c * nbins >= n && c <= n);
+            // more security needs more room
+            assert!(bin_capacity(n, nbins, 80) >= c);
+        }
+
+        let bins = Bins::new(1 << 10, 8, 40);
+        let mut rng = SeedTree::random().sets().rng();
+        let set = (0..1 << 10).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let key = rng.gen::<Block>();
+        let parts = partition(&set, key, bins, &mut rng).unwrap();
+        assert_eq!(parts.len(), 8);
+        assert!(parts.iter().all(|part| part.len() == bins.capacity));
+
+        // the same element falls into the same bin
+        let hasher = AesHasher::new(key);
+        for x in set.iter() {
+            let ([b], _) = hasher.indices_and_bits::<F128b, 1>(*x, bins.nbins, 0);
+            assert!(parts[b].contains(x));
+        }
+
+        let small = Bins {
+            nbins: 8,
+            capacity: 10,
+        };
+        let err = partition(&set, key, small, &mut rng).unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+    }
+
+    #[test]
+    fn test_protocol_partitioned() {
+        type S = PaxosSolver<F128b>;
+        let nparties = 3;
+        let set_size = 1 << 10;
+        let common_size = 1 << 5;
+        let bins = Bins::new(set_size, 8, SolverOptions::default().lambda);
+
+        // set PMPSI_TEST_SEED to the printed seed to reproduce a failure
+        let seeds = SeedTree::from_env_or_random("PMPSI_TEST_SEED");
+        let m_size = S::calc_params(bins.capacity).code_length();
+        let vole_share_for_s = LPNVoleSender::<F128b>::for_size(m_size);
+        let vole_share_for_r = LPNVoleReceiver::<F128b>::for_size(m_size);
+
+        let (intersection, mut sets) =
+            create_sets_without_check(nparties, set_size, common_size, &mut seeds.sets().rng())
+                .unwrap();
+
+        let (mut receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let pid = i + 1;
+                let set: Vec<F128b> = sets.pop().unwrap();
+                std::thread::spawn(move || {
+                    let mut rng = seeds.party(pid).rng();
+                    let sender = PartitionedSender::<F128b, S, _, _>::precomp(
+                        pid,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        bins,
+                    )
+                    .unwrap();
+                    sender.send(&set, &mut channels, &mut rng).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = seeds.party(0).rng();
+        let receiver = PartitionedReceiver::<F128b, S, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            bins,
+        )
+        .unwrap();
+        let res = receiver
+            .receive(&sets.pop().unwrap(), &mut receiver_channels, &mut rng)
+            .unwrap();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let res: HashSet<F128b> = HashSet::from_iter(res);
+        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+        assert_eq!(res, intersection);
+    }
+}
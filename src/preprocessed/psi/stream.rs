@@ -0,0 +1,698 @@
+//! Streaming online phase whose memory does not grow with the number of parties.
+//!
+//! [Sender::send_stream] and [Receiver::receive_stream] read the set from a [SetSource] in batches every time they need it,
+//! and the receiver passes the intersection to a sink batch by batch.
+//!
+//! # Memory
+//!
+//! A solver needs all points of an OPPRF (or all queries of an OPRF) at once to encode,
+//! so every OPPRF with a peer collects the $`n`$ elements of the set in memory,
+//! and its outputs hold code vectors of length $`m = O(n)`$ until they are evaluated.
+//! Peers are handled one after another, and the outputs of each OPPRF are folded into a sum per element as soon as they exist,
+//! so the peak is $`O(n + m)`$ field elements whatever the number of parties $`N`$ is, instead of the $`2(N-1)`$ outputs held to the end.
+//! It is still linear in $`n`$: streaming does not make a set larger than memory fit.
+//!
+//! The sums are spooled to a file only the owner can read, in a private directory under [the temporary directory](std::env::temp_dir),
+//! between passes, which takes $`n`$ field elements on disk,
+//! and each fold is a pass over the set reading the old sums and writing the new ones.
+//! Shares of zero for a peer are drawn from a PRG seeded for the peer and regenerated when they are folded instead of being stored.
+//!
+//! Messages are the same as [Sender::send] and [Receiver::receive], so streaming parties run with non-streaming ones.
+//! Peers are handled one after another whatever [PsiOptions::execution](super::PsiOptions::execution) is,
+//! since running them at once would hold the points of several OPPRFs at a time.
+
+use super::{Party, PartyId, Receiver, Sender};
+use crate::error::{Context, Error};
+use crate::preprocessed::opprf::SepOpprfSender;
+use crate::solver::Solver;
+use crate::vole::{VoleShareForReceiver, VoleShareForSender};
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{AesRng, Block};
+use std::fs::{File, OpenOptions, Permissions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use typenum::marker_traits::Unsigned;
+
+/// Number of elements processed at once in streaming.
+pub const STREAM_BATCH_SIZE: usize = 1 << 16;
+
+/// Set which can be read repeatedly from the beginning in batches.
+///
+/// Implemented for slices and [SetFile]. Elements of a one-shot iterator can be spooled to a file by [SetFile::create].
+pub trait SetSource<F> {
+    /// Number of elements.
+    fn len(&self) -> usize;
+
+    /// Whether the set is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Call `f` with the elements from the first one in batches of at most `batch_size` elements.
+    fn for_each_batch(
+        &self,
+        batch_size: usize,
+        f: &mut dyn FnMut(&[F]) -> Result<(), Error>,
+    ) -> Result<(), Error>;
+}
+
+impl<F: FF> SetSource<F> for [F] {
+    fn len(&self) -> usize {
+        <[F]>::len(self)
+    }
+
+    fn for_each_batch(
+        &self,
+        batch_size: usize,
+        f: &mut dyn FnMut(&[F]) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.chunks(batch_size).try_for_each(f)
+    }
+}
+
+/// Set in a file of field elements in their byte representation without any header.
+pub struct SetFile<F> {
+    path: PathBuf,
+    len: usize,
+    _p: PhantomData<fn() -> F>,
+}
+
+impl<F: FF> SetFile<F> {
+    /// Open a set file.
+    ///
+    /// The file is read on every pass, so it must not be modified while in use.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let bytes_len = std::fs::metadata(&path)
+            .map_err(|e| Error::Config(format!("failed to open {}: {}", path.display(), e)))?
+            .len() as usize;
+
+        let elem_len = F::ByteReprLen::to_usize();
+        if bytes_len % elem_len != 0 {
+            return Err(Error::Config(format!(
+                "size of {} (={}) is not a multiple of the size of a field element (={})",
+                path.display(),
+                bytes_len,
+                elem_len
+            )));
+        }
+
+        Ok(Self {
+            path,
+            len: bytes_len / elem_len,
+            _p: PhantomData,
+        })
+    }
+
+    /// Write `elements` to a set file at `path` and open it.
+    pub fn create<P: AsRef<Path>, I: IntoIterator<Item = F>>(
+        path: P,
+        elements: I,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        (|| -> std::io::Result<()> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            for x in elements {
+                writer.write_all(&x.to_bytes())?;
+            }
+            writer.flush()
+        })()
+        .map_err(|e| Error::Config(format!("failed to write {}: {}", path.display(), e)))?;
+
+        Self::open(path)
+    }
+}
+
+impl<F: FF> SetSource<F> for SetFile<F> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn for_each_batch(
+        &self,
+        batch_size: usize,
+        f: &mut dyn FnMut(&[F]) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let read_error = |e: std::io::Error| {
+            Error::Internal(format!("failed to read {}: {}", self.path.display(), e))
+        };
+
+        let mut reader = BufReader::new(File::open(&self.path).map_err(read_error)?);
+        let elem_len = F::ByteReprLen::to_usize();
+        let mut buf = Vec::new();
+        let mut batch = Vec::with_capacity(batch_size.min(self.len));
+
+        let mut remaining = self.len;
+        while remaining > 0 {
+            let n = remaining.min(batch_size);
+            buf.resize(n * elem_len, 0);
+            reader.read_exact(&mut buf).map_err(read_error)?;
+
+            batch.clear();
+            for x in buf.chunks_exact(elem_len) {
+                batch.push(F::from_bytes(x.into()).map_err(|e| {
+                    Error::Config(format!(
+                        "{} has a malformed field element: {}",
+                        self.path.display(),
+                        e
+                    ))
+                })?);
+            }
+            f(&batch).located()?;
+
+            remaining -= n;
+        }
+
+        Ok(())
+    }
+}
+
+// Collect the elements of `inputs` mapped by `f` batch by batch.
+fn collect_batches<F, I, T>(
+    inputs: &I,
+    mut f: impl FnMut(&[F], &mut Vec<T>) -> Result<(), Error>,
+) -> Result<Vec<T>, Error>
+where
+    I: SetSource<F> + ?Sized,
+{
+    let mut res = Vec::with_capacity(inputs.len());
+    inputs
+        .for_each_batch(STREAM_BATCH_SIZE, &mut |batch| f(batch, &mut res))
+        .located()?;
+
+    Ok(res)
+}
+
+/// Sum per element of a set, spooled to a temporary file so that only a batch of the sums is in memory.
+///
+/// The sums are as secret as the shares, so the file is readable only by the owner (mode 0600)
+/// in a directory of its own which only the owner can enter (mode 0700). The directory is removed on drop.
+///
+/// The sums are zero until the first [add](Spool::add).
+struct Spool<F> {
+    dir: TempDir,
+    path: PathBuf,
+    written: bool,
+    _p: PhantomData<fn() -> F>,
+}
+
+impl<F: FF> Spool<F> {
+    fn new() -> Result<Self, Error> {
+        let dir = tempfile::Builder::new()
+            .prefix("pmpsi_spool_")
+            .permissions(Permissions::from_mode(0o700))
+            .tempdir()
+            .map_err(|e| Error::Internal(format!("failed to create a spool directory: {}", e)))?;
+        let path = dir.path().join("sums");
+
+        Ok(Self {
+            dir,
+            path,
+            written: false,
+            _p: PhantomData,
+        })
+    }
+
+    fn io_error(path: &Path, e: std::io::Error) -> Error {
+        Error::Internal(format!("failed to spool to {}: {}", path.display(), e))
+    }
+
+    /// Call `f` with each batch of `inputs` and the sums of its elements.
+    fn for_each_batch<I>(
+        &self,
+        inputs: &I,
+        mut f: impl FnMut(&[F], &[F]) -> Result<(), Error>,
+    ) -> Result<(), Error>
+    where
+        I: SetSource<F> + ?Sized,
+    {
+        let mut reader = match self.written {
+            true => Some(BufReader::new(
+                File::open(&self.path).map_err(|e| Self::io_error(&self.path, e))?,
+            )),
+            false => None,
+        };
+        let elem_len = F::ByteReprLen::to_usize();
+        let mut buf = Vec::new();
+        let mut sums = Vec::new();
+
+        inputs
+            .for_each_batch(STREAM_BATCH_SIZE, &mut |batch| {
+                sums.clear();
+                match &mut reader {
+                    Some(reader) => {
+                        buf.resize(batch.len() * elem_len, 0);
+                        reader
+                            .read_exact(&mut buf)
+                            .map_err(|e| Self::io_error(&self.path, e))?;
+                        for x in buf.chunks_exact(elem_len) {
+                            sums.push(F::from_bytes(x.into()).map_err(|e| {
+                                Error::Internal(format!(
+                                    "{} has a malformed field element: {}",
+                                    self.path.display(),
+                                    e
+                                ))
+                            })?);
+                        }
+                    }
+                    None => sums.resize(batch.len(), F::zero()),
+                }
+                f(batch, &sums).located()
+            })
+            .located()
+    }
+
+    /// Add `f(batch)` to the sums of the elements of each batch of `inputs`.
+    fn add<I>(
+        &mut self,
+        inputs: &I,
+        mut f: impl FnMut(&[F]) -> Result<Vec<F>, Error>,
+    ) -> Result<(), Error>
+    where
+        I: SetSource<F> + ?Sized,
+    {
+        let new_path = self.dir.path().join("sums.new");
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&new_path)
+            .map_err(|e| Self::io_error(&new_path, e))?;
+        let mut writer = BufWriter::new(file);
+
+        self.for_each_batch(inputs, |batch, sums| {
+            let ys = f(batch).located()?;
+            for (&sum, y) in sums.iter().zip(ys) {
+                writer
+                    .write_all(&(sum + y).to_bytes())
+                    .map_err(|e| Self::io_error(&new_path, e))?;
+            }
+            Ok(())
+        })
+        .located()?;
+        writer.flush().map_err(|e| Self::io_error(&new_path, e))?;
+        drop(writer);
+
+        std::fs::rename(&new_path, &self.path).map_err(|e| Self::io_error(&self.path, e))?;
+        self.written = true;
+
+        Ok(())
+    }
+}
+
+impl<F, S, VS, VR> Party<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Conditional zero sharing. Returns $`\hat{s}`$ of each element of `inputs`.
+    fn conditional_secret_sharing_stream<C, RNG, I>(
+        self,
+        inputs: &I,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Spool<F>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        I: SetSource<F> + ?Sized,
+    {
+        let ninputs = inputs.len();
+
+        let Self {
+            id,
+            execution: _,
+            threads: _,
+            opprf_senders,
+            opprf_receivers,
+        } = self;
+
+        let mut s_hats = Spool::new().located()?;
+
+        for (((other_id, channel), (si, sender)), (ri, receiver)) in channels
+            .iter_mut()
+            .zip(opprf_senders.into_iter())
+            .zip(opprf_receivers.into_iter())
+        {
+            let other_id = *other_id;
+            assert!(other_id == si);
+            assert!(other_id == ri);
+
+            let share_seed: Block = rng.gen();
+
+            let send = |channel: &mut C, rng: &mut RNG| -> Result<(), Error> {
+                let mut share_rng = AesRng::from_seed(share_seed);
+                let points: Vec<(F, F)> = collect_batches(inputs, |batch, points| {
+                    points.extend(batch.iter().map(|&x| (x, share_rng.gen::<F>())));
+                    Ok(())
+                })
+                .located()?;
+                let _fk = sender.send(channel, &points, ninputs, rng).located()?;
+                Ok(())
+            };
+            let receive = |channel: &mut C, rng: &mut RNG| -> Result<_, Error> {
+                let queries = collect_batches(inputs, |batch, queries| {
+                    queries.extend_from_slice(batch);
+                    Ok(())
+                })
+                .located()?;
+                receiver.receive_outputs(channel, &queries, rng).located()
+            };
+
+            let output = (|| -> Result<_, Error> {
+                if id < other_id {
+                    send(channel, rng).located()?;
+                    receive(channel, rng).located()
+                } else {
+                    let output = receive(channel, rng).located()?;
+                    send(channel, rng).located()?;
+                    Ok(output)
+                }
+            })()
+            .map_err(|e| e.with_peer(other_id))?;
+
+            // the party's own share is minus the sum of the shares for the peers
+            let mut share_rng = AesRng::from_seed(share_seed);
+            s_hats
+                .add(inputs, |batch| {
+                    let ys = output.eval_batch(batch).located()?;
+                    Ok(ys.into_iter().map(|y| y - share_rng.gen::<F>()).collect())
+                })
+                .located()?;
+        }
+
+        Ok(s_hats)
+    }
+}
+
+impl<F, S, VS, VR> Sender<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Streaming version of [send](Sender::send) reading `inputs` in batches. See [the module](self).
+    pub fn send_stream<C, RNG, I>(
+        self,
+        inputs: &I,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        I: SetSource<F> + ?Sized,
+    {
+        assert!(self.id != 0);
+
+        let Self {
+            id: _,
+            party_for_zs,
+            opprf_sender_for_rc,
+        } = self;
+        let _threads = party_for_zs.threads.enter();
+
+        // conditional zero sharing
+        let s_hats = party_for_zs
+            .conditional_secret_sharing_stream(inputs, channels, rng)
+            .located()?;
+
+        // conditional reconstruction
+        let mut points = Vec::with_capacity(inputs.len());
+        s_hats
+            .for_each_batch(inputs, |batch, s_hats| {
+                points.extend(batch.iter().copied().zip(s_hats.iter().copied()));
+                Ok(())
+            })
+            .located()?;
+        drop(s_hats);
+
+        let channel = &mut channels[0].1;
+        let _fk = opprf_sender_for_rc
+            .send(channel, &points, inputs.len(), rng)
+            .located()?;
+
+        Ok(())
+    }
+}
+
+impl<F, S, VS, VR> Receiver<F, S, VS, VR>
+where
+    F: FF,
+    S: Solver<F>,
+    VS: VoleShareForSender<F>,
+    VR: VoleShareForReceiver<F>,
+    Standard: Distribution<F>,
+{
+    /// Streaming version of [receive](Receiver::receive) reading `inputs` in batches. See [the module](self).
+    ///
+    /// The intersection is passed to `sink` batch by batch in the order of `inputs`. Returns the size of the intersection.
+    pub fn receive_stream<C, RNG, I>(
+        self,
+        inputs: &I,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        mut sink: impl FnMut(&[F]) -> Result<(), Error>,
+    ) -> Result<usize, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        I: SetSource<F> + ?Sized,
+    {
+        let Self {
+            party_for_zs,
+            opprf_receivers_for_rc,
+        } = self;
+        let _threads = party_for_zs.threads.enter();
+
+        // conditional zero sharing
+        let mut s_hats = party_for_zs
+            .conditional_secret_sharing_stream(inputs, channels, rng)
+            .located()?;
+
+        // conditional reconstruction
+        for ((them, channel), (ri, receiver)) in
+            channels.iter_mut().zip(opprf_receivers_for_rc.into_iter())
+        {
+            assert!(ri == *them);
+
+            let queries = collect_batches(inputs, |batch, queries| {
+                queries.extend_from_slice(batch);
+                Ok(())
+            })
+            .located()?;
+            let outputs = receiver
+                .receive_outputs(channel, &queries, rng)
+                .located()
+                .map_err(|e| e.with_peer(ri))?;
+            drop(queries);
+            s_hats
+                .add(inputs, |batch| outputs.eval_batch(batch))
+                .located()?;
+        }
+
+        let mut intersection_size = 0;
+        s_hats
+            .for_each_batch(inputs, |batch, sums| {
+                let found = batch
+                    .iter()
+                    .zip(sums)
+                    .filter_map(|(&x, s)| if s.is_zero() { Some(x) } else { None })
+                    .collect::<Vec<_>>();
+                intersection_size += found.len();
+                sink(&found).located()
+            })
+            .located()?;
+
+        Ok(intersection_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_utils::sync_channel::create_unix_channels;
+    use crate::set_utils::create_sets_without_check;
+    use crate::solver::{PaxosSolver, SolverParams};
+    use crate::testing::SeedTree;
+    use crate::vole::{LPNVoleReceiver, LPNVoleSender};
+    use scuttlebutt::field::F128b;
+    use std::collections::HashSet;
+
+    // slice read in batches much smaller than requested
+    struct SmallBatches<'a>(&'a [F128b]);
+
+    impl SetSource<F128b> for SmallBatches<'_> {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn for_each_batch(
+            &self,
+            batch_size: usize,
+            f: &mut dyn FnMut(&[F128b]) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            self.0.chunks(batch_size.min(7)).try_for_each(f)
+        }
+    }
+
+    #[test]
+    fn test_protocol_stream() {
+        type S = PaxosSolver<F128b>;
+        let nparties = 4;
+        let set_size = 200;
+        let common_size = 20;
+
+        // set PMPSI_TEST_SEED to the printed seed to reproduce a failure
+        let seeds = SeedTree::from_env_or_random("PMPSI_TEST_SEED");
+        let m_size = S::calc_params(set_size).code_length();
+        let vole_share_for_s = LPNVoleSender::<F128b>::for_size(m_size);
+        let vole_share_for_r = LPNVoleReceiver::<F128b>::for_size(m_size);
+
+        let (intersection, mut sets) =
+            create_sets_without_check(nparties, set_size, common_size, &mut seeds.sets().rng())
+                .unwrap();
+
+        let (mut receiver_channels, channels) = create_unix_channels(nparties).unwrap();
+
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut channels)| {
+                let pid = i + 1;
+                let set: Vec<F128b> = sets.pop().unwrap();
+                std::thread::spawn(move || {
+                    let mut rng = seeds.party(pid).rng();
+                    let sender = Sender::<F128b, S, _, _>::precomp(
+                        pid,
+                        &mut channels,
+                        &mut rng,
+                        vole_share_for_s,
+                        vole_share_for_r,
+                        set_size,
+                    )
+                    .unwrap();
+
+                    // streaming and non-streaming parties run together
+                    match pid % 3 {
+                        0 => sender.send(&set, &mut channels, &mut rng),
+                        1 => sender.send_stream(&SmallBatches(&set), &mut channels, &mut rng),
+                        _ => sender.send_stream(set.as_slice(), &mut channels, &mut rng),
+                    }
+                    .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = seeds.party(0).rng();
+        let receiver = Receiver::<F128b, S, _, _>::precomp(
+            &mut receiver_channels,
+            &mut rng,
+            vole_share_for_s,
+            vole_share_for_r,
+            set_size,
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "pmpsi_test_protocol_stream_{:x}.set",
+            u128::from(seeds.seed())
+        ));
+        let set = SetFile::create(&path, sets.pop().unwrap()).unwrap();
+        assert_eq!(set.len(), set_size);
+
+        let mut res = Vec::new();
+        let n = receiver
+            .receive_stream(&set, &mut receiver_channels, &mut rng, |found| {
+                res.extend_from_slice(found);
+                Ok(())
+            })
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(n, res.len());
+        let res: HashSet<F128b> = HashSet::from_iter(res);
+        let intersection: HashSet<F128b> = HashSet::from_iter(intersection);
+        assert_eq!(res, intersection);
+    }
+
+    #[test]
+    fn test_spool() {
+        let mut rng = AesRng::new();
+        let v = (0..100).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+
+        let mut spool = Spool::new().unwrap();
+        let dir = spool.dir.path().to_owned();
+        let path = spool.path.clone();
+        for _ in 0..3 {
+            spool
+                .add(&SmallBatches(&v), |batch| Ok(batch.to_vec()))
+                .unwrap();
+        }
+
+        let mut sums = Vec::new();
+        spool
+            .for_each_batch(v.as_slice(), |batch, s| {
+                assert_eq!(batch.len(), s.len());
+                sums.extend_from_slice(s);
+                Ok(())
+            })
+            .unwrap();
+        let expected = v.iter().map(|&x| x + x + x).collect::<Vec<_>>();
+        assert_eq!(sums, expected);
+
+        // only the owner can read the sums
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        drop(spool);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_set_file() {
+        let path = std::env::temp_dir().join(format!(
+            "pmpsi_test_set_file_{:x}.set",
+            u128::from(SeedTree::random().seed())
+        ));
+
+        let mut rng = AesRng::new();
+        let v = (0..1000).map(|_| rng.gen::<F128b>()).collect::<Vec<_>>();
+        let set = SetFile::create(&path, v.iter().copied()).unwrap();
+        assert_eq!(set.len(), v.len());
+
+        let mut read = Vec::new();
+        set.for_each_batch(300, &mut |batch| {
+            assert!(batch.len() <= 300);
+            read.extend_from_slice(batch);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(read, v);
+
+        // truncated
+        std::fs::write(&path, [0u8; 17]).unwrap();
+        assert!(matches!(
+            SetFile::<F128b>::open(&path).err().unwrap(),
+            Error::Config(_)
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
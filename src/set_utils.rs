@@ -1,6 +1,6 @@
 //! Utility functions for creating sets for the set intersection protocol.
 
-use anyhow::{bail, Result};
+use crate::error::{Error, Result};
 use rand::distributions::{Distribution, Standard};
 use rand::seq::SliceRandom;
 use rand::{CryptoRng, Rng};
@@ -41,17 +41,14 @@ where
     Standard: Distribution<T>,
 {
     if nparties <= 1 {
-        bail!("nparties (={}) <= 1 @{}:{}", nparties, file!(), line!());
+        return Err(Error::Config(format!("nparties (={}) <= 1", nparties)));
     }
 
     if set_size < common_size {
-        bail!(
-            "set_size (={}) < common_size (={}) @{}:{}",
-            set_size,
-            common_size,
-            file!(),
-            line!()
-        );
+        return Err(Error::Config(format!(
+            "set_size (={}) < common_size (={})",
+            set_size, common_size
+        )));
     }
 
     let common = (0..common_size).map(|_| rng.gen::<T>()).collect::<Vec<_>>();
@@ -113,17 +110,14 @@ where
     Standard: Distribution<T>,
 {
     if nparties <= 1 {
-        bail!("nparties (={}) <= 1 @{}:{}", nparties, file!(), line!());
+        return Err(Error::Config(format!("nparties (={}) <= 1", nparties)));
     }
 
     if set_size < common_size {
-        bail!(
-            "set_size (={}) < common_size (={}) @{}:{}",
-            set_size,
-            common_size,
-            file!(),
-            line!()
-        );
+        return Err(Error::Config(format!(
+            "set_size (={}) < common_size (={})",
+            set_size, common_size
+        )));
     }
 
     let common = (0..common_size).map(|_| rng.gen::<T>()).collect::<Vec<_>>();
@@ -0,0 +1,648 @@
+//! A kind of solver methods using random band matrix OKVS (RB-OKVS).
+//!
+//! See [Near-Optimal Oblivious Key-Value Stores for Efficient PSI, PSU and Volume-Hiding Multi-Maps](https://eprint.iacr.org/2023/903)
+//! by Bienstock, Patel, Seo and Yeo.
+//!
+//! Each point $`(x, y)`$ is encoded as a row of a linear system $`\langle \bm{b}(x), P \rangle = y`$ where
+//! $`\bm{b}(x) \in \{0, 1\}^m`$ is zero except a band of width $`w`$ starting at a random position $`s(x) \in [0, m - w]`$.
+//! Sorting rows by $`s(x)`$ keeps the band structure during gaussian elimination,
+//! so encoding takes $`O(n w)`$ word operations (bits of band are packed into `u64`) and decoding takes $`O(w)`$.
+//!
+//! # Paramaters
+//!
+//! - $`n`$: the number of points (or elements in the set).
+//! - $`w`$: band width, growing linearly in $`\lambda`$ (see [band_width]). $`w = 192`$ for $`\lambda = 40`$.
+//! - $`m = \max(1.1 \cdot n, n + w)`$: the number of band columns.
+//! - $`s`$: stash size (default $`0`$). The code vector is $`m`$ band columns followed by $`s`$ dense stash columns.
+//!
+//! The band and its position are derived by one call of the [hash function](crate::hasher) keyed by the shared seed,
+//! and the stash bits of a row by another call.
+//!
+//! # Example
+//!
+//! ```
+//! use scuttlebutt::field::F128b;
+//! use rand::Rng;
+//! use scuttlebutt::AesRng;
+//! use preprocessing_mpsi_with_vole::solver::{Solver, BandSolver};
+//! use preprocessing_mpsi_with_vole::error::Result;
+//! # fn try_main() -> Result<()> {
+//!
+//! let mut rng: AesRng = AesRng::new();
+//! let set: Vec<F128b> = (0..5).map(|_| rng.gen()).collect();
+//!
+//! let aux = BandSolver::<F128b>::gen_aux(&mut rng)?;
+//! let params = BandSolver::<F128b>::calc_params(set.len());
+//!
+//! let points: Vec<(F128b, F128b)> = set
+//!     .iter()
+//!     .map(|x| (*x, *x * *x))
+//!     .collect();
+//!
+//! // Encoding points to vector.
+//! let p: Vec<F128b> = BandSolver::<F128b>::encode(&mut rng, &points, aux, params)?;
+//!
+//! // Decoding vector to corresponding values.
+//! let decoded_values: Vec<F128b> = set
+//!     .iter()
+//!     .map(|x| BandSolver::<F128b>::decode(&p, *x, aux, params))
+//!     .collect::<Result<_>>()?;
+//!
+//! let values: Vec<F128b> = points.iter().map(|(_, y)| *y).collect();
+//!
+//! assert_eq!(values, decoded_values);
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use super::*;
+use crate::error::{Context, Result};
+use crate::hasher::{AesHasher, KeyedHasher};
+use crate::parallel_utils::par_map;
+use gaussian_eliminations::gaussian_elimination;
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{AbstractChannel, Block};
+use std::marker::PhantomData;
+
+/// Band width $`w`$ at the default $`\lambda`$. Band widths are multiples of 64.
+pub const BAND_WIDTH: usize = 192;
+
+/// Largest band width, which is enough for $`\lambda \leq 131`$. Larger $`\lambda`$ makes encoding and decoding fail.
+pub const MAX_BAND_WIDTH: usize = 512;
+
+const MAX_WORDS: usize = MAX_BAND_WIDTH / 64;
+
+// band of one row: (start position, packed bits). bit k of the band corresponds to column start + k.
+type Band<const N: usize> = (usize, [u64; N]);
+
+// a row of the linear system: band, packed bits of the stash columns and the value.
+type Row<F, const N: usize> = (Band<N>, Vec<u64>, F);
+
+// Call `$f` with the number of words of the band width as the last type parameter.
+macro_rules! with_words {
+    ($params:expr, $f:ident::<$($t:ty),*>($($arg:expr),*)) => {
+        match $params.w / 64 {
+            1 => $f::<$($t,)* 1>($($arg),*),
+            2 => $f::<$($t,)* 2>($($arg),*),
+            3 => $f::<$($t,)* 3>($($arg),*),
+            4 => $f::<$($t,)* 4>($($arg),*),
+            5 => $f::<$($t,)* 5>($($arg),*),
+            6 => $f::<$($t,)* 6>($($arg),*),
+            7 => $f::<$($t,)* 7>($($arg),*),
+            8 => $f::<$($t,)* 8>($($arg),*),
+            _ => Err(SolverError::InvalidInput(format!(
+                "band width (={}) is larger than {}",
+                $params.w, MAX_BAND_WIDTH
+            ))
+            .into()),
+        }
+    };
+}
+
+struct BandHasher<H: KeyedHasher> {
+    hasher: H,
+    m: usize,
+    w: usize,
+    s: usize,
+}
+
+impl<H: KeyedHasher> BandHasher<H> {
+    fn new(seed: Block, params: BandSolverParams) -> Self {
+        Self {
+            hasher: H::new(seed),
+            m: params.m,
+            w: params.w,
+            s: params.s,
+        }
+    }
+
+    // One hash call gives the start position (first 8 bytes) and the band bits (rest).
+    // The stash bits are given by another call with a suffix.
+    #[inline]
+    fn row<F: FF, const N: usize>(&self, x: F) -> (Band<N>, Vec<u64>) {
+        let x = x.to_bytes();
+        let mut buf = [0u8; 8 * (MAX_WORDS + 1)];
+        let out = &mut buf[..8 * (N + 1)];
+        self.hasher.hash_into(&[x.as_slice()], out);
+
+        let start = (u64::from_le_bytes(out[..8].try_into().unwrap())
+            % (self.m - self.w + 1) as u64) as usize;
+
+        let mut bits = [0u64; N];
+        for (b, chunk) in bits.iter_mut().zip(out[8..].chunks(8)) {
+            *b = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        // the first bit is always 1 so that every row has a nonzero entry.
+        bits[0] |= 1;
+
+        let stash = if self.s == 0 {
+            Vec::new()
+        } else {
+            let mut out = vec![0u8; 8 * self.s.div_ceil(64)];
+            self.hasher.hash_into(&[x.as_slice(), b"stash"], &mut out);
+            let mut stash = out
+                .chunks(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            if self.s % 64 != 0 {
+                *stash.last_mut().unwrap() &= (1 << (self.s % 64)) - 1;
+            }
+            stash
+        };
+
+        ((start, bits), stash)
+    }
+}
+
+#[inline]
+fn first_one<const N: usize>(bits: &[u64; N]) -> Option<usize> {
+    bits.iter()
+        .enumerate()
+        .find(|(_, &word)| word != 0)
+        .map(|(i, word)| i * 64 + word.trailing_zeros() as usize)
+}
+
+// dst ^= src >> shift (bit k of src goes to bit k - shift of dst). bits shifted out are dropped.
+#[inline]
+fn xor_shifted_down<const N: usize>(dst: &mut [u64; N], src: &[u64; N], shift: usize) {
+    let (word_shift, bit_shift) = (shift / 64, shift % 64);
+    for i in 0..N {
+        let lo = src.get(i + word_shift).copied().unwrap_or(0);
+        let hi = src.get(i + word_shift + 1).copied().unwrap_or(0);
+        dst[i] ^= if bit_shift == 0 {
+            lo
+        } else {
+            (lo >> bit_shift) | (hi << (64 - bit_shift))
+        };
+    }
+}
+
+// dst ^= src << shift (bit k of src goes to bit k + shift of dst). bits shifted out are dropped.
+#[inline]
+fn xor_shifted_up<const N: usize>(dst: &mut [u64; N], src: &[u64; N], shift: usize) {
+    let (word_shift, bit_shift) = (shift / 64, shift % 64);
+    for i in word_shift..N {
+        let lo = src[i - word_shift];
+        let prev = if i > word_shift {
+            src[i - word_shift - 1]
+        } else {
+            0
+        };
+        dst[i] ^= if bit_shift == 0 {
+            lo
+        } else {
+            (lo << bit_shift) | (prev >> (64 - bit_shift))
+        };
+    }
+}
+
+/// Solver for random band matrix OKVS.
+///
+/// Please look the parent document ( [crate::solver::band] ) for usage example.
+pub struct BandSolver<F, H = AesHasher>(PhantomData<(F, H)>)
+where
+    F: FF,
+    Standard: Distribution<F>,
+    H: KeyedHasher;
+
+/// Parameters for band solver. It contains the number of band columns $`m`$, band width $`w`$ and stash size $`s`$.
+#[derive(Clone, Copy)]
+pub struct BandSolverParams {
+    m: usize,
+    w: usize,
+    s: usize,
+}
+
+impl SolverParams for BandSolverParams {
+    fn code_length(&self) -> usize {
+        self.m + self.s
+    }
+}
+
+/// Band width for statistical security parameter `lambda`.
+///
+/// The failure probability of RB-OKVS decreases exponentially in $`w`$ (roughly $`\lambda \approx a w - b`$ by the paper).
+/// $`w`$ is the smallest multiple of 64 at least $`192 (\lambda + 15) / 55`$, which is [BAND_WIDTH] at the default $`\lambda = 40`$.
+pub fn band_width(lambda: usize) -> usize {
+    ((lambda + 15) * BAND_WIDTH).div_ceil(55 * 64) * 64
+}
+
+impl<F, H> Solver<F> for BandSolver<F, H>
+where
+    F: FF,
+    Standard: Distribution<F>,
+    H: KeyedHasher,
+{
+    const ID: &'static str = "band";
+
+    fn id() -> String {
+        format!("{}({})", Self::ID, H::ID)
+    }
+
+    /// Seed (key of the hash function `H`) to derive bands. Seed is generated randomly by [gen_aux](BandSolver::gen_aux).
+    type AuxInfo = Block;
+    /// BandSolver Parameters consists of $`m`$, $`w`$ and $`s`$.
+    type Params = BandSolverParams;
+
+    fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo> {
+        Ok(rng.gen::<Block>())
+    }
+
+    fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _rng: &mut RNG,
+        aux: Self::AuxInfo,
+    ) -> Result<()> {
+        channel.write_block(&aux).located()?;
+
+        Ok(())
+    }
+
+    fn aux_receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _rng: &mut RNG,
+    ) -> Result<Self::AuxInfo> {
+        let aux = channel.read_block().located()?;
+
+        Ok(aux)
+    }
+
+    /// $`m = \max(e \cdot n, n + w)`$ band columns followed by $`s`$ stash columns,
+    /// where $`e`$ is the expansion (default $`1.1`$, must be larger than $`1`$), $`w`$ is the [band width](band_width) for $`\lambda`$
+    /// and $`s`$ is the stash size.
+    ///
+    /// Failure probability is about $`2^{-\lambda}`$ with the default expansion and decreases as the expansion grows.
+    /// Rows which are linearly dependent on the others in the band are left to the dense stash columns,
+    /// so encoding fails only if more than $`s`$ rows are.
+    fn calc_params_with(n: usize, options: SolverOptions) -> BandSolverParams {
+        let w = band_width(options.lambda);
+        let m = match options.expansion {
+            None => n + n / 10,
+            Some(e) => (e * n as f64).ceil() as usize,
+        };
+
+        BandSolverParams {
+            m: m.max(n + w),
+            w,
+            s: options.stash_size,
+        }
+    }
+
+    /// Encode points to a code vector.
+    ///
+    /// This function take $`O(n \log n + n (w + s))`$ where $`n`$ is set size, $`w`$ is the band width and $`s`$ is the stash size.
+    fn encode<RNG: CryptoRng + Rng>(
+        rng: &mut RNG,
+        points: &[(F, F)],
+        aux: Block,
+        params: Self::Params,
+    ) -> Result<Vec<F>> {
+        with_words!(params, encode_with::<F, H, RNG>(rng, points, aux, params))
+    }
+
+    fn decode(p: &[F], x: F, aux: Block, params: Self::Params) -> Result<F> {
+        let ys = with_words!(params, decode_with::<F, H>(p, &[x], aux, params))?;
+
+        Ok(ys[0])
+    }
+
+    /// Decode code vector $`P`$ at all of `xs` at once.
+    ///
+    /// The AES key schedule is computed only once, and large batches are decoded in parallel.
+    fn decode_batch(p: &[F], xs: &[F], aux: Block, params: Self::Params) -> Result<Vec<F>> {
+        with_words!(params, decode_with::<F, H>(p, xs, aux, params))
+    }
+}
+
+fn encode_with<F, H, RNG, const N: usize>(
+    rng: &mut RNG,
+    points: &[(F, F)],
+    aux: Block,
+    params: BandSolverParams,
+) -> Result<Vec<F>>
+where
+    F: FF,
+    Standard: Distribution<F>,
+    H: KeyedHasher,
+    RNG: CryptoRng + Rng,
+{
+    let BandSolverParams { m, s, .. } = params;
+    let hasher = BandHasher::<H>::new(aux, params);
+
+    // 1. Compute bands and sort rows by their start positions.
+    let mut rows = points
+        .iter()
+        .map(|(x, y)| {
+            let (band, stash) = hasher.row::<F, N>(*x);
+            (band, stash, *y)
+        })
+        .collect::<Vec<_>>();
+    rows.sort_unstable_by_key(|((start, _), _, _)| *start);
+
+    // 2. Gaussian elimination. pivots[c] is the row (rebased to start at c) whose leading entry is column c.
+    // Rows whose band becomes zero are left to the stash.
+    let mut pivots: Vec<Option<Row<F, N>>> = vec![None; m];
+    let mut pivot_cols = Vec::with_capacity(rows.len());
+    let mut stashed = Vec::new();
+
+    'rows: for ((start, mut bits), mut stash, mut y) in rows.into_iter() {
+        loop {
+            let Some(k) = first_one(&bits) else {
+                stashed.push((stash, y));
+                continue 'rows;
+            };
+            let c = start + k;
+
+            match &pivots[c] {
+                Some(((_, p_bits), p_stash, p_y)) => {
+                    // the pivot row is within [c, c + w) and this row is within [start, start + w) where start <= c.
+                    xor_shifted_up(&mut bits, p_bits, k);
+                    stash.iter_mut().zip(p_stash).for_each(|(a, b)| *a ^= b);
+                    y += *p_y;
+                }
+                None => {
+                    let mut rebased = [0u64; N];
+                    xor_shifted_down(&mut rebased, &bits, k);
+                    pivots[c] = Some(((c, rebased), stash, y));
+                    pivot_cols.push(c);
+                    break;
+                }
+            }
+        }
+    }
+
+    // 3. Solve the rows left to the stash. Stash columns not decided by them are random.
+    if stashed.len() > s {
+        return Err(SolverError::TooManyConstraints {
+            constraints: stashed.len(),
+            capacity: s,
+        })
+        .located();
+    }
+
+    let mut vec_s: Vec<F> = (0..s).map(|_| rng.gen()).collect::<Vec<_>>();
+    if !stashed.is_empty() {
+        let matrix = stashed
+            .into_iter()
+            .map(|(stash, y)| {
+                let bits = (0..s)
+                    .map(|j| (stash[j / 64] >> (j % 64)) & 1 == 1)
+                    .collect::<Vec<_>>();
+                (bits, y)
+            })
+            .collect::<Vec<_>>();
+
+        let equations_w = gaussian_elimination(matrix).context("error in gaussian_elimination")?;
+        let Some(equations) = equations_w else {
+            return Err(SolverError::Unsolvable).located();
+        };
+
+        for (i, bits, val) in equations.iter() {
+            let mut sum = *val;
+            for (j, b) in bits.iter().enumerate() {
+                if *i != j && *b {
+                    sum += vec_s[j];
+                }
+            }
+            vec_s[*i] = sum;
+        }
+    }
+
+    // 4. Back substitution from the last pivot. Non pivot columns are random.
+    let mut p: Vec<F> = (0..m).map(|_| rng.gen()).collect::<Vec<_>>();
+    pivot_cols.sort_unstable();
+
+    for &c in pivot_cols.iter().rev() {
+        let Some(((_, bits), stash, y)) = &pivots[c] else {
+            unreachable!();
+        };
+        p[c] = *y + inner_product(&p, c + 1, bits, 1) + stash_product(&vec_s, stash);
+    }
+
+    p.extend(vec_s);
+
+    Ok(p)
+}
+
+fn decode_with<F, H, const N: usize>(
+    p: &[F],
+    xs: &[F],
+    aux: Block,
+    params: BandSolverParams,
+) -> Result<Vec<F>>
+where
+    F: FF,
+    H: KeyedHasher,
+{
+    if p.len() != params.code_length() {
+        return Err(SolverError::InvalidInput(format!(
+            "p.len() (={}) != code length (={})",
+            p.len(),
+            params.code_length()
+        ))
+        .into());
+    }
+
+    let hasher = BandHasher::<H>::new(aux, params);
+    let (p, p_s) = p.split_at(params.m);
+
+    par_map(xs, |x| {
+        let ((start, bits), stash) = hasher.row::<F, N>(*x);
+        Ok(inner_product(p, start, &bits, 0) + stash_product(p_s, &stash))
+    })
+}
+
+// sum of p[offset + k - skip] for set bits k >= skip.
+#[inline]
+fn inner_product<F: FF, const N: usize>(p: &[F], offset: usize, bits: &[u64; N], skip: usize) -> F {
+    let mut sum = F::zero();
+    for (i, &word) in bits.iter().enumerate() {
+        let mut word = word;
+        while word != 0 {
+            let k = i * 64 + word.trailing_zeros() as usize;
+            word &= word - 1;
+            if k >= skip && offset + k - skip < p.len() {
+                sum += p[offset + k - skip];
+            }
+        }
+    }
+    sum
+}
+
+// sum of p_s[j] for set bits j of the stash.
+#[inline]
+fn stash_product<F: FF>(p_s: &[F], stash: &[u64]) -> F {
+    let mut sum = F::zero();
+    for (i, &word) in stash.iter().enumerate() {
+        let mut word = word;
+        while word != 0 {
+            sum += p_s[i * 64 + word.trailing_zeros() as usize];
+            word &= word - 1;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_utils::{hash_f, oracle};
+    use crate::hasher::Sha256Hasher;
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    fn test_band_base(set_size: usize) {
+        let mut rng = AesRng::new();
+        let set = (0..set_size).map(|_| rng.gen()).collect::<Vec<F128b>>();
+
+        let aux = BandSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params = BandSolver::<F128b>::calc_params(set.len());
+
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let p = BandSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
+
+        assert_eq!(p.len(), params.code_length());
+
+        let reconstructed_ys = set
+            .iter()
+            .map(|x| BandSolver::<F128b>::decode(&p, *x, aux, params).unwrap())
+            .collect::<Vec<_>>();
+
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+
+        assert_eq!(ys, reconstructed_ys);
+        assert_eq!(
+            ys,
+            BandSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_band_small() {
+        for n in 1..=100 {
+            test_band_base(n);
+        }
+    }
+
+    #[test]
+    fn test_band_big() {
+        for e in 10..21 {
+            test_band_base(1 << e);
+        }
+    }
+
+    #[test]
+    fn test_band_width() {
+        assert_eq!(band_width(DEFAULT_LAMBDA), BAND_WIDTH);
+        assert_eq!(band_width(80), 384);
+        assert_eq!(band_width(131), MAX_BAND_WIDTH);
+
+        let params = BandSolver::<F128b>::calc_params_with(100, SolverOptions::with_lambda(132));
+        let mut rng = AesRng::new();
+        let aux = BandSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let points = vec![(rng.gen(), rng.gen())];
+        assert!(BandSolver::<F128b>::encode(&mut rng, &points, aux, params).is_err());
+    }
+
+    #[test]
+    fn test_band_lambda() {
+        let mut rng = AesRng::new();
+        let set = (0..1 << 10).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let aux = BandSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params =
+            BandSolver::<F128b>::calc_params_with(set.len(), SolverOptions::with_lambda(80));
+        assert_eq!(params.w, 384);
+
+        let p = BandSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        assert_eq!(
+            ys,
+            BandSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_band_stash() {
+        let mut rng = AesRng::new();
+        let set = (0..80).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
+        let aux = BandSolver::<F128b>::gen_aux(&mut rng).unwrap();
+
+        // 80 rows in 64 band columns, so at least 16 rows are left to the stash.
+        let params = BandSolverParams { m: 64, w: 64, s: 0 };
+        let err = BandSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap_err();
+        let Error::Solver(err) = err.root() else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert!(matches!(err, SolverError::TooManyConstraints { .. }));
+
+        let params = BandSolverParams { s: 64, ..params };
+        let p = BandSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
+        assert_eq!(p.len(), 128);
+
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        let decoded = set
+            .iter()
+            .map(|x| BandSolver::<F128b>::decode(&p, *x, aux, params).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ys, decoded);
+
+        // the stash size is taken from the options
+        let options = SolverOptions {
+            stash_size: 10,
+            ..Default::default()
+        };
+        let params = BandSolver::<F128b>::calc_params_with(80, options);
+        assert_eq!(params.code_length(), params.m + 10);
+    }
+
+    #[test]
+    fn test_xor_shifted() {
+        const WORDS: usize = BAND_WIDTH / 64;
+
+        let mut rng = AesRng::new();
+
+        for shift in 0..BAND_WIDTH {
+            let src: [u64; WORDS] = rng.gen();
+            let to_bits = |words: &[u64; WORDS]| {
+                (0..BAND_WIDTH)
+                    .map(|k| (words[k / 64] >> (k % 64)) & 1 == 1)
+                    .collect::<Vec<_>>()
+            };
+            let src_bits = to_bits(&src);
+
+            let mut up = [0u64; WORDS];
+            xor_shifted_up(&mut up, &src, shift);
+            let up_bits = to_bits(&up);
+            for k in 0..BAND_WIDTH {
+                let expected = k >= shift && src_bits[k - shift];
+                assert_eq!(up_bits[k], expected);
+            }
+
+            let mut down = [0u64; WORDS];
+            xor_shifted_down(&mut down, &src, shift);
+            let down_bits = to_bits(&down);
+            for k in 0..BAND_WIDTH {
+                let expected = k + shift < BAND_WIDTH && src_bits[k + shift];
+                assert_eq!(down_bits[k], expected);
+            }
+        }
+    }
+}
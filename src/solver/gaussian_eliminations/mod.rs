@@ -1,7 +1,8 @@
-use anyhow::{bail, Context, Result};
+use super::SolverError;
+use crate::error::{Context, Result};
 use scuttlebutt::field::FiniteField as FF;
 mod row;
-use row::{add_rows, Row};
+use row::{add_rows, next_pivot_column, Row};
 
 /*
 
@@ -29,69 +30,75 @@ R_4 = v_1 - R_5 - R_6 - ...
 
 R_3 also can be random value because you have to decide R_i properly if and only if there are at least 1 row of the original matrix its i-th value is 1.
 
+Rows are packed into `u64` words (see `Row`), so adding a row and searching a pivot work 64 columns at a time.
+
 */
 
 pub fn gaussian_elimination<F: FF>(
     matrix: Vec<(Vec<bool>, F)>,
 ) -> Result<Option<Vec<(usize, Vec<bool>, F)>>> {
-    check_matrix(&matrix).with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-    let n = matrix.len();
-    let m = matrix[0].0.len();
+    check_matrix(&matrix).located()?;
 
-    let mut matrix = matrix
+    let matrix = matrix
         .into_iter()
-        .map(|(row, target)| Row::new(row, target))
+        .map(|(row, target)| Row::new(&row, target))
         .collect::<Vec<_>>();
 
-    let mut first_indices = Vec::with_capacity(n);
+    let res = gaussian_elimination_packed(matrix).located()?.map(|rows| {
+        rows.into_iter()
+            .map(|(i, row)| (i, row.to_bools(), row.target))
+            .collect::<Vec<_>>()
+    });
 
-    let mut i = 0;
-    let mut j = 0;
+    Ok(res)
+}
 
-    while i < n {
-        if j >= m {
-            // bail!("matrix is not full rank");
-            return Ok(None);
-        }
+/// Same as [gaussian_elimination] but rows are given and returned packed.
+pub(crate) fn gaussian_elimination_packed<F: FF>(
+    mut matrix: Vec<Row<F>>,
+) -> Result<Option<Vec<(usize, Row<F>)>>> {
+    let n = matrix.len();
+    if n == 0 {
+        return Err(SolverError::InvalidInput("matrix is empty".to_string()).into());
+    }
 
-        let mut t = None;
-        for k in i..n {
-            if matrix[k][j] {
-                t = Some(k);
-                break;
-            }
-        }
+    let m = matrix[0].len;
+    if matrix.iter().any(|row| row.len != m) {
+        return Err(
+            SolverError::InvalidInput("matrix rows have different length".to_string()).into(),
+        );
+    }
+    if n > m {
+        return Err(SolverError::InvalidInput("matrix row is more than column".to_string()).into());
+    }
 
-        let Some(t) = t else {
-            j += 1;
+    let mut first_indices = Vec::with_capacity(n);
 
-            if j >= m {
-                // bail!("matrix is not full rank");
-                return Ok(None);
-            }
+    let mut j = 0;
 
-            continue;
+    for i in 0..n {
+        let Some(pivot_col) = next_pivot_column(&matrix[i..], j, m) else {
+            // matrix is not full rank
+            return Ok(None);
         };
+        j = pivot_col;
 
+        let t = (i..n).find(|&k| matrix[k].get(j)).unwrap();
         matrix.swap(i, t);
 
         for k in 0..n {
-            if k != i && matrix[k][j] {
-                add_rows(&mut matrix, k, i, j..m);
+            if k != i && matrix[k].get(j) {
+                add_rows(&mut matrix, k, i, j / 64);
             }
         }
 
         first_indices.push(j);
-
-        i += 1;
         j += 1;
     }
 
     let res = first_indices
         .into_iter()
         .zip(matrix.into_iter())
-        .map(|(i, row)| (i, row.values, row.target))
         .collect::<Vec<_>>();
 
     Ok(Some(res))
@@ -101,22 +108,26 @@ fn check_matrix<F: FF>(matrix: &[(Vec<bool>, F)]) -> Result<()> {
     let n = matrix.len();
 
     if n == 0 {
-        bail!("matrix is empty");
+        return Err(SolverError::InvalidInput("matrix is empty".to_string()).into());
     }
 
     let m = matrix[0].0.len();
 
     if n > m {
-        bail!("matrix row is more than column");
+        return Err(SolverError::InvalidInput("matrix row is more than column".to_string()).into());
     }
 
     if m == 0 {
-        bail!("matrix row is empty");
+        return Err(SolverError::InvalidInput("matrix row is empty".to_string()).into());
     }
 
     for (i, (row, _)) in matrix.iter().enumerate() {
         if row.len() != m {
-            bail!("matrix row {} has different length", i);
+            return Err(SolverError::InvalidInput(format!(
+                "matrix row {} has different length",
+                i
+            ))
+            .into());
         }
     }
 
@@ -265,6 +276,96 @@ mod tests {
         );
     }
 
+    // The previous implementation storing rows as `Vec<bool>`.
+    fn gaussian_elimination_reference(
+        mut matrix: Vec<(Vec<bool>, F128b)>,
+    ) -> Option<Vec<(usize, Vec<bool>, F128b)>> {
+        let n = matrix.len();
+        let m = matrix[0].0.len();
+
+        let mut first_indices = Vec::with_capacity(n);
+
+        let mut i = 0;
+        let mut j = 0;
+
+        while i < n {
+            if j >= m {
+                return None;
+            }
+
+            let Some(t) = (i..n).find(|&k| matrix[k].0[j]) else {
+                j += 1;
+                continue;
+            };
+
+            matrix.swap(i, t);
+
+            for k in 0..n {
+                if k != i && matrix[k].0[j] {
+                    let (values, target) = matrix[i].clone();
+                    for l in j..m {
+                        matrix[k].0[l] ^= values[l];
+                    }
+                    matrix[k].1 += target;
+                }
+            }
+
+            first_indices.push(j);
+
+            i += 1;
+            j += 1;
+        }
+
+        Some(
+            first_indices
+                .into_iter()
+                .zip(matrix.into_iter())
+                .map(|(i, (values, target))| (i, values, target))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_against_reference() {
+        let mut rng = AesRng::new();
+
+        for _ in 0..1000 {
+            let n = rng.gen_range(1..=150);
+            let m = rng.gen_range(n..=n + 150);
+            // sparse rows and duplicated rows make rank deficient matrices
+            let density = rng.gen_range(0.01..=0.5);
+
+            let mut matrix: Vec<(Vec<bool>, F128b)> = (0..n)
+                .map(|_| ((0..m).map(|_| rng.gen_bool(density)).collect(), rng.gen()))
+                .collect();
+            if n > 1 && rng.gen_bool(0.1) {
+                matrix[n - 1] = matrix[0].clone();
+            }
+
+            let res = gaussian_elimination(matrix.clone()).unwrap();
+            let expected = gaussian_elimination_reference(matrix);
+
+            assert_eq!(res, expected);
+        }
+    }
+
+    #[test]
+    fn test_row_packing() {
+        let mut rng = AesRng::new();
+
+        for len in [1, 63, 64, 65, 128, 200] {
+            let values = (0..len).map(|_| rng.gen()).collect::<Vec<bool>>();
+            let row = Row::new(&values, F128b::zero());
+
+            assert_eq!(row.words.len(), len.div_ceil(64));
+            assert_eq!(row.to_bools(), values);
+            assert_eq!(
+                row.words.iter().map(|w| w.count_ones()).sum::<u32>() as usize,
+                values.iter().filter(|b| **b).count()
+            );
+        }
+    }
+
     #[test]
     fn test_edge_case_1() {
         let matrix = vec![(vec![true, false, false], F128b::from_u128(1))];
@@ -1,40 +1,80 @@
 use scuttlebutt::field::FiniteField as FF;
-use std::ops::{Index, IndexMut, Range};
 
+/// A row of a matrix over GF(2) packed into `u64` words, with the right-hand side `target`.
+///
+/// Bit `j` is stored in `words[j / 64]` at bit position `j % 64`. Bits beyond `len` are always 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct Row<F: FF> {
-    pub values: Vec<bool>,
+    pub words: Vec<u64>,
+    pub len: usize,
     pub target: F,
 }
 
 impl<F: FF> Row<F> {
-    pub fn new(values: Vec<bool>, target: F) -> Self {
-        Self { values, target }
+    pub fn new(values: &[bool], target: F) -> Self {
+        let mut words = vec![0u64; values.len().div_ceil(64)];
+        for (j, _) in values.iter().enumerate().filter(|(_, b)| **b) {
+            words[j / 64] |= 1 << (j % 64);
+        }
+
+        Self {
+            words,
+            len: values.len(),
+            target,
+        }
     }
-}
 
-impl<F: FF> Index<usize> for Row<F> {
-    type Output = bool;
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.values[index]
+    #[inline]
+    pub fn get(&self, j: usize) -> bool {
+        self.words[j / 64] >> (j % 64) & 1 == 1
     }
-}
 
-impl<F: FF> IndexMut<usize> for Row<F> {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.values[index]
+    pub fn to_bools(&self) -> Vec<bool> {
+        (0..self.len).map(|j| self.get(j)).collect()
     }
 }
 
+/// `matrix[added_row_idx] += matrix[add_row_idx]`, word by word from `from_word`.
+/// Words before `from_word` must be 0 in the added row.
 pub(crate) fn add_rows<F: FF>(
-    matrix: &mut Vec<Row<F>>,
+    matrix: &mut [Row<F>],
     added_row_idx: usize,
     add_row_idx: usize,
-    range: Range<usize>,
+    from_word: usize,
 ) {
-    for i in range {
-        let val = matrix[add_row_idx].values[i];
-        matrix[added_row_idx].values[i] ^= val;
+    debug_assert_ne!(added_row_idx, add_row_idx);
+
+    let (added, add) = if added_row_idx < add_row_idx {
+        let (left, right) = matrix.split_at_mut(add_row_idx);
+        (&mut left[added_row_idx], &right[0])
+    } else {
+        let (left, right) = matrix.split_at_mut(added_row_idx);
+        (&mut right[0], &left[add_row_idx])
+    };
+
+    for (a, b) in added.words[from_word..]
+        .iter_mut()
+        .zip(add.words[from_word..].iter())
+    {
+        *a ^= b;
     }
-    let val = matrix[add_row_idx].target;
-    matrix[added_row_idx].target += val;
+    added.target += add.target;
+}
+
+/// The smallest column `>= j` in which some of `rows` has 1.
+///
+/// Words are ORed over all rows so that columns with no 1 are skipped 64 at a time.
+pub(crate) fn next_pivot_column<F: FF>(rows: &[Row<F>], j: usize, m: usize) -> Option<usize> {
+    let words = m.div_ceil(64);
+    let mut mask = !0u64 << (j % 64);
+
+    for w in (j / 64)..words {
+        let ored = rows.iter().fold(0, |acc, row| acc | row.words[w]) & mask;
+        if ored != 0 {
+            return Some(w * 64 + ored.trailing_zeros() as usize);
+        }
+        mask = !0;
+    }
+
+    None
 }
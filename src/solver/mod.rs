@@ -13,15 +13,21 @@
 //!
 //! Or, the implementation source code of [PaxosSolver].
 
-use anyhow::Error;
+use crate::error::{Context, Error};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField;
 use scuttlebutt::AbstractChannel;
+use thiserror::Error;
 pub mod vandelmonde;
 pub use vandelmonde::VandelmondeSolver;
 mod gaussian_eliminations;
 pub mod paxos;
+mod polynomial;
 pub use paxos::PaxosSolver;
+pub mod two_core;
+pub use two_core::TwoCoreSolver;
+pub mod band;
+pub use band::BandSolver;
 // mod lu_decomp;
 
 /// Trait for solver parameters.
@@ -31,8 +37,127 @@ pub trait SolverParams {
     fn code_length(&self) -> usize;
 }
 
+/// Default statistical security parameter $`\lambda`$.
+pub const DEFAULT_LAMBDA: usize = 40;
+
+/// Errors of [Solver] telling why points could not be encoded or decoded.
+///
+/// They are returned as [Error::Solver].
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum SolverError {
+    /// The linear constraints (e.g. cycles of the cuckoo graph) are more than the dense columns can absorb.
+    #[error("too many constraints (={constraints}) for {capacity} dense columns")]
+    TooManyConstraints {
+        /// Number of constraints found so far.
+        constraints: usize,
+        /// Number of constraints the code vector can absorb.
+        capacity: usize,
+    },
+    /// The linear constraints are linearly dependent and inconsistent.
+    #[error("failed to solve linear equations")]
+    Unsolvable,
+    /// The number of points exceeds what the solver can index.
+    #[error("too many points (={0})")]
+    TooManyPoints(usize),
+    /// Inputs are invalid, e.g. duplicated keys or a code vector of a wrong length.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    /// Encoding failed with every key allowed by the [RetryPolicy].
+    #[error("encoding failed {attempts} times. last error: {last}")]
+    RetriesExhausted {
+        /// Number of attempts.
+        attempts: usize,
+        /// Error of the last attempt.
+        last: Box<SolverError>,
+    },
+}
+
+impl SolverError {
+    /// Whether encoding may succeed with another [AuxInfo](Solver::AuxInfo).
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            SolverError::TooManyConstraints { .. } | SolverError::Unsolvable
+        )
+    }
+}
+
+/// How many times [encode_with_retry] tries to encode with fresh [AuxInfo](Solver::AuxInfo).
+///
+/// Only [retryable](SolverError::is_retryable) errors are retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of encodings including the first one. It must be positive.
+    pub max_attempts: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 2 }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        Self { max_attempts: 1 }
+    }
+}
+
+/// Options deciding solver parameters together with the set size.
+///
+/// Encoding fails with probability about $`2^{-\lambda}`$ for a random hash key.
+/// Each solver documents how the options are reflected to its parameters in its `calc_params_with`.
+///
+/// Both parties must use the same options except for `retry`, which only the encoding party uses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolverOptions {
+    /// Statistical security parameter $`\lambda`$.
+    pub lambda: usize,
+    /// Ratio of the main part of the code vector to the set size. `None` means the solver's default.
+    ///
+    /// A smaller expansion gives a shorter code vector (so less VOLE correlations and communication)
+    /// at the cost of a higher failure probability, which is no longer bounded by $`2^{-\lambda}`$ below a solver specific threshold.
+    pub expansion: Option<f64>,
+    /// Number of extra dense columns (stash) absorbing constraints which the main part cannot.
+    ///
+    /// Each of them lets encoding succeed with one more independent constraint, e.g. one more cycle of the cuckoo graph in [PaxosSolver].
+    pub stash_size: usize,
+    /// Retry policy of encoding.
+    pub retry: RetryPolicy,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self {
+            lambda: DEFAULT_LAMBDA,
+            expansion: None,
+            stash_size: 0,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl SolverOptions {
+    /// Options with statistical security parameter `lambda` and the default expansion.
+    pub fn with_lambda(lambda: usize) -> Self {
+        Self {
+            lambda,
+            ..Default::default()
+        }
+    }
+}
+
 /// Trait for the solver.
 pub trait Solver<FF: FiniteField> {
+    /// Stable name of the solver sent in the [handshake](crate::preprocessed::handshake). Change it when the encoding changes.
+    const ID: &'static str;
+
+    /// [ID](Solver::ID) together with the ID of the hash function of the solver if any, e.g. `paxos(aes)`.
+    fn id() -> String {
+        Self::ID.to_owned()
+    }
+
     /// Auxillary information for the solver. e.g. shared seeds to create random matrix used in [PaxosSolver].
     /// Auxillary information is decided according to set size.
     type AuxInfo: 'static + Clone + Copy + Send;
@@ -56,8 +181,13 @@ pub trait Solver<FF: FiniteField> {
         rng: &mut RNG,
     ) -> Result<Self::AuxInfo, Error>;
 
-    /// Calculate parameters for the solver according to set size.
-    fn calc_params(n: usize) -> Self::Params;
+    /// Calculate parameters for the solver according to set size with the default [SolverOptions].
+    fn calc_params(n: usize) -> Self::Params {
+        Self::calc_params_with(n, SolverOptions::default())
+    }
+
+    /// Calculate parameters for the solver according to set size and [SolverOptions].
+    fn calc_params_with(n: usize, options: SolverOptions) -> Self::Params;
 
     /// Encode points $`(\in (\mathbb{F} \times \mathbb{F})^n)`$ into code vector $`P \in \mathbb{F}^m`$.
     fn encode<RNG: CryptoRng + Rng>(
@@ -69,4 +199,59 @@ pub trait Solver<FF: FiniteField> {
 
     /// Decode code vector $`P`$ and value $`x \in \mathbb{F}`$ into value $`y \in \mathbb{F}`$ which corresponds to $`x`$.
     fn decode(p: &[FF], x: FF, aux: Self::AuxInfo, params: Self::Params) -> Result<FF, Error>;
+
+    /// Decode code vector $`P`$ at all of `xs` at once.
+    ///
+    /// The default implementation calls [decode](Solver::decode) for each $`x`$. Solvers override it to share hash computation or run it in parallel.
+    fn decode_batch(
+        p: &[FF],
+        xs: &[FF],
+        aux: Self::AuxInfo,
+        params: Self::Params,
+    ) -> Result<Vec<FF>, Error> {
+        xs.iter()
+            .map(|x| Self::decode(p, *x, aux, params))
+            .collect()
+    }
+}
+
+/// Encode `points` with a fresh [AuxInfo](Solver::AuxInfo) until it succeeds or `policy` gives up.
+///
+/// Returns the code vector and the [AuxInfo](Solver::AuxInfo) used for it.
+/// Errors other than [retryable](SolverError::is_retryable) [SolverError]s are returned immediately.
+pub fn encode_with_retry<FF, S, RNG>(
+    rng: &mut RNG,
+    points: &[(FF, FF)],
+    params: S::Params,
+    policy: RetryPolicy,
+) -> Result<(Vec<FF>, S::AuxInfo), Error>
+where
+    FF: FiniteField,
+    S: Solver<FF>,
+    RNG: CryptoRng + Rng,
+{
+    let mut attempts = 0;
+    loop {
+        let aux = S::gen_aux(rng).located()?;
+        attempts += 1;
+
+        let err = match S::encode(rng, points, aux, params) {
+            Ok(p) => return Ok((p, aux)),
+            Err(err) => err,
+        };
+
+        let Error::Solver(solver_err) = err.root() else {
+            return Err(err).located();
+        };
+        if !solver_err.is_retryable() {
+            return Err(err).located();
+        }
+        if attempts >= policy.max_attempts {
+            return Err(SolverError::RetriesExhausted {
+                attempts,
+                last: Box::new(solver_err.clone()),
+            })
+            .located();
+        }
+    }
 }
@@ -38,6 +38,12 @@
 //!
 //! This solver is DFS based one. So we use $`m = (2.01 \cdot n) + (\log n + 40)`$
 //!
+//! The cuckoo graph is stored in compressed sparse row form (flat `u32` arrays of offsets and neighbors),
+//! and the spanning forest is found by DFS with an explicit stack, so encoding allocates a few flat vectors
+//! and never recurses however long the paths in the graph are.
+//!
+//! The 2-core based variant with three hash functions is implemented in [TwoCoreSolver](crate::solver::TwoCoreSolver).
+//!
 //! See the appendix B and figure 7 in full version of "PSI from PaXoS: Fast, Malicious Private Set Intersection"
 //! @ <https://eprint.iacr.org/2020/193>
 //!
@@ -50,7 +56,7 @@
 //! use rand::Rng;
 //! use scuttlebutt::AesRng;
 //! use preprocessing_mpsi_with_vole::solver::{Solver, PaxosSolver};
-//! use anyhow::Result;
+//! use preprocessing_mpsi_with_vole::error::Result;
 //! # fn try_main() -> Result<()> {
 //!
 //! let mut rng: AesRng = AesRng::new();
@@ -65,14 +71,14 @@
 //!     .collect();
 //!
 //! // Encoding points to vector.
-//! let p: Vec<F128b> = PaxosSolver::encode(&mut rng, &points, aux, params)?;
+//! let p: Vec<F128b> = PaxosSolver::<F128b>::encode(&mut rng, &points, aux, params)?;
 //!
 //! // Vector p has the information correspondig value of each x is x * x.
 //!
 //! // Decoding vector to corresponding values.
 //! let decoded_values: Vec<F128b> = set
 //!     .iter()
-//!     .map(|x| PaxosSolver::decode(&p, *x, aux, params))
+//!     .map(|x| PaxosSolver::<F128b>::decode(&p, *x, aux, params))
 //!     .collect::<Result<_>>()?;
 //!
 //! let values: Vec<F128b> = points.iter().map(|(_, y)| *y).collect();
@@ -90,47 +96,36 @@
 //! The Paxos solver uses the Paxos algorithm.
 
 use super::*;
-use anyhow::{bail, Context, Result};
+use crate::error::{Context, Result};
+use crate::hasher::{AesHasher, KeyedHasher};
+use crate::parallel_utils::{par_map, Threads};
 use gaussian_eliminations::gaussian_elimination;
 use rand::distributions::{Distribution, Standard};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::field::FiniteField as FF;
-use scuttlebutt::AbstractChannel;
-use sha2::{Digest, Sha256};
-use std::cell::RefCell;
+use scuttlebutt::{AbstractChannel, Block};
 use std::marker::PhantomData;
-use std::rc::{Rc, Weak};
 
-// H_i: key x F -> [m]
-#[inline]
-fn hash2index<F: FF>(k: u64, x: F, max: usize) -> usize {
-    let mut hasher = Sha256::new();
-    hasher.update(k.to_be_bytes());
-    hasher.update(x.to_bytes());
-    let res = hasher.finalize();
-    let res = res.as_slice();
-    let res = res[0..8].try_into().unwrap();
-    let res = u64::from_be_bytes(res);
-    (res as usize) % max
-}
+mod parallel;
+#[cfg(test)]
+mod reference;
 
-// r: key x F -> {0, 1}^r_size
+/// From this set size, [PaxosSolver::encode](Solver::encode) uses the parallel encoding path.
+pub const PARALLEL_ENCODE_THRESHOLD: usize = 1 << 14;
+
+// (h_1(x), h_2(x), r(x)) by one hash call
 #[inline]
-fn r<F: FF>(k: u64, x: F, m: usize) -> Vec<bool> {
-    let mut hasher = Sha256::new();
-    hasher.update(k.to_be_bytes());
-    hasher.update(x.to_bytes());
-    let res = hasher.finalize();
-
-    res.iter()
-        .flat_map(|&byte| (0..8).map(move |i| byte & (1 << i) != 0))
-        .take(m)
-        .collect()
+pub(crate) fn hash_point<F: FF, H: KeyedHasher>(
+    hasher: &H,
+    x: F,
+    l_size: usize,
+    r_size: usize,
+) -> (usize, usize, Vec<bool>) {
+    let ([i, j], bits) = hasher.indices_and_bits::<F, 2>(x, l_size, r_size);
+    (i, j, bits)
 }
 
-fn calc_r_inner_product<F: FF>(x: F, vec_r: &[F], k3: u64, r_size: usize) -> F {
-    let bits = r(k3, x, r_size);
-
+pub(crate) fn inner_product_by_bits<F: FF>(bits: &[bool], vec_r: &[F]) -> F {
     let mut sum = F::zero();
     for (i, b) in bits.iter().enumerate() {
         if *b {
@@ -144,10 +139,11 @@ fn calc_r_inner_product<F: FF>(x: F, vec_r: &[F], k3: u64, r_size: usize) -> F {
 /// Solver for PaXoS algorithm.
 ///
 /// Please look the parent document ( [crate::solver::paxos] ) for usage example.
-pub struct PaxosSolver<F>(PhantomData<F>)
+pub struct PaxosSolver<F, H = AesHasher>(PhantomData<(F, H)>)
 where
     F: FF,
-    Standard: Distribution<F>;
+    Standard: Distribution<F>,
+    H: KeyedHasher;
 
 /// Parameters for PaXoS solver. It contains $`|L|`$ and $`|R|`$.
 #[derive(Clone, Copy)]
@@ -162,22 +158,25 @@ impl SolverParams for PaxosSolverParams {
     }
 }
 
-impl<F> Solver<F> for PaxosSolver<F>
+impl<F, H> Solver<F> for PaxosSolver<F, H>
 where
     F: FF,
     Standard: Distribution<F>,
+    H: KeyedHasher,
 {
-    /// Keys for hash functions. Keys are generated randomly by [gen_aux](PaxosSolver::gen_aux).
-    type AuxInfo = (u64, u64, u64);
+    const ID: &'static str = "paxos";
+
+    fn id() -> String {
+        format!("{}({})", Self::ID, H::ID)
+    }
+
+    /// Key of the hash function `H`. Key is generated randomly by [gen_aux](PaxosSolver::gen_aux).
+    type AuxInfo = Block;
     /// PaxosSolver Parameters consists of $`|L|`$ and $`|R|`$.
     type Params = PaxosSolverParams;
 
     fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo> {
-        let k1 = rng.gen::<u64>();
-        let k2 = rng.gen::<u64>();
-        let k3 = rng.gen::<u64>();
-
-        Ok((k1, k2, k3))
+        Ok(rng.gen::<Block>())
     }
 
     fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -185,16 +184,7 @@ where
         _rng: &mut RNG,
         aux: Self::AuxInfo,
     ) -> Result<()> {
-        let (k1, k2, k3) = aux;
-        channel
-            .write_u64(k1)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        channel
-            .write_u64(k2)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        channel
-            .write_u64(k3)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        channel.write_block(&aux).located()?;
 
         Ok(())
     }
@@ -203,23 +193,25 @@ where
         channel: &mut C,
         _rng: &mut RNG,
     ) -> Result<Self::AuxInfo> {
-        let k1 = channel
-            .read_u64()
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let k2 = channel
-            .read_u64()
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let k3 = channel
-            .read_u64()
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        Ok((k1, k2, k3))
+        let aux = channel.read_block().located()?;
+
+        Ok(aux)
     }
 
-    fn calc_params(n: usize) -> PaxosSolverParams {
-        let l_size = 2 * n + n / 100;
+    /// $`|L| = e \cdot n`$ where $`e`$ is the expansion (default $`2.01`$, must be larger than $`2`$) and $`|R| = \lceil \log n \rceil + \lambda + s`$
+    /// where $`s`$ is the stash size.
+    ///
+    /// With $`e > 2`$, the cuckoo graph has at most $`\log n`$ independent cycles except with negligible probability,
+    /// and the constraints given by them are linearly dependent (so encoding fails) with probability at most $`2^{-\lambda}`$.
+    /// The stash is a dense overflow region: it lets up to $`\log n + s`$ cycles be absorbed with the same failure probability,
+    /// which matters for small $`n`$ where the number of cycles has a heavy tail compared to $`\log n`$.
+    fn calc_params_with(n: usize, options: SolverOptions) -> PaxosSolverParams {
+        let l_size = match options.expansion {
+            None => 2 * n + n / 100,
+            Some(e) => (e * n as f64).ceil() as usize,
+        };
         let logn = n.next_power_of_two().trailing_zeros() as usize;
-        let r_size = logn + 40;
+        let r_size = logn + options.lambda + options.stash_size;
 
         PaxosSolverParams { l_size, r_size }
     }
@@ -227,395 +219,356 @@ where
     /// Encode points to a code vector.
     ///
     /// This function take $`O(n \lambda)`$ where $`n`$ is set size and $`\lambda`$ is the statistical security parameter.
+    ///
+    /// For $`n \geq`$ [PARALLEL_ENCODE_THRESHOLD], connected components of the cuckoo graph are processed in parallel.
     fn encode<RNG: CryptoRng + Rng>(
         rng: &mut RNG,
         points: &[(F, F)],
-        aux: (u64, u64, u64),
+        aux: Block,
         params: Self::Params,
     ) -> Result<Vec<F>> {
-        // 1. Construct the Cuckoo graph $G_{h_1, h_2, X}$ for $X = \{x_1, \ldots, x_n\}$.
-        let graph = construct_cuckoo_graph(points, aux, params);
-
-        // 2. Initialize variables $L$ and $R$ and an initialliy empty set of linear constraints S.
-        let mut vec_l: Vec<F> = (0..params.l_size).map(|_| rng.gen()).collect::<Vec<_>>();
-        let mut vec_r: Vec<F> = (0..params.r_size).map(|_| rng.gen()).collect::<Vec<_>>();
-
-        // 3. Perform a DFS on $G_{h_1, h_2, X}$.
-        let (constraints, graph) = dfs_to_find_constraints(&graph, aux, params);
-
-        if constraints.len() > params.r_size {
-            bail!("too many constraints");
-        }
-
-        if constraints.len() > 0 {
-            // 4. Solve for variables R satisfying the constraints of system S.
-            let equations_w = gaussian_elimination(constraints).with_context(|| {
-                format!("error in gaussian_elimination at @{}:{}", file!(), line!())
-            })?;
-            let Some(equations) = equations_w else {
-                bail!("failed to solve linear equations");
-            };
-
-            // 4'. Adjust vec_r by equations.
-            adjust_vec_r(&equations, &mut vec_r);
+        if points.len() >= PARALLEL_ENCODE_THRESHOLD && Threads::current().num_threads() > 1 {
+            parallel::encode_parallel::<F, H, RNG>(rng, points, aux, params)
+        } else {
+            encode_sequential::<F, H, RNG>(rng, points, aux, params)
         }
-
-        // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-        dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
-
-        // 6. Output D = L || R
-        let mut result = vec_l;
-        result.extend(vec_r);
-
-        Ok(result)
     }
 
-    fn decode(p: &[F], x: F, aux: (u64, u64, u64), params: Self::Params) -> Result<F> {
-        let (k1, k2, k3) = aux;
+    fn decode(p: &[F], x: F, aux: Block, params: Self::Params) -> Result<F> {
         let PaxosSolverParams { l_size, r_size } = params;
 
-        let i = hash2index(k1, x, l_size);
-        let j = hash2index(k2, x, l_size);
+        let (i, j, bits) = hash_point(&H::new(aux), x, l_size, r_size);
         let l1 = p[i];
         let l2 = p[j];
         let vec_r = &p[l_size..];
-        let inner_product = calc_r_inner_product(x, vec_r, k3, r_size);
+        let inner_product = inner_product_by_bits(&bits, vec_r);
 
         Ok(l1 + l2 + inner_product)
     }
-}
 
-// (boolean vector, F) tuple to construct constraints.
-#[derive(Clone, Debug)]
-pub(crate) struct ConstraintParts<F: FF> {
-    v: Vec<bool>,
-    f: F,
-}
-
-impl<F: FF> ConstraintParts<F> {
-    pub(crate) fn new(v: Vec<bool>, f: F) -> Self {
-        Self { v, f }
-    }
+    /// Decode code vector $`P`$ at all of `xs` at once.
+    ///
+    /// The hash function is keyed only once, and large batches are decoded in parallel.
+    fn decode_batch(p: &[F], xs: &[F], aux: Block, params: Self::Params) -> Result<Vec<F>> {
+        let PaxosSolverParams { l_size, r_size } = params;
 
-    pub(crate) fn zero(r_size: usize) -> Self {
-        Self {
-            v: vec![false; r_size],
-            f: F::zero(),
+        if p.len() != l_size + r_size {
+            return Err(SolverError::InvalidInput(format!(
+                "p.len() (={}) != code length (={})",
+                p.len(),
+                l_size + r_size
+            ))
+            .into());
         }
-    }
 
-    pub(crate) fn add_other(&self, other: &Self) -> Self {
-        let mut v = self.v.clone();
-        for (i, val) in other.v.iter().enumerate() {
-            v[i] ^= val;
-        }
-        let f = self.f + other.f;
+        let hasher = H::new(aux);
+        let vec_r = &p[l_size..];
 
-        Self { v, f }
-    }
+        par_map(xs, |x| {
+            let (i, j, bits) = hash_point(&hasher, *x, l_size, r_size);
 
-    pub(crate) fn into(self) -> (Vec<bool>, F) {
-        (self.v, self.f)
+            Ok(p[i] + p[j] + inner_product_by_bits(&bits, vec_r))
+        })
     }
 }
 
-pub(crate) use ConstraintParts as CP;
+fn adjust_vec_r<F: FF>(equations: &[(usize, Vec<bool>, F)], vec_r: &mut [F]) {
+    for (i, bits, val) in equations.iter() {
+        let mut sum = val.clone();
+        for (j, b) in bits.iter().enumerate() {
+            if *i == j {
+                continue;
+            }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub(crate) enum VisitStatus {
-    NotVisited,
-    VisitedOnce,  // for find constraints
-    VisitedTwice, // for calc vec_l
+            if *b {
+                sum += vec_r[j];
+            }
+        }
+        vec_r[*i] = sum;
+    }
 }
 
-use VisitStatus::*;
-
-#[derive(Debug)]
-pub(crate) struct Edge<F: FF> {
-    point: (F, F),
-    visit_status: VisitStatus,
-    back_edge: bool,
-}
+impl<F, H> PaxosSolver<F, H>
+where
+    F: FF,
+    Standard: Distribution<F>,
+    H: KeyedHasher,
+{
+    /// Encode points on the current thread regardless of the set size.
+    pub fn encode_sequential<RNG: CryptoRng + Rng>(
+        rng: &mut RNG,
+        points: &[(F, F)],
+        aux: Block,
+        params: PaxosSolverParams,
+    ) -> Result<Vec<F>> {
+        encode_sequential::<F, H, RNG>(rng, points, aux, params)
+    }
 
-impl<F: FF> Edge<F> {
-    pub(crate) fn is_visited(&self, mode: Mode) -> bool {
-        match mode {
-            FindConstraints => self.visit_status != NotVisited,
-            CalcVecL => self.visit_status == VisitedTwice,
-        }
+    /// Encode points processing connected components of the cuckoo graph in parallel regardless of the set size.
+    pub fn encode_parallel<RNG: CryptoRng + Rng>(
+        rng: &mut RNG,
+        points: &[(F, F)],
+        aux: Block,
+        params: PaxosSolverParams,
+    ) -> Result<Vec<F>> {
+        parallel::encode_parallel::<F, H, RNG>(rng, points, aux, params)
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct DirectTo<F: FF> {
-    to: Weak<RefCell<Node<F>>>,
-    edge: Rc<RefCell<Edge<F>>>,
-}
+const NONE: u32 = u32::MAX;
 
-#[derive(Debug)]
-pub(crate) struct Node<F: FF> {
-    id: usize,
-    dirs: Vec<DirectTo<F>>,
-    visit_status: VisitStatus,
-    accumulator: CP<F>,
+// Cuckoo graph $G_{h_1, h_2, X}$ in compressed sparse row form.
+// Neighbors of node u are adj[offsets[u]..offsets[u + 1]] as (neighbor, edge index). A self loop appears once.
+struct CuckooGraph {
+    edges: Vec<(u32, u32)>,
+    offsets: Vec<usize>,
+    adj: Vec<(u32, u32)>,
 }
 
-#[derive(Clone, Copy)]
-pub(crate) enum Mode {
-    FindConstraints,
-    CalcVecL,
-}
+impl CuckooGraph {
+    fn new(l_size: usize, edges: Vec<(u32, u32)>) -> Self {
+        let mut offsets = vec![0; l_size + 1];
+        for &(i, j) in edges.iter() {
+            offsets[i as usize + 1] += 1;
+            if i != j {
+                offsets[j as usize + 1] += 1;
+            }
+        }
+        for u in 0..l_size {
+            offsets[u + 1] += offsets[u];
+        }
 
-use Mode::*;
+        let mut fill = offsets.clone();
+        let mut adj = vec![(0, 0); offsets[l_size]];
+        for (e, &(i, j)) in edges.iter().enumerate() {
+            adj[fill[i as usize]] = (j, e as u32);
+            fill[i as usize] += 1;
+            if i != j {
+                adj[fill[j as usize]] = (i, e as u32);
+                fill[j as usize] += 1;
+            }
+        }
 
-impl<F: FF> Node<F> {
-    pub(crate) fn is_visited(&self, mode: Mode) -> bool {
-        match mode {
-            FindConstraints => self.visit_status != NotVisited,
-            CalcVecL => self.visit_status == VisitedTwice,
+        Self {
+            edges,
+            offsets,
+            adj,
         }
     }
 
-    pub(crate) fn next_dirs(
-        &self,
-        mode: Mode,
-    ) -> Vec<(Rc<RefCell<Node<F>>>, Rc<RefCell<Edge<F>>>)> {
-        self.dirs
-            .iter()
-            .filter_map(|dir| {
-                let to = dir.to.upgrade().unwrap();
-                let edge = Rc::clone(&dir.edge);
-
-                if edge.borrow().is_visited(mode) || edge.borrow().back_edge {
-                    None
-                } else {
-                    Some((to, edge))
-                }
-            })
-            .collect::<Vec<_>>()
+    fn node_num(&self) -> usize {
+        self.offsets.len() - 1
     }
-}
 
-fn construct_cuckoo_graph<F: FF>(
-    points: &[(F, F)],
-    keys: (u64, u64, u64),
-    params: PaxosSolverParams,
-) -> Vec<Rc<RefCell<Node<F>>>> {
-    let (k1, k2, _) = keys;
-    let m = params.l_size; // m = |L| = 2.01 * set.len()
-    let r_size = params.r_size;
-    let mut nodes: Vec<Option<Rc<RefCell<Node<F>>>>> = vec![None; m]; // random accessable table for nodes. using it for upsert_node.
-    let mut result = Vec::with_capacity(m); // available nodes they will be included in the result.
-
-    let mut upsert_node = |i: usize| match nodes[i].as_ref() {
-        Some(node) => node.clone(),
-        None => {
-            let new_node = Rc::new(RefCell::new(Node {
-                id: i,
-                dirs: Vec::new(),
-                visit_status: NotVisited,
-                accumulator: CP::zero(r_size),
-            }));
-            nodes[i] = Some(new_node.clone());
-            result.push(new_node.clone());
-            new_node
-        }
-    };
-
-    for &point in points {
-        let x = point.0;
-        let i = hash2index(k1, x, m);
-        let j = hash2index(k2, x, m);
-
-        let node_i = upsert_node(i);
-        let node_j = upsert_node(j);
-
-        let edge = Rc::new(RefCell::new(Edge {
-            point,
-            visit_status: NotVisited,
-            back_edge: false,
-        }));
-
-        node_i.borrow_mut().dirs.push(DirectTo {
-            to: Rc::downgrade(&node_j),
-            edge: Rc::clone(&edge),
-        });
-        node_j.borrow_mut().dirs.push(DirectTo {
-            to: Rc::downgrade(&node_i),
-            edge,
-        });
+    #[inline]
+    fn neighbors(&self, u: usize) -> &[(u32, u32)] {
+        &self.adj[self.offsets[u]..self.offsets[u + 1]]
     }
 
-    result
-}
-
-// #[derive(Clone, PartialEq, Eq)]
-enum TofcRecRes<F: FF> {
-    NoProblem,
-    BackEdge(CP<F>),
-}
-
-fn dfs_to_find_constraints<F: FF>(
-    graph: &[Rc<RefCell<Node<F>>>],
-    keys: (u64, u64, u64),
-    params: PaxosSolverParams,
-) -> (Vec<(Vec<bool>, F)>, Vec<Rc<RefCell<Node<F>>>>) {
-    let k3 = keys.2;
-    let r_size = params.r_size;
-    let mut constraints = Vec::new();
-    let mut new_graph = Vec::with_capacity(graph.len());
-
-    for node in graph.iter() {
-        if node.borrow().is_visited(FindConstraints) {
-            continue;
+    #[inline]
+    fn other(&self, e: usize, u: usize) -> usize {
+        let (i, j) = self.edges[e];
+        if i as usize == u {
+            j as usize
+        } else {
+            i as usize
         }
-
-        new_graph.push(Rc::clone(node));
-
-        let total = CP::zero(r_size);
-        let _ = dfs_tofc_rec(Rc::clone(node), total, k3, r_size, &mut constraints);
     }
-
-    (constraints, new_graph)
 }
 
-use TofcRecRes::*;
-
-/*
-n1 -- e1 -> n2 -- e2 -> n3 -- e3 -> n4
-                        ^           |
-                        +---- e4 ---+
-
-e4 is back_edge. then, proper constraints is e3.cp + e4.cp
-
-to calc this, below function uses
-
-n3.acc + (n4.acc + e4.cp)
-= (e1.cp + e2.cp) + (e1.cp + e2.cp + e3.cp + e4.cp)
-= e1.cp + e1.cp + e2.cp + e2.cp + e3.cp + e4.cp
-= e3.cp + e4.cp
-
-since same cps' xoring is 0.
-*/
-
-fn dfs_tofc_rec<F: FF>(
-    node: Rc<RefCell<Node<F>>>,
-    total: CP<F>,
-    k3: u64,
-    r_size: usize,
-    result: &mut Vec<(Vec<bool>, F)>,
-) -> TofcRecRes<F> {
-    if node.borrow().is_visited(FindConstraints) {
-        let cp = node.borrow().accumulator.clone();
-        return BackEdge(cp);
-    }
-
-    {
-        let mut n = node.borrow_mut();
-        n.visit_status = VisitedOnce;
-        n.accumulator = total.clone();
-    }
+// Spanning forest of a cuckoo graph.
+// `order` lists non-root nodes so that a parent comes before its children.
+struct SpanningForest {
+    parent_edge: Vec<u32>,
+    depth: Vec<u32>,
+    order: Vec<u32>,
+    non_tree_edges: Vec<u32>,
+}
 
-    for (next_node, next_edge) in node.borrow().next_dirs(FindConstraints) {
-        // follow the case of the edge is self loop
-        if next_edge.borrow().is_visited(FindConstraints) {
+// DFS with an explicit stack, so long paths do not overflow the call stack.
+fn spanning_forest(graph: &CuckooGraph) -> SpanningForest {
+    let node_num = graph.node_num();
+    let mut parent_edge = vec![NONE; node_num];
+    let mut depth = vec![NONE; node_num];
+    let mut used = vec![false; graph.edges.len()];
+    let mut order = Vec::with_capacity(graph.edges.len());
+    let mut non_tree_edges = Vec::new();
+    let mut stack = Vec::new();
+
+    for root in 0..node_num {
+        if depth[root] != NONE || graph.neighbors(root).is_empty() {
             continue;
         }
 
-        next_edge.borrow_mut().visit_status = VisitedOnce;
+        depth[root] = 0;
+        stack.push(root);
 
-        let cp = {
-            let x = next_edge.borrow().point.0;
-            let v = r(k3, x, r_size);
-            let f = next_edge.borrow().point.1;
-            CP::new(v, f)
-        };
-        let next_total = total.add_other(&cp);
-        let res = dfs_tofc_rec(next_node, next_total.clone(), k3, r_size, result);
-
-        if let BackEdge(cp) = res {
-            next_edge.borrow_mut().back_edge = true;
+        while let Some(u) = stack.pop() {
+            for &(v, e) in graph.neighbors(u) {
+                let (v, e) = (v as usize, e as usize);
+                if used[e] {
+                    continue;
+                }
+                used[e] = true;
 
-            let cp = next_total.add_other(&cp);
-            result.push(cp.into());
+                if depth[v] == NONE {
+                    parent_edge[v] = e as u32;
+                    depth[v] = depth[u] + 1;
+                    order.push(v as u32);
+                    stack.push(v);
+                } else {
+                    non_tree_edges.push(e as u32);
+                }
+            }
         }
     }
 
-    NoProblem
+    SpanningForest {
+        parent_edge,
+        depth,
+        order,
+        non_tree_edges,
+    }
 }
 
-fn adjust_vec_r<F: FF>(equations: &[(usize, Vec<bool>, F)], vec_r: &mut [F]) {
-    for (i, bits, val) in equations.iter() {
-        let mut sum = val.clone();
-        for (j, b) in bits.iter().enumerate() {
-            if *i == j {
-                continue;
-            }
-
-            if *b {
-                sum += vec_r[j];
-            }
+// Each non-tree edge (u, v) closes the cycle u -> v -> lca -> u.
+// The constraint is the sum of (r(x), y) over the edges of the cycle.
+// `cp(e)` returns (r(x_e), y_e).
+fn find_constraints<F: FF>(
+    graph: &CuckooGraph,
+    forest: &SpanningForest,
+    r_size: usize,
+    cp: impl Fn(usize) -> (Vec<bool>, F),
+) -> Result<Vec<(Vec<bool>, F)>> {
+    let mut constraints = Vec::with_capacity(forest.non_tree_edges.len());
+
+    for &e in forest.non_tree_edges.iter() {
+        if constraints.len() >= r_size {
+            return Err(SolverError::TooManyConstraints {
+                constraints: forest.non_tree_edges.len(),
+                capacity: r_size,
+            })
+            .located();
         }
-        vec_r[*i] = sum;
-    }
-}
 
-fn dfs_to_calc_vec_l<F: FF>(
-    graph: &[Rc<RefCell<Node<F>>>],
-    keys: (u64, u64, u64),
-    params: PaxosSolverParams,
-    vec_r: &[F],
-    vec_l: &mut [F],
-) {
-    let k3 = keys.2;
-    let r_size = params.r_size;
+        let e = e as usize;
+        let (mut bits, mut f) = cp(e);
+        let (i, j) = graph.edges[e];
+        let (mut u, mut v) = (i as usize, j as usize);
 
-    for node in graph.iter() {
-        if node.borrow().is_visited(CalcVecL) {
-            continue;
+        while u != v {
+            let w = if forest.depth[u] >= forest.depth[v] {
+                &mut u
+            } else {
+                &mut v
+            };
+            let pe = forest.parent_edge[*w] as usize;
+            let (pe_bits, pe_f) = cp(pe);
+            for (b, p) in bits.iter_mut().zip(pe_bits) {
+                *b ^= p;
+            }
+            f += pe_f;
+            *w = graph.other(pe, *w);
         }
 
-        dfs_tocvl_rec(Rc::clone(node), k3, r_size, vec_r, vec_l);
+        constraints.push((bits, f));
     }
+
+    Ok(constraints)
 }
 
-fn dfs_tocvl_rec<F: FF>(
-    node: Rc<RefCell<Node<F>>>,
-    k3: u64,
-    r_size: usize,
-    vec_r: &[F],
+// Assign L along tree edges: L[v] = L[u] + <r(x_e), R> + y_e where e = (u, v) and u is the parent of v.
+// `weight(e)` returns <r(x_e), R> + y_e.
+fn calc_vec_l<F: FF>(
+    graph: &CuckooGraph,
+    forest: &SpanningForest,
+    weight: impl Fn(usize) -> F,
     vec_l: &mut [F],
 ) {
-    if node.borrow().is_visited(CalcVecL) {
-        panic!("Unreachable");
+    for &v in forest.order.iter() {
+        let v = v as usize;
+        let e = forest.parent_edge[v] as usize;
+        let u = graph.other(e, v);
+        vec_l[v] = vec_l[u] + weight(e);
     }
+}
 
-    node.borrow_mut().visit_status = VisitedTwice;
-    let u = node.borrow().id;
-
-    for (next_node, next_edge) in node.borrow().next_dirs(CalcVecL) {
-        // follow the case of the edge is self loop
-        if next_edge.borrow().is_visited(CalcVecL) {
-            continue;
-        }
+fn encode_sequential<F: FF, H: KeyedHasher, RNG: CryptoRng + Rng>(
+    rng: &mut RNG,
+    points: &[(F, F)],
+    aux: Block,
+    params: PaxosSolverParams,
+) -> Result<Vec<F>>
+where
+    Standard: Distribution<F>,
+{
+    let hasher = H::new(aux);
+    let PaxosSolverParams { l_size, r_size } = params;
+
+    if l_size >= NONE as usize || points.len() >= NONE as usize {
+        return Err(SolverError::TooManyPoints(points.len())).located();
+    }
+
+    // 1. Construct the Cuckoo graph $G_{h_1, h_2, X}$ for $X = \{x_1, \ldots, x_n\}$.
+    let edges = points
+        .iter()
+        .map(|(x, _)| {
+            // r(x) is not needed here. Indices do not depend on the number of bits.
+            let ([i, j], _) = hasher.indices_and_bits::<F, 2>(*x, l_size, 0);
+            (i as u32, j as u32)
+        })
+        .collect::<Vec<_>>();
+    let graph = CuckooGraph::new(l_size, edges);
+
+    // 2. Initialize variables $L$ and $R$.
+    let mut vec_l: Vec<F> = (0..l_size).map(|_| rng.gen()).collect::<Vec<_>>();
+    let mut vec_r: Vec<F> = (0..r_size).map(|_| rng.gen()).collect::<Vec<_>>();
+
+    // 3. Find a spanning forest and the linear constraints from non-tree edges.
+    let forest = spanning_forest(&graph);
+    let constraints = find_constraints(&graph, &forest, r_size, |e| {
+        let (x, y) = points[e];
+        let (_, _, bits) = hash_point(&hasher, x, l_size, r_size);
+        (bits, y)
+    })
+    .located()?;
+
+    if !constraints.is_empty() {
+        // 4. Solve for variables R satisfying the constraints.
+        let equations_w =
+            gaussian_elimination(constraints).context("error in gaussian_elimination")?;
+        let Some(equations) = equations_w else {
+            return Err(SolverError::Unsolvable).located();
+        };
 
-        next_edge.borrow_mut().visit_status = VisitedTwice;
-        let v = next_node.borrow().id;
+        // 4'. Adjust vec_r by equations.
+        adjust_vec_r(&equations, &mut vec_r);
+    }
 
-        let x = next_edge.borrow().point.0;
-        let inner_product = calc_r_inner_product(x, vec_r, k3, r_size);
-        let y = next_edge.borrow().point.1;
+    // 5. Compute $L$ along the spanning forest.
+    calc_vec_l(
+        &graph,
+        &forest,
+        |e| {
+            let (x, y) = points[e];
+            let (_, _, bits) = hash_point(&hasher, x, l_size, r_size);
+            inner_product_by_bits(&bits, &vec_r) + y
+        },
+        &mut vec_l,
+    );
 
-        vec_l[v] = vec_l[u] + inner_product + y;
+    // 6. Output D = L || R
+    let mut result = vec_l;
+    result.extend(vec_r);
 
-        dfs_tocvl_rec(next_node, k3, r_size, vec_r, vec_l);
-    }
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::hash_utils::hash_f;
+    use crate::hash_utils::{hash_f, oracle};
+    use crate::hasher::Sha256Hasher;
     use rand::distributions::{Distribution, Standard};
     use rand::Rng;
     use scuttlebutt::field::{F128b, FiniteField};
@@ -641,10 +594,10 @@ mod tests {
 
         let points = set
             .iter()
-            .map(|x| (*x, hash_f(*x).unwrap()))
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
             .collect::<Vec<_>>();
 
-        let p = PaxosSolver::encode(&mut rng, &points, aux, params).unwrap();
+        let p = PaxosSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
 
         if verbose {
             println!("p: {:?}", p);
@@ -652,7 +605,7 @@ mod tests {
 
         let reconstructed_ys = set
             .iter()
-            .map(|x| PaxosSolver::decode(&p, *x, aux, params).unwrap())
+            .map(|x| PaxosSolver::<F128b>::decode(&p, *x, aux, params).unwrap())
             .collect::<Vec<_>>();
 
         let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
@@ -680,452 +633,238 @@ mod tests {
     }
 
     #[test]
-    fn test_paxos_2e20() {
-        test_paxos_base(1 << 20, false);
-    }
-}
-
-mod graphs_for_test;
+    fn test_paxos_decode_batch() {
+        for n in [10, 1 << 14] {
+            let set = create_set::<F128b>(n);
 
-#[cfg(test)]
-mod detail_tests {
-    use super::graphs_for_test::*;
-    use super::*;
-    use crate::set_utils::FromU128;
-    use scuttlebutt::field::F128b;
-    use scuttlebutt::AesRng;
-    // use rand::Rng;
+            let mut rng = AesRng::new();
+            let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+            let params = PaxosSolver::<F128b>::calc_params(set.len());
 
-    fn test_construct_cuckoo_graph_base(n: usize, verbose: bool) {
-        let mut rng = AesRng::new();
-        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(n);
+            let points = set
+                .iter()
+                .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+                .collect::<Vec<_>>();
 
-        let points = (0..n)
-            .map(|i| (F128b::from_u128(i as _), F128b::from_u128(i as _)))
-            .collect::<Vec<(F128b, F128b)>>();
+            let p = PaxosSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
 
-        let graph = construct_cuckoo_graph(&points, aux, params);
+            let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+            let decoded = PaxosSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap();
 
-        let (analyzed_result, _new_graph) = graph_analyze(&graph, verbose, verbose);
+            assert_eq!(ys, decoded);
 
-        println!("analyzed_result:\n{}", analyzed_result);
-    }
+            let others = create_set::<F128b>(n);
+            let expected = others
+                .iter()
+                .map(|x| PaxosSolver::<F128b>::decode(&p, *x, aux, params).unwrap())
+                .collect::<Vec<_>>();
 
-    #[test]
-    fn test_construct_cuckoo_graph_small() {
-        for n in 0..=10 {
-            test_construct_cuckoo_graph_base(n, true);
+            assert_eq!(
+                PaxosSolver::<F128b>::decode_batch(&p, &others, aux, params).unwrap(),
+                expected
+            );
         }
     }
 
     #[test]
-    fn test_construct_cuckoo_graph_big() {
-        for n in 10..100 {
-            test_construct_cuckoo_graph_base(n, false);
-        }
+    fn test_paxos_lambda() {
+        let n = 1 << 12;
+        let default = PaxosSolver::<F128b>::calc_params(n);
+        let same = PaxosSolver::<F128b>::calc_params_with(n, SolverOptions::default());
+        assert_eq!((same.l_size, same.r_size), (default.l_size, default.r_size));
 
-        for e in 10..21 {
-            let n = 2usize.pow(e);
-            test_construct_cuckoo_graph_base(n, false);
-        }
-    }
+        for lambda in [40, 60, 80] {
+            let set = create_set::<F128b>(n);
 
-    #[test]
-    fn test_construct_cuckoo_graph_2e20() {
-        test_construct_cuckoo_graph_base(1 << 20, false);
-    }
+            let mut rng = AesRng::new();
+            let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+            let params =
+                PaxosSolver::<F128b>::calc_params_with(n, SolverOptions::with_lambda(lambda));
 
-    #[test]
-    fn test_find_constraints_for_fixnum_graphs() {
-        let mut rng = AesRng::new();
-        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(20);
-
-        let create_funcs: Vec<fn(PaxosSolverParams) -> Vec<Rc<RefCell<Node<F128b>>>>> = vec![
-            create_specific_graph_empty,
-            create_big_dipper_graph,
-            create_triangle_graph,
-            create_double_constraints_graph_0,
-            create_double_constraints_graph_1,
-            create_multi_constraints_graph,
-            create_bird_graph,
-        ];
-
-        let verbose = true;
-
-        for func in create_funcs.into_iter() {
-            println!("\n####################################\n");
+            assert_eq!(params.l_size, default.l_size);
+            assert_eq!(params.r_size, default.r_size + lambda - DEFAULT_LAMBDA);
 
-            let graph = func(params);
+            let points = set
+                .iter()
+                .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+                .collect::<Vec<_>>();
 
-            let (analyzed_result, _new_graph) = graph_analyze(&graph, verbose, verbose);
+            let p = PaxosSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
+            assert_eq!(p.len(), params.code_length());
 
-            println!("analyzed_result:\n{}", analyzed_result);
+            let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+            let decoded = PaxosSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap();
 
-            let graph = func(params);
-
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
-
-            println!("constraints ({}):", constraints.len());
-
-            for cons in constraints.into_iter() {
-                println!("{:?}", cons);
-            }
+            assert_eq!(ys, decoded);
         }
-    }
-
-    fn test_find_constraints_for_specific_graphs_base(n: usize, verbose: bool) {
-        let mut rng = AesRng::new();
-        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(2 * n);
-
-        let create_funcs: Vec<fn(PaxosSolverParams, usize) -> Vec<Rc<RefCell<Node<F128b>>>>> = vec![
-            create_specific_graph_no_edge,
-            create_specific_graph_self_loop,
-            create_specific_graph_straight,
-            create_specific_graph_discrete_straight,
-            create_specific_graph_straight_with_self_loop,
-            create_specific_graph_straight_with_back_edges,
-        ];
-
-        for func in create_funcs.into_iter() {
-            println!("\n####################################\n");
-
-            let graph = func(params, n);
-
-            let (analyzed_result, _new_graph) = graph_analyze(&graph, verbose, verbose);
-
-            println!("analyzed_result:\n{}", analyzed_result);
-
-            let graph = func(params, n);
 
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
-
-            println!("constraints ({}):", constraints.len());
-
-            if verbose {
-                for cons in constraints.into_iter() {
-                    println!("{:?}", cons);
-                }
-            }
-        }
-    }
-
-    #[test]
-    fn test_find_constraints_for_specific_graphs_small() {
-        for n in 0..=10 {
-            println!(
-                "\n%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%% {} %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%\n",
-                n
-            );
-            test_find_constraints_for_specific_graphs_base(n, true);
-        }
+        let options = SolverOptions {
+            expansion: Some(2.5),
+            ..Default::default()
+        };
+        let params = PaxosSolver::<F128b>::calc_params_with(n, options);
+        assert_eq!(params.l_size, n * 5 / 2);
     }
 
-    /* // stack overflow. the overflow probability would be small in random case. so we don't need to test this.
     #[test]
-    fn test_find_constraints_for_specific_graphs_big() {
-        for e in 10..=20 {
-            println!(
-                "\n%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%% 2^{} %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%\n",
-                e
-            );
-            test_find_constraints_for_specific_graphs_base(1 << e, false);
-        }
-    }
-    */
+    fn test_paxos_stash() {
+        let n = 64;
+        let set = create_set::<F128b>(n);
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
 
-    fn test_find_constraints_base(n: usize, verbose: bool) {
         let mut rng = AesRng::new();
         let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(n);
-
-        let points = (0..n)
-            .map(|i| (F128b::from_u128(i as _), F128b::from_u128(i as _)))
-            .collect::<Vec<(F128b, F128b)>>();
-
-        let graph = construct_cuckoo_graph(&points, aux, params);
 
-        let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
-
-        println!("constraints ({}):", constraints.len());
-
-        if verbose {
-            for cons in constraints.into_iter() {
-                println!("{:?}", cons);
-            }
-        }
-    }
+        // |L| = 16 gives at least 64 - 16 + 1 = 49 independent cycles, more than |R| = 6 + 40.
+        let options = SolverOptions {
+            expansion: Some(0.25),
+            ..Default::default()
+        };
+        let params = PaxosSolver::<F128b>::calc_params_with(n, options);
 
-    #[test]
-    fn test_find_constraints_small() {
-        for n in 0..=10 {
-            test_find_constraints_base(n, true);
-        }
-    }
+        let err = PaxosSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap_err();
+        let Error::Solver(err) = err.root() else {
+            panic!("unexpected error: {:?}", err);
+        };
+        assert!(matches!(err, SolverError::TooManyConstraints { .. }));
+        assert!(err.is_retryable());
+
+        let policy = RetryPolicy { max_attempts: 3 };
+        let err = encode_with_retry::<_, PaxosSolver<F128b>, _>(&mut rng, &points, params, policy)
+            .unwrap_err();
+        assert!(matches!(
+            err.root(),
+            Error::Solver(SolverError::RetriesExhausted { attempts: 3, .. })
+        ));
+
+        // at most 64 cycles, which the stash absorbs
+        let options = SolverOptions {
+            stash_size: n,
+            ..options
+        };
+        let params = PaxosSolver::<F128b>::calc_params_with(n, options);
 
-    #[test]
-    fn test_find_constraints_big() {
-        for n in 10..100 {
-            test_find_constraints_base(n, false);
-        }
+        let (p, aux) =
+            encode_with_retry::<_, PaxosSolver<F128b>, _>(&mut rng, &points, params, options.retry)
+                .unwrap();
+        assert_eq!(p.len(), params.code_length());
 
-        for e in 10..21 {
-            let n = 2usize.pow(e);
-            test_find_constraints_base(n, false);
-        }
-    }
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        let decoded = PaxosSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap();
 
-    #[test]
-    fn test_find_constraints_2e20() {
-        test_find_constraints_base(1 << 20, false);
+        assert_eq!(ys, decoded);
     }
 
     #[test]
-    fn test_dfs_to_calc_vec_l_for_fixnum_graphs() {
-        let mut rng = AesRng::new();
-        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(20);
-
-        let create_funcs: Vec<fn(PaxosSolverParams) -> Vec<Rc<RefCell<Node<F128b>>>>> = vec![
-            create_specific_graph_empty,
-            create_big_dipper_graph,
-            create_triangle_graph,
-            create_double_constraints_graph_0,
-            create_double_constraints_graph_1,
-            create_multi_constraints_graph,
-            create_bird_graph,
-        ];
-
-        let verbose = true;
-
-        for func in create_funcs.into_iter() {
-            println!("\n####################################\n");
-
-            // 2. Initialize variables $L$ and $R$ and an initialliy empty set of linear constraints S.
-            let mut vec_l: Vec<F128b> = (0..params.l_size).map(|_| rng.gen()).collect::<Vec<_>>();
-            let mut vec_r: Vec<F128b> = (0..params.r_size).map(|_| rng.gen()).collect::<Vec<_>>();
-
-            let graph = func(params);
-
-            let (analyzed_result, _new_graph) = graph_analyze(&graph, verbose, verbose);
+    fn test_paxos_sha256() {
+        type S = PaxosSolver<F128b, Sha256Hasher>;
 
-            println!("analyzed_result:\n{}", analyzed_result);
+        for n in [1, 10, 100, 1 << 10, 1 << 15] {
+            let set = create_set::<F128b>(n);
 
-            let graph = func(params);
+            let mut rng = AesRng::new();
+            let aux = S::gen_aux(&mut rng).unwrap();
+            let params = S::calc_params(set.len());
 
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+            let points = set
+                .iter()
+                .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+                .collect::<Vec<_>>();
 
-            println!("constraints ({}):", constraints.len());
-
-            for cons in constraints.iter() {
-                println!("{:?}", cons);
-            }
-
-            if constraints.len() > params.r_size {
-                panic!("too many constraints");
-            }
-
-            if constraints.len() > 0 {
-                // 4. Solve for variables R satisfying the constraints of system S.
-                let equations_w = match gaussian_elimination(constraints) {
-                    Ok(equs) => equs,
-                    Err(e) => {
-                        println!("gaussian elimination Error: {:?}", e);
-                        continue;
-                    }
-                };
-                let Some(equations) = equations_w else {
-                    println!("failed to solve linear equations");
-                    continue;
-                };
-
-                // 4'. Adjust vec_r by equations.
-                adjust_vec_r(&equations, &mut vec_r);
-            } else {
-                println!("no constraints");
-            }
+            let p = S::encode(&mut rng, &points, aux, params).unwrap();
 
-            // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-            dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
+            let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+            let decoded = S::decode_batch(&p, &set, aux, params).unwrap();
 
-            // 6. Output D = L || R
-            let mut result = vec_l;
-            result.extend(vec_r);
+            assert_eq!(ys, decoded);
 
-            println!("result (len: {}): {:?}", result.len(), result);
+            // the code is bound to the hash function
+            let decoded = PaxosSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap();
+            assert_ne!(ys, decoded);
         }
     }
 
-    fn test_dfs_to_calc_vec_l_for_specific_graphs_base(n: usize, verbose: bool) {
-        let mut rng = AesRng::new();
-        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(2 * n);
-
-        let create_funcs: Vec<fn(PaxosSolverParams, usize) -> Vec<Rc<RefCell<Node<F128b>>>>> = vec![
-            create_specific_graph_no_edge,
-            create_specific_graph_self_loop,
-            create_specific_graph_straight,
-            create_specific_graph_discrete_straight,
-            create_specific_graph_straight_with_self_loop,
-            create_specific_graph_straight_with_back_edges,
-        ];
-
-        for func in create_funcs.into_iter() {
-            println!("\n####################################\n");
-
-            // 2. Initialize variables $L$ and $R$ and an initialliy empty set of linear constraints S.
-            let mut vec_l: Vec<F128b> = (0..params.l_size).map(|_| rng.gen()).collect::<Vec<_>>();
-            let mut vec_r: Vec<F128b> = (0..params.r_size).map(|_| rng.gen()).collect::<Vec<_>>();
-
-            let graph = func(params, n);
-
-            let (analyzed_result, _new_graph) = graph_analyze(&graph, verbose, verbose);
-
-            println!("analyzed_result:\n{}", analyzed_result);
-
-            let graph = func(params, n);
-
-            let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+    // differential test against the reference (Rc and recursion based) encoder
+    #[test]
+    fn test_paxos_against_reference() {
+        for n in (1..=100).chain((10..=16).map(|e| 1 << e)) {
+            let set = create_set::<F128b>(n);
 
-            println!("constraints ({}):", constraints.len());
+            let mut rng = AesRng::new();
+            let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+            let params = PaxosSolver::<F128b>::calc_params(set.len());
 
-            if verbose {
-                for cons in constraints.iter() {
-                    println!("{:?}", cons);
-                }
-            }
-
-            if constraints.len() > params.r_size {
-                panic!("too many constraints");
-            }
+            let points = set
+                .iter()
+                .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+                .collect::<Vec<_>>();
 
-            if constraints.len() > 0 {
-                // 4. Solve for variables R satisfying the constraints of system S.
-                let equations_w = match gaussian_elimination(constraints) {
-                    Ok(equs) => equs,
-                    Err(e) => {
-                        println!("gaussian elimination Error: {:?}", e);
-                        continue;
-                    }
-                };
-                let Some(equations) = equations_w else {
-                    println!("failed to solve linear equations");
-                    continue;
-                };
-
-                // 4'. Adjust vec_r by equations.
-                adjust_vec_r(&equations, &mut vec_r);
-            } else {
-                println!("no constraints");
-            }
+            let p = encode_sequential::<_, AesHasher, _>(&mut rng, &points, aux, params).unwrap();
+            let p_ref = reference::encode_reference(&mut rng, &points, aux, params).unwrap();
 
-            // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-            dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
+            assert_eq!(p.len(), p_ref.len());
 
-            // 6. Output D = L || R
-            let mut result = vec_l;
-            result.extend(vec_r);
+            let decoded = PaxosSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap();
+            let decoded_ref =
+                PaxosSolver::<F128b>::decode_batch(&p_ref, &set, aux, params).unwrap();
 
-            if verbose {
-                println!("result (len: {}): {:?}", result.len(), result);
-            }
+            assert_eq!(decoded, decoded_ref);
         }
     }
 
+    // A path of 2^20 edges with a cycle at the end. Recursive DFS overflows the stack on it.
     #[test]
-    fn test_dfs_to_calc_vec_l_for_specific_graphs_small() {
-        for n in 0..=10 {
-            println!(
-                "\n%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%% {} %%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%%\n",
-                n
-            );
-            test_dfs_to_calc_vec_l_for_specific_graphs_base(n, true);
-        }
-    }
-
-    fn test_dfs_to_calc_vec_l_base(n: usize, verbose: bool) {
+    fn test_long_path() {
+        let n = 1 << 20;
+        let r_size = 60;
         let mut rng = AesRng::new();
-        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
-        let params = PaxosSolver::<F128b>::calc_params(n);
-
-        // 2. Initialize variables $L$ and $R$ and an initialliy empty set of linear constraints S.
-        let mut vec_l: Vec<F128b> = (0..params.l_size).map(|_| rng.gen()).collect::<Vec<_>>();
-        let mut vec_r: Vec<F128b> = (0..params.r_size).map(|_| rng.gen()).collect::<Vec<_>>();
 
-        let points = (0..n)
-            .map(|i| (F128b::from_u128(i as _), F128b::from_u128(i as _)))
-            .collect::<Vec<(F128b, F128b)>>();
+        let mut edges = (0..n).map(|i| (i as u32, i as u32 + 1)).collect::<Vec<_>>();
+        edges.push((n as u32, n as u32 - 2));
+        let cps = edges
+            .iter()
+            .map(|_| {
+                let bits = (0..r_size).map(|_| rng.gen()).collect::<Vec<bool>>();
+                (bits, rng.gen::<F128b>())
+            })
+            .collect::<Vec<_>>();
 
-        let graph = construct_cuckoo_graph(&points, aux, params);
+        let graph = CuckooGraph::new(n + 1, edges);
+        let forest = spanning_forest(&graph);
 
-        let (constraints, _new_graph) = dfs_to_find_constraints(&graph, aux, params);
+        assert_eq!(forest.order.len(), n);
+        assert_eq!(forest.non_tree_edges.len(), 1);
 
-        println!("constraints ({}):", constraints.len());
+        let constraints = find_constraints(&graph, &forest, r_size, |e| cps[e].clone()).unwrap();
 
-        if verbose {
-            for cons in constraints.iter() {
-                println!("{:?}", cons);
+        // the cycle consists of the last three edges whichever of them is the non-tree edge
+        let mut expected = cps[n].clone();
+        for e in [n - 2, n - 1] {
+            for (b, p) in expected.0.iter_mut().zip(cps[e].0.iter()) {
+                *b ^= p;
             }
+            expected.1 += cps[e].1;
         }
 
-        if constraints.len() > params.r_size {
-            panic!("too many constraints");
-        }
-
-        if constraints.len() > 0 {
-            // 4. Solve for variables R satisfying the constraints of system S.
-            let equations_w = gaussian_elimination(constraints).unwrap();
-            let Some(equations) = equations_w else {
-                panic!("failed to solve linear equations");
-            };
-
-            // 4'. Adjust vec_r by equations.
-            adjust_vec_r(&equations, &mut vec_r);
-        } else {
-            println!("no constraints");
-        }
-
-        // 5. Perform another DFS on $G_{h_1, h_2, X}$ to compute $L$.
-        dfs_to_calc_vec_l(&graph, aux, params, &vec_r, &mut vec_l);
-
-        // 6. Output D = L || R
-        let mut result = vec_l;
-        result.extend(vec_r);
-
-        if verbose {
-            println!("result (len: {}): {:?}", result.len(), result);
-        }
-
-        for (x, y) in points.iter() {
-            let reconstructed_y = PaxosSolver::decode(&result, *x, aux, params).unwrap();
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(constraints[0].0, expected.0);
+        assert_eq!(constraints[0].1, expected.1);
 
-            assert_eq!(*y, reconstructed_y);
-        }
-    }
+        let mut vec_l = (0..=n).map(|_| rng.gen()).collect::<Vec<F128b>>();
+        calc_vec_l(&graph, &forest, |e| cps[e].1, &mut vec_l);
 
-    #[test]
-    fn test_dfs_to_calc_vec_l_small() {
-        for n in 1..=10 {
-            test_dfs_to_calc_vec_l_base(n, true);
+        for &v in forest.order.iter() {
+            let e = forest.parent_edge[v as usize] as usize;
+            let (i, j) = graph.edges[e];
+            assert_eq!(vec_l[i as usize] + vec_l[j as usize], cps[e].1);
         }
     }
 
     #[test]
-    fn test_dfs_to_calc_vec_l_big() {
-        for n in 10..100 {
-            test_dfs_to_calc_vec_l_base(n, false);
-        }
-
-        for e in 10..21 {
-            let n = 2usize.pow(e);
-            test_dfs_to_calc_vec_l_base(n, false);
-        }
-    }
-
-    #[test]
-    fn test_dfs_to_calc_vec_l_2e20() {
-        test_dfs_to_calc_vec_l_base(1 << 20, false);
+    fn test_paxos_2e20() {
+        test_paxos_base(1 << 20, false);
     }
 }
@@ -0,0 +1,286 @@
+//! Parallel encoding path of [PaxosSolver](super::PaxosSolver).
+//!
+//! 1. Hash all points concurrently.
+//! 2. Split the cuckoo graph into connected components by union-find.
+//! 3. For each component (in parallel), build a spanning tree with an explicit stack.
+//!    Each non-tree edge closes a cycle and gives one linear constraint on $`R`$.
+//! 4. Solve all constraints at once by gaussian elimination.
+//! 5. For each component (in parallel), assign $`L`$ along the tree edges from the root.
+//!
+//! Components share no node, so steps 3 and 5 need no synchronization.
+//!
+//! Components are not balanced. The cuckoo graph has $`|L| = 2.01 \cdot n`$ nodes and $`n`$ edges,
+//! just below the density $`|L| = 2n`$ where a giant component appears, so one component is far larger than the others
+//! and its thread bounds steps 3 and 5. The speedup comes mostly from hashing (step 1) and the many small components.
+
+use super::*;
+use crate::parallel_utils::par_map;
+use std::collections::HashMap;
+
+// (h_1(x), h_2(x), r(x))
+type Hashed = (usize, usize, Vec<bool>);
+
+// Tree edges in the order from the root (parent, child, edge index) and cycle constraints of one component.
+type ComponentResult<F> = (Vec<(usize, usize, usize)>, Vec<(Vec<bool>, F)>);
+
+fn find(parents: &mut [usize], mut u: usize) -> usize {
+    while parents[u] != u {
+        parents[u] = parents[parents[u]];
+        u = parents[u];
+    }
+    u
+}
+
+// Group edges by connected components.
+fn components(hashed: &[Hashed], l_size: usize) -> Vec<Vec<usize>> {
+    let mut parents = (0..l_size).collect::<Vec<_>>();
+
+    for &(i, j, _) in hashed.iter() {
+        let (ri, rj) = (find(&mut parents, i), find(&mut parents, j));
+        if ri != rj {
+            parents[ri] = rj;
+        }
+    }
+
+    let mut comp_ids = HashMap::new();
+    let mut comps: Vec<Vec<usize>> = Vec::new();
+    for (e, &(i, _, _)) in hashed.iter().enumerate() {
+        let root = find(&mut parents, i);
+        let id = *comp_ids.entry(root).or_insert_with(|| {
+            comps.push(Vec::new());
+            comps.len() - 1
+        });
+        comps[id].push(e);
+    }
+
+    comps
+}
+
+fn xor_bits(a: &[bool], b: &[bool]) -> Vec<bool> {
+    a.iter().zip(b.iter()).map(|(x, y)| x ^ y).collect()
+}
+
+fn spanning_tree<F: FF>(
+    comp: &[usize],
+    hashed: &[Hashed],
+    points: &[(F, F)],
+    r_size: usize,
+) -> ComponentResult<F> {
+    // local node ids of the component
+    let mut local_ids: HashMap<usize, usize> = HashMap::with_capacity(comp.len() + 1);
+    let mut nodes = Vec::with_capacity(comp.len() + 1);
+    let mut local_id = |u: usize| {
+        *local_ids.entry(u).or_insert_with(|| {
+            nodes.push(u);
+            nodes.len() - 1
+        })
+    };
+    let ends = comp
+        .iter()
+        .map(|&e| (local_id(hashed[e].0), local_id(hashed[e].1)))
+        .collect::<Vec<_>>();
+
+    // local adjacency: node -> [(neighbor, local edge)]
+    let mut adj: Vec<Vec<(usize, usize)>> = vec![Vec::new(); nodes.len()];
+    for (k, &(i, j)) in ends.iter().enumerate() {
+        adj[i].push((j, k));
+        if i != j {
+            adj[j].push((i, k));
+        }
+    }
+
+    // accumulated sum of (r(x), y) along the tree path from the root.
+    // The bits of node u are acc_bits[u * r_size..(u + 1) * r_size], so one buffer serves the whole component.
+    let mut acc_bits = vec![false; nodes.len() * r_size];
+    let mut acc_f: Vec<Option<F>> = vec![None; nodes.len()];
+    let mut bits = vec![false; r_size];
+    let mut used = vec![false; comp.len()];
+
+    let mut tree_edges = Vec::with_capacity(comp.len());
+    let mut constraints = Vec::new();
+
+    let root = ends[0].0;
+    acc_f[root] = Some(F::zero());
+    let mut stack = vec![root];
+
+    while let Some(u) = stack.pop() {
+        let acc_u_f = acc_f[u].unwrap();
+        for &(v, k) in adj[u].iter() {
+            if used[k] {
+                continue;
+            }
+            used[k] = true;
+
+            let e = comp[k];
+            for ((b, &acc), &r) in bits
+                .iter_mut()
+                .zip(acc_bits[u * r_size..(u + 1) * r_size].iter())
+                .zip(hashed[e].2.iter())
+            {
+                *b = acc ^ r;
+            }
+            let f = acc_u_f + points[e].1;
+
+            match acc_f[v] {
+                Some(acc_v_f) => {
+                    // cycle: root -> u -> v -> root
+                    let acc_v_bits = &acc_bits[v * r_size..(v + 1) * r_size];
+                    constraints.push((xor_bits(&bits, acc_v_bits), f + acc_v_f));
+                }
+                None => {
+                    acc_bits[v * r_size..(v + 1) * r_size].copy_from_slice(&bits);
+                    acc_f[v] = Some(f);
+                    tree_edges.push((nodes[u], nodes[v], e));
+                    stack.push(v);
+                }
+            }
+        }
+    }
+
+    (tree_edges, constraints)
+}
+
+pub(crate) fn encode_parallel<F: FF, H: KeyedHasher, RNG: CryptoRng + Rng>(
+    rng: &mut RNG,
+    points: &[(F, F)],
+    aux: Block,
+    params: PaxosSolverParams,
+) -> Result<Vec<F>>
+where
+    Standard: Distribution<F>,
+{
+    let hasher = H::new(aux);
+    let PaxosSolverParams { l_size, r_size } = params;
+
+    // 1. Hash concurrently.
+    let hashed = par_map(points, |(x, _)| Ok(hash_point(&hasher, *x, l_size, r_size))).located()?;
+
+    // 2. Connected components.
+    let comps = components(&hashed, l_size);
+
+    // 3. Spanning trees and cycle constraints per component.
+    let results = par_map(&comps, |comp| {
+        Ok(spanning_tree(comp, &hashed, points, r_size))
+    })
+    .located()?;
+
+    let mut vec_l: Vec<F> = (0..l_size).map(|_| rng.gen()).collect::<Vec<_>>();
+    let mut vec_r: Vec<F> = (0..r_size).map(|_| rng.gen()).collect::<Vec<_>>();
+
+    // 4. Solve constraints.
+    let constraints = results
+        .iter()
+        .flat_map(|(_, cs)| cs.iter().cloned())
+        .collect::<Vec<_>>();
+
+    if constraints.len() > r_size {
+        return Err(SolverError::TooManyConstraints {
+            constraints: constraints.len(),
+            capacity: r_size,
+        })
+        .located();
+    }
+
+    if !constraints.is_empty() {
+        let equations_w =
+            gaussian_elimination(constraints).context("error in gaussian_elimination")?;
+        let Some(equations) = equations_w else {
+            return Err(SolverError::Unsolvable).located();
+        };
+
+        adjust_vec_r(&equations, &mut vec_r);
+    }
+
+    // 5. Assign L along tree edges per component.
+    let assigned = {
+        let vec_l = &vec_l;
+        let vec_r = &vec_r;
+        par_map(&results, |(tree_edges, _)| {
+            let mut values: HashMap<usize, F> = HashMap::new();
+            let mut res = Vec::with_capacity(tree_edges.len());
+            for &(u, v, e) in tree_edges.iter() {
+                let l_u = values.get(&u).copied().unwrap_or(vec_l[u]);
+                let l_v = l_u + inner_product_by_bits(&hashed[e].2, vec_r) + points[e].1;
+                values.insert(v, l_v);
+                res.push((v, l_v));
+            }
+            Ok(res)
+        })
+        .located()?
+    };
+
+    for (v, l_v) in assigned.into_iter().flatten() {
+        vec_l[v] = l_v;
+    }
+
+    // 6. Output D = L || R
+    let mut result = vec_l;
+    result.extend(vec_r);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_utils::{hash_f, oracle};
+    use crate::hasher::Sha256Hasher;
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    // differential test against the sequential encoder
+    fn test_parallel_base(set_size: usize) {
+        let mut rng = AesRng::new();
+        let set = (0..set_size).map(|_| rng.gen()).collect::<Vec<F128b>>();
+
+        let aux = PaxosSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params = PaxosSolver::<F128b>::calc_params(set.len());
+
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let p_seq = encode_sequential::<_, AesHasher, _>(&mut rng, &points, aux, params).unwrap();
+        let p_par = encode_parallel::<_, AesHasher, _>(&mut rng, &points, aux, params).unwrap();
+
+        assert_eq!(p_seq.len(), p_par.len());
+
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+        let decoded_seq = PaxosSolver::<F128b>::decode_batch(&p_seq, &set, aux, params).unwrap();
+        let decoded_par = PaxosSolver::<F128b>::decode_batch(&p_par, &set, aux, params).unwrap();
+
+        assert_eq!(decoded_seq, ys);
+        assert_eq!(decoded_par, ys);
+    }
+
+    #[test]
+    fn test_parallel_small() {
+        for n in 1..=100 {
+            test_parallel_base(n);
+        }
+    }
+
+    #[test]
+    fn test_parallel_big() {
+        for e in 10..=20 {
+            test_parallel_base(1 << e);
+        }
+    }
+
+    #[test]
+    fn test_components() {
+        // 0 - 1 - 2, 3 - 3 (self loop), 4 - 5
+        let hashed = vec![
+            (0, 1, vec![]),
+            (1, 2, vec![]),
+            (3, 3, vec![]),
+            (4, 5, vec![]),
+        ];
+
+        let mut comps = components(&hashed, 6);
+        comps.sort();
+
+        assert_eq!(comps, vec![vec![0, 1], vec![2], vec![3]]);
+    }
+}
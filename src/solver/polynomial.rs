@@ -0,0 +1,701 @@
+//! Fast polynomial arithmetic used by [VandelmondeSolver](crate::solver::VandelmondeSolver).
+//!
+//! Polynomials are represented as coefficient vectors in ascending order (`p[i]` is the coefficient of $`x^i`$).
+//!
+//! - Multiplication: additive FFT for binary fields ( $`O(n \log n)`$ multiplications and $`O(n \log^2 n)`$ additions ),
+//!   Karatsuba ( $`O(n^{1.58})`$ ) otherwise.
+//!   Schoolbook below [KARATSUBA_THRESHOLD].
+//! - Division: Newton iteration of inverse power series ( $`O(M(n))`$ ).
+//! - Multipoint evaluation and interpolation: subproduct tree ( $`O(M(n) \log n)`$ ).
+//!
+//! # Additive FFT
+//!
+//! Fields of this library ( e.g. [F128b](scuttlebutt::field::F128b) ) have no large multiplicative subgroup of smooth order,
+//! so multiplicative FFT is not applicable. Binary fields use the additive FFT of
+//! [Lin, Chung and Han](https://arxiv.org/abs/1404.3458) instead.
+//!
+//! The bits of the byte representation of a binary field are its coordinates over $`\mathbb{F}_2`$,
+//! so $`x \mapsto x^2 + x`$ is a linear map of bit vectors. Solving $`\beta_i^2 + \beta_i = \beta_{i-1}`$ from $`\beta_0 = 1`$
+//! gives a Cantor basis $`\beta_0, \dots, \beta_{k-1}`$ whose span $`V_k`$ is the evaluation domain.
+//! The subspace polynomials $`s_j(x) = \prod_{a \in V_j} (x - a) = \sum_{t \subseteq j} x^{2^t}`$ have coefficients in $`\mathbb{F}_2`$
+//! and $`s_j(\beta_j) = 1`$, so conversion to the novel polynomial basis $`X_i = \prod_j s_j^{i_j}`$ takes additions only
+//! and the butterflies take one multiplication each.
+//!
+//! A field of $`2^{2^e d}`$ elements ( $`d`$ odd) has a Cantor basis of length $`2^e`$, e.g. 128 for [F128b](scuttlebutt::field::F128b),
+//! which is far more than [MAX_FFT_LOG].
+
+use super::SolverError;
+use crate::error::Result;
+use scuttlebutt::field::FiniteField as FF;
+
+/// Below this length, schoolbook multiplication is used.
+pub(crate) const KARATSUBA_THRESHOLD: usize = 32;
+
+/// Below this length, Karatsuba is used even for binary fields.
+pub(crate) const FFT_THRESHOLD: usize = 64;
+
+/// Maximum $`\log_2`$ of the length of the additive FFT.
+pub(crate) const MAX_FFT_LOG: usize = 32;
+
+/// Below this degree difference, long division is used.
+const NEWTON_THRESHOLD: usize = 64;
+
+fn trim<F: FF>(mut a: Vec<F>) -> Vec<F> {
+    while a.last().map_or(false, |c| *c == F::zero()) {
+        a.pop();
+    }
+    a
+}
+
+fn add<F: FF>(a: &[F], b: &[F]) -> Vec<F> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut res = long.to_vec();
+    for (r, s) in res.iter_mut().zip(short.iter()) {
+        *r += *s;
+    }
+    res
+}
+
+fn sub<F: FF>(a: &[F], b: &[F]) -> Vec<F> {
+    let mut res = a.to_vec();
+    if res.len() < b.len() {
+        res.resize(b.len(), F::zero());
+    }
+    for (r, s) in res.iter_mut().zip(b.iter()) {
+        *r -= *s;
+    }
+    res
+}
+
+// i * a where i is an integer (i.e. a + a + ... + a).
+fn mul_int<F: FF>(a: F, mut i: usize) -> F {
+    let mut res = F::zero();
+    let mut base = a;
+    while i > 0 {
+        if i & 1 == 1 {
+            res += base;
+        }
+        base += base;
+        i >>= 1;
+    }
+    res
+}
+
+fn mul_naive<F: FF>(a: &[F], b: &[F]) -> Vec<F> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    let mut res = vec![F::zero(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            res[i + j] += *x * *y;
+        }
+    }
+    res
+}
+
+fn mul_karatsuba<F: FF>(a: &[F], b: &[F]) -> Vec<F> {
+    if a.len().min(b.len()) < KARATSUBA_THRESHOLD {
+        return mul_naive(a, b);
+    }
+
+    let h = a.len().max(b.len()) / 2;
+    let (a0, a1) = a.split_at(h.min(a.len()));
+    let (b0, b1) = b.split_at(h.min(b.len()));
+
+    // a * b = z0 + (z1 - z0 - z2) x^h + z2 x^{2h}
+    let z0 = mul_karatsuba(a0, b0);
+    let z2 = mul_karatsuba(a1, b1);
+    let z1 = mul_karatsuba(&add(a0, a1), &add(b0, b1));
+    let z1 = sub(&sub(&z1, &z0), &z2);
+
+    let mut res = vec![F::zero(); a.len() + b.len() - 1];
+    for (i, c) in z0.into_iter().enumerate() {
+        res[i] += c;
+    }
+    for (i, c) in z1.into_iter().enumerate() {
+        if h + i < res.len() {
+            res[h + i] += c;
+        }
+    }
+    for (i, c) in z2.into_iter().enumerate() {
+        if 2 * h + i < res.len() {
+            res[2 * h + i] += c;
+        }
+    }
+    res
+}
+
+// Coordinates of `x` over F2 given by the bits of its byte representation.
+fn to_bits<F: FF>(x: F) -> u128 {
+    let mut bytes = [0u8; 16];
+    let x = x.to_bytes();
+    bytes[..x.len()].copy_from_slice(&x);
+    u128::from_le_bytes(bytes)
+}
+
+fn from_bits<F: FF>(bits: u128) -> Option<F> {
+    let mut bytes = F::zero().to_bytes();
+    let len = bytes.len();
+    bytes.copy_from_slice(&bits.to_le_bytes()[..len]);
+    F::from_bytes(&bytes).ok()
+}
+
+/// Cantor basis $`\beta_0 = 1, \beta_i^2 + \beta_i = \beta_{i-1}`$ of length at most `len`.
+///
+/// It is empty if `F` is not a binary field whose byte representation is a bit vector of at most 128 bits.
+fn cantor_basis<F: FF>(len: usize) -> Vec<F> {
+    let width = 8 * F::zero().to_bytes().len();
+    if width > 128 || F::one() + F::one() != F::zero() {
+        return Vec::new();
+    }
+
+    let units = match (0..width)
+        .map(|i| from_bits::<F>(1 << i))
+        .collect::<Option<Vec<F>>>()
+    {
+        Some(units) => units,
+        None => return Vec::new(),
+    };
+    let mask = u128::MAX >> (128 - width);
+    if from_bits::<F>(mask) != Some(units.iter().fold(F::zero(), |acc, e| acc + *e)) {
+        return Vec::new();
+    }
+
+    // Echelon form of x -> x^2 + x: `pivots[b]` is (image, preimage) whose image has the leading bit b.
+    let mut pivots: Vec<Option<(u128, u128)>> = vec![None; width];
+    let reduce = |pivots: &[Option<(u128, u128)>], mut image: u128, mut preimage: u128| {
+        for (b, pivot) in pivots.iter().enumerate().rev() {
+            if let Some((i, p)) = pivot {
+                if (image >> b) & 1 == 1 {
+                    image ^= i;
+                    preimage ^= p;
+                }
+            }
+        }
+        (image, preimage)
+    };
+    for (i, e) in units.iter().enumerate() {
+        let (image, preimage) = reduce(&pivots, to_bits(*e * *e + *e), 1 << i);
+        if image != 0 {
+            pivots[127 - image.leading_zeros() as usize] = Some((image, preimage));
+        }
+    }
+
+    let mut basis = vec![F::one()];
+    while basis.len() < len {
+        let (rest, preimage) = reduce(&pivots, to_bits(*basis.last().unwrap()), 0);
+        match from_bits(preimage) {
+            Some(beta) if rest == 0 => basis.push(beta),
+            _ => break,
+        }
+    }
+    basis.truncate(len);
+    basis
+}
+
+// ω_i = Σ_j i_j β_j for i < 2^k.
+fn subspace<F: FF>(basis: &[F], k: usize) -> Vec<F> {
+    let mut omega = vec![F::zero(); 1 << k];
+    for (j, beta) in basis.iter().take(k).enumerate() {
+        let (lo, hi) = omega.split_at_mut(1 << j);
+        for (w, l) in hi[..1 << j].iter_mut().zip(lo.iter()) {
+            *w = *l + *beta;
+        }
+    }
+    omega
+}
+
+// Offsets 2^t of the non-leading terms of s_j for t ⊊ j.
+fn subspace_terms(j: usize) -> Vec<usize> {
+    let mut terms = Vec::new();
+    let mut t = j;
+    while t != 0 {
+        t = (t - 1) & j;
+        terms.push(1 << t);
+    }
+    terms
+}
+
+// Monomial basis to the novel polynomial basis, in place. f.len() must be a power of two.
+fn to_novel<F: FF>(f: &mut [F]) {
+    let k = f.len().trailing_zeros() as usize;
+    for j in (0..k).rev() {
+        let h = 1 << j;
+        let terms = subspace_terms(j);
+        // divide each block by s_j: the remainder stays in the lower half and the quotient in the upper half
+        for block in f.chunks_mut(2 * h) {
+            for d in (h..2 * h).rev() {
+                let c = block[d];
+                if c != F::zero() {
+                    for t in terms.iter() {
+                        block[d - h + t] -= c;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Inverse of `to_novel`.
+fn from_novel<F: FF>(f: &mut [F]) {
+    let k = f.len().trailing_zeros() as usize;
+    for j in 0..k {
+        let h = 1 << j;
+        let terms = subspace_terms(j);
+        for block in f.chunks_mut(2 * h) {
+            for d in h..2 * h {
+                let c = block[d];
+                if c != F::zero() {
+                    for t in terms.iter() {
+                        block[d - h + t] += c;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Evaluate f given in the novel polynomial basis at ω_0, ..., ω_{n-1} in place. n = f.len() must be a power of two.
+//
+// f = f_0 + s_r f_1 on each block of 2^{r + 1} points starting at ω_s, and s_r(ω_s + ω_i) is λ = ω_{s >> r} for the lower half
+// and λ + 1 for the upper half.
+fn fft<F: FF>(f: &mut [F], omega: &[F]) {
+    let k = f.len().trailing_zeros() as usize;
+    for r in (0..k).rev() {
+        let h = 1 << r;
+        for (b, block) in f.chunks_mut(2 * h).enumerate() {
+            let lambda = omega[2 * b];
+            let (lo, hi) = block.split_at_mut(h);
+            for (x, y) in lo.iter_mut().zip(hi.iter_mut()) {
+                *x += lambda * *y;
+                *y += *x;
+            }
+        }
+    }
+}
+
+// Inverse of `fft`.
+fn ifft<F: FF>(f: &mut [F], omega: &[F]) {
+    let k = f.len().trailing_zeros() as usize;
+    for r in 0..k {
+        let h = 1 << r;
+        for (b, block) in f.chunks_mut(2 * h).enumerate() {
+            let lambda = omega[2 * b];
+            let (lo, hi) = block.split_at_mut(h);
+            for (x, y) in lo.iter_mut().zip(hi.iter_mut()) {
+                *y -= *x;
+                *x -= lambda * *y;
+            }
+        }
+    }
+}
+
+// a * b by the additive FFT on span(basis[..k]) where 2^k >= a.len() + b.len() - 1.
+fn mul_fft<F: FF>(a: &[F], b: &[F], basis: &[F]) -> Vec<F> {
+    let n = a.len() + b.len() - 1;
+    let k = n.next_power_of_two().trailing_zeros() as usize;
+    let omega = subspace(basis, k);
+
+    let transform = |a: &[F]| {
+        let mut f = a.to_vec();
+        f.resize(1 << k, F::zero());
+        to_novel(&mut f);
+        fft(&mut f, &omega);
+        f
+    };
+    let mut res = transform(a);
+    for (x, y) in res.iter_mut().zip(transform(b)) {
+        *x *= y;
+    }
+
+    ifft(&mut res, &omega);
+    from_novel(&mut res);
+    res.truncate(n);
+    res
+}
+
+/// Polynomial arithmetic over `F`.
+///
+/// Multiplication uses the additive FFT if `F` is a binary field and Karatsuba otherwise.
+/// Create it once and reuse it: [new](PolyRing::new) computes the Cantor basis.
+pub(crate) struct PolyRing<F: FF> {
+    // Cantor basis. It is empty if `F` is not a binary field.
+    basis: Vec<F>,
+}
+
+impl<F: FF> PolyRing<F> {
+    pub(crate) fn new() -> Self {
+        Self {
+            basis: cantor_basis(MAX_FFT_LOG),
+        }
+    }
+
+    /// Multiply two polynomials.
+    pub(crate) fn mul(&self, a: &[F], b: &[F]) -> Vec<F> {
+        if a.is_empty() || b.is_empty() {
+            return Vec::new();
+        }
+
+        let n = a.len() + b.len() - 1;
+        if a.len().min(b.len()) >= FFT_THRESHOLD
+            && n.next_power_of_two().trailing_zeros() as usize <= self.basis.len()
+        {
+            mul_fft(a, b, &self.basis)
+        } else {
+            mul_karatsuba(a, b)
+        }
+    }
+
+    // g^{-1} mod x^k. g[0] must be nonzero.
+    fn inv_series(&self, g: &[F], k: usize) -> Vec<F> {
+        let mut h = vec![g[0].inverse()];
+        let two = F::one() + F::one();
+        let mut len = 1;
+
+        while len < k {
+            len = (2 * len).min(k);
+            // h = h * (2 - g * h) mod x^len
+            let mut e = self.mul(&g[..g.len().min(len)], &h);
+            e.truncate(len);
+            let mut t = e.into_iter().map(|c| -c).collect::<Vec<_>>();
+            if t.is_empty() {
+                t.push(F::zero());
+            }
+            t[0] += two;
+            h = self.mul(&h, &t);
+            h.truncate(len);
+        }
+
+        h
+    }
+
+    /// Divide $`f`$ by $`g`$ and return the quotient and the remainder. $`g`$ must be nonzero.
+    pub(crate) fn div_rem(&self, f: &[F], g: &[F]) -> (Vec<F>, Vec<F>) {
+        let f = trim(f.to_vec());
+        let g = trim(g.to_vec());
+        assert!(!g.is_empty(), "division by zero polynomial");
+
+        if f.len() < g.len() {
+            return (Vec::new(), f);
+        }
+
+        let dg = g.len() - 1;
+        let m = f.len() - g.len(); // deg q
+
+        if m < NEWTON_THRESHOLD || dg < NEWTON_THRESHOLD {
+            return div_rem_naive(&f, &g);
+        }
+
+        // rev(q) = rev(f) * rev(g)^{-1} mod x^{m + 1}
+        let f_rev = f.iter().rev().copied().collect::<Vec<_>>();
+        let g_rev = g.iter().rev().copied().collect::<Vec<_>>();
+        let inv = self.inv_series(&g_rev, m + 1);
+        let mut q_rev = self.mul(&f_rev[..m + 1], &inv);
+        q_rev.resize(m + 1, F::zero());
+        let q = q_rev.into_iter().rev().collect::<Vec<_>>();
+
+        let mut r = sub(&f[..dg], &self.mul(&q, &g)[..dg]);
+        r.truncate(dg);
+
+        (q, r)
+    }
+}
+
+fn div_rem_naive<F: FF>(f: &[F], g: &[F]) -> (Vec<F>, Vec<F>) {
+    let dg = g.len() - 1;
+    let lead_inv = g[dg].inverse();
+    let mut r = f.to_vec();
+    let mut q = vec![F::zero(); f.len() - dg];
+
+    for i in (0..q.len()).rev() {
+        let c = r[i + dg] * lead_inv;
+        q[i] = c;
+        if c != F::zero() {
+            for (j, gj) in g.iter().enumerate() {
+                r[i + j] -= c * *gj;
+            }
+        }
+    }
+
+    r.truncate(dg);
+    (q, r)
+}
+
+/// Evaluate $`f(x)`$ by Horner's method.
+pub(crate) fn evaluate<F: FF>(f: &[F], x: F) -> F {
+    f.iter().rev().fold(F::zero(), |acc, c| acc * x + *c)
+}
+
+fn derivative<F: FF>(f: &[F]) -> Vec<F> {
+    f.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| mul_int(*c, i))
+        .collect()
+}
+
+/// Subproduct tree of $`\prod_i (x - x_i)`$.
+///
+/// `levels[0]` are $`x - x_i`$ and `levels[l + 1][j]` is `levels[l][2j] * levels[l][2j + 1]` (or `levels[l][2j]` if it is the last one).
+pub(crate) struct SubproductTree<F: FF> {
+    ring: PolyRing<F>,
+    levels: Vec<Vec<Vec<F>>>,
+}
+
+impl<F: FF> SubproductTree<F> {
+    pub(crate) fn new(xs: &[F]) -> Self {
+        let ring = PolyRing::new();
+        let mut levels = vec![xs.iter().map(|x| vec![-*x, F::one()]).collect::<Vec<_>>()];
+
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [l, r] => ring.mul(l, r),
+                    [l] => l.clone(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            levels.push(next);
+        }
+
+        Self { ring, levels }
+    }
+
+    fn root(&self) -> &[F] {
+        &self.levels.last().unwrap()[0]
+    }
+
+    /// Evaluate $`f`$ at all $`x_i`$ by the remainder tree.
+    pub(crate) fn evaluate(&self, f: &[F]) -> Vec<F> {
+        if self.levels[0].is_empty() {
+            return Vec::new();
+        }
+
+        let mut rems = vec![self.ring.div_rem(f, self.root()).1];
+
+        for level in self.levels.iter().rev().skip(1) {
+            rems = level
+                .iter()
+                .enumerate()
+                .map(|(j, node)| self.ring.div_rem(&rems[j / 2], node).1)
+                .collect();
+        }
+
+        rems.into_iter()
+            .map(|r| r.first().copied().unwrap_or(F::zero()))
+            .collect()
+    }
+
+    /// Interpolate the polynomial $`f`$ of degree less than $`n`$ such that $`f(x_i) = y_i`$.
+    pub(crate) fn interpolate(&self, ys: &[F]) -> Result<Vec<F>> {
+        let n = self.levels[0].len();
+        if ys.len() != n {
+            return Err(SolverError::InvalidInput(format!(
+                "ys.len() (={}) != xs.len() (={})",
+                ys.len(),
+                n
+            ))
+            .into());
+        }
+
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Lagrange weights: y_i / M'(x_i) where M = \prod (x - x_i)
+        let denoms = self.evaluate(&derivative(self.root()));
+        let mut polys = Vec::with_capacity(n);
+        for (y, d) in ys.iter().zip(denoms) {
+            if d == F::zero() {
+                return Err(
+                    SolverError::InvalidInput("x values must be distinct".to_string()).into(),
+                );
+            }
+            polys.push(vec![*y * d.inverse()]);
+        }
+
+        // f_{parent} = f_{left} * M_{right} + f_{right} * M_{left}
+        for level in self.levels.iter().take(self.levels.len() - 1) {
+            polys = polys
+                .chunks(2)
+                .zip(level.chunks(2))
+                .map(|(fs, ms)| match (fs, ms) {
+                    ([fl, fr], [ml, mr]) => add(&self.ring.mul(fl, mr), &self.ring.mul(fr, ml)),
+                    ([f], [_]) => f.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+
+        let mut res = polys.pop().unwrap();
+        res.resize(n, F::zero());
+
+        Ok(res)
+    }
+}
+
+/// Interpolate the polynomial of degree less than $`n`$ passing through $`n`$ points.
+pub(crate) fn interpolate<F: FF>(points: &[(F, F)]) -> Result<Vec<F>> {
+    let xs = points.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+    let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+
+    SubproductTree::new(&xs).interpolate(&ys)
+}
+
+/// Evaluate $`f`$ at all points of `xs`.
+pub(crate) fn multipoint_evaluate<F: FF>(f: &[F], xs: &[F]) -> Vec<F> {
+    if xs.len() < KARATSUBA_THRESHOLD {
+        return xs.iter().map(|x| evaluate(f, *x)).collect();
+    }
+
+    SubproductTree::new(xs).evaluate(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use scuttlebutt::field::{F128b, F61p, F64b};
+    use scuttlebutt::AesRng;
+
+    fn random_poly<F: FF>(rng: &mut AesRng, n: usize) -> Vec<F>
+    where
+        rand::distributions::Standard: rand::distributions::Distribution<F>,
+    {
+        (0..n).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn test_mul() {
+        let mut rng = AesRng::new();
+        for (n, m) in [
+            (1, 1),
+            (10, 100),
+            (33, 33),
+            (64, 64),
+            (100, 257),
+            (500, 300),
+        ] {
+            let a = random_poly::<F128b>(&mut rng, n);
+            let b = random_poly::<F128b>(&mut rng, m);
+            assert_eq!(PolyRing::new().mul(&a, &b), mul_naive(&a, &b));
+            assert_eq!(mul_karatsuba(&a, &b), mul_naive(&a, &b));
+
+            let a = random_poly::<F64b>(&mut rng, n);
+            let b = random_poly::<F64b>(&mut rng, m);
+            assert_eq!(PolyRing::new().mul(&a, &b), mul_naive(&a, &b));
+
+            let a = random_poly::<F61p>(&mut rng, n);
+            let b = random_poly::<F61p>(&mut rng, m);
+            assert_eq!(PolyRing::new().mul(&a, &b), mul_naive(&a, &b));
+        }
+    }
+
+    #[test]
+    fn test_mul_large() {
+        let mut rng = AesRng::new();
+        let a = random_poly::<F128b>(&mut rng, 1 << 12);
+        let b = random_poly::<F128b>(&mut rng, (1 << 12) + 1);
+        assert_eq!(PolyRing::new().mul(&a, &b), mul_karatsuba(&a, &b));
+    }
+
+    #[test]
+    fn test_cantor_basis() {
+        let basis = cantor_basis::<F128b>(MAX_FFT_LOG);
+        assert_eq!(basis.len(), MAX_FFT_LOG);
+        assert_eq!(basis[0], F128b::one());
+        for w in basis.windows(2) {
+            assert_eq!(w[1] * w[1] + w[1], w[0]);
+        }
+
+        assert_eq!(cantor_basis::<F64b>(MAX_FFT_LOG).len(), MAX_FFT_LOG);
+        assert!(cantor_basis::<F61p>(MAX_FFT_LOG).is_empty());
+    }
+
+    #[test]
+    fn test_fft() {
+        let mut rng = AesRng::new();
+        let basis = cantor_basis::<F128b>(MAX_FFT_LOG);
+        for k in [0, 1, 2, 5, 10] {
+            let f = random_poly::<F128b>(&mut rng, 1 << k);
+            let omega = subspace(&basis, k);
+
+            let mut g = f.clone();
+            to_novel(&mut g);
+            fft(&mut g, &omega);
+            let expected = omega.iter().map(|x| evaluate(&f, *x)).collect::<Vec<_>>();
+            assert_eq!(g, expected);
+
+            ifft(&mut g, &omega);
+            from_novel(&mut g);
+            assert_eq!(g, f);
+        }
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let mut rng = AesRng::new();
+        let ring = PolyRing::new();
+        for (n, m) in [(10, 3), (100, 50), (300, 100), (1000, 999), (500, 600)] {
+            let f = random_poly::<F128b>(&mut rng, n);
+            let g = random_poly::<F128b>(&mut rng, m);
+            let (q, r) = ring.div_rem(&f, &g);
+
+            assert!(r.len() < g.len());
+            assert_eq!(trim(add(&ring.mul(&q, &g), &r)), trim(f));
+        }
+    }
+
+    #[test]
+    fn test_multipoint_evaluate() {
+        let mut rng = AesRng::new();
+        for (n, m) in [(5, 5), (100, 1000), (1000, 100), (1 << 10, 1 << 10)] {
+            let f = random_poly::<F128b>(&mut rng, n);
+            let xs = random_poly::<F128b>(&mut rng, m);
+
+            let expected = xs.iter().map(|x| evaluate(&f, *x)).collect::<Vec<_>>();
+
+            assert_eq!(multipoint_evaluate(&f, &xs), expected);
+            assert_eq!(SubproductTree::new(&xs).evaluate(&f), expected);
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let mut rng = AesRng::new();
+        for n in [1, 2, 3, 10, 100, 1 << 10] {
+            let points = (0..n)
+                .map(|_| (rng.gen(), rng.gen()))
+                .collect::<Vec<(F128b, F128b)>>();
+
+            let f = interpolate(&points).unwrap();
+
+            assert_eq!(f.len(), n);
+            for (x, y) in points.iter() {
+                assert_eq!(evaluate(&f, *x), *y);
+            }
+        }
+
+        let points = (0..100)
+            .map(|_| (rng.gen(), rng.gen()))
+            .collect::<Vec<(F61p, F61p)>>();
+        let f = interpolate(&points).unwrap();
+        for (x, y) in points.iter() {
+            assert_eq!(evaluate(&f, *x), *y);
+        }
+    }
+
+    #[test]
+    fn test_interpolate_duplicated_x() {
+        let mut rng = AesRng::new();
+        let x: F128b = rng.gen();
+        let points = vec![(x, rng.gen()), (x, rng.gen())];
+
+        assert!(interpolate(&points).is_err());
+    }
+}
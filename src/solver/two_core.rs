@@ -0,0 +1,476 @@
+//! A kind of solver methods using 3-hash 2-core peeling OKVS (Oblivious Key-Value Store).
+//!
+//! This is the 2-core based variant of [PaXoS](crate::solver::paxos) with three hash functions
+//! (so called "3H-GCT" in [VOLE-PSI](https://eprint.iacr.org/2021/266)).
+//! Each point $`(x, y)`$ is encoded as a row of a sparse linear system
+//!
+//! $`L_{h_1(x)} + L_{h_2(x)} + L_{h_3(x)} + \langle r(x), R \rangle = y`$
+//!
+//! where $`h_1, h_2, h_3 : \mathbb{F} \to [m']`$ and $`r : \mathbb{F} \to \{0, 1\}^{d + \lambda}`$.
+//!
+//! # Paramaters
+//!
+//! - $`n`$: the number of points (or elements in the set).
+//! - $`d = \log n`$
+//! - $`\lambda = 40`$
+//! - $`|L| = m' = 1.3 \cdot n`$
+//!     - the threshold that a random 3-hypergraph has an empty 2-core is $`\approx 1.222 \cdot n`$.
+//! - $`|R| = d + \lambda`$
+//! - $`m = (1.3 \cdot n) + (d + \lambda)`$
+//!
+//! Compared with DFS based [PaxosSolver](crate::solver::PaxosSolver) ( $`m = 2.01 \cdot n + (d + \lambda)`$ ),
+//! the code length is shorter, so the length of VOLE correlations used in offline phase is also shorter.
+//!
+//! # Algorithm
+//!
+//! 1. Peel the hypergraph: repeatedly remove a row which has a column appearing in no other remaining row.
+//! 2. Rows which cannot be peeled (2-core) are solved by gaussian elimination over the columns of $`L`$ they touch and $`R`$.
+//! 3. Assign the peeled rows in reverse order. Each of them decides its own column of $`L`$.
+//!
+//! # Example
+//!
+//! ```
+//! use scuttlebutt::field::F128b;
+//! use rand::Rng;
+//! use scuttlebutt::AesRng;
+//! use preprocessing_mpsi_with_vole::solver::{Solver, TwoCoreSolver};
+//! use preprocessing_mpsi_with_vole::error::Result;
+//! # fn try_main() -> Result<()> {
+//!
+//! let mut rng: AesRng = AesRng::new();
+//! let set: Vec<F128b> = (0..5).map(|_| rng.gen()).collect();
+//!
+//! let aux = TwoCoreSolver::<F128b>::gen_aux(&mut rng)?;
+//! let params = TwoCoreSolver::<F128b>::calc_params(set.len());
+//!
+//! let points: Vec<(F128b, F128b)> = set
+//!     .iter()
+//!     .map(|x| (*x, *x * *x))
+//!     .collect();
+//!
+//! // Encoding points to vector.
+//! let p: Vec<F128b> = TwoCoreSolver::<F128b>::encode(&mut rng, &points, aux, params)?;
+//!
+//! // Decoding vector to corresponding values.
+//! let decoded_values: Vec<F128b> = set
+//!     .iter()
+//!     .map(|x| TwoCoreSolver::<F128b>::decode(&p, *x, aux, params))
+//!     .collect::<Result<_>>()?;
+//!
+//! let values: Vec<F128b> = points.iter().map(|(_, y)| *y).collect();
+//!
+//! assert_eq!(values, decoded_values);
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use super::*;
+use crate::error::{Context, Result};
+use crate::hasher::{AesHasher, KeyedHasher};
+use crate::parallel_utils::par_map;
+use gaussian_eliminations::gaussian_elimination;
+use paxos::inner_product_by_bits;
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{AbstractChannel, Block};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+// Columns of L which one row touches and r(x) by one hash call.
+// Same indices are cancelled out in pairs since the field is characteristic 2.
+fn hash_row<F: FF, H: KeyedHasher>(
+    hasher: &H,
+    x: F,
+    l_size: usize,
+    r_size: usize,
+) -> (Vec<usize>, Vec<bool>) {
+    let (hs, bits) = hasher.indices_and_bits::<F, 3>(x, l_size, r_size);
+    let mut indices: Vec<usize> = Vec::with_capacity(3);
+
+    for i in hs {
+        match indices.iter().position(|&j| j == i) {
+            Some(pos) => {
+                indices.swap_remove(pos);
+            }
+            None => indices.push(i),
+        }
+    }
+
+    (indices, bits)
+}
+
+/// Solver for 3-hash 2-core peeling OKVS.
+///
+/// Please look the parent document ( [crate::solver::two_core] ) for usage example.
+pub struct TwoCoreSolver<F, H = AesHasher>(PhantomData<(F, H)>)
+where
+    F: FF,
+    Standard: Distribution<F>,
+    H: KeyedHasher;
+
+/// Parameters for 2-core solver. It contains $`|L|`$ and $`|R|`$.
+#[derive(Clone, Copy)]
+pub struct TwoCoreSolverParams {
+    l_size: usize,
+    r_size: usize,
+}
+
+impl SolverParams for TwoCoreSolverParams {
+    fn code_length(&self) -> usize {
+        self.l_size + self.r_size
+    }
+}
+
+impl<F, H> Solver<F> for TwoCoreSolver<F, H>
+where
+    F: FF,
+    Standard: Distribution<F>,
+    H: KeyedHasher,
+{
+    const ID: &'static str = "two-core";
+
+    fn id() -> String {
+        format!("{}({})", Self::ID, H::ID)
+    }
+
+    /// Key of the hash function `H` which gives $`h_1, h_2, h_3`$ and $`r`$. Key is generated randomly by [gen_aux](TwoCoreSolver::gen_aux).
+    type AuxInfo = Block;
+    /// TwoCoreSolver Parameters consists of $`|L|`$ and $`|R|`$.
+    type Params = TwoCoreSolverParams;
+
+    fn gen_aux<RNG: CryptoRng + Rng>(rng: &mut RNG) -> Result<Self::AuxInfo> {
+        Ok(rng.gen::<Block>())
+    }
+
+    fn aux_send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _rng: &mut RNG,
+        aux: Self::AuxInfo,
+    ) -> Result<()> {
+        channel.write_block(&aux).located()?;
+
+        Ok(())
+    }
+
+    fn aux_receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _rng: &mut RNG,
+    ) -> Result<Self::AuxInfo> {
+        let aux = channel.read_block().located()?;
+
+        Ok(aux)
+    }
+
+    /// $`|L| = e \cdot n`$ where $`e`$ is the expansion (default $`1.3`$, must be larger than the 2-core threshold $`\approx 1.222`$)
+    /// and $`|R| = \lceil \log n \rceil + \lambda + s`$ where $`s`$ is the stash size.
+    ///
+    /// Above the threshold, the 2-core has $`O(\log n)`$ rows except with negligible probability,
+    /// and the core is not full rank (so encoding fails) with probability at most $`2^{-\lambda}`$.
+    /// The stash lets $`s`$ more core rows be absorbed.
+    fn calc_params_with(n: usize, options: SolverOptions) -> TwoCoreSolverParams {
+        let l_size = match options.expansion {
+            None => n + (3 * n) / 10 + 1,
+            Some(e) => (e * n as f64).ceil() as usize + 1,
+        };
+        let logn = n.next_power_of_two().trailing_zeros() as usize;
+        let r_size = logn + options.lambda + options.stash_size;
+
+        TwoCoreSolverParams { l_size, r_size }
+    }
+
+    /// Encode points to a code vector.
+    ///
+    /// Peeling takes $`O(n)`$ and assignment takes $`O(n \lambda)`$ where $`\lambda`$ is the statistical security parameter.
+    /// Gaussian elimination is only applied to the 2-core, which is small (or empty) with overwhelming probability.
+    fn encode<RNG: CryptoRng + Rng>(
+        rng: &mut RNG,
+        points: &[(F, F)],
+        aux: Block,
+        params: Self::Params,
+    ) -> Result<Vec<F>> {
+        let TwoCoreSolverParams { l_size, r_size } = params;
+        let hasher = H::new(aux);
+
+        // 1. Construct the hypergraph. rows[i] is the columns of L touched by points[i].
+        let (rows, bits): (Vec<_>, Vec<_>) = points
+            .iter()
+            .map(|(x, _)| hash_row(&hasher, *x, l_size, r_size))
+            .unzip();
+
+        let mut col_rows: Vec<Vec<usize>> = vec![Vec::new(); l_size];
+        for (i, row) in rows.iter().enumerate() {
+            for &c in row.iter() {
+                col_rows[c].push(i);
+            }
+        }
+
+        // 2. Peel rows having a column of degree 1.
+        let (peeled, alive) = peel(&rows, &col_rows);
+
+        let mut vec_l: Vec<F> = (0..l_size).map(|_| rng.gen()).collect::<Vec<_>>();
+        let mut vec_r: Vec<F> = (0..r_size).map(|_| rng.gen()).collect::<Vec<_>>();
+
+        // 3. Solve the 2-core by gaussian elimination over touched columns of L and R.
+        let core = (0..rows.len()).filter(|&i| alive[i]).collect::<Vec<_>>();
+        if !core.is_empty() {
+            solve_core(&core, &rows, &bits, points, &mut vec_l, &mut vec_r).located()?;
+        }
+
+        // 4. Assign peeled rows in reverse order.
+        for &(i, c) in peeled.iter().rev() {
+            let y = points[i].1;
+            let mut sum = y + inner_product_by_bits(&bits[i], &vec_r);
+            for &j in rows[i].iter() {
+                if j != c {
+                    sum += vec_l[j];
+                }
+            }
+            vec_l[c] = sum;
+        }
+
+        // 5. Output D = L || R
+        let mut result = vec_l;
+        result.extend(vec_r);
+
+        Ok(result)
+    }
+
+    fn decode(p: &[F], x: F, aux: Block, params: Self::Params) -> Result<F> {
+        let TwoCoreSolverParams { l_size, r_size } = params;
+
+        if p.len() != l_size + r_size {
+            return Err(SolverError::InvalidInput(format!(
+                "p.len() (={}) != code length (={})",
+                p.len(),
+                l_size + r_size
+            ))
+            .into());
+        }
+
+        let (row, bits) = hash_row(&H::new(aux), x, l_size, r_size);
+
+        Ok(decode_row(p, l_size, &row, &bits))
+    }
+
+    /// Decode code vector $`P`$ at all of `xs` at once. Large batches are decoded in parallel.
+    fn decode_batch(p: &[F], xs: &[F], aux: Block, params: Self::Params) -> Result<Vec<F>> {
+        let TwoCoreSolverParams { l_size, r_size } = params;
+
+        if p.len() != l_size + r_size {
+            return Err(SolverError::InvalidInput(format!(
+                "p.len() (={}) != code length (={})",
+                p.len(),
+                l_size + r_size
+            ))
+            .into());
+        }
+
+        let hasher = H::new(aux);
+
+        par_map(xs, |x| {
+            let (row, bits) = hash_row(&hasher, *x, l_size, r_size);
+            Ok(decode_row(p, l_size, &row, &bits))
+        })
+    }
+}
+
+fn decode_row<F: FF>(p: &[F], l_size: usize, row: &[usize], bits: &[bool]) -> F {
+    let mut sum = inner_product_by_bits(bits, &p[l_size..]);
+    for &i in row.iter() {
+        sum += p[i];
+    }
+
+    sum
+}
+
+// Returns pairs of (row, column decided by the row) in peeled order, and flags of rows remaining in the 2-core.
+fn peel(rows: &[Vec<usize>], col_rows: &[Vec<usize>]) -> (Vec<(usize, usize)>, Vec<bool>) {
+    let mut alive = vec![true; rows.len()];
+    let mut degrees = col_rows.iter().map(|rs| rs.len()).collect::<Vec<_>>();
+    let mut stack = (0..col_rows.len())
+        .filter(|&c| degrees[c] == 1)
+        .collect::<Vec<_>>();
+    let mut peeled = Vec::with_capacity(rows.len());
+
+    while let Some(c) = stack.pop() {
+        if degrees[c] != 1 {
+            continue;
+        }
+
+        let Some(&i) = col_rows[c].iter().find(|&&i| alive[i]) else {
+            continue;
+        };
+
+        alive[i] = false;
+        peeled.push((i, c));
+
+        for &j in rows[i].iter() {
+            degrees[j] -= 1;
+            if degrees[j] == 1 {
+                stack.push(j);
+            }
+        }
+    }
+
+    (peeled, alive)
+}
+
+fn solve_core<F: FF>(
+    core: &[usize],
+    rows: &[Vec<usize>],
+    bits: &[Vec<bool>],
+    points: &[(F, F)],
+    vec_l: &mut [F],
+    vec_r: &mut [F],
+) -> Result<()> {
+    let r_size = vec_r.len();
+
+    // columns of L touched by the 2-core, mapped to 0..core_cols.len()
+    let mut core_cols = Vec::new();
+    let mut col_map = HashMap::new();
+    for &i in core.iter() {
+        for &c in rows[i].iter() {
+            col_map.entry(c).or_insert_with(|| {
+                core_cols.push(c);
+                core_cols.len() - 1
+            });
+        }
+    }
+
+    let width = core_cols.len() + r_size;
+    if core.len() > width {
+        return Err(SolverError::TooManyConstraints {
+            constraints: core.len(),
+            capacity: width,
+        })
+        .located();
+    }
+
+    let matrix = core
+        .iter()
+        .map(|&i| {
+            let y = points[i].1;
+            let mut v = vec![false; width];
+            for c in rows[i].iter() {
+                v[col_map[c]] = true;
+            }
+            for (j, &b) in bits[i].iter().enumerate() {
+                v[core_cols.len() + j] = b;
+            }
+            (v, y)
+        })
+        .collect::<Vec<_>>();
+
+    let equations_w = gaussian_elimination(matrix).context("error in gaussian_elimination")?;
+    let Some(equations) = equations_w else {
+        return Err(SolverError::Unsolvable).located();
+    };
+
+    // variables = L[core_cols] || R
+    let mut vars = core_cols
+        .iter()
+        .map(|&c| vec_l[c])
+        .chain(vec_r.iter().copied())
+        .collect::<Vec<_>>();
+
+    for (i, bits, val) in equations.iter() {
+        let mut sum = *val;
+        for (j, b) in bits.iter().enumerate() {
+            if *i != j && *b {
+                sum += vars[j];
+            }
+        }
+        vars[*i] = sum;
+    }
+
+    for (k, &c) in core_cols.iter().enumerate() {
+        vec_l[c] = vars[k];
+    }
+    vec_r.copy_from_slice(&vars[core_cols.len()..]);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_utils::{hash_f, oracle};
+    use crate::hasher::Sha256Hasher;
+    use scuttlebutt::field::F128b;
+    use scuttlebutt::AesRng;
+
+    fn test_two_core_base(set_size: usize) {
+        let mut rng = AesRng::new();
+        let set = (0..set_size).map(|_| rng.gen()).collect::<Vec<F128b>>();
+
+        let aux = TwoCoreSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params = TwoCoreSolver::<F128b>::calc_params(set.len());
+
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let p = TwoCoreSolver::<F128b>::encode(&mut rng, &points, aux, params).unwrap();
+
+        assert_eq!(p.len(), params.code_length());
+
+        let reconstructed_ys = set
+            .iter()
+            .map(|x| TwoCoreSolver::<F128b>::decode(&p, *x, aux, params).unwrap())
+            .collect::<Vec<_>>();
+
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+
+        assert_eq!(ys, reconstructed_ys);
+        assert_eq!(
+            ys,
+            TwoCoreSolver::<F128b>::decode_batch(&p, &set, aux, params).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_two_core_small() {
+        for n in 1..=100 {
+            test_two_core_base(n);
+        }
+    }
+
+    #[test]
+    fn test_two_core_big() {
+        for e in 10..21 {
+            test_two_core_base(1 << e);
+        }
+    }
+
+    #[test]
+    fn test_code_length() {
+        for e in 10..21 {
+            let n = 1 << e;
+            let two_core = TwoCoreSolver::<F128b>::calc_params(n).code_length();
+            let paxos = PaxosSolver::<F128b>::calc_params(n).code_length();
+            assert!(two_core < paxos);
+        }
+    }
+
+    #[test]
+    fn test_peel() {
+        // row 0 and row 1 share columns 1 and 2. row 2 forms 2-core with row 3.
+        let rows = vec![vec![0, 1, 2], vec![1, 2, 3], vec![4, 5, 6], vec![4, 5, 6]];
+        let mut col_rows = vec![Vec::new(); 7];
+        for (i, row) in rows.iter().enumerate() {
+            for &c in row.iter() {
+                col_rows[c].push(i);
+            }
+        }
+
+        let (peeled, alive) = peel(&rows, &col_rows);
+
+        assert_eq!(alive, vec![false, false, true, true]);
+        assert_eq!(peeled.len(), 2);
+    }
+}
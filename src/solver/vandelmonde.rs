@@ -9,7 +9,7 @@
 //! use rand::Rng;
 //! use scuttlebutt::AesRng;
 //! use preprocessing_mpsi_with_vole::solver::{Solver, VandelmondeSolver};
-//! use anyhow::Result;
+//! use preprocessing_mpsi_with_vole::error::Result;
 //! # fn try_main() -> Result<()> {
 //!
 //! let mut rng: AesRng = AesRng::new();
@@ -47,11 +47,15 @@
 //! The usage is similar to that of the Paxos solver.
 //!
 //! The Vandelmonde solver uses a polynomial interpolation algorithm.
+//!
+//! Interpolation and batch decoding ( [decode_batch](VandelmondeSolver::decode_batch) ) use subproduct trees,
+//! so they take $`O(M(n) \log n)`$ where $`M(n)`$ is the cost of polynomial multiplication:
+//! the additive FFT for binary fields such as [F128b](scuttlebutt::field::F128b), Karatsuba otherwise.
 
 use super::*;
-use anyhow::Error;
+use crate::error::{Context, Error};
 use rand::{CryptoRng, Rng};
-use scuttlebutt::field::{polynomial::Polynomial, FiniteField};
+use scuttlebutt::field::FiniteField;
 use scuttlebutt::AbstractChannel;
 use std::marker::PhantomData;
 
@@ -71,6 +75,8 @@ impl SolverParams for VandelmondeSolverParams {
 }
 
 impl<FF: FiniteField> Solver<FF> for VandelmondeSolver<FF> {
+    const ID: &'static str = "vandelmonde";
+
     type AuxInfo = ();
     type Params = VandelmondeSolverParams;
 
@@ -93,47 +99,48 @@ impl<FF: FiniteField> Solver<FF> for VandelmondeSolver<FF> {
         Ok(())
     }
 
-    fn calc_params(n: usize) -> VandelmondeSolverParams {
+    /// Interpolation never fails, so options are ignored.
+    fn calc_params_with(n: usize, _options: SolverOptions) -> VandelmondeSolverParams {
         VandelmondeSolverParams(n)
     }
 
     /// Encode points to a code vector.
     ///
-    /// This function take $`O(n^3)`$ where $`n`$ is set size.
+    /// This function take $`O(M(n) \log n)`$ where $`n`$ is set size and $`M(n)`$ is $`O(n \log^2 n)`$ for binary fields
+    /// and $`O(n^{1.58})`$ otherwise.
     fn encode<RNG: CryptoRng + Rng>(
         _rng: &mut RNG,
         points: &[(FF, FF)],
         _aux: (),
         _params: Self::Params,
     ) -> Result<Vec<FF>, Error> {
-        let Polynomial {
-            constant,
-            coefficients,
-        } = Polynomial::interpolate(&points);
-
-        let mut res = vec![constant];
-        res.extend(coefficients);
+        let res = polynomial::interpolate(points).located()?;
 
         Ok(res)
     }
 
     fn decode(p: &[FF], x: FF, _aux: (), _params: Self::Params) -> Result<FF, Error> {
-        let mut temp = FF::one();
-        let mut sum = FF::zero();
-
-        for coeff in p.iter() {
-            sum += *coeff * temp;
-            temp *= x;
-        }
+        Ok(polynomial::evaluate(p, x))
+    }
 
-        Ok(sum)
+    /// Decode code vector $`P`$ at all of `xs` at once by multipoint evaluation.
+    ///
+    /// This function take $`O(M(n) \log n)`$ for $`n`$ queries, while calling [decode](VandelmondeSolver::decode) $`n`$ times takes $`O(n^2)`$.
+    fn decode_batch(
+        p: &[FF],
+        xs: &[FF],
+        _aux: (),
+        _params: Self::Params,
+    ) -> Result<Vec<FF>, Error> {
+        Ok(polynomial::multipoint_evaluate(p, xs))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::hash_utils::hash_f;
+    use crate::hash_utils::{hash_f, oracle};
+    use crate::hasher::Sha256Hasher;
     use rand::distributions::{Distribution, Standard};
     use rand::Rng;
     use scuttlebutt::field::{F128b, FiniteField};
@@ -160,7 +167,7 @@ mod tests {
 
         let points = set
             .iter()
-            .map(|x| (*x, hash_f(*x).unwrap()))
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
             .collect::<Vec<_>>();
 
         let p = VandelmondeSolver::encode(&mut rng, &points, aux, params).unwrap();
@@ -174,4 +181,53 @@ mod tests {
 
         assert_eq!(ys, reconstructed_ys);
     }
+
+    #[test]
+    fn test_vandelmonde_large() {
+        let set = create_set::<F128b>(1 << 12);
+
+        let mut rng = AesRng::new();
+        let aux = VandelmondeSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params = VandelmondeSolver::<F128b>::calc_params(set.len());
+
+        let points = set
+            .iter()
+            .map(|x| (*x, hash_f(&oracle::<Sha256Hasher>(), *x).unwrap()))
+            .collect::<Vec<_>>();
+
+        let p = VandelmondeSolver::encode(&mut rng, &points, aux, params).unwrap();
+
+        assert_eq!(p.len(), params.code_length());
+
+        let reconstructed_ys = VandelmondeSolver::decode_batch(&p, &set, aux, params).unwrap();
+
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+
+        assert_eq!(ys, reconstructed_ys);
+    }
+
+    #[test]
+    fn test_vandelmonde_16() {
+        let set = create_set::<F128b>(1 << 16);
+
+        let mut rng = AesRng::new();
+        let aux = VandelmondeSolver::<F128b>::gen_aux(&mut rng).unwrap();
+        let params = VandelmondeSolver::<F128b>::calc_params(set.len());
+
+        let points = set.iter().map(|x| (*x, rng.gen())).collect::<Vec<_>>();
+
+        let p = VandelmondeSolver::encode(&mut rng, &points, aux, params).unwrap();
+
+        assert_eq!(p.len(), params.code_length());
+
+        let reconstructed_ys = VandelmondeSolver::decode_batch(&p, &set, aux, params).unwrap();
+
+        let ys = points.iter().map(|(_, y)| *y).collect::<Vec<_>>();
+
+        assert_eq!(ys, reconstructed_ys);
+
+        for (x, y) in points.iter().take(10) {
+            assert_eq!(VandelmondeSolver::decode(&p, *x, aux, params).unwrap(), *y);
+        }
+    }
 }
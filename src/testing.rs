@@ -0,0 +1,147 @@
+//! Utilities for reproducible tests and benchmarks. **Never use them in production.**
+//!
+//! [SeedTree] derives every RNG of a run from one master seed, so that a run is reproduced by its seed:
+//! the same seed gives the same sets, VOLE correlations, solver keys and intersection.
+//! Anyone who knows the seed knows all secrets of all parties, so a seeded run has no security at all.
+//!
+//! The module is only built for the tests of this crate and with the `testing` feature.
+
+use crate::hasher::{AesHasher, KeyedHasher};
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{AesRng, Block};
+
+/// Tree of seeds derived from a master seed. **Test-only**, see [the module](self).
+///
+/// A child is derived from its parent by a label as $`\mathrm{AES}_{seed}`$-based hash of the label (see [AesHasher]),
+/// so children with different labels are independent and the derivation does not depend on the order of calls.
+///
+/// ```
+/// use preprocessing_mpsi_with_vole::testing::SeedTree;
+/// use rand::Rng;
+///
+/// let tree = SeedTree::new(42u128.into());
+///
+/// let x: u64 = tree.party(1).phase("offline").rng().gen();
+/// let y: u64 = SeedTree::new(42u128.into()).party(1).phase("offline").rng().gen();
+/// assert_eq!(x, y);
+///
+/// let z: u64 = tree.party(1).phase("online").rng().gen();
+/// assert_ne!(x, z);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedTree {
+    seed: Block,
+}
+
+impl SeedTree {
+    /// Root of the tree.
+    pub fn new(seed: Block) -> Self {
+        Self { seed }
+    }
+
+    /// Root with a seed from the OS.
+    pub fn random() -> Self {
+        Self::new(AesRng::new().gen())
+    }
+
+    /// Root with the seed in the environment variable `var` if set, otherwise with a random seed.
+    ///
+    /// The seed is printed to stderr, so that a failed test can be reproduced by setting `var` to it.
+    ///
+    /// # Panics
+    ///
+    /// If `var` is set but not a decimal or `0x`-prefixed hexadecimal `u128`.
+    pub fn from_env_or_random(var: &str) -> Self {
+        let tree = match std::env::var(var) {
+            Ok(s) => {
+                let seed = match s.strip_prefix("0x") {
+                    Some(hex) => u128::from_str_radix(hex, 16),
+                    None => s.parse(),
+                }
+                .unwrap_or_else(|e| panic!("{}={} is not a seed: {}", var, s, e));
+                Self::new(seed.into())
+            }
+            Err(_) => Self::random(),
+        };
+        eprintln!("{}={:#x}", var, u128::from(tree.seed));
+
+        tree
+    }
+
+    /// Seed of this node.
+    pub fn seed(&self) -> Block {
+        self.seed
+    }
+
+    /// Child labeled by `label`.
+    pub fn child(&self, label: &str) -> Self {
+        let mut out = [0u8; 16];
+        AesHasher::new(self.seed).hash_into(&[label.as_bytes()], &mut out);
+        Self::new(Block::from(u128::from_le_bytes(out)))
+    }
+
+    /// Subtree of the sets of all parties.
+    pub fn sets(&self) -> Self {
+        self.child("sets")
+    }
+
+    /// Subtree of party `pid`.
+    pub fn party(&self, pid: usize) -> Self {
+        self.child(&format!("party/{}", pid))
+    }
+
+    /// Subtree of a party for its communication with `peer`.
+    pub fn peer(&self, peer: usize) -> Self {
+        self.child(&format!("peer/{}", peer))
+    }
+
+    /// Subtree of a party for `phase`, e.g. "offline" or "online".
+    pub fn phase(&self, phase: &str) -> Self {
+        self.child(&format!("phase/{}", phase))
+    }
+
+    /// RNG seeded by this node.
+    pub fn rng(&self) -> AesRng {
+        AesRng::from_seed(self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_seed_tree() {
+        let tree = SeedTree::random();
+
+        // deterministic
+        assert_eq!(tree.party(2).peer(1), tree.party(2).peer(1));
+        assert_eq!(
+            tree.sets().rng().gen::<u128>(),
+            tree.sets().rng().gen::<u128>()
+        );
+
+        // different paths give different seeds
+        let nodes = [
+            tree,
+            tree.sets(),
+            tree.party(0),
+            tree.party(1),
+            tree.party(1).peer(0),
+            tree.party(1).peer(2),
+            tree.party(0).peer(1),
+            tree.party(1).phase("offline"),
+            tree.party(1).phase("online"),
+            tree.party(1).peer(0).phase("offline"),
+        ];
+        let seeds = nodes
+            .iter()
+            .map(|node| u128::from(node.seed()))
+            .collect::<HashSet<_>>();
+        assert_eq!(seeds.len(), nodes.len());
+
+        // different roots give different trees
+        assert_ne!(SeedTree::random().party(0), tree.party(0));
+    }
+}
@@ -0,0 +1,385 @@
+//! Consistency check of VOLE correlations.
+//!
+//! [CheckedVoleSender] and [CheckedVoleReceiver] wrap any VOLE implementation
+//! over the whole field and check $`\bm{C} = \bm{A} \Delta + \bm{B}`$ by a random linear combination after generation.
+//!
+//! # Outline
+//!
+//! 1. Parties share VOLE of length $`m + 1`$ by the inner implementation. The last correlation $`(a^*, b^*, c^*)`$ is used as a mask.
+//! 2. The party having $`\Delta`$ chooses a seed and sends it. Both parties expand it to coefficients $`\chi_1, \dots, \chi_m \in \mathbb{F}`$.
+//! 3. The other party sends $`\hat{a} = \sum_i \chi_i a_i + a^*`$ and $`\hat{c} = \sum_i \chi_i c_i + c^*`$.
+//! 4. The party having $`\Delta`$ checks $`\hat{c} = \hat{a} \Delta + \sum_i \chi_i b_i + b^*`$ and sends the result.
+//!
+//! # Security
+//!
+//! What is checked:
+//! if the party having $`\bm{A}`$ and $`\bm{C}`$ holds some correlation with $`c_i \ne a_i \Delta + b_i`$
+//! (e.g. it deviated in the inner protocol), the check fails except with probability about $`1 / |\mathbb{F}|`$,
+//! and both parties return an error.
+//!
+//! What is hidden:
+//! $`\hat{a}`$ is masked by $`a^*`$, which hides $`\sum_i \chi_i a_i`$ only if $`a^*`$ is uniform over the whole field $`\mathbb{F}`$.
+//! If $`\bm{A}`$ lives in a subfield (as in [LPN based VOLE](crate::vole::lpn_based), where $`a_i \in \mathrm{GF}(2)`$),
+//! $`\hat{a}`$ leaks a linear function of $`\bm{A}`$. So both wrappers require the inner VOLE to implement [FullFieldVole].
+//! $`\hat{c}`$ reveals nothing more, since it is determined by $`\hat{a}`$, $`\Delta`$ and $`\bm{B}`$. $`\Delta`$ is not sent at all.
+//!
+//! What is NOT checked:
+//! the check is one-sided. Only the party having $`\bm{A}`$ and $`\bm{C}`$ is tested.
+//! The party having $`\Delta`$ chooses the seed, computes the verdict and reports it, and the other party trusts the report.
+//! A malicious party having $`\Delta`$ can hold inconsistent $`\bm{B}`$ or report a false verdict without being detected.
+//! The verdict only tells whether it is willing to go on.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::vole::checked::{CheckedVoleReceiver, CheckedVoleSender};
+//! use preprocessing_mpsi_with_vole::vole::{VoleShareForReceiver, VoleShareForSender, OtVoleSender, OtVoleReceiver};
+//! use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
+//! use scuttlebutt::field::F128b;
+//! # use scuttlebutt::Channel;
+//! use scuttlebutt::AesRng;
+//! # use std::io::{BufReader, BufWriter};
+//! # use std::os::unix::net::UnixStream;
+//! use anyhow::Result;
+//!
+//! # fn try_main() -> Result<()> {
+//! # let (sender, receiver) = UnixStream::pair().unwrap();
+//! let handle = std::thread::spawn(move || -> Result<(F128b, Vec<F128b>)> {
+//!     // let mut channel = ...
+//!     let mut rng = AesRng::new();
+//! #     let reader = BufReader::new(sender.try_clone().unwrap());
+//! #     let writer = BufWriter::new(sender);
+//! #     let mut channel = Channel::new(reader, writer);
+//!
+//!     let mut vole_sender = CheckedVoleSender::new(OtVoleSender::<F128b, OtSender>::new());
+//!     vole_sender.receive(&mut channel, &mut rng, 100)
+//! });
+//!
+//! // let mut channel = ...
+//! let mut rng = AesRng::new();
+//! # let reader = BufReader::new(receiver.try_clone().unwrap());
+//! # let writer = BufWriter::new(receiver);
+//! # let mut channel = Channel::new(reader, writer);
+//!
+//! let mut vole_receiver = CheckedVoleReceiver::new(OtVoleReceiver::<F128b, OtReceiver>::new());
+//! let (a_vec, c_vec) = vole_receiver.receive(&mut channel, &mut rng, 100)?;
+//!
+//! let (delta, b_vec) = handle.join().unwrap()?;
+//!
+//! for ((a, b), c) in a_vec.into_iter().zip(b_vec).zip(c_vec) {
+//!     assert_eq!(delta * a + b, c);
+//! }
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use super::{FullFieldVole, VoleShareForReceiver, VoleShareForSender};
+use crate::error::{Context, Error};
+use rand::distributions::{Distribution, Standard};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::channel::AbstractChannel;
+use scuttlebutt::field::FiniteField as FF;
+use scuttlebutt::{AesRng, Block};
+use std::marker::PhantomData;
+
+const CHECK_OK: u8 = 1;
+const CHECK_NG: u8 = 0;
+
+// Coefficients of the random linear combination.
+fn coefficients<F: FF>(seed: Block, m: usize) -> Vec<F>
+where
+    Standard: Distribution<F>,
+{
+    let mut chi_rng = AesRng::from_seed(seed);
+    (0..m).map(|_| chi_rng.gen()).collect()
+}
+
+/// VOLE sender (the party having $`\Delta`$) with consistency check of the counterpart.
+///
+/// The inner VOLE must be [FullFieldVole]. See the parent document for what is and isn't checked.
+///
+/// Please look the parent document ( [crate::vole::checked] ) for usage example.
+pub struct CheckedVoleSender<F: FF, V: VoleShareForSender<F>> {
+    inner: V,
+    _p: PhantomData<F>,
+}
+
+impl<F: FF, V: VoleShareForSender<F>> Clone for CheckedVoleSender<F, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<F: FF, V: VoleShareForSender<F>> Copy for CheckedVoleSender<F, V> {}
+
+impl<F: FF, V: VoleShareForSender<F> + FullFieldVole> FullFieldVole for CheckedVoleSender<F, V> {}
+
+impl<F: FF, V: VoleShareForSender<F> + FullFieldVole> CheckedVoleSender<F, V> {
+    /// Wrap VOLE sender `inner`.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<F: FF, V: VoleShareForSender<F> + FullFieldVole> VoleShareForSender<F>
+    for CheckedVoleSender<F, V>
+where
+    Standard: Distribution<F>,
+{
+    fn id(&self) -> String {
+        format!("checked({})", self.inner.id())
+    }
+
+    /// Receive $`\Delta \in \mathbb{F}, \bm{B} \in \mathbb{F}^m`$ and check them with the counterpart.
+    ///
+    /// Return an error if the check fails.
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        m: usize,
+    ) -> Result<(F, Vec<F>), Error> {
+        let (delta, mut b_vec) = self.inner.receive(channel, rng, m + 1).located()?;
+
+        if b_vec.len() != m + 1 {
+            return Err(Error::Vole(format!(
+                "b_vec.len() (={}) != m + 1 (={})",
+                b_vec.len(),
+                m + 1
+            )));
+        }
+
+        let b_star = b_vec.pop().unwrap();
+
+        let seed: Block = rng.gen();
+        channel.write_block(&seed).located()?;
+        channel.flush().located()?;
+
+        let a_hat: F = channel.read_serializable().located()?;
+        let c_hat: F = channel.read_serializable().located()?;
+
+        let b_hat = coefficients::<F>(seed, m)
+            .into_iter()
+            .zip(b_vec.iter())
+            .map(|(chi, &b)| chi * b)
+            .sum::<F>()
+            + b_star;
+
+        let ok = c_hat == a_hat * delta + b_hat;
+
+        channel
+            .write_u8(if ok { CHECK_OK } else { CHECK_NG })
+            .located()?;
+        channel.flush().located()?;
+
+        if !ok {
+            return Err(Error::protocol_violation(
+                "VOLE consistency check failed: the counterpart has malformed correlations.",
+            ));
+        }
+
+        Ok((delta, b_vec))
+    }
+}
+
+/// VOLE receiver (the party having $`\bm{A}`$ and $`\bm{C}`$) with consistency check by the counterpart.
+///
+/// The inner VOLE must be [FullFieldVole]. The verdict is reported by the counterpart and is trusted as is.
+///
+/// Please look the parent document ( [crate::vole::checked] ) for usage example.
+pub struct CheckedVoleReceiver<F: FF, V: VoleShareForReceiver<F>> {
+    inner: V,
+    _p: PhantomData<F>,
+}
+
+impl<F: FF, V: VoleShareForReceiver<F>> Clone for CheckedVoleReceiver<F, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<F: FF, V: VoleShareForReceiver<F>> Copy for CheckedVoleReceiver<F, V> {}
+
+impl<F: FF, V: VoleShareForReceiver<F> + FullFieldVole> FullFieldVole
+    for CheckedVoleReceiver<F, V>
+{
+}
+
+impl<F: FF, V: VoleShareForReceiver<F> + FullFieldVole> CheckedVoleReceiver<F, V> {
+    /// Wrap VOLE receiver `inner`.
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<F: FF, V: VoleShareForReceiver<F> + FullFieldVole> VoleShareForReceiver<F>
+    for CheckedVoleReceiver<F, V>
+where
+    Standard: Distribution<F>,
+{
+    fn id(&self) -> String {
+        format!("checked({})", self.inner.id())
+    }
+
+    /// Receive $`\bm{A}, \bm{C} \in \mathbb{F}^m`$ and let the counterpart check them.
+    ///
+    /// Return an error if the counterpart reports that the check failed.
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        m: usize,
+    ) -> Result<(Vec<F>, Vec<F>), Error> {
+        let (mut a_vec, mut c_vec) = self.inner.receive(channel, rng, m + 1).located()?;
+
+        if a_vec.len() != m + 1 || c_vec.len() != m + 1 {
+            return Err(Error::Vole(format!(
+                "a_vec.len() (={}) != m + 1 (={}) or c_vec.len() (={}) != m + 1",
+                a_vec.len(),
+                m + 1,
+                c_vec.len()
+            )));
+        }
+
+        let a_star = a_vec.pop().unwrap();
+        let c_star = c_vec.pop().unwrap();
+
+        let seed = channel.read_block().located()?;
+        let chis = coefficients::<F>(seed, m);
+
+        let a_hat = chis
+            .iter()
+            .zip(a_vec.iter())
+            .map(|(&chi, &a)| chi * a)
+            .sum::<F>()
+            + a_star;
+        let c_hat = chis
+            .iter()
+            .zip(c_vec.iter())
+            .map(|(&chi, &c)| chi * c)
+            .sum::<F>()
+            + c_star;
+
+        channel.write_serializable(&a_hat).located()?;
+        channel.write_serializable(&c_hat).located()?;
+        channel.flush().located()?;
+
+        let result = channel.read_u8().located()?;
+
+        if result != CHECK_OK {
+            return Err(Error::protocol_violation(
+                "VOLE consistency check failed: reported by the counterpart.",
+            ));
+        }
+
+        Ok((a_vec, c_vec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vole::{OtVoleReceiver, OtVoleSender};
+    use num_traits::One;
+    use ocelot::ot::{AlszReceiver, AlszSender};
+    use scuttlebutt::{field::F128b, Channel};
+    use std::io::{BufReader, BufWriter};
+    use std::os::unix::net::UnixStream;
+
+    // VOLE receiver breaking one correlation on purpose.
+    #[derive(Clone, Copy)]
+    struct FaultyVoleReceiver<V: VoleShareForReceiver<F128b>>(V);
+
+    impl<V: VoleShareForReceiver<F128b> + FullFieldVole> FullFieldVole for FaultyVoleReceiver<V> {}
+
+    impl<V: VoleShareForReceiver<F128b>> VoleShareForReceiver<F128b> for FaultyVoleReceiver<V> {
+        fn id(&self) -> String {
+            format!("faulty({})", self.0.id())
+        }
+
+        fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+            &mut self,
+            channel: &mut C,
+            rng: &mut RNG,
+            m: usize,
+        ) -> Result<(Vec<F128b>, Vec<F128b>), Error> {
+            let (a_vec, mut c_vec) = self.0.receive(channel, rng, m)?;
+            let i = rng.gen_range(0..m);
+            c_vec[i] += F128b::one();
+            Ok((a_vec, c_vec))
+        }
+    }
+
+    fn test_checked_vole_base<VR>(vole_size: usize, vole_receiver: VR) -> (bool, bool)
+    where
+        VR: VoleShareForReceiver<F128b> + FullFieldVole,
+    {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let mut vole_sender = CheckedVoleSender::new(OtVoleSender::<F128b, AlszSender>::new());
+            vole_sender.receive(&mut channel, &mut rng, vole_size)
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let mut vole_receiver = CheckedVoleReceiver::new(vole_receiver);
+        let r_res = vole_receiver.receive(&mut channel, &mut rng, vole_size);
+        let s_res = handle.join().unwrap();
+
+        if let (Ok((delta, b_vec)), Ok((a_vec, c_vec))) = (&s_res, &r_res) {
+            assert_eq!(a_vec.len(), vole_size);
+            assert_eq!(b_vec.len(), vole_size);
+            assert_eq!(c_vec.len(), vole_size);
+            for ((&a, &b), &c) in a_vec.iter().zip(b_vec.iter()).zip(c_vec.iter()) {
+                assert_eq!(*delta * a + b, c);
+            }
+        }
+
+        (s_res.is_ok(), r_res.is_ok())
+    }
+
+    #[test]
+    fn test_checked_vole_honest() {
+        for vole_size in [1, 10, 100, 1000] {
+            let vole_receiver = OtVoleReceiver::<F128b, AlszReceiver>::new();
+            assert_eq!(
+                test_checked_vole_base(vole_size, vole_receiver),
+                (true, true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_checked_vole_malformed() {
+        for vole_size in [1, 10, 100, 1000] {
+            let vole_receiver = FaultyVoleReceiver(OtVoleReceiver::<F128b, AlszReceiver>::new());
+            assert_eq!(
+                test_checked_vole_base(vole_size, vole_receiver),
+                (false, false)
+            );
+        }
+    }
+}
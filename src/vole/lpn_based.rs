@@ -16,7 +16,7 @@
 //! # use std::io::{BufReader, BufWriter};
 //! # use std::os::unix::net::UnixStream;
 //! use anyhow::Result;
-//! # use anyhow::Context;
+//! # use preprocessing_mpsi_with_vole::error::Context;
 //! use ocelot::svole::wykw::{LPN_EXTEND_SMALL, LPN_SETUP_SMALL};
 //! # fn try_main() -> Result<()> {
 //!
@@ -40,10 +40,10 @@
 //!         .receive(&mut channel, &mut rng, vole_size)?;
 //! #     channel
 //! #         .write_serializable(&delta)
-//! #         .with_context(|| format!("@{}:{}", file!(), line!()))
+//! #         .located()
 //! #         .unwrap();
 //! #     write_vec_f(&mut channel, &out)
-//! #         .with_context(|| format!("@{}:{}", file!(), line!()))
+//! #         .located()
 //! #         .unwrap();
 //!     // ...
 //!     Ok(())
@@ -61,7 +61,7 @@
 //!     .receive(&mut channel, &mut rng, vole_size)?;
 //! # let delta: F128b = channel.read_serializable().unwrap();
 //! # let b_vec: Vec<F128b> = read_vec_f(&mut channel)
-//! #     .with_context(|| format!("@{}:{}", file!(), line!()))
+//! #     .located()
 //! #     .unwrap();
 //! # handle.join().unwrap().unwrap();
 //! // ...
@@ -83,14 +83,18 @@
 //!
 //! # Note
 //!
-//! Actualy, you can only three levels for size of VOLE vector: small, medium, and large.
+//! The internal algorithm ([ocelot::svole::wykw]) has only three levels for size of VOLE vector: small, medium, and large.
+//! One extension generates a fixed number of correlations decided by these parameters.
 //!
-//! Returned vector is shortened one from the vector generated by internal algorithm.
+//! [LPNVoleSender] and [LPNVoleReceiver] repeat extensions until $`m`$ correlations are collected,
+//! so any $`m`$ is accepted. Use [LPNVoleSender::for_size] or [LPNVoleReceiver::for_size] to choose the parameters from $`m`$ automatically.
 //!
-//! So communications will take longer time than you expect.
-//! (e.g. You specify the length as 100 and specify `LPN_SETUP_SMALL` and `LPN_EXTEND_SMALL`, then the length of the internal vector is larger than 100.)
+//! If you need VOLE vectors several times, use [LPNVoleSenderSession] and [LPNVoleReceiverSession].
+//! They run the setup only once and keep the surplus of extensions for later calls.
+//! [VoleShareForSender::receive] and [VoleShareForReceiver::receive] run the setup on every call and drop the surplus,
+//! which is fine for the protocols since they call them once per peer with the whole capacity.
 use super::{VoleShareForReceiver, VoleShareForSender};
-use anyhow::{bail, Context, Error};
+use crate::error::{Context, Error};
 use ocelot::svole::wykw::Receiver as SVoleReceiverStruct;
 use ocelot::svole::wykw::Sender as SVoleSenderStruct;
 pub use ocelot::svole::wykw::{
@@ -104,6 +108,25 @@ use scuttlebutt::channel::AbstractChannel;
 use scuttlebutt::field::FiniteField as FF;
 use std::marker::PhantomData;
 
+/// Threshold of $`m`$ to use medium parameters instead of small ones.
+const LPN_MEDIUM_THRESHOLD: usize = 1 << 17;
+/// Threshold of $`m`$ to use large parameters instead of medium ones.
+const LPN_LARGE_THRESHOLD: usize = 1 << 23;
+
+/// Choose LPN parameters `(setup_param, extend_param)` from the length of VOLE vector.
+///
+/// Small parameters are used for $`m < 2^{17}`$, medium ones for $`m < 2^{23}`$ and large ones otherwise.
+/// Every choice works for any $`m`$ because extensions are repeated, but fewer extensions are better.
+pub fn lpn_params_for_size(m: usize) -> (LpnParams, LpnParams) {
+    if m < LPN_MEDIUM_THRESHOLD {
+        (LPN_SETUP_SMALL, LPN_EXTEND_SMALL)
+    } else if m < LPN_LARGE_THRESHOLD {
+        (LPN_SETUP_MEDIUM, LPN_EXTEND_MEDIUM)
+    } else {
+        (LPN_SETUP_LARGE, LPN_EXTEND_LARGE)
+    }
+}
+
 /// VOLE sender based on LPN.
 ///
 /// Please look the parent document ( [crate::vole::lpn_based] ) for usage example.
@@ -123,41 +146,95 @@ impl<F: FF> LPNVoleSender<F> {
             _ff: PhantomData,
         }
     }
+
+    /// Create new LPN VOLE sender whose parameters are chosen by [lpn_params_for_size].
+    pub fn for_size(m: usize) -> Self {
+        let (setup_param, extend_param) = lpn_params_for_size(m);
+        Self::new(setup_param, extend_param)
+    }
+
+    /// Run the setup and start a session. The counterpart should call [LPNVoleReceiver::session].
+    pub fn session<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<LPNVoleSenderSession<F>, Error> {
+        let vole = SVoleReceiverStruct::init(channel, rng, self.setup_param, self.extend_param)
+            .located()?;
+
+        Ok(LPNVoleSenderSession {
+            vole,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+/// Session of [LPNVoleSender]. $`\Delta`$ is fixed during the session.
+///
+/// Surplus of extensions is buffered and used in later calls of [LPNVoleSenderSession::receive].
+pub struct LPNVoleSenderSession<F: FF> {
+    vole: SVoleReceiverStruct<F>,
+    buffer: Vec<F>,
+}
+
+impl<F: FF> LPNVoleSenderSession<F> {
+    /// $`\Delta`$ of this session.
+    pub fn delta(&self) -> F {
+        self.vole.delta()
+    }
+
+    /// Receive $`\bm{B} \in \mathbb{F}^m`$. Extensions are repeated until $`m`$ elements are collected.
+    pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        m: usize,
+    ) -> Result<Vec<F>, Error> {
+        while self.buffer.len() < m {
+            let mut out = Vec::new();
+            self.vole.receive(channel, rng, &mut out).located()?;
+
+            if out.is_empty() {
+                return Err(Error::Vole(
+                    "extension returned no correlations".to_string(),
+                ));
+            }
+
+            self.buffer.extend(out);
+        }
+
+        let rest = self.buffer.split_off(m);
+        let b_vec = std::mem::replace(&mut self.buffer, rest);
+
+        Ok(b_vec)
+    }
 }
 
 impl<F: FF> VoleShareForSender<F> for LPNVoleSender<F> {
+    fn id(&self) -> String {
+        "lpn".to_owned()
+    }
+
     /// Receive $`\Delta \in \mathbb{F}, \bm{B} \in \mathbb{F}^m`$
     ///
-    /// The inner algorithm in this function allows only three choices
-    /// for the length of the shared VOLE vector: small, medium, and large.
-    /// This parameter is given by `setup_param` and `extend_param` fields of [LPNVoleSender].
+    /// The inner algorithm generates a fixed number of correlations per extension,
+    /// which is given by `setup_param` and `extend_param` fields of [LPNVoleSender].
+    /// Extensions are repeated until $`m`$ correlations are collected.
+    ///
+    /// Each call runs the setup in a fresh [LPNVoleSenderSession] and drops the surplus of the last extension,
+    /// which is less than one extension. [LPNVoleSender] is [Copy] as the trait requires, so it cannot keep a session.
+    /// The protocols call this once per peer with the whole capacity through [open_session](VoleShareForSender::open_session),
+    /// so the setup and the surplus are paid once per peer. Use [LPNVoleSender::session] to draw vectors several times.
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
         channel: &mut C,
         rng: &mut RNG,
         m: usize,
     ) -> Result<(F, Vec<F>), Error> {
-        let setup_param = self.setup_param;
-        let extend_param = self.extend_param;
-        let mut vole = SVoleReceiverStruct::init(channel, rng, setup_param, extend_param)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let mut out = Vec::new();
-        vole.receive(channel, rng, &mut out)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-
-        if out.len() < m {
-            bail!(
-                "TOO BIG M!\nout.len() (={}) < m (={}) @ {}:{}",
-                out.len(),
-                m,
-                file!(),
-                line!()
-            );
-        }
-
-        let b_vec = out[..m].to_vec();
+        let mut session = self.session(channel, rng).located()?;
+        let b_vec = session.receive(channel, rng, m).located()?;
 
-        Ok((vole.delta(), b_vec))
+        Ok((session.delta(), b_vec))
     }
 }
 
@@ -180,49 +257,94 @@ impl<F: FF> LPNVoleReceiver<F> {
             _ff: PhantomData,
         }
     }
+
+    /// Create new LPN VOLE receiver whose parameters are chosen by [lpn_params_for_size].
+    pub fn for_size(m: usize) -> Self {
+        let (setup_param, extend_param) = lpn_params_for_size(m);
+        Self::new(setup_param, extend_param)
+    }
+
+    /// Run the setup and start a session. The counterpart should call [LPNVoleSender::session].
+    pub fn session<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<LPNVoleReceiverSession<F>, Error> {
+        let vole =
+            SVoleSenderStruct::init(channel, rng, self.setup_param, self.extend_param).located()?;
+
+        Ok(LPNVoleReceiverSession {
+            vole,
+            buffer: Vec::new(),
+        })
+    }
 }
 
-impl<F: FF> VoleShareForReceiver<F> for LPNVoleReceiver<F> {
-    /// Receive $`\bm{A}, \bm{C} \in \mathbb{F}^m`$
-    ///
-    /// The inner algorithm in this function allows only three choices
-    /// for the length of the shared VOLE vector: small, medium, and large.
-    /// This parameter is given by `setup_param` and `extend_param` fields of [LPNVoleReceiver].
-    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+/// Session of [LPNVoleReceiver].
+///
+/// Surplus of extensions is buffered and used in later calls of [LPNVoleReceiverSession::receive].
+pub struct LPNVoleReceiverSession<F: FF> {
+    vole: SVoleSenderStruct<F>,
+    buffer: Vec<(F::PrimeField, F)>,
+}
+
+impl<F: FF> LPNVoleReceiverSession<F> {
+    /// Receive $`\bm{A}, \bm{C} \in \mathbb{F}^m`$. Extensions are repeated until $`m`$ elements are collected.
+    pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
         channel: &mut C,
         rng: &mut RNG,
         m: usize,
     ) -> Result<(Vec<F>, Vec<F>), Error> {
-        let setup_param = self.setup_param;
-        let extend_param = self.extend_param;
-        let mut vole = SVoleSenderStruct::init(channel, rng, setup_param, extend_param)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let mut out: Vec<(F::PrimeField, F)> = Vec::new();
-        vole.send(channel, rng, &mut out)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        while self.buffer.len() < m {
+            let mut out = Vec::new();
+            self.vole.send(channel, rng, &mut out).located()?;
+
+            if out.is_empty() {
+                return Err(Error::Vole(
+                    "extension returned no correlations".to_string(),
+                ));
+            }
+
+            self.buffer.extend(out);
+        }
+
+        let rest = self.buffer.split_off(m);
+        let out = std::mem::replace(&mut self.buffer, rest);
 
-        let mut a_vec = Vec::with_capacity(out.len());
-        let mut c_vec = Vec::with_capacity(out.len());
+        let mut a_vec = Vec::with_capacity(m);
+        let mut c_vec = Vec::with_capacity(m);
 
         for (a, c) in out {
             a_vec.push(a.into());
             c_vec.push(c);
         }
 
-        if a_vec.len() < m || c_vec.len() < m {
-            bail!(
-                "TOO BIG M!\nvec.len() (={} or {}) < m (={}) @ {}:{}",
-                a_vec.len(),
-                c_vec.len(),
-                m,
-                file!(),
-                line!()
-            );
-        }
+        Ok((a_vec, c_vec))
+    }
+}
 
-        let a_vec = a_vec[..m].to_vec();
-        let c_vec = c_vec[..m].to_vec();
+impl<F: FF> VoleShareForReceiver<F> for LPNVoleReceiver<F> {
+    fn id(&self) -> String {
+        "lpn".to_owned()
+    }
+
+    /// Receive $`\bm{A}, \bm{C} \in \mathbb{F}^m`$
+    ///
+    /// The inner algorithm generates a fixed number of correlations per extension,
+    /// which is given by `setup_param` and `extend_param` fields of [LPNVoleReceiver].
+    /// Extensions are repeated until $`m`$ correlations are collected.
+    ///
+    /// As [LPNVoleSender], each call runs the setup in a fresh [LPNVoleReceiverSession] and drops the surplus of the last extension.
+    /// Use [LPNVoleReceiver::session] to draw vectors several times.
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        m: usize,
+    ) -> Result<(Vec<F>, Vec<F>), Error> {
+        let mut session = self.session(channel, rng).located()?;
+        let (a_vec, c_vec) = session.receive(channel, rng, m).located()?;
 
         Ok((a_vec, c_vec))
     }
@@ -248,17 +370,12 @@ mod tests {
             let mut vole_sender = LPNVoleSender::<F128b>::new(setup_param, extend_param);
             let (delta, out) = vole_sender
                 .receive(&mut channel, &mut rng, vole_size)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
+                .located()
                 .unwrap();
 
-            channel
-                .write_serializable(&delta)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
-                .unwrap();
+            channel.write_serializable(&delta).located().unwrap();
 
-            write_vec_f(&mut channel, &out)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
-                .unwrap();
+            write_vec_f(&mut channel, &out).located().unwrap();
         });
 
         let mut rng = AesRng::new();
@@ -269,14 +386,12 @@ mod tests {
         let mut vole_receiver = LPNVoleReceiver::<F128b>::new(setup_param, extend_param);
         let (a_vec, c_vec) = vole_receiver
             .receive(&mut channel, &mut rng, vole_size)
-            .with_context(|| format!("@{}:{}", file!(), line!()))
+            .located()
             .unwrap();
 
         let delta: F128b = channel.read_serializable().unwrap();
 
-        let b_vec: Vec<F128b> = read_vec_f(&mut channel)
-            .with_context(|| format!("@{}:{}", file!(), line!()))
-            .unwrap();
+        let b_vec: Vec<F128b> = read_vec_f(&mut channel).located().unwrap();
 
         handle.join().unwrap();
 
@@ -318,6 +433,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_vole_share_repeated_extension() {
+        // small parameters cannot produce 2^19 correlations by one extension.
+        let setup_param = LPN_SETUP_SMALL;
+        let extend_param = LPN_EXTEND_SMALL;
+        test_vole_share_base(1 << 19, setup_param, extend_param);
+    }
+
+    #[test]
+    fn test_lpn_params_for_size() {
+        for e in 1..=20 {
+            let m = 2usize.pow(e);
+            let (setup_param, extend_param) = lpn_params_for_size(m);
+            test_vole_share_base(m, setup_param, extend_param);
+        }
+    }
+
+    #[test]
+    fn test_vole_share_session() {
+        let sizes = [100, 1 << 16, 1 << 17, 3, 1 << 18];
+
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+
+            let vole_sender = LPNVoleSender::<F128b>::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+            let mut session = vole_sender.session(&mut channel, &mut rng).unwrap();
+
+            channel.write_serializable(&session.delta()).unwrap();
+
+            for m in sizes {
+                let b_vec = session.receive(&mut channel, &mut rng, m).unwrap();
+                write_vec_f(&mut channel, &b_vec).unwrap();
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+
+        let vole_receiver = LPNVoleReceiver::<F128b>::new(LPN_SETUP_SMALL, LPN_EXTEND_SMALL);
+        let mut session = vole_receiver.session(&mut channel, &mut rng).unwrap();
+
+        let delta: F128b = channel.read_serializable().unwrap();
+
+        for m in sizes {
+            let (a_vec, c_vec) = session.receive(&mut channel, &mut rng, m).unwrap();
+            let b_vec: Vec<F128b> = read_vec_f(&mut channel).unwrap();
+
+            assert_eq!(a_vec.len(), m);
+            assert_eq!(b_vec.len(), m);
+            assert_eq!(c_vec.len(), m);
+
+            for ((a, b), c) in a_vec
+                .into_iter()
+                .zip(b_vec.into_iter())
+                .zip(c_vec.into_iter())
+            {
+                assert_eq!(delta * a + b, c);
+            }
+        }
+
+        handle.join().unwrap();
+    }
+
     use rand::distributions::{Distribution, Standard};
     use rand::Rng;
     use scuttlebutt::field::FiniteField;
@@ -335,7 +519,8 @@ mod tests {
 
     #[test]
     fn test_vole_compute() {
-        use crate::hash_utils::hash_f;
+        use crate::hash_utils::{hash_f, oracle};
+        use crate::hasher::Sha256Hasher;
         use crate::solver::{Solver, VandelmondeSolver};
 
         const VOLE_SIZE: usize = 100;
@@ -358,12 +543,10 @@ mod tests {
             let mut vole_sender = LPNVoleSender::<F128b>::new(setup_param, extend_param);
             let (delta, b_vec) = vole_sender
                 .receive(&mut channel, &mut rng, VOLE_SIZE)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
+                .located()
                 .unwrap();
 
-            let a_dash_vec: Vec<F128b> = read_vec_f(&mut channel)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
-                .unwrap();
+            let a_dash_vec: Vec<F128b> = read_vec_f(&mut channel).located().unwrap();
 
             // k = b + delta * (a_dash)
             let k_vec = b_vec
@@ -380,7 +563,7 @@ mod tests {
                 .collect::<Vec<_>>();
             let res = decoded
                 .into_iter()
-                .map(|(x, y)| y - (delta * hash_f(x).unwrap()))
+                .map(|(x, y)| y - (delta * hash_f(&oracle::<Sha256Hasher>(), x).unwrap()))
                 .collect::<Vec<_>>();
 
             write_vec_f(&mut channel, &res).unwrap();
@@ -397,14 +580,14 @@ mod tests {
         let mut vole_receiver = LPNVoleReceiver::<F128b>::new(setup_param, extend_param);
         let (a_vec, c_vec) = vole_receiver
             .receive(&mut channel, &mut rng, VOLE_SIZE)
-            .with_context(|| format!("@{}:{}", file!(), line!()))
+            .located()
             .unwrap();
 
         let params = VandelmondeSolver::<F128b>::calc_params(set.len());
 
         let points = set
             .iter()
-            .map(|&x| (x, hash_f(x).unwrap()))
+            .map(|&x| (x, hash_f(&oracle::<Sha256Hasher>(), x).unwrap()))
             .collect::<Vec<_>>();
         let p = VandelmondeSolver::encode(&mut rng, &points, (), params).unwrap();
 
@@ -417,9 +600,7 @@ mod tests {
 
         write_vec_f(&mut channel, &a_dash).unwrap();
 
-        let sender_res: Vec<F128b> = read_vec_f(&mut channel)
-            .with_context(|| format!("@{}:{}", file!(), line!()))
-            .unwrap();
+        let sender_res: Vec<F128b> = read_vec_f(&mut channel).located().unwrap();
 
         let res = set
             .into_iter()
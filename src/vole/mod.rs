@@ -17,7 +17,7 @@
 //!
 //! - [VOLE-PSI: Fast OPRF and Circuit-PSI from Vector-OLE](https://eprint.iacr.org/2021/266)
 
-use anyhow::Error;
+use crate::error::{Context, Error};
 pub use ocelot::svole::wykw::{
     LpnParams, LPN_EXTEND_LARGE, LPN_EXTEND_MEDIUM, LPN_EXTEND_SMALL, LPN_SETUP_LARGE,
     LPN_SETUP_MEDIUM, LPN_SETUP_SMALL,
@@ -26,13 +26,24 @@ use rand::{CryptoRng, Rng};
 use scuttlebutt::channel::AbstractChannel;
 use scuttlebutt::field::FiniteField as FF;
 
+pub mod checked;
+pub use checked::{CheckedVoleReceiver, CheckedVoleSender};
 pub mod lpn_based;
-pub use lpn_based::{LPNVoleReceiver, LPNVoleSender};
+pub use lpn_based::{
+    lpn_params_for_size, LPNVoleReceiver, LPNVoleReceiverSession, LPNVoleSender,
+    LPNVoleSenderSession,
+};
 pub mod ot_based;
 pub use ot_based::{OtVoleReceiver, OtVoleSender};
+pub mod session;
+pub use session::{VoleReceiverSession, VoleSenderSession};
 
 /// Trait for VOLE sender.
 pub trait VoleShareForSender<F: FF>: Clone + Copy {
+    /// Stable name of this VOLE sent in the [handshake](crate::preprocessed::handshake)
+    /// together with the names of the VOLEs it is built on, e.g. `checked(ot)`. Change it when the messages change.
+    fn id(&self) -> String;
+
     /// Receive $`\Delta \in \mathbb{F}, \bm{B} \in \mathbb{F}^m`$
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
@@ -40,10 +51,31 @@ pub trait VoleShareForSender<F: FF>: Clone + Copy {
         rng: &mut RNG,
         m: usize,
     ) -> Result<(F, Vec<F>), Error>;
+
+    /// Share $`\Delta`$ and $`\bm{B}`$ of length `capacity` at once and start a [session](crate::vole::session).
+    fn open_session<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        capacity: usize,
+    ) -> Result<VoleSenderSession<F>, Error> {
+        let (delta, b_vec) = self.receive(channel, rng, capacity).located()?;
+        Ok(VoleSenderSession::new(delta, b_vec))
+    }
 }
 
+/// Marker for VOLE whose $`\bm{A}`$ is (pseudo)random over the whole field $`\mathbb{F}`$, not over a subfield.
+///
+/// [LPNVoleSender] and [LPNVoleReceiver] give $`\bm{A}`$ in the prime subfield, so they do not implement this.
+/// [CheckedVoleSender] and [CheckedVoleReceiver] require it to mask the linear combination of $`\bm{A}`$.
+pub trait FullFieldVole {}
+
 /// Trait for VOLE receiver.
 pub trait VoleShareForReceiver<F: FF>: Clone + Copy {
+    /// Stable name of this VOLE sent in the [handshake](crate::preprocessed::handshake)
+    /// together with the names of the VOLEs it is built on, e.g. `checked(ot)`. Change it when the messages change.
+    fn id(&self) -> String;
+
     /// Receive $`\bm{A}, \bm{C} \in \mathbb{F}^m`$
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
@@ -51,4 +83,15 @@ pub trait VoleShareForReceiver<F: FF>: Clone + Copy {
         rng: &mut RNG,
         m: usize,
     ) -> Result<(Vec<F>, Vec<F>), Error>;
+
+    /// Share $`\bm{A}`$ and $`\bm{C}`$ of length `capacity` at once and start a [session](crate::vole::session).
+    fn open_session<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        capacity: usize,
+    ) -> Result<VoleReceiverSession<F>, Error> {
+        let (a_vec, c_vec) = self.receive(channel, rng, capacity).located()?;
+        VoleReceiverSession::new(a_vec, c_vec)
+    }
 }
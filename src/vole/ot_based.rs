@@ -6,7 +6,7 @@
 //! # Example
 //!
 //! ```
-//! # use anyhow::Context;
+//! # use preprocessing_mpsi_with_vole::error::Context;
 //! # use preprocessing_mpsi_with_vole::channel_utils::{read_vec_f, write_vec_f};
 //! use preprocessing_mpsi_with_vole::vole::{VoleShareForReceiver, VoleShareForSender, OtVoleSender, OtVoleReceiver};
 //! use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
@@ -33,15 +33,15 @@
 //! #     let writer = BufWriter::new(sender);
 //! #     let mut channel = Channel::new(reader, writer);
 //!
-//!     let mut vole_sender = OtVoleSender::<F128b, 128, OtSender>::new();
+//!     let mut vole_sender = OtVoleSender::<F128b, OtSender>::new();
 //!     let (delta, b_vec) = vole_sender
 //!         .receive(&mut channel, &mut rng, vole_size)?;
 //! #     channel
 //! #         .write_serializable(&delta)
-//! #         .with_context(|| format!("@{}:{}", file!(), line!()))
+//! #         .located()
 //! #         .unwrap();
 //! #     write_vec_f(&mut channel, &b_vec)
-//! #         .with_context(|| format!("@{}:{}", file!(), line!()))
+//! #         .located()
 //! #         .unwrap();
 //!     // ...
 //!     Ok(())
@@ -55,13 +55,13 @@
 //! # let writer = BufWriter::new(receiver);
 //! # let mut channel = Channel::new(reader, writer);
 //!
-//! let mut vole_receiver = OtVoleReceiver::<F128b, 128, OtReceiver>::new();
+//! let mut vole_receiver = OtVoleReceiver::<F128b, OtReceiver>::new();
 //! let (a_vec, c_vec) = vole_receiver
 //!     .receive(&mut channel, &mut rng, vole_size)?;
 //! // ...
 //! # let delta: F128b = channel.read_serializable().unwrap();
 //! # let b_vec: Vec<F128b> = read_vec_f(&mut channel)
-//! #     .with_context(|| format!("@{}:{}", file!(), line!()))
+//! #     .located()
 //! #     .unwrap();
 //! # handle.join().unwrap().unwrap();
 //!
@@ -80,11 +80,26 @@
 //! # }
 //! ```
 //!
-use super::{VoleShareForReceiver, VoleShareForSender};
-use crate::set_utils::FromU128;
-use anyhow::{Context, Error, Result};
-use generic_array::GenericArray;
-use itertools::Itertools;
+//! # Outline
+//!
+//! Let $`e_1, \dots, e_L \in \mathbb{F}`$ be the field elements whose byte representations have only one bit set
+//! (invalid representations are skipped). They are the binary basis for binary fields and powers of two for prime fields.
+//!
+//! 1. The receiver chooses random bits $`a_{j,1}, \dots, a_{j,L}`$ and sets $`a_j = \sum_i a_{j,i} e_i`$.
+//! 2. For each bit, the sender chooses a random $`\rho`$ and sends $`(\rho, \rho + \Delta e_i)`$ by OT,
+//!    so the receiver gets $`\rho + a_{j,i} \Delta e_i`$.
+//! 3. $`b_j = \sum_i \rho`$ and $`c_j = \sum_i (\rho + a_{j,i} \Delta e_i) = a_j \Delta + b_j`$.
+//!
+//! Fields of at most 16 bytes are sent directly as OT messages. For wider fields, the OT messages are random seeds
+//! $`(k_0, k_1)`$, $`\rho = \mathrm{PRG}(k_0)`$ and the sender sends the correction
+//! $`\tau = \rho + \Delta e_i - \mathrm{PRG}(k_1)`$ after the OTs. The OTs and the corrections are processed
+//! [`CHUNK_SIZE`] correlations at a time, so neither party holds all of them at once.
+//! Exactly $`m`$ correlations are returned.
+//!
+//! For prime fields, $`a_j`$ is statistically close to uniform (the bias is about $`2^L / p - 1`$ at most).
+use super::{FullFieldVole, VoleShareForReceiver, VoleShareForSender};
+use crate::channel_utils::{read_vec_f_exact, write_vec_f};
+use crate::error::{Context, Error};
 use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
 use rand::distributions::{Distribution, Standard};
 use rand::{CryptoRng, Rng};
@@ -93,27 +108,65 @@ use scuttlebutt::field::FiniteField as FF;
 use scuttlebutt::serialization::CanonicalSerialize;
 use scuttlebutt::Block;
 use std::marker::PhantomData;
+use typenum::marker_traits::Unsigned;
 
-fn bytes2block(bytes: &[u8]) -> Block {
-    let mut b = [0u8; 16];
-    b.copy_from_slice(bytes);
-    Block::from(b)
+/// Number of correlations whose OTs (and corrections) are processed at a time.
+pub const CHUNK_SIZE: usize = 1 << 10;
+
+// Whether an element of `F` fits in an OT message.
+#[inline]
+fn fits_block<F: FF>() -> bool {
+    F::ByteReprLen::to_usize() <= 16
+}
+
+#[inline]
+fn to_block<F: FF>(x: F) -> Block {
+    let mut bytes = [0u8; 16];
+    let x = x.to_bytes();
+    bytes[..x.len()].copy_from_slice(&x);
+    Block::from(u128::from_le_bytes(bytes))
+}
+
+#[inline]
+fn from_block<F: FF>(block: Block) -> Result<F, Error> {
+    let mut bytes = F::zero().to_bytes();
+    let len = bytes.len();
+    bytes.copy_from_slice(&u128::from(block).to_le_bytes()[..len]);
+    F::from_bytes(&bytes)
+        .map_err(|_| Error::protocol_violation("an OT message is not a field element"))
+}
+
+// Field elements whose byte representations have only one bit set.
+fn unit_basis<F: FF + CanonicalSerialize>() -> Vec<F> {
+    let zero_bytes = F::zero().to_bytes();
+    let bits = zero_bytes.len() * 8;
+
+    (0..bits)
+        .filter_map(|i| {
+            let mut bytes = zero_bytes.clone();
+            bytes[i / 8] = 1 << (i % 8);
+            F::from_bytes(&bytes).ok().filter(|e| *e != F::zero())
+        })
+        .collect()
+}
+
+#[inline]
+fn prg<F: FF>(seed: Block) -> F {
+    F::from_uniform_bytes(&<[u8; 16]>::from(seed))
 }
 
 /// VOLE sender based on OT.
 ///
-/// Please set the field `F_LENGTH` to the byte length of type `F`. e.g. `F_LENGTH = 128` for `F128b`.
-///
 /// Please look the parent document ( [crate::vole::ot_based] ) for usage example.
-pub struct OtVoleSender<F, const F_LENGTH: usize, OT>(PhantomData<(F, OT)>)
+pub struct OtVoleSender<F, OT>(PhantomData<(F, OT)>)
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtSender,
     Standard: Distribution<F>;
 
-impl<F, const F_LENGTH: usize, OT> Clone for OtVoleSender<F, F_LENGTH, OT>
+impl<F, OT> Clone for OtVoleSender<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtSender,
     Standard: Distribution<F>,
 {
@@ -121,17 +174,17 @@ where
         Self(PhantomData)
     }
 }
-impl<F, const F_LENGTH: usize, OT> Copy for OtVoleSender<F, F_LENGTH, OT>
+impl<F, OT> Copy for OtVoleSender<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtSender,
     Standard: Distribution<F>,
 {
 }
 
-impl<F, const F_LENGTH: usize, OT> OtVoleSender<F, F_LENGTH, OT>
+impl<F, OT> OtVoleSender<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtSender,
     Standard: Distribution<F>,
 {
@@ -141,46 +194,89 @@ where
     }
 }
 
-impl<F, const F_LENGTH: usize, OT> VoleShareForSender<F> for OtVoleSender<F, F_LENGTH, OT>
+impl<F, OT> FullFieldVole for OtVoleSender<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtSender,
     Standard: Distribution<F>,
-    OT::Msg: From<Block>,
 {
+}
+
+impl<F, OT> VoleShareForSender<F> for OtVoleSender<F, OT>
+where
+    F: FF + CanonicalSerialize,
+    OT: OtSender<Msg = Block>,
+    Standard: Distribution<F>,
+{
+    fn id(&self) -> String {
+        "ot".to_owned()
+    }
+
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
         channel: &mut C,
         rng: &mut RNG,
         m: usize,
+    ) -> Result<(F, Vec<F>), Error> {
+        self.receive_with(channel, rng, m, fits_block::<F>())
+    }
+}
+
+impl<F, OT> OtVoleSender<F, OT>
+where
+    F: FF + CanonicalSerialize,
+    OT: OtSender<Msg = Block>,
+    Standard: Distribution<F>,
+{
+    // `direct` chooses whether the field elements are sent as OT messages.
+    fn receive_with<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        m: usize,
+        direct: bool,
     ) -> Result<(F, Vec<F>), Error> {
         let delta: F = rng.gen();
 
-        let mut delta_2pows: [F; F_LENGTH] = [F::zero(); F_LENGTH];
-        let mut delta_2pow = delta;
-        let two = F::from_u128(2);
-        for i in 0..F_LENGTH {
-            delta_2pows[i] = delta_2pow;
-            delta_2pow *= two;
-        }
+        let delta_units = unit_basis::<F>()
+            .into_iter()
+            .map(|e| delta * e)
+            .collect::<Vec<F>>();
+        let l = delta_units.len();
+
+        let mut ot = OT::init(channel, rng).located()?;
 
         let mut b_vec: Vec<F> = Vec::with_capacity(m);
-        let mut inputs: Vec<(OT::Msg, OT::Msg)> = Vec::with_capacity(m * F_LENGTH);
-        for _ in 0..m {
-            let mut b = F::zero();
-            for i in 0..F_LENGTH {
-                let rho: F = rng.gen();
-                b += rho;
-                let left = bytes2block(&rho.to_bytes());
-                let right = bytes2block(&(rho + delta_2pows[i]).to_bytes());
-                inputs.push((left.into(), right.into()));
+        let mut inputs: Vec<(Block, Block)> = Vec::with_capacity(CHUNK_SIZE.min(m) * l);
+        let mut taus: Vec<F> = Vec::new();
+        for start in (0..m).step_by(CHUNK_SIZE) {
+            inputs.clear();
+            taus.clear();
+            for _ in start..m.min(start + CHUNK_SIZE) {
+                let mut b = F::zero();
+                for &delta_unit in delta_units.iter() {
+                    let rho: F = if direct {
+                        let rho: F = rng.gen();
+                        inputs.push((to_block(rho), to_block(rho + delta_unit)));
+                        rho
+                    } else {
+                        let k0: Block = rng.gen();
+                        let k1: Block = rng.gen();
+                        let rho: F = prg(k0);
+                        taus.push(rho + delta_unit - prg(k1));
+                        inputs.push((k0, k1));
+                        rho
+                    };
+                    b += rho;
+                }
+                b_vec.push(b);
             }
-            b_vec.push(b);
-        }
 
-        let mut ot = OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        ot.send(channel, &inputs, rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+            ot.send(channel, &inputs, rng).located()?;
+            if !direct {
+                write_vec_f(channel, &taus).located()?;
+            }
+        }
 
         Ok((delta, b_vec))
     }
@@ -188,18 +284,16 @@ where
 
 /// VOLE receiver based on OT.
 ///
-/// Please set the field `F_LENGTH` to the byte length of type `F`. e.g. `F_LENGTH = 128` for `F128b`.
-///
 /// Please look the parent document ( [crate::vole::ot_based] ) for usage example.
-pub struct OtVoleReceiver<F, const F_LENGTH: usize, OT>(PhantomData<(F, OT)>)
+pub struct OtVoleReceiver<F, OT>(PhantomData<(F, OT)>)
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtReceiver,
     Standard: Distribution<F>;
 
-impl<F, const F_LENGTH: usize, OT> Clone for OtVoleReceiver<F, F_LENGTH, OT>
+impl<F, OT> Clone for OtVoleReceiver<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtReceiver,
     Standard: Distribution<F>,
 {
@@ -208,17 +302,17 @@ where
     }
 }
 
-impl<F, const F_LENGTH: usize, OT> Copy for OtVoleReceiver<F, F_LENGTH, OT>
+impl<F, OT> Copy for OtVoleReceiver<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtReceiver,
     Standard: Distribution<F>,
 {
 }
 
-impl<F, const F_LENGTH: usize, OT> OtVoleReceiver<F, F_LENGTH, OT>
+impl<F, OT> OtVoleReceiver<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtReceiver,
     Standard: Distribution<F>,
 {
@@ -228,67 +322,110 @@ where
     }
 }
 
-impl<F, const F_LENGTH: usize, OT> VoleShareForReceiver<F> for OtVoleReceiver<F, F_LENGTH, OT>
+impl<F, OT> FullFieldVole for OtVoleReceiver<F, OT>
 where
-    F: FF + FromU128 + CanonicalSerialize,
+    F: FF + CanonicalSerialize,
     OT: OtReceiver,
     Standard: Distribution<F>,
-    <F as CanonicalSerialize>::ByteReprLen: generic_array::ArrayLength<u8>,
-    OT::Msg: From<Block>,
 {
+}
+
+impl<F, OT> VoleShareForReceiver<F> for OtVoleReceiver<F, OT>
+where
+    F: FF + CanonicalSerialize,
+    OT: OtReceiver<Msg = Block>,
+    Standard: Distribution<F>,
+{
+    fn id(&self) -> String {
+        "ot".to_owned()
+    }
+
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
         channel: &mut C,
         rng: &mut RNG,
         m: usize,
     ) -> Result<(Vec<F>, Vec<F>), Error> {
+        self.receive_with(channel, rng, m, fits_block::<F>())
+    }
+}
+
+impl<F, OT> OtVoleReceiver<F, OT>
+where
+    F: FF + CanonicalSerialize,
+    OT: OtReceiver<Msg = Block>,
+    Standard: Distribution<F>,
+{
+    // `direct` chooses whether the field elements are sent as OT messages.
+    fn receive_with<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+        m: usize,
+        direct: bool,
+    ) -> Result<(Vec<F>, Vec<F>), Error> {
+        let units = unit_basis::<F>();
+        let l = units.len();
+
         let mut a_vec = Vec::with_capacity(m);
-        let mut inputs: Vec<bool> = Vec::with_capacity(m * F_LENGTH);
+        let mut inputs: Vec<bool> = Vec::with_capacity(m * l);
 
         for _ in 0..m {
-            let a = rng.gen::<u128>();
-            if a == 0_u128 {
-                continue;
-            }
-
-            for i in 0..F_LENGTH {
-                let a_bit = (a >> i) & 1 == 1;
+            let mut a = F::zero();
+            for &e in units.iter() {
+                let a_bit: bool = rng.gen();
+                if a_bit {
+                    a += e;
+                }
                 inputs.push(a_bit);
             }
-
-            a_vec.push(F::from_u128(a));
+            a_vec.push(a);
         }
 
-        let mut ot = OT::init(channel, rng).with_context(|| format!("@{}:{}", file!(), line!()))?;
-        let rhos = ot
-            .receive(channel, &inputs, rng)
-            .with_context(|| format!("@{}:{}", file!(), line!()))?;
+        let mut ot = OT::init(channel, rng).located()?;
+
+        let mut c_vec = Vec::with_capacity(m);
+        for inputs in inputs.chunks(CHUNK_SIZE * l) {
+            let keys = ot.receive(channel, inputs, rng).located()?;
+            if keys.len() != inputs.len() {
+                return Err(Error::protocol_violation(format!(
+                    "keys.len() (={}) != inputs.len() (={})",
+                    keys.len(),
+                    inputs.len()
+                )));
+            }
 
-        let c_vec = rhos
-            .into_iter()
-            .chunks(F_LENGTH)
-            .into_iter()
-            .map(|rho| {
-                let res = rho
-                    .into_iter()
-                    .map(|mut msg| {
-                        let v = msg.as_mut();
-                        let ga =
-                            GenericArray::<u8, <F as CanonicalSerialize>::ByteReprLen>::from_slice(
-                                v,
-                            );
-                        let f = F::from_bytes(ga)
-                            .with_context(|| format!("@{}:{}", file!(), line!()))?;
-                        Ok(f)
-                    })
-                    .collect::<Result<Vec<_>, Error>>()?
-                    .into_iter()
-                    .sum();
-                Ok(res)
-            })
-            .collect::<Result<Vec<_>>>()?;
-
-        assert!(c_vec.len() == m);
+            if direct {
+                for keys in keys.chunks(l) {
+                    let mut c = F::zero();
+                    for &k in keys.iter() {
+                        c += from_block::<F>(k)?;
+                    }
+                    c_vec.push(c);
+                }
+            } else {
+                let taus: Vec<F> = read_vec_f_exact(channel, inputs.len()).located()?;
+                c_vec.extend(
+                    keys.chunks(l)
+                        .zip(taus.chunks(l))
+                        .zip(inputs.chunks(l))
+                        .map(|((keys, taus), a_bits)| {
+                            keys.iter()
+                                .zip(taus.iter())
+                                .zip(a_bits.iter())
+                                .map(|((&k, &tau), &a_bit)| {
+                                    let v: F = prg(k);
+                                    if a_bit {
+                                        v + tau
+                                    } else {
+                                        v
+                                    }
+                                })
+                                .sum::<F>()
+                        }),
+                );
+            }
+        }
 
         Ok((a_vec, c_vec))
     }
@@ -300,11 +437,66 @@ mod tests {
 
     use super::*;
     use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
-    use scuttlebutt::{field::F128b, AesRng, Channel};
+    use rand::RngCore;
+    use scuttlebutt::field::{F128b, F128p, F61p, F64b};
+    use scuttlebutt::{AesRng, Channel};
     use std::io::{BufReader, BufWriter};
     use std::os::unix::net::UnixStream;
 
-    fn test_vole_share_base(vole_size: usize) {
+    // RNG whose `next_u32` returns 0 while drawing the bits of the given entries of a_vec.
+    // Every bit drawn by `gen::<bool>()` is then false, so these entries are 0.
+    struct ZeroEntriesRng {
+        inner: AesRng,
+        bits: usize,
+        zero_entries: Vec<usize>,
+        count: usize,
+    }
+
+    impl RngCore for ZeroEntriesRng {
+        fn next_u32(&mut self) -> u32 {
+            let i = self.count / self.bits;
+            self.count += 1;
+            let v = self.inner.next_u32();
+            if self.zero_entries.contains(&i) {
+                0
+            } else {
+                v
+            }
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.inner.next_u64()
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            self.inner.fill_bytes(dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.inner.try_fill_bytes(dest)
+        }
+    }
+
+    impl CryptoRng for ZeroEntriesRng {}
+
+    fn test_vole_share_base_f<F>(vole_size: usize)
+    where
+        F: FF + CanonicalSerialize,
+        Standard: Distribution<F>,
+    {
+        test_vole_share_base_f_with_rng::<F, _>(vole_size, AesRng::new(), fits_block::<F>());
+    }
+
+    fn test_vole_share_base_f_with_rng<F, RNG>(
+        vole_size: usize,
+        mut rng: RNG,
+        direct: bool,
+    ) -> Vec<F>
+    where
+        F: FF + CanonicalSerialize,
+        Standard: Distribution<F>,
+        RNG: CryptoRng + Rng,
+    {
         let (sender, receiver) = UnixStream::pair().unwrap();
         let handle = std::thread::spawn(move || {
             let mut rng = AesRng::new();
@@ -312,50 +504,46 @@ mod tests {
             let writer = BufWriter::new(sender);
             let mut channel = Channel::new(reader, writer);
 
-            let mut vole_sender = OtVoleSender::<F128b, 128, OtSender>::new();
+            let mut vole_sender = OtVoleSender::<F, OtSender>::new();
             let (delta, out) = vole_sender
-                .receive(&mut channel, &mut rng, vole_size)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
+                .receive_with(&mut channel, &mut rng, vole_size, direct)
+                .located()
                 .unwrap();
 
-            channel
-                .write_serializable(&delta)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
-                .unwrap();
+            channel.write_serializable(&delta).located().unwrap();
 
-            write_vec_f(&mut channel, &out)
-                .with_context(|| format!("@{}:{}", file!(), line!()))
-                .unwrap();
+            write_vec_f(&mut channel, &out).located().unwrap();
         });
 
-        let mut rng = AesRng::new();
         let reader = BufReader::new(receiver.try_clone().unwrap());
         let writer = BufWriter::new(receiver);
         let mut channel = Channel::new(reader, writer);
 
-        let mut vole_receiver = OtVoleReceiver::<F128b, 128, OtReceiver>::new();
+        let mut vole_receiver = OtVoleReceiver::<F, OtReceiver>::new();
         let (a_vec, c_vec) = vole_receiver
-            .receive(&mut channel, &mut rng, vole_size)
-            .with_context(|| format!("@{}:{}", file!(), line!()))
+            .receive_with(&mut channel, &mut rng, vole_size, direct)
+            .located()
             .unwrap();
 
-        let delta: F128b = channel.read_serializable().unwrap();
+        let delta: F = channel.read_serializable().unwrap();
 
-        let b_vec: Vec<F128b> = read_vec_f(&mut channel)
-            .with_context(|| format!("@{}:{}", file!(), line!()))
-            .unwrap();
+        let b_vec: Vec<F> = read_vec_f(&mut channel).located().unwrap();
 
         handle.join().unwrap();
 
-        dbg!(b_vec.len());
+        assert_eq!(a_vec.len(), vole_size);
+        assert_eq!(b_vec.len(), vole_size);
+        assert_eq!(c_vec.len(), vole_size);
 
-        for ((a, b), c) in a_vec
-            .into_iter()
-            .zip(b_vec.into_iter())
-            .zip(c_vec.into_iter())
-        {
+        for ((&a, b), c) in a_vec.iter().zip(b_vec.into_iter()).zip(c_vec.into_iter()) {
             assert_eq!(delta * a + b, c);
         }
+
+        a_vec
+    }
+
+    fn test_vole_share_base(vole_size: usize) {
+        test_vole_share_base_f::<F128b>(vole_size);
     }
 
     #[test]
@@ -383,4 +571,63 @@ mod tests {
     fn test_vole_share_20() {
         test_vole_share_base(2usize.pow(20));
     }
+
+    #[test]
+    fn test_unit_basis() {
+        assert_eq!(unit_basis::<F128b>().len(), 128);
+        assert_eq!(unit_basis::<F64b>().len(), 64);
+        assert!(unit_basis::<F61p>().len() >= 61);
+        assert!(unit_basis::<F128p>().len() >= 127);
+    }
+
+    #[test]
+    fn test_vole_share_fields() {
+        let mut rng = AesRng::new();
+        for _ in 0..10 {
+            let vole_size = rng.gen_range(1..1000);
+            test_vole_share_base_f::<F128b>(vole_size);
+            test_vole_share_base_f::<F64b>(vole_size);
+            test_vole_share_base_f::<F61p>(vole_size);
+            test_vole_share_base_f::<F128p>(vole_size);
+        }
+    }
+
+    #[test]
+    fn test_vole_share_chunks() {
+        let mut rng = AesRng::new();
+        for vole_size in [
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE - 5,
+        ] {
+            for direct in [true, false] {
+                test_vole_share_base_f_with_rng::<F128b, _>(vole_size, &mut rng, direct);
+                test_vole_share_base_f_with_rng::<F61p, _>(vole_size, &mut rng, direct);
+            }
+        }
+    }
+
+    #[test]
+    fn test_vole_share_exact_length() {
+        for vole_size in 1..20 {
+            test_vole_share_base_f::<F64b>(vole_size);
+        }
+
+        // a == 0 must not shorten the output.
+        let bits = unit_basis::<F64b>().len();
+        for vole_size in 1..20 {
+            let zero_entries = vec![0, vole_size / 2, vole_size - 1];
+            let rng = ZeroEntriesRng {
+                inner: AesRng::new(),
+                bits,
+                zero_entries: zero_entries.clone(),
+                count: 0,
+            };
+            let a_vec = test_vole_share_base_f_with_rng::<F64b, _>(vole_size, rng, true);
+            for i in zero_entries {
+                assert_eq!(a_vec[i], F64b::zero());
+            }
+        }
+    }
 }
@@ -0,0 +1,162 @@
+//! VOLE sessions.
+//!
+//! A session runs VOLE sharing only once between two parties and hands out slices of the shared correlations on demand.
+//! All instances between the same two parties (e.g. OPPRF instances of [psi](crate::preprocessed::psi)) can draw from one session,
+//! so the base setup of VOLE (e.g. `SVole::init` of [LPNVoleSender](crate::vole::LPNVoleSender)) is run once per direction per peer pair.
+//!
+//! Every slice drawn from a session shares the same $`\Delta`$.
+//! Both parties must draw slices of the same lengths in the same order.
+//!
+//! # Example
+//!
+//! ```
+//! use preprocessing_mpsi_with_vole::vole::{VoleShareForReceiver, VoleShareForSender, OtVoleSender, OtVoleReceiver};
+//! use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
+//! use scuttlebutt::field::F128b;
+//! # use scuttlebutt::{Channel, AbstractChannel};
+//! use scuttlebutt::AesRng;
+//! # use std::io::{BufReader, BufWriter};
+//! # use std::os::unix::net::UnixStream;
+//! use anyhow::Result;
+//!
+//! # fn try_main() -> Result<()> {
+//! # let (sender, receiver) = UnixStream::pair().unwrap();
+//! let handle = std::thread::spawn(move || -> Result<(F128b, Vec<F128b>, Vec<F128b>)> {
+//!     // let mut channel = ...
+//!     let mut rng = AesRng::new();
+//! #     let reader = BufReader::new(sender.try_clone().unwrap());
+//! #     let writer = BufWriter::new(sender);
+//! #     let mut channel = Channel::new(reader, writer);
+//!
+//!     let mut vole_sender = OtVoleSender::<F128b, OtSender>::new();
+//!     let mut session = vole_sender.open_session(&mut channel, &mut rng, 30)?;
+//!     let b_vec1 = session.draw(10)?;
+//!     let b_vec2 = session.draw(20)?;
+//!     Ok((session.delta(), b_vec1, b_vec2))
+//! });
+//!
+//! // let mut channel = ...
+//! let mut rng = AesRng::new();
+//! # let reader = BufReader::new(receiver.try_clone().unwrap());
+//! # let writer = BufWriter::new(receiver);
+//! # let mut channel = Channel::new(reader, writer);
+//!
+//! let mut vole_receiver = OtVoleReceiver::<F128b, OtReceiver>::new();
+//! let mut session = vole_receiver.open_session(&mut channel, &mut rng, 30)?;
+//! let (a_vec1, c_vec1) = session.draw(10)?;
+//! let (a_vec2, c_vec2) = session.draw(20)?;
+//!
+//! let (delta, b_vec1, b_vec2) = handle.join().unwrap()?;
+//!
+//! for ((a, b), c) in a_vec1.into_iter().chain(a_vec2)
+//!     .zip(b_vec1.into_iter().chain(b_vec2))
+//!     .zip(c_vec1.into_iter().chain(c_vec2))
+//! {
+//!     assert_eq!(delta * a + b, c);
+//! }
+//! # Ok(())
+//! # }
+//! # fn main() {
+//! #     try_main().unwrap();
+//! # }
+//! ```
+
+use crate::error::Error;
+use scuttlebutt::field::FiniteField as FF;
+
+/// VOLE session for the party having $`\Delta`$ and $`\bm{B}`$.
+///
+/// Created by [VoleShareForSender::open_session](crate::vole::VoleShareForSender::open_session).
+pub struct VoleSenderSession<F: FF> {
+    delta: F,
+    b_vec: Vec<F>,
+    used: usize,
+}
+
+impl<F: FF> VoleSenderSession<F> {
+    /// Create a session from already shared $`\Delta`$ and $`\bm{B}`$.
+    pub fn new(delta: F, b_vec: Vec<F>) -> Self {
+        Self {
+            delta,
+            b_vec,
+            used: 0,
+        }
+    }
+
+    /// $`\Delta`$ of this session.
+    pub fn delta(&self) -> F {
+        self.delta
+    }
+
+    /// Number of correlations not drawn yet.
+    pub fn remaining(&self) -> usize {
+        self.b_vec.len() - self.used
+    }
+
+    /// Draw next $`m`$ elements of $`\bm{B}`$.
+    pub fn draw(&mut self, m: usize) -> Result<Vec<F>, Error> {
+        if self.remaining() < m {
+            return Err(Error::Vole(format!(
+                "VOLE session is exhausted. remaining (={}) < m (={})",
+                self.remaining(),
+                m
+            )));
+        }
+
+        let b_vec = self.b_vec[self.used..self.used + m].to_vec();
+        self.used += m;
+
+        Ok(b_vec)
+    }
+}
+
+/// VOLE session for the party having $`\bm{A}`$ and $`\bm{C}`$.
+///
+/// Created by [VoleShareForReceiver::open_session](crate::vole::VoleShareForReceiver::open_session).
+pub struct VoleReceiverSession<F: FF> {
+    a_vec: Vec<F>,
+    c_vec: Vec<F>,
+    used: usize,
+}
+
+impl<F: FF> VoleReceiverSession<F> {
+    /// Create a session from already shared $`\bm{A}`$ and $`\bm{C}`$.
+    pub fn new(a_vec: Vec<F>, c_vec: Vec<F>) -> Result<Self, Error> {
+        if a_vec.len() != c_vec.len() {
+            return Err(Error::Vole(format!(
+                "a_vec.len() (={}) != c_vec.len() (={})",
+                a_vec.len(),
+                c_vec.len()
+            )));
+        }
+
+        Ok(Self {
+            a_vec,
+            c_vec,
+            used: 0,
+        })
+    }
+
+    /// Number of correlations not drawn yet.
+    pub fn remaining(&self) -> usize {
+        self.a_vec.len() - self.used
+    }
+
+    /// Draw next $`m`$ elements of $`\bm{A}`$ and $`\bm{C}`$.
+    pub fn draw(&mut self, m: usize) -> Result<(Vec<F>, Vec<F>), Error> {
+        if self.remaining() < m {
+            return Err(Error::Vole(format!(
+                "VOLE session is exhausted. remaining (={}) < m (={})",
+                self.remaining(),
+                m
+            )));
+        }
+
+        let range = self.used..self.used + m;
+        let a_vec = self.a_vec[range.clone()].to_vec();
+        let c_vec = self.c_vec[range].to_vec();
+        self.used += m;
+
+        Ok((a_vec, c_vec))
+    }
+}